@@ -35,11 +35,11 @@ use msg::constellation_msg::{NavigationDirection, PipelineId, WindowSizeData};
 use pipeline::CompositionPipeline;
 use profile_traits::mem::{self, ReportKind, Reporter, ReporterRequest};
 use profile_traits::time::{self, ProfilerCategory, profile};
-use script_traits::CompositorEvent::{MouseMoveEvent, MouseButtonEvent, TouchEvent};
-use script_traits::{AnimationState, ConstellationControlMsg, LayoutControlMsg};
-use script_traits::{MouseButton, MouseEventType, TouchEventType, TouchId};
+use script_traits::CompositorEvent::{MouseMoveEvent, MouseButtonEvent, TouchEvent, WheelEvent};
+use script_traits::{AnimationState, ConstellationControlMsg, EventResult, LayoutControlMsg};
+use script_traits::{CompositionEventType, MouseButton, MouseEventType, TouchEventType, TouchId};
 use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::mem as std_mem;
 use std::rc::Rc;
@@ -182,6 +182,11 @@ pub struct IOCompositor<Window: WindowMethods> {
     /// Pending scroll/zoom events.
     pending_scroll_zoom_events: Vec<ScrollZoomEvent>,
 
+    /// Wheel scrolls waiting on script to decide whether the default scroll is prevented, by
+    /// the pipeline they were sent to. Each script thread answers its wheel events in order.
+    pending_wheel_events: HashMap<PipelineId,
+                                  VecDeque<(TypedPoint2D<DevicePixel, f32>, TypedPoint2D<DevicePixel, i32>)>>,
+
     /// Used by the logic that determines when it is safe to output an
     /// image for the reftest framework.
     ready_to_save_state: ReadyState,
@@ -414,6 +419,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             composition_request: CompositionRequest::NoCompositingNecessary,
             touch_handler: TouchHandler::new(),
             pending_scroll_zoom_events: Vec::new(),
+            pending_wheel_events: HashMap::new(),
             composite_target: composite_target,
             shutdown_state: ShutdownState::NotShuttingDown,
             page_zoom: ScaleFactor::new(1.0),
@@ -631,6 +637,10 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.touch_handler.on_event_processed(result);
             }
 
+            (Msg::WheelEventProcessed(pipeline_id, result), ShutdownState::NotShuttingDown) => {
+                self.on_wheel_event_processed(pipeline_id, result);
+            }
+
             (Msg::SetCursor(cursor), ShutdownState::NotShuttingDown) => {
                 self.window.set_cursor(cursor)
             }
@@ -871,6 +881,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         // any buffers that they owned.
         root_layer.remove_root_layer_with_pipeline_id(self, pipeline_id);
         self.pipeline_details.remove(&pipeline_id);
+        self.pending_wheel_events.remove(&pipeline_id);
     }
 
     fn update_layer_if_exists(&mut self,
@@ -1193,7 +1204,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
 
             WindowEvent::Scroll(delta, cursor, phase) => {
                 match phase {
-                    TouchEventType::Move => self.on_scroll_window_event(delta, cursor),
+                    TouchEventType::Move => self.on_wheel_window_event(delta, cursor),
                     TouchEventType::Up | TouchEventType::Cancel => {
                         self.on_scroll_end_window_event(delta, cursor);
                     }
@@ -1223,6 +1234,10 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.on_key_event(key, state, modifiers);
             }
 
+            WindowEvent::Composition(event_type, data) => {
+                self.on_composition_event(event_type, data);
+            }
+
             WindowEvent::Quit => {
                 if self.shutdown_state == ShutdownState::NotShuttingDown {
                     debug!("Shutting down the constellation for WindowEvent::Quit");
@@ -1430,6 +1445,32 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         }
     }
 
+    /// Offers a wheel scroll to script first, so that content can prevent the default scroll.
+    fn on_wheel_window_event(&mut self,
+                             delta: TypedPoint2D<DevicePixel, f32>,
+                             cursor: TypedPoint2D<DevicePixel, i32>) {
+        match self.find_topmost_layer_at_point(cursor.as_f32() / self.scene.scale) {
+            Some(result) => {
+                let wheel_delta = (delta / self.scene.scale).to_untyped();
+                result.layer.send_event(self, WheelEvent(wheel_delta, result.point.to_untyped()));
+                self.pending_wheel_events.entry(result.layer.pipeline_id())
+                                         .or_insert_with(VecDeque::new)
+                                         .push_back((delta, cursor));
+            }
+            None => self.on_scroll_window_event(delta, cursor),
+        }
+    }
+
+    fn on_wheel_event_processed(&mut self, pipeline_id: PipelineId, result: EventResult) {
+        let pending = self.pending_wheel_events.get_mut(&pipeline_id).and_then(|events| events.pop_front());
+        if let Some((delta, cursor)) = pending {
+            match result {
+                EventResult::DefaultAllowed => self.on_scroll_window_event(delta, cursor),
+                EventResult::DefaultPrevented => {}
+            }
+        }
+    }
+
     fn on_scroll_window_event(&mut self,
                               delta: TypedPoint2D<DevicePixel, f32>,
                               cursor: TypedPoint2D<DevicePixel, i32>) {
@@ -1698,6 +1739,10 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         self.constellation_chan.send(ConstellationMsg::KeyEvent(key, state, modifiers)).unwrap()
     }
 
+    fn on_composition_event(&self, event_type: CompositionEventType, data: String) {
+        self.constellation_chan.send(ConstellationMsg::CompositionEvent(event_type, data)).unwrap()
+    }
+
     fn fill_paint_request_with_cached_layer_buffers(&mut self, paint_request: &mut PaintRequest) {
         for buffer_request in &mut paint_request.buffer_requests {
             if self.surface_map.mem() == 0 {
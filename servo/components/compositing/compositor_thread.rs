@@ -98,6 +98,10 @@ pub fn run_script_listener_thread(compositor_proxy: Box<CompositorProxy + 'stati
             ScriptToCompositorMsg::TouchEventProcessed(result) => {
                 compositor_proxy.send(Msg::TouchEventProcessed(result))
             }
+
+            ScriptToCompositorMsg::WheelEventProcessed(pipeline_id, result) => {
+                compositor_proxy.send(Msg::WheelEventProcessed(pipeline_id, result))
+            }
         }
     }
 }
@@ -205,6 +209,9 @@ pub enum Msg {
     KeyEvent(Key, KeyState, KeyModifiers),
     /// Script has handled a touch event, and either prevented or allowed default actions.
     TouchEventProcessed(EventResult),
+    /// Script has handled a wheel event sent to a pipeline, and either prevented or allowed the
+    /// default scroll.
+    WheelEventProcessed(PipelineId, EventResult),
     /// Changes the cursor.
     SetCursor(Cursor),
     /// Composite to a PNG file and return the Image over a passed channel.
@@ -255,6 +262,7 @@ impl Debug for Msg {
             Msg::Recomposite(..) => write!(f, "Recomposite"),
             Msg::KeyEvent(..) => write!(f, "KeyEvent"),
             Msg::TouchEventProcessed(..) => write!(f, "TouchEventProcessed"),
+            Msg::WheelEventProcessed(..) => write!(f, "WheelEventProcessed"),
             Msg::SetCursor(..) => write!(f, "SetCursor"),
             Msg::CreatePng(..) => write!(f, "CreatePng"),
             Msg::PaintThreadExited(..) => write!(f, "PaintThreadExited"),
@@ -45,7 +45,7 @@ use profile_traits::mem;
 use profile_traits::time;
 #[cfg(not(target_os = "windows"))]
 use sandboxing;
-use script_traits::{AnimationState, CompositorEvent, ConstellationControlMsg};
+use script_traits::{AnimationState, CompositionEventType, CompositorEvent, ConstellationControlMsg};
 use script_traits::{DocumentState, LayoutControlMsg};
 use script_traits::{IFrameLoadInfo, IFrameSandboxState, MozBrowserEvent, TimerEventRequest};
 use script_traits::{LayoutMsg as FromLayoutMsg, ScriptMsg as FromScriptMsg, ScriptThreadFactory};
@@ -554,6 +554,10 @@ impl<LTF: LayoutThreadFactory, STF: ScriptThreadFactory> Constellation<LTF, STF>
                 debug!("constellation got key event message");
                 self.handle_key_msg(key, state, modifiers);
             }
+            Request::Compositor(FromCompositorMsg::CompositionEvent(event_type, data)) => {
+                debug!("constellation got composition event message");
+                self.handle_composition_msg(event_type, data);
+            }
             // Load a new page from a typed url
             // If there is already a pending page (self.pending_frames), it will not be overridden;
             // However, if the id is not encompassed by another change, it will be.
@@ -1129,6 +1133,21 @@ impl<LTF: LayoutThreadFactory, STF: ScriptThreadFactory> Constellation<LTF, STF>
         }
     }
 
+    fn handle_composition_msg(&self, event_type: CompositionEventType, data: String) {
+        // Compositions only make sense for a pipeline with a focused text control, so unlike
+        // key events there is nothing to fall back to.
+        let target_pipeline_id = self.focus_pipeline_id.or(self.root_frame_id.map(|frame_id| {
+            self.frame(frame_id).current
+        }));
+
+        if let Some(target_pipeline_id) = target_pipeline_id {
+            let pipeline = self.pipeline(target_pipeline_id);
+            let event = CompositorEvent::CompositionEvent(event_type, data);
+            pipeline.script_chan.send(
+                ConstellationControlMsg::SendEvent(pipeline.id, event)).unwrap();
+        }
+    }
+
     fn handle_get_pipeline_title_msg(&mut self, pipeline_id: PipelineId) {
         match self.pipelines.get(&pipeline_id) {
             None => self.compositor_proxy.send(ToCompositorMsg::ChangePageTitle(pipeline_id, None)),
@@ -121,6 +121,7 @@ impl CompositorEventListener for NullCompositor {
             Msg::ChangePageUrl(..) |
             Msg::KeyEvent(..) |
             Msg::TouchEventProcessed(..) |
+            Msg::WheelEventProcessed(..) |
             Msg::SetCursor(..) |
             Msg::ViewportConstrained(..) => {}
             Msg::CreatePng(..) |
@@ -59,6 +59,7 @@ use ipc_channel::ipc::{IpcSender};
 use msg::constellation_msg::{FrameId, Key, KeyState, KeyModifiers, LoadData};
 use msg::constellation_msg::{NavigationDirection, PipelineId, SubpageId};
 use msg::constellation_msg::{WebDriverCommandMsg, WindowSizeData};
+use script_traits::CompositionEventType;
 use std::collections::HashMap;
 use url::Url;
 
@@ -101,6 +102,7 @@ pub enum CompositorMsg {
     /// Query the constellation to see if the current compositor output is stable
     IsReadyToSaveImage(HashMap<PipelineId, Epoch>),
     KeyEvent(Key, KeyState, KeyModifiers),
+    CompositionEvent(CompositionEventType, String),
     LoadUrl(PipelineId, LoadData),
     Navigate(Option<(PipelineId, SubpageId)>, NavigationDirection),
     ResizedWindow(WindowSizeData),
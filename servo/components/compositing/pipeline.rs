@@ -407,6 +407,7 @@ impl UnprivilegedPipelineContent {
             window_size: self.window_size,
             pipeline_namespace_id: self.pipeline_namespace_id,
             content_process_shutdown_chan: self.script_content_process_shutdown_chan.clone(),
+            vdom_mount: self.opts.vdom_mount.clone(),
         }, &layout_pair, self.load_data.clone());
 
         LayoutThreadFactory::create(None::<&mut LTF>,
@@ -91,8 +91,9 @@ impl FontTemplates {
 pub enum Command {
     GetFontTemplate(FontFamily, FontTemplateDescriptor, IpcSender<Reply>),
     GetLastResortFontTemplate(FontTemplateDescriptor, IpcSender<Reply>),
-    AddWebFont(FontFamily, Source, IpcSender<()>),
-    AddDownloadedWebFont(FontFamily, Url, Vec<u8>, IpcSender<()>),
+    /// Adds a web font to a family, replying with whether it loaded.
+    AddWebFont(FontFamily, Source, IpcSender<bool>),
+    AddDownloadedWebFont(FontFamily, Url, Vec<u8>, IpcSender<bool>),
     Exit(IpcSender<()>),
 }
 
@@ -201,7 +202,7 @@ impl FontCache {
                                     }
                                     ResponseAction::ResponseComplete(response) => {
                                         if response.is_err() || !*response_valid.lock().unwrap() {
-                                            drop(result.send(()));
+                                            drop(result.send(false));
                                             return;
                                         }
                                         let mut bytes = bytes.lock().unwrap();
@@ -222,7 +223,7 @@ impl FontCache {
                             for_each_variation(&font_face_name, |path| {
                                 templates.add_template(Atom::from(&*path), None);
                             });
-                            result.send(()).unwrap();
+                            result.send(true).unwrap();
                         }
                     }
                 }
@@ -231,7 +232,7 @@ impl FontCache {
 
                     let templates = &mut self.web_families.get_mut(&family_name).unwrap();
                     templates.add_template(Atom::from(url.to_string()), Some(bytes));
-                    drop(result.send(()));
+                    drop(result.send(true));
                 }
                 Command::Exit(result) => {
                     result.send(()).unwrap();
@@ -410,7 +411,7 @@ impl FontCacheThread {
         }
     }
 
-    pub fn add_web_font(&self, family: FontFamily, src: Source, sender: IpcSender<()>) {
+    pub fn add_web_font(&self, family: FontFamily, src: Source, sender: IpcSender<bool>) {
         self.chan.send(Command::AddWebFont(family, src, sender)).unwrap();
     }
 
@@ -44,9 +44,9 @@ use profile_traits::time::{TimerMetadataFrameType, TimerMetadataReflowType};
 use profile_traits::time::{self, TimerMetadata, profile};
 use query::{LayoutRPCImpl, process_content_box_request, process_content_boxes_request};
 use query::{process_node_geometry_request, process_node_scroll_area_request, process_offset_parent_query};
-use query::{process_resolved_style_request, process_margin_style_query};
+use query::{process_resolved_style_request, process_margin_style_query, process_box_model_query};
 use script::dom::node::OpaqueStyleAndLayoutData;
-use script::layout_interface::{LayoutRPC, OffsetParentResponse, MarginStyleResponse};
+use script::layout_interface::{BoxModelSides, LayoutRPC, OffsetParentResponse, MarginStyleResponse};
 use script::layout_interface::{Msg, NewLayoutThreadInfo, Reflow, ReflowQueryType};
 use script::layout_interface::{ScriptLayoutChan, ScriptReflow};
 use script::reporter::CSSErrorReporter;
@@ -63,10 +63,12 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use style::animation::Animation;
+use style::computed_values::font_family::FontFamily;
 use style::computed_values::{filter, mix_blend_mode};
 use style::context::{ReflowGoal, StylistWrapper};
 use style::dom::{TDocument, TElement, TNode};
 use style::error_reporting::ParseErrorReporter;
+use style::font_face::Source;
 use style::logical_geometry::LogicalPoint;
 use style::media_queries::{Device, MediaType};
 use style::parallel::WorkQueueData;
@@ -128,6 +130,9 @@ pub struct LayoutThreadData {
 
     /// A queued response for the offset parent/rect of a node.
     pub margin_style_response: MarginStyleResponse,
+
+    /// A queued response for the box model of a node.
+    pub box_model_response: Option<BoxModelSides>,
 }
 
 /// Information needed by the layout thread.
@@ -154,10 +159,10 @@ pub struct LayoutThread {
     image_cache_sender: ImageCacheChan,
 
     /// The port on which we receive messages from the font cache thread.
-    font_cache_receiver: Receiver<()>,
+    font_cache_receiver: Receiver<bool>,
 
     /// The channel on which the font cache can send messages to us.
-    font_cache_sender: IpcSender<()>,
+    font_cache_sender: IpcSender<bool>,
 
     /// The channel on which messages can be sent to the constellation.
     constellation_chan: ConstellationChan<ConstellationMsg>,
@@ -351,7 +356,7 @@ impl<'a, 'b: 'a> RwData<'a, 'b> {
 fn add_font_face_rules(stylesheet: &Stylesheet,
                        device: &Device,
                        font_cache_thread: &FontCacheThread,
-                       font_cache_sender: &IpcSender<()>,
+                       font_cache_sender: &IpcSender<bool>,
                        outstanding_web_fonts_counter: &Arc<AtomicUsize>) {
     for font_face in stylesheet.effective_rules(&device).font_face() {
         for source in &font_face.sources {
@@ -469,6 +474,7 @@ impl LayoutThread {
                     resolved_style_response: None,
                     offset_parent_response: OffsetParentResponse::empty(),
                     margin_style_response: MarginStyleResponse::empty(),
+                    box_model_response: None,
               })),
               error_reporter: CSSErrorReporter {
                   pipelineid: id,
@@ -584,6 +590,20 @@ impl LayoutThread {
         }
     }
 
+    /// Loads a web font outside of any stylesheet. Once it loads, layout reflows with it as it
+    /// would for an `@font-face` rule, and `result` is told whether it loaded.
+    fn preload_font(&self, family: FontFamily, source: Source, result: IpcSender<bool>) {
+        self.outstanding_web_fonts.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = ipc::channel().unwrap();
+        let font_cache_sender = self.font_cache_sender.clone();
+        ROUTER.add_route(receiver.to_opaque(), box move |message| {
+            let loaded: bool = message.to().unwrap();
+            font_cache_sender.send(loaded).unwrap();
+            drop(result.send(loaded));
+        });
+        self.font_cache_thread.add_web_font(family, source, sender);
+    }
+
     /// Repaint the scene, without performing style matching. This is typically
     /// used when an image arrives asynchronously and triggers a relayout and
     /// repaint.
@@ -633,6 +653,7 @@ impl LayoutThread {
             Msg::ReflowWithNewlyLoadedWebFont => {
                 self.reflow_with_newly_loaded_web_font(possibly_locked_rw_data)
             }
+            Msg::PreloadFont(family, source, result) => self.preload_font(family, source, result),
             Msg::SetVisibleRects(new_visible_rects) => {
                 self.set_visible_rects(new_visible_rects, possibly_locked_rw_data);
             }
@@ -1005,6 +1026,9 @@ impl LayoutThread {
                     ReflowQueryType::MarginStyleQuery(_) => {
                         rw_data.margin_style_response = MarginStyleResponse::empty();
                     },
+                    ReflowQueryType::BoxModelQuery(_) => {
+                        rw_data.box_model_response = None;
+                    },
                     ReflowQueryType::NoQuery => {}
                 }
                 return;
@@ -1024,7 +1048,7 @@ impl LayoutThread {
                                               Au::from_f32_px(initial_viewport.height.get()));
 
         // Calculate the actual viewport as per DEVICE-ADAPT § 6
-        let device = Device::new(MediaType::Screen, initial_viewport);
+        let device = Device::new(MediaType::Screen, initial_viewport).with_color_scheme(data.color_scheme);
         rw_data.stylist.set_device(device, &data.document_stylesheets);
 
         let constraints = rw_data.stylist.viewport_constraints().clone();
@@ -1168,6 +1192,10 @@ impl LayoutThread {
                     let node = unsafe { ServoLayoutNode::new(&node) };
                     rw_data.margin_style_response = process_margin_style_query(node);
                 },
+                ReflowQueryType::BoxModelQuery(node) => {
+                    let node = unsafe { ServoLayoutNode::new(&node) };
+                    rw_data.box_model_response = process_box_model_query(node, &mut root_flow);
+                },
                 ReflowQueryType::NoQuery => {}
             }
         }
@@ -8,6 +8,7 @@ use app_units::Au;
 use construct::ConstructionResult;
 use euclid::point::Point2D;
 use euclid::rect::Rect;
+use euclid::side_offsets::SideOffsets2D;
 use euclid::size::Size2D;
 use flow;
 use flow_ref::FlowRef;
@@ -16,7 +17,8 @@ use gfx::display_list::OpaqueNode;
 use layout_thread::LayoutThreadData;
 use msg::constellation_msg::ConstellationChan;
 use opaque_node::OpaqueNodeMethods;
-use script::layout_interface::{ContentBoxResponse, ContentBoxesResponse, NodeGeometryResponse};
+use script::layout_interface::{BoxModelResponse, BoxModelSides, ContentBoxResponse, ContentBoxesResponse};
+use script::layout_interface::NodeGeometryResponse;
 use script::layout_interface::{HitTestResponse, LayoutRPC, OffsetParentResponse};
 use script::layout_interface::{ResolvedStyleResponse, ScriptLayoutChan, MarginStyleResponse};
 use script_traits::LayoutMsg as ConstellationMsg;
@@ -116,6 +118,10 @@ impl LayoutRPC for LayoutRPCImpl {
         let rw_data = rw_data.lock().unwrap();
         rw_data.margin_style_response.clone()
     }
+
+    fn box_model(&self) -> BoxModelResponse {
+        BoxModelResponse(self.0.lock().unwrap().box_model_response)
+    }
 }
 
 struct UnioningFragmentBorderBoxIterator {
@@ -319,6 +325,20 @@ impl FragmentLocatingFragmentIterator {
     }
 }
 
+struct BoxModelFragmentIterator {
+    node_address: OpaqueNode,
+    box_model: Option<BoxModelSides>,
+}
+
+impl BoxModelFragmentIterator {
+    fn new(node_address: OpaqueNode) -> BoxModelFragmentIterator {
+        BoxModelFragmentIterator {
+            node_address: node_address,
+            box_model: None,
+        }
+    }
+}
+
 struct UnioningFragmentScrollAreaIterator {
     node_address: OpaqueNode,
     union_rect: Rect<i32>,
@@ -386,6 +406,34 @@ impl FragmentBorderBoxIterator for FragmentLocatingFragmentIterator {
     }
 }
 
+impl FragmentBorderBoxIterator for BoxModelFragmentIterator {
+    fn process(&mut self, fragment: &Fragment, _: i32, border_box: &Rect<Au>) {
+        let style_structs::Border {
+            border_top_width: top_width,
+            border_right_width: right_width,
+            border_bottom_width: bottom_width,
+            border_left_width: left_width,
+            ..
+        } = *fragment.style.get_border();
+        let border = SideOffsets2D::new(top_width, right_width, bottom_width, left_width);
+        let border_padding = fragment.border_padding.to_physical(fragment.style.writing_mode);
+        self.box_model = Some(BoxModelSides {
+            border_box: *border_box,
+            border: border,
+            padding: SideOffsets2D::new(border_padding.top - top_width,
+                                        border_padding.right - right_width,
+                                        border_padding.bottom - bottom_width,
+                                        border_padding.left - left_width),
+            margin: fragment.margin.to_physical(fragment.style.writing_mode),
+        });
+    }
+
+    fn should_process(&mut self, fragment: &Fragment) -> bool {
+        // Only the first fragment of a node split across lines is measured.
+        self.box_model.is_none() && fragment.node == self.node_address
+    }
+}
+
 // https://drafts.csswg.org/cssom-view/#scrolling-area
 impl FragmentBorderBoxIterator for UnioningFragmentScrollAreaIterator {
     fn process(&mut self, fragment: &Fragment, level: i32, border_box: &Rect<Au>) {
@@ -497,6 +545,13 @@ pub fn process_node_geometry_request<N: LayoutNode>(requested_node: N, layout_ro
     iterator.client_rect
 }
 
+pub fn process_box_model_query<N: LayoutNode>(requested_node: N, layout_root: &mut FlowRef)
+        -> Option<BoxModelSides> {
+    let mut iterator = BoxModelFragmentIterator::new(requested_node.opaque());
+    sequential::iterate_through_flow_tree_fragment_border_boxes(layout_root, &mut iterator);
+    iterator.box_model
+}
+
 pub fn process_node_scroll_area_request< N: LayoutNode>(requested_node: N, layout_root: &mut FlowRef)
         -> Rect<i32> {
     let mut iterator = UnioningFragmentScrollAreaIterator::new(requested_node.opaque());
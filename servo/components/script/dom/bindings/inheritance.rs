@@ -135,10 +135,13 @@ pub enum HTMLMediaElementTypeId {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UIEventTypeId {
     UIEvent,
+    CompositionEvent,
+    DragEvent,
     FocusEvent,
     KeyboardEvent,
     MouseEvent,
-    TouchEvent
+    TouchEvent,
+    WheelEvent
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -257,6 +260,8 @@ impl NodeList {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum EventTypeId {
     Event,
+    ClipboardEvent,
+    PopStateEvent,
     UIEvent(UIEventTypeId)
 }
 
@@ -275,11 +280,18 @@ impl DerivedFrom<EventTarget> for CharacterData {}
 impl DerivedFrom<Node> for CharacterData {}
 impl DerivedFrom<CharacterData> for CharacterData {}
 
+impl Castable for ClipboardEvent {}
+impl DerivedFrom<Event> for ClipboardEvent {}
+
 impl Castable for Comment {}
 impl DerivedFrom<EventTarget> for Comment {}
 impl DerivedFrom<Node> for Comment {}
 impl DerivedFrom<CharacterData> for Comment {}
 
+impl Castable for CompositionEvent {}
+impl DerivedFrom<Event> for CompositionEvent {}
+impl DerivedFrom<UIEvent> for CompositionEvent {}
+
 impl Castable for DOMPoint {}
 impl DerivedFrom<DOMPointReadOnly> for DOMPoint {}
 
@@ -304,6 +316,10 @@ impl Castable for DocumentType {}
 impl DerivedFrom<EventTarget> for DocumentType {}
 impl DerivedFrom<Node> for DocumentType {}
 
+impl Castable for DragEvent {}
+impl DerivedFrom<Event> for DragEvent {}
+impl DerivedFrom<UIEvent> for DragEvent {}
+
 impl Castable for Element {}
 impl DerivedFrom<EventTarget> for Element {}
 impl DerivedFrom<Node> for Element {}
@@ -757,6 +773,9 @@ impl DerivedFrom<Node> for Node {}
 impl Castable for NodeList {}
 impl DerivedFrom<NodeList> for NodeList {}
 
+impl Castable for PopStateEvent {}
+impl DerivedFrom<Event> for PopStateEvent {}
+
 impl Castable for ProcessingInstruction {}
 impl DerivedFrom<EventTarget> for ProcessingInstruction {}
 impl DerivedFrom<Node> for ProcessingInstruction {}
@@ -778,5 +797,9 @@ impl Castable for UIEvent {}
 impl DerivedFrom<Event> for UIEvent {}
 impl DerivedFrom<UIEvent> for UIEvent {}
 
+impl Castable for WheelEvent {}
+impl DerivedFrom<Event> for WheelEvent {}
+impl DerivedFrom<UIEvent> for WheelEvent {}
+
 impl Castable for Window {}
 impl DerivedFrom<EventTarget> for Window {}
\ No newline at end of file
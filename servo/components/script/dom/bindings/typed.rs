@@ -53,6 +53,18 @@ make_typed!(DOMRect, DOMRectReadOnly,
 
 // Event Subtypes
 
+make_typed!(ClipboardEvent, Event,
+	TopTypeId::Event(EventTypeId::ClipboardEvent));
+
+make_typed!(PopStateEvent, Event,
+	TopTypeId::Event(EventTypeId::PopStateEvent));
+
+make_typed!(CompositionEvent, Event,
+	TopTypeId::Event(EventTypeId::UIEvent(UIEventTypeId::CompositionEvent)));
+
+make_typed!(DragEvent, Event,
+	TopTypeId::Event(EventTypeId::UIEvent(UIEventTypeId::DragEvent)));
+
 make_typed!(FocusEvent, Event,
 	TopTypeId::Event(EventTypeId::UIEvent(UIEventTypeId::FocusEvent)));
 
@@ -65,6 +77,9 @@ make_typed!(MouseEvent, Event,
 make_typed!(TouchEvent, Event,
 	TopTypeId::Event(EventTypeId::UIEvent(UIEventTypeId::TouchEvent)));
 
+make_typed!(WheelEvent, Event,
+	TopTypeId::Event(EventTypeId::UIEvent(UIEventTypeId::WheelEvent)));
+
 make_typed!(UIEvent, Event,
 	TopTypeId::Event(EventTypeId::UIEvent(_)));
 
@@ -17,6 +17,7 @@ use dom::processinginstruction::ProcessingInstruction;
 use dom::text::Text;
 use std::cell::Ref;
 use util::str::DOMString;
+use vdom::live;
 
 // https://dom.spec.whatwg.org/#characterdata
 
@@ -44,9 +45,17 @@ impl CharacterData {
         self.content_changed();
     }
 
+    #[inline]
+    pub fn set_data(&self, data: DOMString) {
+        *self.data.borrow_mut() = data;
+        self.content_changed();
+    }
+
     fn content_changed(&self) {
         let node = self.upcast::<Node>();
-        node.owner_doc().content_changed(node, NodeDamage::OtherNodeDamage);
+        let doc = node.owner_doc();
+        doc.content_changed(node, NodeDamage::OtherNodeDamage);
+        doc.note_live_mutation(live::TEXT);
     }
 
     // https://dom.spec.whatwg.org/#dom-characterdata-data
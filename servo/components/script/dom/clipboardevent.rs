@@ -0,0 +1,54 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::inheritance::{Castable, EventTypeId};
+use dom::bindings::js::Root;
+use dom::dragevent::DragData;
+use dom::event::{Event, EventBubbles, EventCancelable};
+use std::cell::Ref;
+use string_cache::Atom;
+use util::str::DOMString;
+
+// https://w3c.github.io/clipboard-apis/#clipboard-event-interfaces
+
+pub struct ClipboardEvent {
+    event: Event,
+    clipboard_data: DOMRefCell<DragData>,
+}
+
+impl ClipboardEvent {
+    fn new_inherited() -> ClipboardEvent {
+        ClipboardEvent {
+            event: Event::new_inherited(EventTypeId::ClipboardEvent),
+            clipboard_data: DOMRefCell::new(DragData::new()),
+        }
+    }
+
+    pub fn new_uninitialized() -> Root<ClipboardEvent> {
+        Root::new_box(box ClipboardEvent::new_inherited())
+    }
+
+    pub fn new(type_: DOMString,
+               canBubble: EventBubbles,
+               cancelable: EventCancelable,
+               clipboardData: DragData) -> Root<ClipboardEvent> {
+        let ev = ClipboardEvent::new_uninitialized();
+        ev.upcast::<Event>().init_event(Atom::from(type_),
+                                        canBubble == EventBubbles::Bubbles,
+                                        cancelable == EventCancelable::Cancelable);
+        *ev.clipboard_data.borrow_mut() = clipboardData;
+        ev
+    }
+
+    // https://w3c.github.io/clipboard-apis/#widl-ClipboardEvent-clipboardData
+    pub fn clipboard_data(&self) -> Ref<DragData> {
+        self.clipboard_data.borrow()
+    }
+
+    /// The `text/plain` item of the clipboard data, if any.
+    pub fn text(&self) -> Option<String> {
+        self.clipboard_data().get_data("text/plain").map(|text| text.to_owned())
+    }
+}
@@ -0,0 +1,50 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::inheritance::{Castable, UIEventTypeId};
+use dom::bindings::js::Root;
+use dom::event::{EventBubbles, EventCancelable};
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use util::str::DOMString;
+
+// https://w3c.github.io/uievents/#interface-compositionevent
+
+pub struct CompositionEvent {
+    uievent: UIEvent,
+    data: DOMRefCell<DOMString>,
+}
+
+impl CompositionEvent {
+    fn new_inherited() -> CompositionEvent {
+        CompositionEvent {
+            uievent: UIEvent::new_inherited(UIEventTypeId::CompositionEvent),
+            data: DOMRefCell::new(DOMString::new()),
+        }
+    }
+
+    pub fn new_uninitialized() -> Root<CompositionEvent> {
+        Root::new_box(box CompositionEvent::new_inherited())
+    }
+
+    pub fn new(type_: DOMString,
+               canBubble: EventBubbles,
+               cancelable: EventCancelable,
+               view: Option<&Window>,
+               data: DOMString) -> Root<CompositionEvent> {
+        let ev = CompositionEvent::new_uninitialized();
+        ev.upcast::<UIEvent>().InitUIEvent(type_,
+                                           canBubble == EventBubbles::Bubbles,
+                                           cancelable == EventCancelable::Cancelable,
+                                           view, 0);
+        *ev.data.borrow_mut() = data;
+        ev
+    }
+
+    // https://w3c.github.io/uievents/#widl-CompositionEvent-data
+    pub fn Data(&self) -> DOMString {
+        self.data.borrow().clone()
+    }
+}
@@ -250,13 +250,22 @@ pub fn create_element_named(
                       creator: ElementCreator)
                       -> Root<Element> {
 
+    // A shell kept from a removed element of the same kind is reused if there is one.
     macro_rules! make(
         ($ctor:ident, $atom:expr) => ({
-            let obj = $ctor::new(id, $atom, None, document);
+            let local_name = $atom;
+            if let Some(element) = document.reuse_vdom_element(id, &local_name) {
+                return element;
+            }
+            let obj = $ctor::new(id, local_name, None, document);
             Root::upcast(obj)
         });
         ($ctor:ident, $atom:expr, $($arg:expr),+) => ({
-            let obj = $ctor::new(id, $atom, None, document, $($arg),+);
+            let local_name = $atom;
+            if let Some(element) = document.reuse_vdom_element(id, &local_name) {
+                return element;
+            }
+            let obj = $ctor::new(id, local_name, None, document, $($arg),+);
             Root::upcast(obj)
         })
     );
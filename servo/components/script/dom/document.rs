@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use clipboard_provider::ClipboardProvider;
 use devtools_traits::CSSError;
 use document_loader::{DocumentLoader, LoadType};
 use dom::activation::{ActivationSource, synthetic_click_activation};
@@ -21,9 +22,12 @@ use dom::bindings::xmlname::XMLName::InvalidXMLName;
 use dom::bindings::xmlname::{validate_and_extract, namespace_from_domstring, xml_name_type};
 use dom::browsingcontext::BrowsingContext;
 use dom::comment::Comment;
+use dom::clipboardevent::ClipboardEvent;
+use dom::compositionevent::CompositionEvent;
 use dom::documentfragment::DocumentFragment;
 use dom::documenttype::DocumentType;
 use dom::domimplementation::DOMImplementation;
+use dom::dragevent::{DragData, DragEvent};
 use dom::element::{Element, ElementCreator};
 use dom::event::{Event, EventBubbles, EventCancelable};
 use dom::eventtarget::EventTarget;
@@ -40,21 +44,28 @@ use dom::htmlformelement::HTMLFormElement;
 use dom::htmlheadelement::HTMLHeadElement;
 use dom::htmlhtmlelement::HTMLHtmlElement;
 use dom::htmlimageelement::HTMLImageElement;
+use dom::htmlinputelement::HTMLInputElement;
 use dom::htmllinkelement::HTMLLinkElement;
 use dom::htmlmetaelement::HTMLMetaElement;
 use dom::htmlstyleelement::HTMLStyleElement;
+use dom::htmltextareaelement::HTMLTextAreaElement;
 use dom::htmltitleelement::HTMLTitleElement;
 use dom::keyboardevent::KeyboardEvent;
 use dom::mouseevent::MouseEvent;
 use dom::node::{self, CloneChildrenFlag, Node, NodeDamage, window_from_node};
 use dom::nodelist::NodeList;
+use dom::popstateevent::PopStateEvent;
 use dom::processinginstruction::ProcessingInstruction;
 use dom::text::Text;
 use dom::touch::Touch;
 use dom::touchevent::TouchEvent;
 use dom::touchlist::TouchList;
 use dom::uievent::UIEvent;
+use dom::virtualmethods::vtable_for;
+use dom::wheelevent::{WheelEvent, WheelEventConstants};
 use dom::window::{ReflowReason, Window};
+use editing::{EditCommand, clamp_to_host, edit_host, selection_end};
+use euclid::length::Length;
 use euclid::point::Point2D;
 use ipc_channel::ipc::{self, IpcSender};
 use layout_interface::{LayoutChan, Msg, ReflowQueryType};
@@ -65,9 +76,10 @@ use net_traits::CookieSource::NonHTTP;
 use net_traits::response::HttpsState;
 use net_traits::{AsyncResponseTarget, PendingAsyncLoad};
 use num::ToPrimitive;
-use script_thread::{MainThreadScriptChan, MainThreadScriptMsg, Runnable, ScriptChan};
-use script_traits::{AnimationState, MouseButton, MouseEventType};
-use script_traits::{ScriptMsg as ConstellationMsg, ScriptToCompositorMsg};
+use script_thread::{CommonScriptMsg, MainThreadScriptChan, MainThreadScriptMsg, Runnable, ScriptChan};
+use script_thread::{ScriptThreadEventCategory, SizeCache};
+use script_traits::{AnimationState, CompositionEventType, MouseButton, MouseEventType};
+use script_traits::{ScriptMsg as ConstellationMsg, ScriptToCompositorMsg, TimerEventId};
 use script_traits::{TouchEventType, TouchId};
 use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
@@ -78,15 +90,37 @@ use std::collections::{BTreeMap, HashMap};
 use std::default::Default;
 use std::mem;
 use std::sync::Arc;
+use std::usize;
 use string_cache::{Atom, QualName};
 use style::context::ReflowGoal;
 use style::restyle_hints::ElementSnapshot;
 use style::servo::Stylesheet;
+use style_traits::color_scheme::ColorScheme;
 use task_source::dom_manipulation::DOMManipulationTask;
+use textinput::clipboard_event_type;
 use time;
 use url::percent_encoding::percent_decode;
 use url::{Host, Url};
 use util::str::{DOMString, split_html_space_chars, str_join};
+use vdom::atoms::AtomCache;
+use vdom::events::{self, Delivery, EventDelivery, EventSet};
+use vdom::history::{HistoryEntry, SessionHistory};
+use vdom::hover::HoverChanges;
+use vdom::live::{MutationKinds, MutationVersions};
+use vdom::mousemoves::MouseMoves;
+use vdom::msg::{EventMsg, ServerMsg};
+use vdom::ops::box_rect;
+use vdom::pool::ElementPool;
+use vdom::registry;
+use vdom::resize::ResizeWatch;
+use vdom::selection::{SelectionEnd, SelectionReports};
+use vdom::stylesheets::cascade_order;
+use vdom::ticks::TickThrottle;
+use vdom::timers::{VdomTimer, VdomTimers};
+use vdom::tooltips::{TOOLTIP_DWELL_MS, TooltipDwell};
+use vdom::tombstones::{LookupError, Tombstones};
+use vdom::undo::UndoLog;
+use vdom::visibility::{VisibilityWatch, visible_ratio};
 
 #[repr(usize)]
 #[derive(PartialEq, Copy, Clone, HeapSizeOf, Debug)]
@@ -96,6 +130,23 @@ pub enum DocumentReadyState {
     Complete
 }
 
+/// How far in CSS pixels the mouse moves from where it was pressed over a draggable element
+/// before a drag starts, so that a click with a slight jitter isn't taken for a drag.
+pub const DRAG_THRESHOLD: f32 = 4.0;
+
+/// Progress of a drag-and-drop operation driven by the mouse.
+#[derive(PartialEq, Copy, Clone, HeapSizeOf, Debug)]
+enum DragState {
+    /// No drag is in progress.
+    Idle,
+    /// The mouse was pressed over a draggable element; moving it far enough starts a drag.
+    Pending,
+    /// A drag was started from the drag source.
+    Dragging,
+    /// A drag ended on mouseup, so the click that follows is not dispatched.
+    Finished,
+}
+
 #[derive(PartialEq)]
 pub enum IsHTMLDocument {
     HTMLDocument,
@@ -110,12 +161,17 @@ pub struct Document {
     /// https://html.spec.whatwg.org/multipage/#concept-document-bc
     browsing_context: Option<JS<BrowsingContext>>,
     implementation: MutNullableHeap<JS<DOMImplementation>>,
-    content_type: DOMString,
+    content_type: DOMRefCell<DOMString>,
     last_modified: Option<String>,
     encoding_name: DOMRefCell<DOMString>,
     is_html_document: bool,
-    url: Url,
+    url: DOMRefCell<Url>,
     node_id_map: DOMRefCell<HashMap<u64, JS<Node>>>,
+    /// The ids removed from `node_id_map` since it was last compacted.
+    node_id_removals: Cell<usize>,
+    /// The mutations of this document by kind, which live collections created from selectors
+    /// check to tell whether they have to recompute their elements.
+    live_versions: Cell<MutationVersions>,
     /// Caches for the getElement methods
     id_map: DOMRefCell<HashMap<Atom, Vec<JS<Element>>>>,
     tag_map: DOMRefCell<HashMap<Atom, JS<HTMLCollection>>>,
@@ -138,6 +194,21 @@ pub struct Document {
     possibly_focused: MutNullableHeap<JS<Element>>,
     /// The element that currently has the document focus context.
     focused: MutNullableHeap<JS<Element>>,
+    /// The focused element removed from the document, and the ancestor that should be focused
+    /// instead, until `fix_up_focus` moves focus to it.
+    removed_focus: MutNullableHeap<JS<Element>>,
+    focus_fallback: MutNullableHeap<JS<Element>>,
+    /// How the user last interacted with the document, which decides whether focus is shown.
+    input_modality: Cell<InputModality>,
+    /// The caret in the text of the focused editing host, in UTF-8 bytes.
+    editing_caret: Cell<usize>,
+    /// The end of the selection in the text of the focused editing host where it started, which
+    /// is the caret unless the vdom server selected a range.
+    editing_anchor: Cell<usize>,
+    /// The selection changes of the focused editing host not reported to the vdom server yet.
+    vdom_selection: DOMRefCell<SelectionReports>,
+    /// https://html.spec.whatwg.org/multipage/#designMode
+    design_mode: Cell<bool>,
     /// Number of stylesheets that block executing the next parser-inserted script
     script_blocking_stylesheets_count: Cell<u32>,
     /// https://html.spec.whatwg.org/multipage/#concept-n-noscript
@@ -164,6 +235,53 @@ pub struct Document {
     modified_elements: DOMRefCell<HashMap<JS<Element>, ElementSnapshot>>,
     /// http://w3c.github.io/touch-events/#dfn-active-touch-point
     active_touch_points: DOMRefCell<Vec<JS<Touch>>>,
+    /// The state of the current mouse driven drag-and-drop operation.
+    drag_state: Cell<DragState>,
+    /// The draggable element the current drag started from.
+    drag_source: MutNullableHeap<JS<Element>>,
+    /// Where the mouse was pressed over the drag source.
+    drag_origin: Cell<Point2D<f32>>,
+    /// The element that accepted the drop by canceling the last `dragover`.
+    drop_target: MutNullableHeap<JS<Element>>,
+    /// https://html.spec.whatwg.org/multipage/#drag-data-store
+    drag_data: DOMRefCell<DragData>,
+    /// The size of the nodes of this document, as of the last memory report.
+    dom_tree_size: SizeCache,
+    /// Key/value pairs the vdom server keeps with this document between patch batches.
+    vdom_state: DOMRefCell<HashMap<String, String>>,
+    /// The mutations made by vdom patches since the oldest checkpoint the server may revert to.
+    vdom_history: DOMRefCell<UndoLog>,
+    /// The color scheme the vdom server prefers for this document over the platform's.
+    vdom_color_scheme: Cell<Option<ColorScheme>>,
+    /// The ids of nodes recently removed from this document, if the vdom server asked for them.
+    vdom_tombstones: DOMRefCell<Tombstones>,
+    /// The atoms for the attribute names vdom patches to this document recently used.
+    vdom_atoms: DOMRefCell<AtomCache>,
+    /// The shells of removed elements that vdom patches can reuse, by local name.
+    vdom_pool: DOMRefCell<ElementPool<Atom, Root<Element>>>,
+    /// The session history entries the vdom server created for this document.
+    vdom_session_history: DOMRefCell<SessionHistory>,
+    /// The elements whose visibility the vdom server watches.
+    vdom_visibility: DOMRefCell<VisibilityWatch>,
+    /// The elements whose size the vdom server watches.
+    vdom_resizes: DOMRefCell<ResizeWatch>,
+    /// The throttle of the animation frame ticks the vdom server subscribed to, if it did.
+    vdom_ticks: DOMRefCell<Option<TickThrottle>>,
+    /// The ids of the nodes whose stylesheets the vdom server put in cascade order.
+    vdom_stylesheet_order: DOMRefCell<Vec<u64>>,
+    /// How the vdom server chose to have events of each type delivered.
+    vdom_event_delivery: Cell<EventDelivery>,
+    /// The element with a title the mouse is on, whose tooltip the vdom server is sent once the
+    /// mouse dwelt on it.
+    vdom_tooltip: DOMRefCell<TooltipDwell>,
+    /// The `mousemove` events held back from the vdom server, if it asked for them to be
+    /// coalesced.
+    vdom_mouse_moves: DOMRefCell<MouseMoves>,
+    /// The timers of the vdom client armed with the scheduler.
+    vdom_timers: DOMRefCell<VdomTimers>,
+    /// How many nodes the vdom server froze, so that mutations only look for a frozen ancestor
+    /// while there may be one.
+    vdom_frozen_nodes: Cell<u32>,
     /// DOM-Related Navigation Timing properties:
     /// http://w3c.github.io/navigation-timing/#widl-PerformanceTiming-domLoading
     dom_loading: Cell<u64>,
@@ -282,8 +400,13 @@ impl Document {
     }
 
     // https://dom.spec.whatwg.org/#concept-document-url
-    pub fn url(&self) -> &Url {
-        &self.url
+    pub fn url(&self) -> Url {
+        self.url.borrow().clone()
+    }
+
+    /// Changes the URL without a navigation, i.e. for a session history entry.
+    pub fn set_url(&self, url: Url) {
+        *self.url.borrow_mut() = url;
     }
 
     // https://html.spec.whatwg.org/multipage/#fallback-base-url
@@ -291,7 +414,7 @@ impl Document {
         // Step 1: iframe srcdoc (#4767).
         // Step 2: about:blank with a creator browsing context.
         // Step 3.
-        self.url().clone()
+        self.url()
     }
 
     // https://html.spec.whatwg.org/multipage/#document-base-url
@@ -332,6 +455,15 @@ impl Document {
         self.base_element.set(base.r());
     }
 
+    pub fn content_type(&self) -> Ref<DOMString> {
+        self.content_type.borrow()
+    }
+
+    /// Sets the content type the vdom server declared for the document after creating it.
+    pub fn set_content_type(&self, content_type: DOMString) {
+        *self.content_type.borrow_mut() = content_type;
+    }
+
     pub fn set_encoding_name(&self, name: DOMString) {
         *self.encoding_name.borrow_mut() = DOMString::from(
             match name.as_ref() {
@@ -469,6 +601,38 @@ impl Document {
     pub fn register_node_id(&self, id: u64, node: &Node) {
         let mut node_id_map = self.node_id_map.borrow_mut();
         node_id_map.insert(id, JS::from_ref(node));
+        self.vdom_tombstones.borrow_mut().forget(id);
+    }
+
+    pub fn unregister_node_id(&self, id: u64, node: &Node) {
+        let mut node_id_map = self.node_id_map.borrow_mut();
+        if node_id_map.get(&id).map_or(false, |registered| &**registered == node) {
+            node_id_map.remove(&id);
+            self.node_id_removals.set(self.node_id_removals.get() + 1);
+            self.vdom_tombstones.borrow_mut().bury(id, time::precise_time_ns());
+        }
+    }
+
+    /// Records a mutation of the given kinds, for the live collections created from selectors.
+    pub fn note_live_mutation(&self, kinds: MutationKinds) {
+        let mut versions = self.live_versions.get();
+        versions.note(kinds);
+        self.live_versions.set(versions);
+    }
+
+    /// A version that changes whenever a mutation of one of the given kinds happens.
+    pub fn live_version(&self, kinds: MutationKinds) -> u64 {
+        self.live_versions.get().version(kinds)
+    }
+
+    /// Shrinks the id registry if many ids were removed from it since it last was, i.e. after the
+    /// vdom server removed a large subtree, so it doesn't keep the memory it grew to.
+    pub fn compact_registry(&self) {
+        let mut removals = self.node_id_removals.get();
+        if registry::compact(&mut self.node_id_map.borrow_mut(), &mut removals) {
+            debug!("compacted the vdom id registry to {} ids", self.node_id_map.borrow().len());
+        }
+        self.node_id_removals.set(removals);
     }
 
     /// Attempt to find a named element in this page's document.
@@ -552,9 +716,15 @@ impl Document {
     /// Reassign the focus context to the element that last requested focus during this
     /// transaction, or none if no elements requested it.
     pub fn commit_focus_transaction(&self, focus_type: FocusType) {
+        let active_element = self.active_element_id();
+        self.move_focus(focus_type);
+        self.report_active_element(active_element);
+    }
 
+    fn move_focus(&self, focus_type: FocusType) {
         if let Some(ref elem) = self.focused.get() {
             let node = elem.upcast::<Node>();
+            elem.set_focus_visible_state(false);
             elem.set_focus_state(false);
             // FIXME: pass appropriate relatedTarget
             self.fire_focus_event(FocusEventType::Blur, node, None);
@@ -563,6 +733,11 @@ impl Document {
         self.focused.set(self.possibly_focused.get().r());
 
         if let Some(ref elem) = self.focused.get() {
+            // Editing starts at the end of the text, where the caret is clamped to.
+            self.editing_caret.set(usize::MAX);
+            self.editing_anchor.set(usize::MAX);
+            self.report_editing_selection();
+            elem.set_focus_visible_state(self.input_modality.get().focus_visible());
             elem.set_focus_state(true);
             let node = elem.upcast::<Node>();
             // FIXME: pass appropriate relatedTarget
@@ -577,6 +752,72 @@ impl Document {
         }
     }
 
+    /// The vdom id of the active element, or 0 if there is none.
+    pub fn active_element_id(&self) -> u64 {
+        self.GetActiveElement().map_or(0, |elem| elem.upcast::<Node>().get_id())
+    }
+
+    /// Tells the vdom server about the active element if it changed from the one with
+    /// `previous` id.
+    fn report_active_element(&self, previous: u64) {
+        let active_element = self.active_element_id();
+        if active_element != previous {
+            self.window.vdom_chan().send(ServerMsg::ActiveElementChanged(active_element));
+        }
+    }
+
+    /// Called when the focused element is removed from the document, from `parent`. Focus is
+    /// reset to the body; as events can't be fired in the middle of the removal, moving it on
+    /// is left to `fix_up_focus`.
+    pub fn focused_element_removed(&self, elem: &Element, parent: &Node) {
+        elem.set_focus_visible_state(false);
+        elem.set_focus_state(false);
+        self.focused.set(None);
+        self.possibly_focused.set(None);
+        self.removed_focus.set(Some(elem));
+        let fallback = parent.inclusive_ancestors()
+                             .filter_map(Root::downcast::<Element>)
+                             .find(|ancestor| ancestor.is_focusable_area());
+        self.focus_fallback.set(fallback.r());
+    }
+
+    /// Gives focus back to a focused element that was moved, which removing it from its old
+    /// place took away, without firing events: as far as the page can tell it never lost focus.
+    pub fn keep_focus(&self, elem: &Element, focus_visible: bool) {
+        if self.removed_focus.get().r() == Some(elem) {
+            self.removed_focus.set(None);
+            self.focus_fallback.set(None);
+        }
+        self.focused.set(Some(elem));
+        elem.set_focus_state(true);
+        elem.set_focus_visible_state(focus_visible);
+    }
+
+    /// Finishes moving focus away from a focused element that was removed: fires `blur` and
+    /// `focusout` at it, then focuses its closest focusable ancestor still in the document,
+    /// leaving the body active if there is none.
+    pub fn fix_up_focus(&self) {
+        let removed = match self.removed_focus.get() {
+            Some(removed) => removed,
+            None => return
+        };
+        self.removed_focus.set(None);
+        let fallback = self.focus_fallback.get();
+        self.focus_fallback.set(None);
+
+        let node = removed.upcast::<Node>();
+        self.fire_focus_event(FocusEventType::Blur, node, None);
+        self.fire_focus_event(FocusEventType::FocusOut, node, None);
+        if let Some(ref fallback) = fallback {
+            if fallback.upcast::<Node>().is_in_doc() {
+                self.begin_focus_transaction();
+                self.request_focus(fallback);
+                self.move_focus(FocusType::Element);
+            }
+        }
+        self.report_active_element(node.get_id());
+    }
+
     /// Handles any updates when the document's title has changed.
     pub fn title_changed(&self) {
         self.send_title_to_compositor();
@@ -608,6 +849,11 @@ impl Document {
             MouseEventType::MouseDown => "mousedown".to_owned(),
         };
         debug!("{}: at {:?}", mouse_event_type_string, client_point);
+        self.input_modality.set(InputModality::Pointer);
+
+        if self.handle_drag_mouse_event(mouse_event_type, client_point) {
+            return;
+        }
 
         let page_point = Point2D::new(client_point.x + self.window.PageXOffset() as f32,
                                       client_point.y + self.window.PageYOffset() as f32);
@@ -629,9 +875,21 @@ impl Document {
                 }
             },
         };
+        let el = match self.image_map_area(&el, page_point) {
+            Some(area) => Root::upcast(area),
+            None => el,
+        };
 
         let node = el.upcast::<Node>();
         debug!("{} on {:?}", mouse_event_type_string, node.debug_str());
+        if let MouseEventType::MouseDown = mouse_event_type {
+            let source = node.inclusive_ancestors()
+                             .filter_map(Root::downcast::<Element>)
+                             .find(|el| el.is_draggable());
+            self.drag_state.set(if source.is_some() { DragState::Pending } else { DragState::Idle });
+            self.drag_source.set(source.r());
+            self.drag_origin.set(client_point);
+        }
         // Prevent click event if form control element is disabled.
         if let MouseEventType::Click = mouse_event_type {
             if el.click_event_filter_by_disabled_state() {
@@ -639,6 +897,9 @@ impl Document {
             }
 
             self.begin_focus_transaction();
+            if let Some(host) = el.editing_host() {
+                self.request_focus(&host);
+            }
         }
 
         // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#event-type-click
@@ -681,6 +942,84 @@ impl Document {
                            ReflowReason::MouseEvent);
     }
 
+    /// Edits the text of the focused editing host from a key press. When the text changes, fires
+    /// `input` at the host and reports the new text to the vdom server.
+    fn edit_content(&self, host: &Element, key: Key, modifiers: KeyModifiers) {
+        let command = match EditCommand::from_key(key, modifiers) {
+            Some(command) => command,
+            None => return,
+        };
+        let (anchor, caret, text) = edit_host(host, self.editing_anchor.get(), self.editing_caret.get(), &command);
+        self.editing_anchor.set(anchor);
+        self.editing_caret.set(caret);
+        self.report_editing_selection();
+        if let Some(text) = text {
+            host.upcast::<EventTarget>().fire_event("input",
+                                                    EventBubbles::Bubbles,
+                                                    EventCancelable::NotCancelable);
+            self.window.vdom_chan().send(ServerMsg::ContentEdited(host.upcast::<Node>().get_id(), text));
+        }
+    }
+
+    fn focused_editing_host(&self) -> Option<Root<Element>> {
+        self.focused.get().and_then(|focused| focused.editing_host())
+    }
+
+    /// The focused editing host, with the anchor and focus of the selection in its text.
+    pub fn editing_selection(&self) -> Option<(Root<Element>, usize, usize)> {
+        self.focused_editing_host().map(|host| {
+            let anchor = clamp_to_host(&host, self.editing_anchor.get());
+            let focus = clamp_to_host(&host, self.editing_caret.get());
+            (host, anchor, focus)
+        })
+    }
+
+    /// Selects the text of an editing host from `anchor` to `focus`, both offsets in its text,
+    /// moving the caret to `focus`. The host is focused first if it isn't already.
+    pub fn set_editing_selection(&self, host: &Element, anchor: usize, focus: usize) {
+        if self.focused_editing_host().r() != Some(host) {
+            self.begin_focus_transaction();
+            self.request_focus(host);
+            self.commit_focus_transaction(FocusType::Element);
+        }
+        self.editing_anchor.set(anchor);
+        self.editing_caret.set(focus);
+        self.report_editing_selection();
+    }
+
+    /// The ends of the selection of the focused editing host, as nodes and offsets in them.
+    pub fn editing_selection_ends(&self) -> Option<(SelectionEnd, SelectionEnd)> {
+        self.editing_selection().map(|(host, anchor, focus)| (selection_end(&host, anchor), selection_end(&host, focus)))
+    }
+
+    /// Records that the selection of the focused editing host may have moved, to be reported
+    /// by `flush_vdom_selection`.
+    fn report_editing_selection(&self) {
+        if let Some((anchor, focus)) = self.editing_selection_ends() {
+            self.vdom_selection.borrow_mut().changed(anchor, focus);
+        }
+    }
+
+    /// Tells the vdom server where the selection of the focused editing host is, if it moved
+    /// since the events handled before. Called once the script thread handled a batch of them.
+    pub fn flush_vdom_selection(&self) {
+        if let Some(((anchor, anchor_offset), (focus, focus_offset))) = self.vdom_selection.borrow_mut().flush() {
+            self.window.vdom_chan().send(ServerMsg::SelectionChanged(anchor, anchor_offset, focus, focus_offset));
+        }
+    }
+
+    /// The area of an image map that mouse events at `page_point` on `el` go to instead, if
+    /// `el` is an image using a map.
+    fn image_map_area(&self, el: &Element, page_point: Point2D<f32>) -> Option<Root<HTMLAreaElement>> {
+        let image = match el.downcast::<HTMLImageElement>() {
+            Some(image) => image,
+            None => return None,
+        };
+        let content_box = image.upcast::<Node>().get_bounding_content_box();
+        image.area_at(page_point.x - content_box.origin.x.to_f32_px(),
+                      page_point.y - content_box.origin.y.to_f32_px())
+    }
+
     pub fn fire_mouse_event(&self, client_point: Point2D<f32>, target: &EventTarget, event_name: String) {
         let client_x = client_point.x.to_i32().unwrap_or(0);
         let client_y = client_point.y.to_i32().unwrap_or(0);
@@ -712,6 +1051,7 @@ impl Document {
                 // If there's no point, there's no target under the mouse
                 // FIXME: dispatch mouseout here. We have no point.
                 prev_mouse_over_target.set(None);
+                self.hover_vdom_tooltip(None);
                 return;
             }
             Some(ref client_point) => {
@@ -729,6 +1069,10 @@ impl Document {
                 .next()
         });
 
+        if self.handle_drag_mouse_move(client_point, maybe_new_target.r()) {
+            return;
+        }
+
         // Send mousemove event to topmost target
         if let Some(ref new_target) = maybe_new_target {
             self.fire_mouse_event(client_point, new_target.upcast(), "mousemove".to_owned());
@@ -748,6 +1092,7 @@ impl Document {
 
         // Here we know the target has changed, so we must update the state,
         // dispatch mouseout to the previous one, mouseover to the new one,
+        let mut hover_changes = HoverChanges::new();
         if let Some(old_target) = prev_mouse_over_target.get() {
             // If the old target is an ancestor of the new target, this can be skipped
             // completely, since the node's hover state will be reseted below.
@@ -755,7 +1100,7 @@ impl Document {
                 for element in old_target.upcast::<Node>()
                                          .inclusive_ancestors()
                                          .filter_map(Root::downcast::<Element>) {
-                    element.set_hover_state(false);
+                    set_hover_state(&element, false, &mut hover_changes);
                 }
             }
 
@@ -774,7 +1119,7 @@ impl Document {
                     break;
                 }
 
-                element.set_hover_state(true);
+                set_hover_state(&element, true, &mut hover_changes);
             }
 
             self.fire_mouse_event(client_point, &new_target.upcast(), "mouseover".to_owned());
@@ -782,6 +1127,11 @@ impl Document {
             // TODO: Fire mouseenter here.
         }
 
+        for (id, hovered) in hover_changes.take() {
+            self.window.vdom_chan().send(ServerMsg::HoverChanged(id, hovered));
+        }
+        self.hover_vdom_tooltip(maybe_new_target.r());
+
         // Store the current mouse over target for next frame.
         prev_mouse_over_target.set(maybe_new_target.as_ref().map(|target| target.r()));
 
@@ -790,11 +1140,148 @@ impl Document {
                            ReflowReason::MouseEvent);
     }
 
+    /// Ends a drag on mouseup and swallows the click that follows it. Returns true if the
+    /// event was consumed by the drag.
+    fn handle_drag_mouse_event(&self, mouse_event_type: MouseEventType, client_point: Point2D<f32>) -> bool {
+        match (mouse_event_type, self.drag_state.get()) {
+            (MouseEventType::MouseUp, DragState::Dragging) => {
+                self.finish_drag(client_point);
+                true
+            },
+            (MouseEventType::MouseUp, DragState::Pending) => {
+                self.drag_state.set(DragState::Idle);
+                self.drag_source.set(None);
+                false
+            },
+            (MouseEventType::Click, DragState::Finished) => {
+                self.drag_state.set(DragState::Idle);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Starts a pending drag once the mouse moved past the threshold, then fires `dragover` at
+    /// the element under the pointer. Returns true if the move belongs to a drag and must not be
+    /// dispatched as a regular mouse move.
+    fn handle_drag_mouse_move(&self, client_point: Point2D<f32>, target: Option<&Element>) -> bool {
+        if self.drag_state.get() == DragState::Pending {
+            let moved = client_point - self.drag_origin.get();
+            if moved.x * moved.x + moved.y * moved.y < DRAG_THRESHOLD * DRAG_THRESHOLD {
+                return false;
+            }
+            let source = match self.drag_source.get() {
+                Some(source) => source,
+                None => {
+                    self.drag_state.set(DragState::Idle);
+                    return false;
+                }
+            };
+            // https://html.spec.whatwg.org/multipage/#drag-and-drop-processing-model
+            // The drag carries the data the vdom server primed its source with. Canceling
+            // dragstart aborts the drag.
+            *self.drag_data.borrow_mut() = source.vdom_drag_data();
+            if self.fire_drag_event("dragstart", source.upcast(), client_point, EventCancelable::Cancelable) {
+                self.drag_state.set(DragState::Idle);
+                self.drag_source.set(None);
+                return false;
+            }
+            self.drag_state.set(DragState::Dragging);
+        }
+
+        if self.drag_state.get() != DragState::Dragging {
+            return false;
+        }
+
+        // Canceling dragover marks the element as a drop target.
+        let drop_target = target.and_then(|target| {
+            if self.fire_drag_event("dragover", target.upcast(), client_point, EventCancelable::Cancelable) {
+                Some(target)
+            } else {
+                None
+            }
+        });
+        self.drop_target.set(drop_target);
+        true
+    }
+
+    /// Fires `drop` at the accepting drop target, if any, and `dragend` at the drag source.
+    fn finish_drag(&self, client_point: Point2D<f32>) {
+        if let Some(target) = self.drop_target.get() {
+            self.fire_drag_event("drop", target.upcast(), client_point, EventCancelable::Cancelable);
+        }
+        if let Some(source) = self.drag_source.get() {
+            self.fire_drag_event("dragend", source.upcast(), client_point, EventCancelable::NotCancelable);
+        }
+
+        self.drag_state.set(DragState::Finished);
+        self.drag_source.set(None);
+        self.drop_target.set(None);
+        self.drag_data.borrow_mut().clear();
+
+        self.window.reflow(ReflowGoal::ForDisplay,
+                           ReflowQueryType::NoQuery,
+                           ReflowReason::MouseEvent);
+    }
+
+    /// Fires a drag event carrying the current drag data. Returns true if it was canceled.
+    fn fire_drag_event(&self,
+                       name: &str,
+                       target: &EventTarget,
+                       client_point: Point2D<f32>,
+                       cancelable: EventCancelable) -> bool {
+        let data = self.drag_data.borrow().clone();
+        let event = DragEvent::new(DOMString::from(name),
+                                   EventBubbles::Bubbles,
+                                   cancelable,
+                                   Some(&self.window),
+                                   client_point.x as i32,
+                                   client_point.y as i32,
+                                   data);
+        !event.upcast::<Event>().fire(target)
+    }
+
+    /// Fires a `copy`, `cut` or `paste` event. Copied and cut events carry the selected text,
+    /// pasted ones the clipboard contents; the focused text control performs the operation
+    /// as the default action. Returns true if the event was canceled.
+    ///
+    /// https://w3c.github.io/clipboard-apis/#fire-a-clipboard-event
+    fn fire_clipboard_event(&self, name: &str, target: &EventTarget) -> bool {
+        let text = if name == "paste" {
+            Some(self.window.constellation_chan().clipboard_contents())
+        } else {
+            target.downcast::<Element>().and_then(|el| el.selection_text())
+        };
+
+        let mut data = DragData::new();
+        if let Some(text) = text {
+            data.set_data("text/plain", &text);
+        }
+        let event = ClipboardEvent::new(DOMString::from(name),
+                                        EventBubbles::Bubbles,
+                                        EventCancelable::Cancelable,
+                                        data);
+        !self.fire_with_default_action(event.upcast(), target)
+    }
+
+    /// Fires an event at a target that performs a default action for it, as text controls do
+    /// for composition and clipboard events. Returns false if the event was canceled.
+    fn fire_with_default_action(&self, event: &Event, target: &EventTarget) -> bool {
+        let allowed = event.fire(target);
+        if let Some(node) = target.downcast::<Node>() {
+            vtable_for(node).handle_event(event);
+        }
+        allowed
+    }
+
+    /// Fires a touch event at the element under the touch point. Returns false if its default
+    /// action was prevented, which it can't be if the vdom server listens to it passively.
     pub fn handle_touch_event(&self,
                               event_type: TouchEventType,
                               TouchId(identifier): TouchId,
                               point: Point2D<f32>)
                               -> bool {
+        self.input_modality.set(InputModality::Pointer);
         let event_name = match event_type {
             TouchEventType::Down => "touchstart",
             TouchEventType::Move => "touchmove",
@@ -816,6 +1303,8 @@ impl Document {
                 }
             },
         };
+        let event_set = EventSet::from_name(event_name).unwrap();
+        let passive = events::listener_passive(el.upcast(), event_set);
         let target = Root::upcast::<EventTarget>(el);
         let window = &*self.window;
 
@@ -886,12 +1375,76 @@ impl Document {
                                     false,
                                     false);
         let event = event.upcast::<Event>();
-        let result = event.fire(target.r());
+        let prevented = !event.fire(target.r());
 
         window.reflow(ReflowGoal::ForDisplay,
                       ReflowQueryType::NoQuery,
                       ReflowReason::MouseEvent);
-        result
+        events::default_allowed(event_set, prevented, passive)
+    }
+
+    /// Fires a `wheel` event at the element under the pointer. Returns false if the default
+    /// scroll was prevented, which it can't be if the vdom server listens to it passively.
+    pub fn handle_wheel_event(&self, delta: Point2D<f32>, point: Point2D<f32>) -> bool {
+        let node = match self.window.hit_test_query(point, false) {
+            Some(node_address) => node::from_untrusted_node_address(node_address),
+            None => return true,
+        };
+        let el = match node.downcast::<Element>() {
+            Some(el) => Root::from_ref(el),
+            None => {
+                let parent = node.GetParentNode();
+                match parent.and_then(Root::downcast::<Element>) {
+                    Some(parent) => parent,
+                    None => return true,
+                }
+            },
+        };
+        debug!("wheel by {:?} on {:?}", delta, el.upcast::<Node>().debug_str());
+
+        let event = WheelEvent::new(DOMString::from("wheel"),
+                                    EventBubbles::Bubbles,
+                                    EventCancelable::Cancelable,
+                                    Some(&self.window),
+                                    point.x as i32,
+                                    point.y as i32,
+                                    delta.x as f64,
+                                    delta.y as f64,
+                                    0.0,
+                                    WheelEventConstants::DOM_DELTA_PIXEL);
+        let prevented = !event.upcast::<Event>().fire(el.upcast());
+        events::default_allowed(events::WHEEL, prevented, events::listener_passive(el.upcast(), events::WHEEL))
+    }
+
+    /// The entry point for IME composition events, which are fired at the focused element.
+    pub fn dispatch_composition_event(&self, event_type: CompositionEventType, data: String) {
+        let focused = self.get_focused_element();
+        let body = self.GetBody();
+
+        let target = match (&focused, &body) {
+            (&Some(ref focused), _) => focused.upcast(),
+            (&None, &Some(ref body)) => body.upcast(),
+            (&None, &None) => self.window.upcast(),
+        };
+
+        // https://w3c.github.io/uievents/#events-composition-canceling
+        let (ev_type, data) = match event_type {
+            CompositionEventType::Start => ("compositionstart", data),
+            CompositionEventType::Update => ("compositionupdate", data),
+            CompositionEventType::End => ("compositionend", data),
+            CompositionEventType::Cancel => ("compositionend", String::new()),
+        };
+
+        let event = CompositionEvent::new(DOMString::from(ev_type),
+                                          EventBubbles::Bubbles,
+                                          EventCancelable::Cancelable,
+                                          Some(&self.window),
+                                          DOMString::from(data));
+        self.fire_with_default_action(event.upcast(), target);
+
+        self.window.reflow(ReflowGoal::ForDisplay,
+                           ReflowQueryType::NoQuery,
+                           ReflowReason::KeyEvent);
     }
 
     /// The entry point for all key processing for web content
@@ -900,6 +1453,7 @@ impl Document {
                               state: KeyState,
                               modifiers: KeyModifiers,
                               compositor: &mut IpcSender<ScriptToCompositorMsg>) {
+        self.input_modality.set(InputModality::Keyboard);
         let focused = self.get_focused_element();
         let body = self.GetBody();
 
@@ -971,6 +1525,18 @@ impl Document {
             // TODO: if keypress event is canceled, prevent firing input events
         }
 
+        if state != KeyState::Released && !prevented {
+            if let Some(name) = clipboard_event_type(key, modifiers) {
+                prevented = self.fire_clipboard_event(name, target);
+            }
+        }
+
+        if state != KeyState::Released && !prevented {
+            if let Some(host) = focused.as_ref().and_then(|focused| focused.editing_host()) {
+                self.edit_content(&host, key, modifiers);
+            }
+        }
+
         if !prevented {
             compositor.send(ScriptToCompositorMsg::SendKeyEvent(key, state, modifiers)).unwrap();
         }
@@ -1074,7 +1640,7 @@ impl Document {
     /// https://html.spec.whatwg.org/multipage/#dom-window-cancelanimationframe
     pub fn cancel_animation_frame(&self, ident: u32) {
         self.animation_frame_list.borrow_mut().remove(&ident);
-        if self.animation_frame_list.borrow().is_empty() {
+        if !self.wants_animation_frames() {
             let ConstellationChan(ref chan) = self.window.constellation_chan();
             let event = ConstellationMsg::ChangeRunningAnimationsState(self.window.pipeline(),
                                                                        AnimationState::NoAnimationCallbacksPresent);
@@ -1082,6 +1648,47 @@ impl Document {
         }
     }
 
+    /// Whether animation frames have to run, for callbacks or for the ticks of the vdom server.
+    fn wants_animation_frames(&self) -> bool {
+        !self.animation_frame_list.borrow().is_empty() || self.vdom_ticks.borrow().is_some()
+    }
+
+    /// How the vdom server chose to have events of each type delivered.
+    pub fn vdom_event_delivery(&self) -> EventDelivery {
+        self.vdom_event_delivery.get()
+    }
+
+    /// Delivers the events of the types in `events` to the vdom server the given way.
+    pub fn set_vdom_event_delivery(&self, events: EventSet, delivery: Delivery) {
+        let mut event_delivery = self.vdom_event_delivery.get();
+        event_delivery.set(events, delivery);
+        self.vdom_event_delivery.set(event_delivery);
+    }
+
+    /// Whether any node of this document was frozen by the vdom server and not unfrozen since.
+    pub fn has_vdom_frozen_nodes(&self) -> bool {
+        self.vdom_frozen_nodes.get() > 0
+    }
+
+    /// Counts a node the vdom server froze, or unfroze.
+    pub fn count_vdom_frozen_node(&self, frozen: bool) {
+        let count = self.vdom_frozen_nodes.get();
+        self.vdom_frozen_nodes.set(if frozen { count + 1 } else { count - 1 });
+    }
+
+    /// Subscribes the vdom server to animation frame ticks, sent at most once per `interval` in
+    /// milliseconds, or unsubscribes it.
+    pub fn subscribe_vdom_ticks(&self, interval: Option<u32>) {
+        *self.vdom_ticks.borrow_mut() = interval.map(TickThrottle::new);
+        let state = if self.wants_animation_frames() {
+            AnimationState::AnimationCallbacksPresent
+        } else {
+            AnimationState::NoAnimationCallbacksPresent
+        };
+        let ConstellationChan(ref chan) = self.window.constellation_chan();
+        chan.send(ConstellationMsg::ChangeRunningAnimationsState(self.window.pipeline(), state)).unwrap();
+    }
+
     /// https://html.spec.whatwg.org/multipage/#run-the-animation-frame-callbacks
     pub fn run_the_animation_frame_callbacks(&self) {
         let animation_frame_list =
@@ -1091,11 +1698,17 @@ impl Document {
             callback(0.0);
         }
 
+        let now = time::precise_time_ns();
+        let tick = self.vdom_ticks.borrow_mut().as_mut().map_or(false, |throttle| throttle.tick(now));
+        if tick {
+            self.window.vdom_chan().send(ServerMsg::Tick(now as f64 / 1000000.0));
+        }
+
         // Only send the animation change state message after running any callbacks.
         // This means that if the animation callback adds a new callback for
         // the next frame (which is the common case), we won't send a NoAnimationCallbacksPresent
         // message quickly followed by an AnimationCallbacksPresent message.
-        if self.animation_frame_list.borrow().is_empty() {
+        if !self.wants_animation_frames() {
             let ConstellationChan(ref chan) = self.window.constellation_chan();
             let event = ConstellationMsg::ChangeRunningAnimationsState(self.window.pipeline(),
                                                                        AnimationState::NoAnimationCallbacksPresent);
@@ -1204,6 +1817,7 @@ impl Document {
         let (event_name, does_bubble) = match focus_event_type {
             FocusEventType::Focus => (DOMString::from("focus"), EventBubbles::DoesNotBubble),
             FocusEventType::Blur => (DOMString::from("blur"), EventBubbles::DoesNotBubble),
+            FocusEventType::FocusOut => (DOMString::from("focusout"), EventBubbles::Bubbles),
         };
         let event = FocusEvent::new(event_name,
                                     does_bubble,
@@ -1269,7 +1883,7 @@ impl Document {
             window: JS::from_ref(window),
             browsing_context: browsing_context.map(JS::from_ref),
             implementation: Default::default(),
-            content_type: match content_type {
+            content_type: DOMRefCell::new(match content_type {
                 Some(string) => string,
                 None => DOMString::from(match is_html_document {
                     // https://dom.spec.whatwg.org/#dom-domimplementation-createhtmldocument
@@ -1277,13 +1891,16 @@ impl Document {
                     // https://dom.spec.whatwg.org/#concept-document-content-type
                     IsHTMLDocument::NonHTMLDocument => "application/xml",
                 }),
-            },
+            }),
             last_modified: last_modified,
-            url: url,
+            vdom_session_history: DOMRefCell::new(SessionHistory::new(url.serialize())),
+            url: DOMRefCell::new(url),
             // https://dom.spec.whatwg.org/#concept-document-encoding
             encoding_name: DOMRefCell::new(DOMString::from("UTF-8")),
             is_html_document: is_html_document == IsHTMLDocument::HTMLDocument,
             node_id_map: DOMRefCell::new(HashMap::new()),
+            node_id_removals: Cell::new(0),
+            live_versions: Cell::new(MutationVersions::new()),
             id_map: DOMRefCell::new(HashMap::new()),
             tag_map: DOMRefCell::new(HashMap::new()),
             tagns_map: DOMRefCell::new(HashMap::new()),
@@ -1300,6 +1917,13 @@ impl Document {
             domcontentloaded_dispatched: Cell::new(domcontentloaded_dispatched),
             possibly_focused: Default::default(),
             focused: Default::default(),
+            removed_focus: Default::default(),
+            focus_fallback: Default::default(),
+            input_modality: Cell::new(InputModality::Keyboard),
+            editing_caret: Cell::new(0),
+            editing_anchor: Cell::new(0),
+            vdom_selection: DOMRefCell::new(SelectionReports::new()),
+            design_mode: Cell::new(false),
             script_blocking_stylesheets_count: Cell::new(0u32),
             scripting_enabled: Cell::new(true),
             animation_frame_ident: Cell::new(0),
@@ -1310,6 +1934,27 @@ impl Document {
             appropriate_template_contents_owner_document: Default::default(),
             modified_elements: DOMRefCell::new(HashMap::new()),
             active_touch_points: DOMRefCell::new(Vec::new()),
+            drag_state: Cell::new(DragState::Idle),
+            drag_source: Default::default(),
+            drag_origin: Cell::new(Point2D::zero()),
+            drop_target: Default::default(),
+            drag_data: DOMRefCell::new(DragData::new()),
+            dom_tree_size: SizeCache::new(),
+            vdom_state: DOMRefCell::new(HashMap::new()),
+            vdom_history: DOMRefCell::new(UndoLog::history()),
+            vdom_color_scheme: Cell::new(None),
+            vdom_tombstones: DOMRefCell::new(Tombstones::new()),
+            vdom_atoms: DOMRefCell::new(AtomCache::new()),
+            vdom_pool: DOMRefCell::new(ElementPool::new()),
+            vdom_visibility: DOMRefCell::new(VisibilityWatch::new()),
+            vdom_resizes: DOMRefCell::new(ResizeWatch::new()),
+            vdom_ticks: DOMRefCell::new(None),
+            vdom_stylesheet_order: DOMRefCell::new(vec![]),
+            vdom_event_delivery: Cell::new(EventDelivery::new()),
+            vdom_tooltip: DOMRefCell::new(TooltipDwell::new()),
+            vdom_mouse_moves: DOMRefCell::new(MouseMoves::new()),
+            vdom_timers: DOMRefCell::new(VdomTimers::new()),
+            vdom_frozen_nodes: Cell::new(0),
             dom_loading: Cell::new(Default::default()),
             dom_interactive: Cell::new(Default::default()),
             dom_content_loaded_event_start: Cell::new(Default::default()),
@@ -1378,10 +2023,10 @@ impl Document {
         {
             let mut stylesheets = self.stylesheets.borrow_mut();
             if stylesheets.is_none() {
-                let new_stylesheets: Vec<Arc<Stylesheet>> = self.upcast::<Node>()
+                let new_stylesheets: Vec<(u64, Arc<Stylesheet>)> = self.upcast::<Node>()
                     .traverse_preorder()
                     .filter_map(|node| {
-                        if let Some(node) = node.downcast::<HTMLStyleElement>() {
+                        let stylesheet = if let Some(node) = node.downcast::<HTMLStyleElement>() {
                             node.get_stylesheet()
                         } else if let Some(node) = node.downcast::<HTMLLinkElement>() {
                             node.get_stylesheet()
@@ -1389,10 +2034,11 @@ impl Document {
                             node.get_stylesheet()
                         } else {
                             None
-                        }
+                        };
+                        stylesheet.map(|stylesheet| (node.get_id(), stylesheet))
                     })
                     .collect();
-                *stylesheets = Some(new_stylesheets);
+                *stylesheets = Some(cascade_order(new_stylesheets, &self.vdom_stylesheet_order.borrow()));
             };
         }
         Ref::map(self.stylesheets.borrow(), |t| t.as_ref().unwrap())
@@ -1419,10 +2065,347 @@ impl Document {
         })
     }
 
+    /// The total size of the nodes of this document. The tree is only walked again if it was
+    /// mutated since the last call.
+    pub fn dom_tree_size(&self) -> usize {
+        let node = self.upcast::<Node>();
+        self.dom_tree_size.get_or_compute(node.get_inclusive_descendants_version(), || {
+            node.traverse_preorder().fold(0, |total, node| total + node.heap_size())
+        })
+    }
+
+    /// Stores a value in the vdom server's state for this document, or clears it.
+    pub fn set_vdom_state(&self, key: String, value: Option<String>) {
+        let mut state = self.vdom_state.borrow_mut();
+        match value {
+            Some(value) => {
+                state.insert(key, value);
+            },
+            None => {
+                state.remove(&key);
+            },
+        }
+    }
+
+    pub fn vdom_state(&self, key: &str) -> Option<String> {
+        self.vdom_state.borrow().get(key).cloned()
+    }
+
+    /// The log vdom patches to this document are recorded into, for reverting to checkpoints.
+    pub fn vdom_history(&self) -> RefMut<UndoLog> {
+        self.vdom_history.borrow_mut()
+    }
+
+    pub fn vdom_color_scheme(&self) -> Option<ColorScheme> {
+        self.vdom_color_scheme.get()
+    }
+
+    /// Overrides the platform's color scheme preference for this document, or stops overriding
+    /// it, restyling if that changes what `prefers-color-scheme` matches.
+    pub fn set_vdom_color_scheme(&self, color_scheme: Option<ColorScheme>) {
+        let old = self.window.color_scheme();
+        self.vdom_color_scheme.set(color_scheme);
+        if self.window.color_scheme() != old {
+            self.invalidate_stylesheets();
+        }
+    }
+
     pub fn get_node_by_id(&self, id: u64) -> Option<Root<Node>> {
         self.node_id_map.borrow().get(&id).map(|r| Root::from_ref(&**r))
     }
 
+    /// Like `get_node_by_id`, but tells a recently removed node apart from an unknown id when
+    /// the vdom server keeps tombstones.
+    pub fn lookup_node_by_id(&self, id: u64) -> Result<Root<Node>, LookupError> {
+        self.get_node_by_id(id).ok_or_else(|| {
+            self.vdom_tombstones.borrow().lookup_error(id, time::precise_time_ns())
+        })
+    }
+
+    /// The atom for an attribute name sent by the vdom server.
+    pub fn vdom_atom(&self, name: &str) -> Atom {
+        self.vdom_atoms.borrow_mut().intern(name)
+    }
+
+    /// Starts or stops keeping removed elements for reuse.
+    pub fn set_vdom_pool(&self, enabled: bool) {
+        self.vdom_pool.borrow_mut().set_enabled(enabled);
+    }
+
+    /// Cleans up after a patch removed the subtree rooted at `root` from `parent`. Unbinding the
+    /// subtree already took its ids out of the registry, and focus from it if it held it, to be
+    /// moved on by `fix_up_focus`. The selection is forgotten if its editing host was removed,
+    /// and reported again if the host only lost some of its text. Unless the removal can be
+    /// undone, the subtree's subscriptions are dropped and its elements recycled.
+    pub fn vdom_subtree_removed(&self, root: &Node, parent: &Node, undoable: bool) {
+        let removed_focus = self.removed_focus.get();
+        if removed_focus.map_or(false, |removed| root.is_inclusive_ancestor_of(removed.upcast())) {
+            self.vdom_selection.borrow_mut().forget();
+        } else if let Some(host) = self.focused_editing_host() {
+            if host.upcast::<Node>().is_inclusive_ancestor_of(parent) {
+                self.report_editing_selection();
+            }
+        }
+        if !undoable {
+            for node in root.traverse_preorder() {
+                node.drop_subscriptions();
+            }
+            self.recycle_vdom_subtree(root);
+        }
+    }
+
+    /// Keeps the elements of a subtree removed by a patch for reuse, if the server asked for it.
+    /// The subtree mustn't be put back into the document afterwards.
+    fn recycle_vdom_subtree(&self, root: &Node) {
+        let mut pool = self.vdom_pool.borrow_mut();
+        if !pool.is_enabled() {
+            return;
+        }
+        let removed_focus = self.removed_focus.get();
+        for node in root.traverse_preorder() {
+            if let Some(element) = node.downcast::<Element>() {
+                // A removed focused element is still blurred once the patches are applied.
+                if element.is_poolable() && removed_focus.r() != Some(element) {
+                    pool.put(element.local_name().clone(), Root::from_ref(element));
+                }
+            }
+        }
+    }
+
+    /// Adds a session history entry for `url` after the current one, and gives the document that
+    /// URL without a navigation.
+    pub fn push_vdom_history(&self, url: Url, state: Option<String>) {
+        self.vdom_session_history.borrow_mut().push(HistoryEntry {
+            url: url.serialize(),
+            state: state,
+        });
+        self.set_url(url);
+    }
+
+    /// Like `push_vdom_history`, but replaces the current entry.
+    pub fn replace_vdom_history(&self, url: Url, state: Option<String>) {
+        self.vdom_session_history.borrow_mut().replace(HistoryEntry {
+            url: url.serialize(),
+            state: state,
+        });
+        self.set_url(url);
+    }
+
+    pub fn observe_vdom_visibility(&self, id: u64, thresholds: Vec<f64>) {
+        self.vdom_visibility.borrow_mut().observe(id, thresholds);
+    }
+
+    /// Tells the vdom server about the watched elements whose fraction inside the viewport
+    /// crossed one of their thresholds since the last time, i.e. after a scroll or a reflow.
+    pub fn notify_vdom_visibility(&self) {
+        let ids = self.vdom_visibility.borrow().ids();
+        if ids.is_empty() {
+            return;
+        }
+        let viewport = box_rect(&self.window.current_viewport());
+        for id in ids {
+            let node = match self.get_node_by_id(id) {
+                Some(node) => node,
+                None => continue
+            };
+            let ratio = visible_ratio(&box_rect(&node.get_bounding_content_box()), &viewport);
+            if self.vdom_visibility.borrow_mut().update(id, ratio) {
+                self.window.vdom_chan().send(ServerMsg::VisibilityChanged(id, ratio));
+            }
+        }
+    }
+
+    /// Puts the stylesheets of the nodes with the given ids in that cascade order, which
+    /// restyles the document.
+    pub fn reorder_vdom_stylesheets(&self, order: Vec<u64>) {
+        *self.vdom_stylesheet_order.borrow_mut() = order;
+        self.invalidate_stylesheets();
+    }
+
+    pub fn observe_vdom_resize(&self, id: u64, observe: bool) {
+        self.vdom_resizes.borrow_mut().observe(id, observe);
+    }
+
+    /// Tells the vdom server about the watched elements whose size changed since the last time,
+    /// which is checked once layout settled after a reflow.
+    pub fn notify_vdom_resizes(&self) {
+        for id in self.vdom_resizes.borrow().ids() {
+            let node = match self.get_node_by_id(id) {
+                Some(node) => node,
+                None => continue
+            };
+            let rect = box_rect(&node.get_bounding_content_box());
+            if self.vdom_resizes.borrow_mut().update(id, rect.width, rect.height) {
+                self.window.vdom_chan().send(ServerMsg::ResizeObserved(id, rect.width, rect.height));
+            }
+        }
+    }
+
+    /// Replaces the document element with one the vdom server rendered from scratch, keeping the
+    /// document and its window. Removing the old tree unregisters its ids like any removal, before
+    /// those of the new one are registered. A loaded document becomes interactive again, then
+    /// complete with a `load` event in a task, as after it first loaded.
+    pub fn replace_vdom_document_element(&self, new: &Element, log: &mut UndoLog) -> ErrorResult {
+        let node = self.upcast::<Node>();
+        if let Some(old) = self.GetDocumentElement() {
+            log.removing(old.upcast());
+            try!(node.RemoveChild(old.upcast()));
+            self.vdom_subtree_removed(old.upcast(), node, log.recording());
+        }
+        try!(node.AppendChild(new.upcast()));
+        log.inserted(new.upcast());
+
+        if self.ready_state.get() == DocumentReadyState::Complete {
+            self.set_ready_state(DocumentReadyState::Interactive);
+            let chan = MainThreadScriptChan(self.window().main_thread_script_chan().clone()).clone();
+            let handler = box DocumentProgressHandler::after_rerender(Trusted::new(self, chan));
+            let _ = self.window().dom_manipulation_task_source().queue(DOMManipulationTask::DocumentProgress(handler));
+        }
+        Ok(())
+    }
+
+    /// Starts the dwell of the mouse on the element under it with a title, if any, after which
+    /// the vdom server is sent its tooltip.
+    fn hover_vdom_tooltip(&self, target: Option<&Element>) {
+        let titled = target.and_then(|target| {
+            target.upcast::<Node>()
+                  .inclusive_ancestors()
+                  .filter_map(Root::downcast::<Element>)
+                  .find(|element| element.has_attribute(&atom!("title")))
+        });
+        // Elements the server doesn't know about can't be reported.
+        let id = titled.map(|element| element.upcast::<Node>().get_id()).and_then(|id| {
+            if id != 0 { Some(id) } else { None }
+        });
+        let generation = match self.vdom_tooltip.borrow_mut().hover(id, time::precise_time_ns()) {
+            Some(generation) => generation,
+            None => return
+        };
+        self.schedule_vdom_timer(VdomTimer::TooltipDwell(generation), TOOLTIP_DWELL_MS);
+    }
+
+    /// Arms a vdom timer to fire after `delay` in milliseconds, in place of the one of its kind.
+    fn schedule_vdom_timer(&self, timer: VdomTimer, delay: u64) {
+        let id = self.vdom_timers.borrow_mut().arm(timer);
+        self.window.schedule_timer_event(id, Length::new(delay));
+    }
+
+    /// Runs the vdom timer the scheduler fired the event `id` for, unless it was replaced or
+    /// cancelled since.
+    pub fn handle_vdom_timer(&self, id: TimerEventId) {
+        let fired = self.vdom_timers.borrow_mut().fired(id);
+        match fired {
+            Some(VdomTimer::TooltipDwell(generation)) => self.report_vdom_tooltip(generation),
+            Some(VdomTimer::FlushMouseMoves) => {
+                self.vdom_mouse_moves.borrow_mut().scheduled_flush();
+                self.flush_vdom_mouse_moves(false);
+            },
+            None => {}
+        }
+    }
+
+    /// Sends the vdom server the tooltip of the element the mouse dwelt on since `generation`,
+    /// unless it moved since, or the element lost its title.
+    fn report_vdom_tooltip(&self, generation: u32) {
+        let id = match self.vdom_tooltip.borrow_mut().dwelt(generation, time::precise_time_ns()) {
+            Some(id) => id,
+            None => return
+        };
+        let title = self.get_node_by_id(id)
+                        .and_then(Root::downcast::<Element>)
+                        .and_then(|element| element.get_attribute(&ns!(), &atom!("title")));
+        if let Some(title) = title {
+            self.window.vdom_chan().send(ServerMsg::Tooltip(id, String::from(&**title.value())));
+        }
+    }
+
+    /// Coalesces the `mousemove` events forwarded to the vdom server per batch of events, and at
+    /// most once per `interval` in milliseconds unless it is 0, or stops if it is `None`.
+    pub fn set_vdom_move_coalescing(&self, interval: Option<u32>) {
+        self.vdom_mouse_moves.borrow_mut().set_interval(interval);
+        if interval.is_none() {
+            self.flush_vdom_mouse_moves(true);
+        }
+    }
+
+    /// Forwards a `mousemove` event to the vdom server, or holds it back to be coalesced with
+    /// the moves after it.
+    pub fn forward_vdom_mouse_move(&self, msg: EventMsg) {
+        let msg = self.vdom_mouse_moves.borrow_mut().coalesce(msg);
+        if let Some(msg) = msg {
+            self.window.vdom_chan().send(ServerMsg::Event(msg));
+        }
+    }
+
+    /// Forwards the `mousemove` events held back, if they are due or `force` is set because
+    /// another event is about to be forwarded. If they aren't due, a timer flushes them once
+    /// they are. Called once the script thread handled a batch of events.
+    pub fn flush_vdom_mouse_moves(&self, force: bool) {
+        let now = time::precise_time_ns();
+        let moves = self.vdom_mouse_moves.borrow_mut().flush(now, force);
+        if !moves.is_empty() {
+            // The moves the timer would have flushed are out already.
+            self.vdom_timers.borrow_mut().cancel(VdomTimer::FlushMouseMoves);
+            self.vdom_mouse_moves.borrow_mut().scheduled_flush();
+        }
+        for msg in moves {
+            self.window.vdom_chan().send(ServerMsg::Event(msg));
+        }
+
+        let delay = self.vdom_mouse_moves.borrow_mut().schedule(now);
+        if let Some(delay) = delay {
+            // Rounded up, so that the moves are due when the timer fires.
+            self.schedule_vdom_timer(VdomTimer::FlushMouseMoves, (delay + 999999) / 1000000);
+        }
+    }
+
+    /// Goes back to the previous session history entry in a history traversal task, which
+    /// restores its URL, fires `popstate` at the window and tells the vdom server.
+    pub fn queue_vdom_history_back(&self) {
+        let window = self.window();
+        let chan = MainThreadScriptChan(window.main_thread_script_chan().clone()).clone();
+        let runnable = box HistoryBackRunnable {
+            document: Trusted::new(self, chan),
+        };
+        let _ = window.history_traversal_task_source()
+                      .send(CommonScriptMsg::RunnableMsg(ScriptThreadEventCategory::DomEvent, runnable));
+    }
+
+    fn vdom_history_back(&self) {
+        let entry = match self.vdom_session_history.borrow_mut().back() {
+            Some(entry) => entry.clone(),
+            None => return
+        };
+        // The URL of the entry was parsed when it was created.
+        self.set_url(Url::parse(&entry.url).unwrap());
+        let window = self.window();
+        let event = PopStateEvent::new(DOMString::from("popstate"),
+                                       EventBubbles::DoesNotBubble,
+                                       EventCancelable::NotCancelable,
+                                       entry.state.clone());
+        event.upcast::<Event>().fire(window.upcast());
+        window.vdom_chan().send(ServerMsg::PopState(entry.url, entry.state));
+    }
+
+    /// A kept element with the given local name, reset to be used as a new element with `id`.
+    pub fn reuse_vdom_element(&self, id: u64, local_name: &Atom) -> Option<Root<Element>> {
+        let element = self.vdom_pool.borrow_mut().take(local_name);
+        if let Some(ref element) = element {
+            element.reset_shell(id);
+        }
+        element
+    }
+
+    /// Starts or stops remembering the ids of removed nodes.
+    pub fn set_vdom_tombstones(&self, enabled: bool) {
+        self.vdom_tombstones.borrow_mut().set_enabled(enabled);
+    }
+
+    /// Forgets the ids of removed nodes that are no longer remembered.
+    pub fn prune_vdom_tombstones(&self) {
+        self.vdom_tombstones.borrow_mut().prune(time::precise_time_ns());
+    }
+
     pub fn get_element_by_id(&self, id: &Atom) -> Option<Root<Element>> {
         self.id_map.borrow().get(&id).map(|ref elements| Root::from_ref(&*(*elements)[0]))
     }
@@ -1522,7 +2505,7 @@ impl Document {
 
     // https://dom.spec.whatwg.org/#dom-document-content_type
     fn ContentType(&self) -> DOMString {
-        self.content_type.clone()
+        self.content_type.borrow().clone()
     }
 
     // https://dom.spec.whatwg.org/#dom-document-doctype
@@ -1709,6 +2692,28 @@ impl Document {
             .and_then(|root| root.upcast::<Node>().children().filter_map(Root::downcast).next())
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-document-designmode
+    pub fn DesignMode(&self) -> DOMString {
+        DOMString::from(if self.design_mode.get() { "on" } else { "off" })
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-document-designmode
+    pub fn SetDesignMode(&self, value: DOMString) {
+        let enabled = match parse_design_mode(&value) {
+            Some(enabled) => enabled,
+            None => return
+        };
+        if enabled && !self.design_mode.get() {
+            self.editing_caret.set(0);
+            self.editing_anchor.set(0);
+        }
+        self.design_mode.set(enabled);
+    }
+
+    pub fn design_mode(&self) -> bool {
+        self.design_mode.get()
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-document-body
     pub fn GetBody(&self) -> Option<Root<HTMLElement>> {
         self.get_html_element().and_then(|root| {
@@ -1842,7 +2847,7 @@ impl Document {
             return Err(Error::Security);
         }
         let (tx, rx) = ipc::channel().unwrap();
-        let _ = self.window.resource_thread().send(GetCookiesForUrl((*url).clone(), tx, NonHTTP));
+        let _ = self.window.resource_thread().send(GetCookiesForUrl(url, tx, NonHTTP));
         let cookies = rx.recv().unwrap();
         Ok(cookies.map_or(DOMString::new(), DOMString::from))
     }
@@ -1851,12 +2856,12 @@ impl Document {
     fn SetCookie(&self, cookie: DOMString) -> ErrorResult {
         // TODO: ignore for cookie-averse Document
         let url = self.url();
-        if !is_scheme_host_port_tuple(url) {
+        if !is_scheme_host_port_tuple(&url) {
             return Err(Error::Security);
         }
         let _ = self.window
                     .resource_thread()
-                    .send(SetCookiesForUrl((*url).clone(), String::from(cookie), NonHTTP));
+                    .send(SetCookiesForUrl(url, String::from(cookie), NonHTTP));
         Ok(())
     }
 
@@ -1931,6 +2936,23 @@ impl Document {
 
 
 impl Element {
+    // https://html.spec.whatwg.org/multipage/#the-draggable-attribute
+    fn is_draggable(&self) -> bool {
+        self.get_attribute(&ns!(), &Atom::from("draggable"))
+            .map_or(false, |attr| attr.value().eq_ignore_ascii_case("true"))
+    }
+
+    /// The text selected in a text control, for the payload of `copy` and `cut` events.
+    fn selection_text(&self) -> Option<String> {
+        if let Some(input) = self.downcast::<HTMLInputElement>() {
+            input.selection_text()
+        } else if let Some(textarea) = self.downcast::<HTMLTextAreaElement>() {
+            textarea.selection_text()
+        } else {
+            None
+        }
+    }
+
     fn click_event_filter_by_disabled_state(&self) -> bool {
         let node = self.upcast::<Node>();
         match node.type_id() {
@@ -1957,14 +2979,50 @@ fn update_with_current_time(marker: &Cell<u64>) {
     }
 }
 
+/// Updates the hover state of an element, recording the change if the vdom server subscribed
+/// to it.
+fn set_hover_state(element: &Element, hovered: bool, changes: &mut HoverChanges) {
+    let node = element.upcast::<Node>();
+    if element.get_hover_state() != hovered && node.subscriptions().contains(events::HOVER) {
+        changes.record(node.get_id(), hovered);
+    }
+    element.set_hover_state(hovered);
+}
+
+struct HistoryBackRunnable {
+    document: Trusted<Document>,
+}
+
+impl Runnable for HistoryBackRunnable {
+    fn handler(self: Box<HistoryBackRunnable>) {
+        let document = self.document.root();
+        if document.window().is_alive() {
+            document.vdom_history_back();
+        }
+    }
+}
+
 pub struct DocumentProgressHandler {
-    addr: Trusted<Document>
+    addr: Trusted<Document>,
+    /// Whether this is the first load of the document, which the constellation is told about,
+    /// rather than the one after a vdom re-render.
+    first_load: bool,
 }
 
 impl DocumentProgressHandler {
      pub fn new(addr: Trusted<Document>) -> DocumentProgressHandler {
         DocumentProgressHandler {
-            addr: addr
+            addr: addr,
+            first_load: true,
+        }
+    }
+
+    /// A handler completing the document again after the vdom server replaced its document
+    /// element.
+    pub fn after_rerender(addr: Trusted<Document>) -> DocumentProgressHandler {
+        DocumentProgressHandler {
+            addr: addr,
+            first_load: false,
         }
     }
 
@@ -1983,7 +3041,9 @@ impl DocumentProgressHandler {
         event.set_trusted(true);
         let _ = wintarget.dispatch_event_with_target(document.upcast(), &event);
 
-        document.notify_constellation_load();
+        if self.first_load {
+            document.notify_constellation_load();
+        }
 
         window.reflow(ReflowGoal::ForDisplay,
                       ReflowQueryType::NoQuery,
@@ -2002,6 +3062,15 @@ impl Runnable for DocumentProgressHandler {
     }
 }
 
+/// The state a value assigned to `designMode` turns it to, if it is a valid one.
+pub fn parse_design_mode(value: &str) -> Option<bool> {
+    match &*value.to_ascii_lowercase() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
 /// Specifies the type of focus event that is sent to a pipeline
 #[derive(Copy, Clone, PartialEq)]
 pub enum FocusType {
@@ -2009,8 +3078,26 @@ pub enum FocusType {
     Parent,     // Focusing a parent element (an iframe)
 }
 
+/// The kind of device the user last interacted with a document through.
+#[derive(PartialEq, Copy, Clone, HeapSizeOf, Debug)]
+pub enum InputModality {
+    /// The keyboard, or no interaction yet.
+    Keyboard,
+    /// A mouse or a touch screen.
+    Pointer,
+}
+
+impl InputModality {
+    /// Whether an element focused after this kind of interaction matches `:focus-visible`: users
+    /// of a keyboard need to see where focus went, while pointer users chose the element.
+    pub fn focus_visible(self) -> bool {
+        self == InputModality::Keyboard
+    }
+}
+
 /// Focus events
 pub enum FocusEventType {
     Focus,      // Element gained focus. Doesn't bubble.
     Blur,       // Element lost focus. Doesn't bubble.
+    FocusOut,   // Element lost focus. Bubbles.
 }
@@ -0,0 +1,108 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::inheritance::{Castable, UIEventTypeId};
+use dom::bindings::js::Root;
+use dom::event::{EventBubbles, EventCancelable};
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use std::ascii::AsciiExt;
+use std::cell::{Cell, Ref};
+use util::str::DOMString;
+
+/// The data carried by a drag operation, as `(format, data)` pairs in the order they were set.
+///
+/// https://html.spec.whatwg.org/multipage/#the-drag-data-store
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct DragData {
+    items: Vec<(String, String)>,
+}
+
+impl DragData {
+    pub fn new() -> DragData {
+        DragData::default()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-setdata
+    pub fn set_data(&mut self, format: &str, data: &str) {
+        let format = format.to_ascii_lowercase();
+        match self.items.iter_mut().find(|item| item.0 == format) {
+            Some(item) => item.1 = data.to_owned(),
+            None => self.items.push((format, data.to_owned())),
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-getdata
+    pub fn get_data(&self, format: &str) -> Option<&str> {
+        let format = format.to_ascii_lowercase();
+        self.items.iter().find(|item| item.0 == format).map(|item| &*item.1)
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-datatransfer-cleardata
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    pub fn items(&self) -> &[(String, String)] {
+        &self.items
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/#the-dragevent-interface
+
+pub struct DragEvent {
+    uievent: UIEvent,
+    client_x: Cell<i32>,
+    client_y: Cell<i32>,
+    data_transfer: DOMRefCell<DragData>,
+}
+
+impl DragEvent {
+    fn new_inherited() -> DragEvent {
+        DragEvent {
+            uievent: UIEvent::new_inherited(UIEventTypeId::DragEvent),
+            client_x: Cell::new(0),
+            client_y: Cell::new(0),
+            data_transfer: DOMRefCell::new(DragData::new()),
+        }
+    }
+
+    pub fn new_uninitialized() -> Root<DragEvent> {
+        Root::new_box(box DragEvent::new_inherited())
+    }
+
+    pub fn new(type_: DOMString,
+               canBubble: EventBubbles,
+               cancelable: EventCancelable,
+               view: Option<&Window>,
+               clientX: i32,
+               clientY: i32,
+               dataTransfer: DragData) -> Root<DragEvent> {
+        let ev = DragEvent::new_uninitialized();
+        ev.upcast::<UIEvent>().InitUIEvent(type_,
+                                           canBubble == EventBubbles::Bubbles,
+                                           cancelable == EventCancelable::Cancelable,
+                                           view, 0);
+        ev.client_x.set(clientX);
+        ev.client_y.set(clientY);
+        *ev.data_transfer.borrow_mut() = dataTransfer;
+        ev
+    }
+
+    // https://w3c.github.io/uievents/#widl-MouseEvent-clientX
+    pub fn ClientX(&self) -> i32 {
+        self.client_x.get()
+    }
+
+    // https://w3c.github.io/uievents/#widl-MouseEvent-clientY
+    pub fn ClientY(&self) -> i32 {
+        self.client_y.get()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-dragevent-datatransfer
+    pub fn data_transfer(&self) -> Ref<DragData> {
+        self.data_transfer.borrow()
+    }
+}
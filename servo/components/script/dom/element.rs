@@ -23,6 +23,7 @@ use dom::document::{Document, LayoutDocumentHelpers};
 use dom::domrect::DOMRect;
 use dom::domrectlist::DOMRectList;
 use dom::domtokenlist::DOMTokenList;
+use dom::dragevent::DragData;
 use dom::event::Event;
 use dom::htmlanchorelement::HTMLAnchorElement;
 use dom::htmlbodyelement::{HTMLBodyElement, HTMLBodyElementLayoutHelpers};
@@ -71,6 +72,8 @@ use style::selector_impl::{NonTSPseudoClass, ServoSelectorImpl};
 use style::values::CSSFloat;
 use style::values::specified::{self, CSSColor, CSSRGBA, LengthOrPercentage};
 use util::str::{DOMString, LengthOrPercentageOrAuto};
+use vdom::events::{DECLARATIVE_ATTR, EventSet};
+use vdom::live;
 
 // TODO: Update focus state when the top-level browsing context gains or loses system focus,
 // and when the element enters or leaves a browsing context container.
@@ -89,6 +92,9 @@ pub struct Element {
     class_list: MutNullableHeap<JS<DOMTokenList>>,
     state: Cell<ElementState>,
     atomic_flags: AtomicElementFlags,
+    /// The drag data the vdom server primed this element with, which a drag starting from it
+    /// carries.
+    vdom_drag_data: DOMRefCell<DragData>,
 }
 
 #[derive(PartialEq)]
@@ -127,6 +133,7 @@ impl Element {
             class_list: Default::default(),
             state: Cell::new(state),
             atomic_flags: AtomicElementFlags::new(),
+            vdom_drag_data: DOMRefCell::new(DragData::new()),
         }
     }
 
@@ -792,13 +799,68 @@ impl Element {
 
 
 impl Element {
+    /// The state of the `contenteditable` attribute, or `None` if the element inherits the
+    /// state of its parent.
+    /// https://html.spec.whatwg.org/multipage/#attr-contenteditable
+    pub fn content_editable(&self) -> Option<bool> {
+        self.get_attribute(&ns!(), &Atom::from("contenteditable")).and_then(|attr| {
+            match &*attr.value().to_ascii_lowercase() {
+                "" | "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            }
+        })
+    }
+
+    /// The language of the element, which is inherited from the nearest inclusive ancestor with
+    /// a `lang` attribute, or the empty string if it is unknown.
+    /// https://html.spec.whatwg.org/multipage/#language
+    pub fn language(&self) -> DOMString {
+        self.upcast::<Node>().inclusive_ancestors()
+            .filter_map(Root::downcast::<Element>)
+            .filter_map(|element| element.get_attribute(&ns!(), &atom!("lang")))
+            .next()
+            .map_or(DOMString::new(), |attr| attr.Value())
+    }
+
+    /// The editing host the element is, or is a descendant of. In design mode, that is the body
+    /// of the document unless a `contenteditable` attribute says otherwise.
+    /// https://html.spec.whatwg.org/multipage/#editing-host
+    pub fn editing_host(&self) -> Option<Root<Element>> {
+        let mut host = None;
+        for element in self.upcast::<Node>().inclusive_ancestors().filter_map(Root::downcast::<Element>) {
+            match element.content_editable() {
+                Some(true) => host = Some(element),
+                Some(false) => return host,
+                None => {},
+            }
+        }
+        let doc = document_from_node(self);
+        if doc.design_mode() {
+            if let Some(body) = doc.GetBody() {
+                if body.upcast::<Node>().is_inclusive_ancestor_of(self.upcast()) {
+                    return Some(Root::upcast(body));
+                }
+            }
+        }
+        host
+    }
+
+    /// Whether the element is the body of a document in design mode, which is the editing host
+    /// of the whole document.
+    fn is_design_mode_host(&self) -> bool {
+        let doc = document_from_node(self);
+        doc.design_mode() && doc.GetBody().map_or(false, |body| body.upcast::<Element>() == self)
+    }
+
     pub fn is_focusable_area(&self) -> bool {
         if self.is_actually_disabled() {
             return false;
         }
         // TODO: Check whether the element is being rendered (i.e. not hidden).
         let node = self.upcast::<Node>();
-        if node.get_flag(SEQUENTIALLY_FOCUSABLE) {
+        if node.get_flag(SEQUENTIALLY_FOCUSABLE) || self.content_editable() == Some(true) ||
+           self.is_design_mode_host() {
             return true;
         }
         // https://html.spec.whatwg.org/multipage/#specially-focusable
@@ -909,13 +971,17 @@ impl Element {
 
     // https://html.spec.whatwg.org/multipage/#attr-data-*
     pub fn set_custom_attribute(&self, name: DOMString, value: DOMString) -> ErrorResult {
+        self.set_custom_attribute_named(Atom::from(name), value)
+    }
+
+    /// Like `set_custom_attribute`, with the name already interned.
+    pub fn set_custom_attribute_named(&self, name: Atom, value: DOMString) -> ErrorResult {
         // Step 1.
         if let InvalidXMLName = xml_name_type(&name) {
             return Err(Error::InvalidCharacter);
         }
 
         // Steps 2-5.
-        let name = Atom::from(name);
         let value = self.parse_attribute(&ns!(), &name, value);
         self.set_first_matching_attribute(name.clone(),
                                           value,
@@ -1051,6 +1117,29 @@ impl Element {
         self.set_attribute(local_name, AttrValue::String(value));
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-domstringmap-nameditem
+    pub fn dataset_get(&self, key: &str) -> Option<DOMString> {
+        self.dataset_get_named(&Atom::from(&*dataset_attr_name(key)))
+    }
+
+    /// Like `dataset_get`, with the attribute name for the key already interned.
+    pub fn dataset_get_named(&self, name: &Atom) -> Option<DOMString> {
+        self.get_attribute(&ns!(), name).map(|attr| attr.Value())
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-domstringmap-setitem
+    pub fn dataset_set(&self, key: &str, value: DOMString) -> ErrorResult {
+        self.dataset_set_named(key, Atom::from(&*dataset_attr_name(key)), value)
+    }
+
+    /// Like `dataset_set`, with the attribute name for the key already interned.
+    pub fn dataset_set_named(&self, key: &str, name: Atom, value: DOMString) -> ErrorResult {
+        if key.chars().zip(key.chars().skip(1)).any(|pair| match pair { ('-', 'a'...'z') => true, _ => false }) {
+            return Err(Error::Syntax);
+        }
+        self.set_custom_attribute_named(name, value)
+    }
+
     pub fn get_tokenlist_attribute(&self, local_name: &Atom) -> Vec<Atom> {
         self.get_attribute(&ns!(), local_name).map(|attr| {
             attr.r()
@@ -1181,7 +1270,7 @@ impl Element {
     }
 
     // https://dom.spec.whatwg.org/#dom-element-getattributenames
-    fn GetAttributeNames(&self) -> Vec<DOMString> {
+    pub fn GetAttributeNames(&self) -> Vec<DOMString> {
         self.attrs.borrow().iter().map(|attr| attr.Name()).collect()
     }
 
@@ -1476,6 +1565,39 @@ impl Element {
     }
 }
 
+/// Maps a `dataset` key to its attribute name, i.e. `userId` to `data-user-id`.
+pub fn dataset_attr_name(key: &str) -> String {
+    let mut name = String::from("data-");
+    for c in key.chars() {
+        if let 'A'...'Z' = c {
+            name.push('-');
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Maps a `data-*` attribute name to its `dataset` key, i.e. `data-user-id` to `userId`.
+pub fn dataset_key(attr_name: &str) -> Option<String> {
+    if !attr_name.starts_with("data-") {
+        return None;
+    }
+    let mut key = String::new();
+    let mut chars = attr_name["data-".len()..].chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek().cloned()) {
+            ('-', Some(next @ 'a'...'z')) => {
+                key.push(next.to_ascii_uppercase());
+                chars.next();
+            },
+            _ => key.push(c),
+        }
+    }
+    Some(key)
+}
+
 pub fn fragment_affecting_attributes() -> [Atom; 3] {
     [atom!("width"), atom!("height"), atom!("src")]
 }
@@ -1489,6 +1611,7 @@ impl VirtualMethods for Element {
         self.super_type().unwrap().attribute_mutated(attr, mutation);
         let node = self.upcast::<Node>();
         let doc = node.owner_doc();
+        doc.note_live_mutation(live::attribute_mutation(attr.local_name()));
         match attr.local_name() {
             &atom!("style") => {
                 // Modifying the `style` attribute might change style.
@@ -1531,6 +1654,15 @@ impl VirtualMethods for Element {
                     }
                 }
             },
+            name if attr.namespace() == &ns!() && &**name == DECLARATIVE_ATTR => {
+                if let AttributeMutation::Set(Some(old_value)) = mutation {
+                    node.unsubscribe(EventSet::from_list(&old_value));
+                }
+                match mutation.new_value(attr) {
+                    Some(value) => node.subscribe(EventSet::from_list(&value)),
+                    None => node.unsubscribe(EventSet::from_list(&attr.value())),
+                }
+            },
             _ if attr.namespace() == &ns!() => {
                 if fragment_affecting_attributes().iter().any(|a| a == attr.local_name()) ||
                    common_style_affecting_attributes().iter().any(|a| &a.atom == attr.local_name()) ||
@@ -1578,10 +1710,13 @@ impl VirtualMethods for Element {
             return;
         }
 
+        let doc = document_from_node(self);
         if let Some(ref value) = *self.id_attribute.borrow() {
-            let doc = document_from_node(self);
             doc.unregister_named_element(self, value.clone());
         }
+        if self.get_focus_state() {
+            doc.focused_element_removed(self, context.parent);
+        }
     }
 
     fn children_changed(&self, mutation: &ChildrenMutation) {
@@ -1678,6 +1813,7 @@ impl<'a> ::selectors::Element for Root<Element> {
 
             NonTSPseudoClass::Active |
             NonTSPseudoClass::Focus |
+            NonTSPseudoClass::FocusVisible |
             NonTSPseudoClass::Hover |
             NonTSPseudoClass::Enabled |
             NonTSPseudoClass::Disabled |
@@ -1873,6 +2009,7 @@ impl Element {
             state.remove(which);
         }
         self.state.set(state);
+        node.owner_doc().note_live_mutation(live::STATE);
     }
 
     pub fn get_active_state(&self) -> bool {
@@ -1883,6 +2020,52 @@ impl Element {
         self.set_state(IN_ACTIVE_STATE, value)
     }
 
+    /// Whether a removed element of this kind can be reused for a new one: it holds no state
+    /// besides its attributes and children, which `reset_shell` clears.
+    pub fn is_poolable(&self) -> bool {
+        match self.upcast::<Node>().type_id() {
+            NodeTypeId::Element(ElementTypeId::HTMLElement(type_id)) => match type_id {
+                HTMLElementTypeId::HTMLElement |
+                HTMLElementTypeId::HTMLDivElement |
+                HTMLElementTypeId::HTMLSpanElement |
+                HTMLElementTypeId::HTMLParagraphElement |
+                HTMLElementTypeId::HTMLHeadingElement |
+                HTMLElementTypeId::HTMLLIElement |
+                HTMLElementTypeId::HTMLUListElement |
+                HTMLElementTypeId::HTMLOListElement |
+                HTMLElementTypeId::HTMLTableRowElement |
+                HTMLElementTypeId::HTMLTableCellElement(_) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Resets a poolable element to the state of a newly created one with the given id:
+    /// detached, without attributes, children, state or subscriptions.
+    pub fn reset_shell(&self, id: u64) {
+        let attrs = self.attrs.borrow().iter()
+                        .map(|attr| (attr.namespace().clone(), attr.local_name().clone()))
+                        .collect::<Vec<_>>();
+        for (namespace, local_name) in attrs {
+            self.remove_attribute(&namespace, &local_name);
+        }
+        self.state.set(ElementState::empty());
+        self.vdom_drag_data.borrow_mut().clear();
+        self.upcast::<Node>().reset_shell(id);
+    }
+
+    /// The drag data a drag starting from this element carries.
+    pub fn vdom_drag_data(&self) -> DragData {
+        self.vdom_drag_data.borrow().clone()
+    }
+
+    /// Primes the drag data of this element with `data` in a format, replacing the data it had
+    /// in that format, if any.
+    pub fn set_vdom_drag_data(&self, format: &str, data: &str) {
+        self.vdom_drag_data.borrow_mut().set_data(format, data);
+    }
+
     pub fn get_focus_state(&self) -> bool {
         self.state.get().contains(IN_FOCUS_STATE)
     }
@@ -1893,6 +2076,14 @@ impl Element {
         doc.content_changed(self.upcast(), NodeDamage::OtherNodeDamage);
     }
 
+    pub fn get_focus_visible_state(&self) -> bool {
+        self.state.get().contains(IN_FOCUS_VISIBLE_STATE)
+    }
+
+    pub fn set_focus_visible_state(&self, value: bool) {
+        self.set_state(IN_FOCUS_VISIBLE_STATE, value)
+    }
+
     pub fn get_hover_state(&self) -> bool {
         self.state.get().contains(IN_HOVER_STATE)
     }
@@ -181,7 +181,7 @@ impl Event {
     }
 
     // https://dom.spec.whatwg.org/#dom-event-target
-    fn GetTarget(&self) -> Option<Root<EventTarget>> {
+    pub fn GetTarget(&self) -> Option<Root<EventTarget>> {
         self.target.get()
     }
 
@@ -219,7 +219,7 @@ impl Event {
     }
 
     // https://dom.spec.whatwg.org/#dom-event-cancelable
-    fn Cancelable(&self) -> bool {
+    pub fn Cancelable(&self) -> bool {
         self.cancelable.get()
     }
 
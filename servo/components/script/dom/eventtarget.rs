@@ -22,6 +22,7 @@ use std::{intrinsics};
 use string_cache::Atom;
 use url::Url;
 use util::str::DOMString;
+use vdom::events::forward_event;
 
 #[derive(PartialEq, Clone)]
 pub enum CommonEventHandler {
@@ -82,9 +83,10 @@ impl EventTarget {
         //dispatch_event(self, Some(target), event)
     }
 
-    pub fn dispatch_event(&self, _event: &Event) -> bool {
-        true
-        //dispatch_event(self, None, event)
+    pub fn dispatch_event(&self, event: &Event) -> bool {
+        event.set_target(self);
+        forward_event(self, event);
+        !event.DefaultPrevented()
     }
 
     /// Store the raw uncompiled event handler for on-demand compilation later.
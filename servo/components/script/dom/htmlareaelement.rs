@@ -8,11 +8,13 @@ use dom::bindings::js::{JS, MutNullableHeap, Root};
 use dom::bindings::inheritance::HTMLElementTypeId;
 use dom::document::Document;
 use dom::domtokenlist::DOMTokenList;
+use dom::element::Element;
 use dom::htmlelement::HTMLElement;
 use dom::virtualmethods::VirtualMethods;
+use std::ascii::AsciiExt;
 use std::default::Default;
 use string_cache::Atom;
-use util::str::DOMString;
+use util::str::{DOMString, char_is_whitespace};
 
 pub struct HTMLAreaElement {
     htmlelement: HTMLElement,
@@ -42,6 +44,127 @@ impl HTMLAreaElement {
             DOMTokenList::new(self.upcast(), &atom!("rel"))
         })
     }
+
+    /// The region of its image this area covers, if its `coords` describe a valid shape.
+    pub fn area(&self) -> Option<Area> {
+        let element = self.upcast::<Element>();
+        let shape = Shape::from_attribute(&element.get_string_attribute(&atom!("shape")));
+        Area::parse(&element.get_string_attribute(&atom!("coords")), shape)
+    }
+}
+
+/// The kind of region an `area` element covers.
+/// https://html.spec.whatwg.org/multipage/#attr-area-shape
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Circle,
+    Rectangle,
+    Polygon,
+    Default,
+}
+
+impl Shape {
+    /// Parses a `shape` attribute, where missing and invalid values mean a rectangle.
+    pub fn from_attribute(value: &str) -> Shape {
+        match &*value.to_ascii_lowercase() {
+            "circle" | "circ" => Shape::Circle,
+            "poly" | "polygon" => Shape::Polygon,
+            "default" => Shape::Default,
+            _ => Shape::Rectangle,
+        }
+    }
+}
+
+/// A region of an image, in CSS pixels from the top left corner of the image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Area {
+    Circle { left: f32, top: f32, radius: f32 },
+    Rectangle { top_left: (f32, f32), bottom_right: (f32, f32) },
+    /// The x and y coordinates of each vertex, in order.
+    Polygon { points: Vec<(f32, f32)> },
+    /// The whole image.
+    Default,
+}
+
+impl Area {
+    /// Builds the region described by a `coords` attribute, or `None` if there are too few
+    /// coordinates for the shape.
+    /// https://html.spec.whatwg.org/multipage/#attr-area-coords
+    pub fn parse(coords: &str, shape: Shape) -> Option<Area> {
+        let numbers = parse_coords(coords);
+        match shape {
+            Shape::Circle => {
+                if numbers.len() < 3 || numbers[2] <= 0.0 {
+                    None
+                } else {
+                    Some(Area::Circle { left: numbers[0], top: numbers[1], radius: numbers[2] })
+                }
+            },
+            Shape::Rectangle => {
+                if numbers.len() < 4 {
+                    None
+                } else {
+                    // The corners may be given in any order.
+                    Some(Area::Rectangle {
+                        top_left: (numbers[0].min(numbers[2]), numbers[1].min(numbers[3])),
+                        bottom_right: (numbers[0].max(numbers[2]), numbers[1].max(numbers[3])),
+                    })
+                }
+            },
+            Shape::Polygon => {
+                if numbers.len() < 6 {
+                    None
+                } else {
+                    // A trailing coordinate without a pair is ignored.
+                    Some(Area::Polygon { points: numbers.chunks(2).filter(|pair| pair.len() == 2)
+                                                        .map(|pair| (pair[0], pair[1])).collect() })
+                }
+            },
+            Shape::Default => Some(Area::Default),
+        }
+    }
+
+    /// Whether the point `(x, y)` of the image lies within the region.
+    pub fn hit_test(&self, x: f32, y: f32) -> bool {
+        match *self {
+            Area::Circle { left, top, radius } => {
+                (x - left) * (x - left) + (y - top) * (y - top) <= radius * radius
+            },
+            Area::Rectangle { top_left, bottom_right } => {
+                x >= top_left.0 && x <= bottom_right.0 && y >= top_left.1 && y <= bottom_right.1
+            },
+            Area::Polygon { ref points } => {
+                // Even-odd rule: count the edges a ray going right from the point crosses.
+                let mut inside = false;
+                let mut previous = points[points.len() - 1];
+                for &point in points {
+                    if (point.1 > y) != (previous.1 > y) &&
+                       x < (previous.0 - point.0) * (y - point.1) / (previous.1 - point.1) + point.0 {
+                        inside = !inside;
+                    }
+                    previous = point;
+                }
+                inside
+            },
+            Area::Default => true,
+        }
+    }
+}
+
+/// Parses a list of numbers separated by commas, semicolons or whitespace, where a number
+/// is read from the start of its item and items without one count as zero.
+/// https://html.spec.whatwg.org/multipage/#rules-for-parsing-a-list-of-floating-point-numbers
+fn parse_coords(coords: &str) -> Vec<f32> {
+    coords.split(|c| c == ',' || c == ';' || char_is_whitespace(c))
+          .filter(|item| !item.is_empty())
+          .map(|item| {
+              let end = item.find(|c| match c {
+                  '0'...'9' | '.' | '-' => false,
+                  _ => true,
+              }).unwrap_or(item.len());
+              item[..end].parse().unwrap_or(0.0)
+          })
+          .collect()
 }
 
 impl VirtualMethods for HTMLAreaElement {
@@ -138,7 +138,7 @@ impl VirtualMethods for HTMLBodyElement {
         match *name {
             atom!("bgcolor") |
             atom!("text") => AttrValue::from_legacy_color(value),
-            atom!("background") => AttrValue::from_url(document_from_node(self).url(), value),
+            atom!("background") => AttrValue::from_url(&document_from_node(self).url(), value),
             _ => self.super_type().unwrap().parse_plain_attribute(name, value),
         }
     }
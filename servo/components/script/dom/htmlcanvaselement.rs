@@ -2,17 +2,23 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use canvas_traits::{CanvasMsg};
+use canvas_traits::{Canvas2dMsg, CanvasCommonMsg, CanvasMsg};
 use dom::attr::Attr;
 use dom::attr::AttrValue;
+use dom::bindings::cell::DOMRefCell;
 use dom::bindings::inheritance::{Castable, HTMLElementTypeId};
 use dom::bindings::js::{LayoutJS, Root};
 use dom::document::Document;
 use dom::element::{AttributeMutation, Element, RawLayoutElementHelpers};
 use dom::htmlelement::HTMLElement;
+use dom::imagedata::ImageData;
+use dom::node::{Node, NodeDamage, window_from_node};
 use dom::virtualmethods::VirtualMethods;
+use euclid::rect::Rect;
 use euclid::size::Size2D;
-use ipc_channel::ipc::{IpcSender};
+use ipc_channel::ipc::{self, IpcSender};
+use script_traits::ScriptMsg as ConstellationMsg;
+use std::cell::Cell;
 use string_cache::Atom;
 use util::str::DOMString;
 
@@ -20,7 +26,10 @@ const DEFAULT_WIDTH: u32 = 300;
 const DEFAULT_HEIGHT: u32 = 150;
 
 pub struct HTMLCanvasElement {
-    htmlelement: HTMLElement
+    htmlelement: HTMLElement,
+    /// The paint thread drawing the bitmap of the canvas, created when something is first drawn.
+    ipc_renderer: DOMRefCell<Option<IpcSender<CanvasMsg>>>,
+    renderer_id: Cell<Option<usize>>,
 }
 
 impl HTMLCanvasElement {
@@ -29,7 +38,9 @@ impl HTMLCanvasElement {
                      prefix: Option<DOMString>,
                      document: &Document) -> HTMLCanvasElement {
         HTMLCanvasElement {
-            htmlelement: HTMLElement::new_inherited(HTMLElementTypeId::HTMLCanvasElement, id, localName, prefix, document)
+            htmlelement: HTMLElement::new_inherited(HTMLElementTypeId::HTMLCanvasElement, id, localName, prefix, document),
+            ipc_renderer: DOMRefCell::new(None),
+            renderer_id: Cell::new(None),
         }
     }
 
@@ -46,6 +57,57 @@ impl HTMLCanvasElement {
         Size2D::new(self.Width() as i32, self.Height() as i32)
     }
 
+    /// The paint thread drawing the bitmap of the canvas, which is asked of the constellation the
+    /// first time.
+    pub fn renderer(&self) -> IpcSender<CanvasMsg> {
+        if let Some(ref renderer) = *self.ipc_renderer.borrow() {
+            return renderer.clone();
+        }
+        let (sender, receiver) = ipc::channel().unwrap();
+        let constellation_chan = window_from_node(self).constellation_chan();
+        constellation_chan.0.send(ConstellationMsg::CreateCanvasPaintThread(self.get_size(), sender)).unwrap();
+        let (renderer, renderer_id) = receiver.recv().unwrap();
+        *self.ipc_renderer.borrow_mut() = Some(renderer.clone());
+        self.renderer_id.set(Some(renderer_id));
+        renderer
+    }
+
+    /// The pixels of the bitmap in a rectangle, which must be inside it, as RGBA in rows.
+    pub fn get_image_data(&self, rect: Rect<i32>) -> Root<ImageData> {
+        let data = match *self.ipc_renderer.borrow() {
+            Some(ref renderer) => {
+                let (sender, receiver) = ipc::channel().unwrap();
+                let canvas_size = Size2D::new(self.Width() as f64, self.Height() as f64);
+                renderer.send(CanvasMsg::Canvas2d(Canvas2dMsg::GetImageData(rect, canvas_size, sender))).unwrap();
+                receiver.recv().unwrap()
+            },
+            // Nothing was drawn yet, so the bitmap is transparent.
+            None => vec![0; (rect.size.width * rect.size.height * 4) as usize]
+        };
+        ImageData::new(rect.size.width as u32, rect.size.height as u32, Some(data))
+    }
+
+    /// Makes layout paint the canvas again, after its bitmap was drawn to.
+    pub fn mark_as_dirty(&self) {
+        self.upcast::<Node>().dirty(NodeDamage::OtherNodeDamage);
+    }
+
+    /// Resets the bitmap to a transparent one of the size of the element, as setting its `width`
+    /// or `height` does, even to the same value, and lays the element out again at that size.
+    fn reset_bitmap(&self) {
+        if let Some(ref renderer) = *self.ipc_renderer.borrow() {
+            renderer.send(resize_msg(self.Width(), self.Height())).unwrap();
+        }
+        let node = self.upcast::<Node>();
+        if node.is_in_doc() {
+            node.owner_doc().content_changed(node, NodeDamage::OtherNodeDamage);
+        }
+    }
+}
+
+/// The message making a canvas renderer reallocate its bitmap at a new size, which clears it.
+pub fn resize_msg(width: u32, height: u32) -> CanvasMsg {
+    CanvasMsg::Common(CanvasCommonMsg::Recreate(Size2D::new(width as i32, height as i32)))
 }
 
 pub struct HTMLCanvasData {
@@ -67,8 +129,8 @@ impl LayoutHTMLCanvasElementHelpers for LayoutJS<HTMLCanvasElement> {
             let width_attr = canvas.upcast::<Element>().get_attr_for_layout(&ns!(), &atom!("width"));
             let height_attr = canvas.upcast::<Element>().get_attr_for_layout(&ns!(), &atom!("height"));
             HTMLCanvasData {
-                renderer_id: None,
-                ipc_renderer: None,
+                renderer_id: canvas.renderer_id.get(),
+                ipc_renderer: canvas.ipc_renderer.borrow_for_layout().clone(),
                 width: width_attr.map_or(DEFAULT_WIDTH, |val| val.as_uint()),
                 height: height_attr.map_or(DEFAULT_HEIGHT, |val| val.as_uint()),
             }
@@ -103,6 +165,10 @@ impl VirtualMethods for HTMLCanvasElement {
 
     fn attribute_mutated(&self, attr: &Attr, mutation: AttributeMutation) {
         self.super_type().unwrap().attribute_mutated(attr, mutation);
+        match attr.local_name() {
+            &atom!("width") | &atom!("height") => self.reset_bitmap(),
+            _ => (),
+        }
     }
 
     fn parse_plain_attribute(&self, name: &Atom, value: DOMString) -> AttrValue {
@@ -8,10 +8,14 @@ use dom::bindings::typed::Typed;
 use dom::bindings::xmlname::namespace_from_domstring;
 use dom::element::Element;
 use dom::node::{Node, FollowingNodeIterator, PrecedingNodeIterator};
+use selectors::matching::matches;
+use selectors::parser::Selector;
 use std::ascii::AsciiExt;
 use std::cell::Cell;
 use string_cache::{Atom, Namespace, QualName};
+use style::selector_impl::ServoSelectorImpl;
 use util::str::{DOMString, split_html_space_chars};
+use vdom::live::{self, MutationKinds};
 
 pub trait CollectionFilter {
     fn filter<'a>(&self, elem: &'a Element, root: &'a Node) -> bool;
@@ -58,6 +62,9 @@ pub struct HTMLCollection {
     cached_cursor_element: MutNullableHeap<JS<Element>>,
     cached_cursor_index: Cell<OptionU32>,
     cached_length: Cell<OptionU32>,
+    /// The kinds of mutations that can change the elements of a collection created from
+    /// selectors, which is only invalidated by those, rather than by any in its root.
+    dependencies: Option<MutationKinds>,
 }
 
 impl HTMLCollection {
@@ -72,6 +79,7 @@ impl HTMLCollection {
             cached_cursor_element: MutNullableHeap::new(None),
             cached_cursor_index: Cell::new(OptionU32::none()),
             cached_length: Cell::new(OptionU32::none()),
+            dependencies: None,
         }
     }
 
@@ -85,10 +93,18 @@ impl HTMLCollection {
         HTMLCollection::new(root, filter)
     }
 
+    /// The version the cache is valid for.
+    fn version(&self) -> u64 {
+        match self.dependencies {
+            Some(kinds) => self.root.owner_doc().live_version(kinds),
+            None => self.root.get_inclusive_descendants_version(),
+        }
+    }
+
     fn validate_cache(&self) {
         // Clear the cache if the root version is different from our cached version
         let cached_version = self.cached_version.get();
-        let curr_version = self.root.get_inclusive_descendants_version();
+        let curr_version = self.version();
         if curr_version != cached_version {
             // Default values for the cache
             self.cached_version.set(curr_version);
@@ -189,6 +205,24 @@ impl HTMLCollection {
         HTMLCollection::create(root, box filter)
     }
 
+    pub fn by_selectors(root: &Node, selectors: Vec<Selector<ServoSelectorImpl>>) -> Root<HTMLCollection> {
+
+        struct SelectorFilter {
+            selectors: Vec<Selector<ServoSelectorImpl>>
+        }
+        impl CollectionFilter for SelectorFilter {
+            fn filter(&self, elem: &Element, _root: &Node) -> bool {
+                matches(&self.selectors, &Root::from_ref(elem), None)
+            }
+        }
+        let kinds = live::dependencies(&selectors);
+        let mut collection = HTMLCollection::new_inherited(HTMLCollectionTypeId::HTMLCollection, root,
+                                                           box SelectorFilter { selectors: selectors });
+        collection.dependencies = Some(kinds);
+        collection.cached_version.set(root.owner_doc().live_version(kinds));
+        Root::new_box(box collection)
+    }
+
     pub fn children(root: &Node) -> Root<HTMLCollection> {
         
         struct ElementChildFilter;
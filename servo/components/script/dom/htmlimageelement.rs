@@ -14,7 +14,9 @@ use dom::bindings::refcounted::Trusted;
 use dom::document::Document;
 use dom::element::{AttributeMutation, Element, RawLayoutElementHelpers};
 use dom::eventtarget::EventTarget;
+use dom::htmlareaelement::HTMLAreaElement;
 use dom::htmlelement::HTMLElement;
+use dom::htmlmapelement::HTMLMapElement;
 use dom::node::{Node, NodeDamage, document_from_node, window_from_node};
 use dom::values::UNSIGNED_LONG_MAX;
 use dom::virtualmethods::VirtualMethods;
@@ -41,6 +43,26 @@ impl HTMLImageElement {
     pub fn get_url(&self) -> Option<Url>{
         self.url.borrow().clone()
     }
+
+    /// The image map named by the `usemap` attribute, i.e. `#nav` for `<map name=nav>`.
+    /// https://html.spec.whatwg.org/multipage/#image-map
+    pub fn image_map(&self) -> Option<Root<HTMLMapElement>> {
+        let usemap = self.UseMap();
+        if !usemap.starts_with('#') || usemap.len() == 1 {
+            return None;
+        }
+        let name = &usemap[1..];
+        let document = document_from_node(self);
+        let mut maps = document.upcast::<Node>().traverse_preorder().filter_map(Root::downcast::<HTMLMapElement>);
+        maps.find(|map| &*map.upcast::<Element>().get_string_attribute(&atom!("name")) == name)
+    }
+
+    /// The first area of the image map whose region contains the point `(x, y)` of the image.
+    pub fn area_at(&self, x: f32, y: f32) -> Option<Root<HTMLAreaElement>> {
+        self.image_map().and_then(|map| {
+            map.areas().into_iter().find(|area| area.area().map_or(false, |region| region.hit_test(x, y)))
+        })
+    }
 }
 
 
@@ -10,6 +10,8 @@ use dom::bindings::error::{Error, ErrorResult};
 use dom::bindings::inheritance::{Castable, HTMLElementTypeId};
 use dom::bindings::js::{JS, LayoutJS, Root, RootedReference};
 use dom::bindings::refcounted::Trusted;
+use dom::clipboardevent::ClipboardEvent;
+use dom::compositionevent::CompositionEvent;
 use dom::document::Document;
 use dom::element::{AttributeMutation, Element, RawLayoutElementHelpers, LayoutElementHelpers};
 use dom::event::{Event, EventBubbles, EventCancelable};
@@ -584,6 +586,14 @@ impl HTMLInputElement {
         doc.content_changed(self.upcast(), NodeDamage::OtherNodeDamage)
     }
 
+    /// The selected text of a text control, if any.
+    pub fn selection_text(&self) -> Option<String> {
+        match self.input_type.get() {
+            InputType::InputText => self.textinput.borrow().get_selection_text(),
+            _ => None,
+        }
+    }
+
     fn radio_group_updated(&self, group: Option<&Atom>) {
         if self.Checked() {
             broadcast_radio_checked(self, group);
@@ -896,6 +906,43 @@ impl VirtualMethods for HTMLInputElement {
                         Nothing => (),
                     }
                 }
+        } else if let Some(compositionevent) = event.downcast::<CompositionEvent>() {
+            if self.input_type.get() == InputType::InputText ||
+               self.input_type.get() == InputType::InputPassword {
+                let action = self.textinput.borrow_mut().handle_composition(compositionevent);
+                match action {
+                    DispatchInput => {
+                        self.value_changed.set(true);
+
+                        if event.IsTrusted() {
+                            ChangeEventRunnable::send(self.upcast::<Node>());
+                        }
+
+                        self.force_relayout();
+                    }
+                    RedrawSelection => self.force_relayout(),
+                    TriggerDefaultAction | Nothing => (),
+                }
+            }
+        } else if let Some(clipboardevent) = event.downcast::<ClipboardEvent>() {
+            // Password fields accept pasted text but never give up their value.
+            let editable = match self.input_type.get() {
+                InputType::InputText => true,
+                InputType::InputPassword => &*event.type_() == "paste",
+                _ => false,
+            };
+            if editable && !event.DefaultPrevented() {
+                let action = self.textinput.borrow_mut().handle_clipboard(clipboardevent);
+                if let DispatchInput = action {
+                    self.value_changed.set(true);
+
+                    if event.IsTrusted() {
+                        ChangeEventRunnable::send(self.upcast::<Node>());
+                    }
+
+                    self.force_relayout();
+                }
+            }
         }
     }
 }
@@ -3,10 +3,12 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 
+use dom::bindings::inheritance::{Castable, HTMLElementTypeId};
 use dom::bindings::js::Root;
-use dom::bindings::inheritance::HTMLElementTypeId;
 use dom::document::Document;
+use dom::htmlareaelement::HTMLAreaElement;
 use dom::htmlelement::HTMLElement;
+use dom::node::Node;
 
 use string_cache::Atom;
 use util::str::DOMString;
@@ -34,4 +36,9 @@ impl HTMLMapElement {
         let element = HTMLMapElement::new_inherited(id, localName, prefix, document);
         Root::new_box(box element)
     }
+
+    /// The `area` descendants of the map, in tree order.
+    pub fn areas(&self) -> Vec<Root<HTMLAreaElement>> {
+        self.upcast::<Node>().traverse_preorder().filter_map(Root::downcast).collect()
+    }
 }
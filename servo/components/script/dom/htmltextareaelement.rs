@@ -6,6 +6,8 @@ use dom::attr::{Attr, AttrValue};
 use dom::bindings::cell::DOMRefCell;
 use dom::bindings::inheritance::{Castable, HTMLElementTypeId};
 use dom::bindings::js::{LayoutJS, Root};
+use dom::clipboardevent::ClipboardEvent;
+use dom::compositionevent::CompositionEvent;
 use dom::document::Document;
 use dom::element::RawLayoutElementHelpers;
 use dom::element::{AttributeMutation, Element};
@@ -211,6 +213,10 @@ impl HTMLTextAreaElement {
         self.SetValue(self.DefaultValue());
         self.value_changed.set(false);
     }
+    /// The selected text, if any.
+    pub fn selection_text(&self) -> Option<String> {
+        self.textinput.borrow().get_selection_text()
+    }
 }
 
 
@@ -315,6 +321,33 @@ impl VirtualMethods for HTMLTextAreaElement {
                     KeyReaction::Nothing => (),
                 }
             }
+        } else if let Some(cevent) = event.downcast::<CompositionEvent>() {
+            let action = self.textinput.borrow_mut().handle_composition(cevent);
+            match action {
+                KeyReaction::DispatchInput => {
+                    self.value_changed.set(true);
+
+                    if event.IsTrusted() {
+                        ChangeEventRunnable::send(self.upcast::<Node>());
+                    }
+
+                    self.force_relayout();
+                }
+                KeyReaction::RedrawSelection => self.force_relayout(),
+                KeyReaction::TriggerDefaultAction | KeyReaction::Nothing => (),
+            }
+        } else if let Some(cevent) = event.downcast::<ClipboardEvent>() {
+            if !event.DefaultPrevented() {
+                if let KeyReaction::DispatchInput = self.textinput.borrow_mut().handle_clipboard(cevent) {
+                    self.value_changed.set(true);
+
+                    if event.IsTrusted() {
+                        ChangeEventRunnable::send(self.upcast::<Node>());
+                    }
+
+                    self.force_relayout();
+                }
+            }
         }
     }
 }
@@ -126,12 +126,12 @@ impl KeyboardEvent {
     }
 
     // https://w3c.github.io/uievents/#widl-KeyboardEvent-key
-    fn Key(&self) -> DOMString {
+    pub fn Key(&self) -> DOMString {
         self.key_string.borrow().clone()
     }
 
     // https://w3c.github.io/uievents/#widl-KeyboardEvent-code
-    fn Code(&self) -> DOMString {
+    pub fn Code(&self) -> DOMString {
         self.code.borrow().clone()
     }
 
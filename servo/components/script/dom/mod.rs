@@ -141,7 +141,9 @@ pub mod create;
 pub mod bindings;
 pub mod browsingcontext;
 pub mod characterdata;
+pub mod clipboardevent;
 pub mod comment;
+pub mod compositionevent;
 pub mod cssstyledeclaration;
 pub mod document;
 pub mod documentfragment;
@@ -155,6 +157,7 @@ pub mod domrect;
 pub mod domrectlist;
 pub mod domrectreadonly;
 pub mod domtokenlist;
+pub mod dragevent;
 pub mod element;
 pub mod event;
 pub mod eventsource;
@@ -238,6 +241,7 @@ pub mod namednodemap;
 pub mod node;
 pub mod nodeiterator;
 pub mod nodelist;
+pub mod popstateevent;
 pub mod processinginstruction;
 pub mod radionodelist;
 pub mod screen;
@@ -248,13 +252,16 @@ pub mod touchlist;
 pub mod uievent;
 pub mod values;
 pub mod virtualmethods;
+pub mod wheelevent;
 pub mod window;
 
 pub mod types {
 	pub use dom::attr::Attr;
 	pub use dom::cssstyledeclaration::CSSStyleDeclaration;
 	pub use dom::characterdata::CharacterData;
+	pub use dom::clipboardevent::ClipboardEvent;
 	pub use dom::comment::Comment;
+	pub use dom::compositionevent::CompositionEvent;
 	pub use dom::domexception::DOMException;
 	pub use dom::domimplementation::DOMImplementation;
 	pub use dom::dompoint::DOMPoint;
@@ -267,6 +274,7 @@ pub mod types {
 	pub use dom::document::Document;
 	pub use dom::documentfragment::DocumentFragment;
 	pub use dom::documenttype::DocumentType;
+	pub use dom::dragevent::DragEvent;
 	pub use dom::element::Element;
 	pub use dom::event::Event;
 	pub use dom::eventsource::EventSource;
@@ -350,6 +358,7 @@ pub mod types {
 	pub use dom::node::Node;
 	pub use dom::nodeiterator::NodeIterator;
 	pub use dom::nodelist::NodeList;
+	pub use dom::popstateevent::PopStateEvent;
 	pub use dom::processinginstruction::ProcessingInstruction;
 	pub use dom::radionodelist::RadioNodeList;
 	pub use dom::screen::Screen;
@@ -358,5 +367,6 @@ pub mod types {
 	pub use dom::touchevent::TouchEvent;
 	pub use dom::touchlist::TouchList;
 	pub use dom::uievent::UIEvent;
+	pub use dom::wheelevent::WheelEvent;
 	pub use dom::window::Window;
 }
\ No newline at end of file
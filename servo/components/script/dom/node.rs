@@ -56,6 +56,8 @@ use style::selector_impl::ServoSelectorImpl;
 use util::str::DOMString;
 use util::thread_state;
 use uuid::Uuid;
+use vdom::events::{self, EventSet};
+use vdom::live;
 
 pub mod NodeConstants {
     pub const ELEMENT_NODE: u16 = 1;
@@ -84,7 +86,7 @@ pub struct Node {
     eventtarget: EventTarget,
 
     /// The id of the node, provided by the vdom client.
-    id: u64,
+    id: Cell<u64>,
 
     /// The parent of this node.
     parent_node: MutNullableHeap<JS<Node>>,
@@ -113,6 +115,16 @@ pub struct Node {
     /// A bitfield of flags for node items.
     flags: Cell<NodeFlags>,
 
+    /// The events the vdom server wants forwarded from this node.
+    subscriptions: Cell<EventSet>,
+
+    /// The subscribed events whose default action waits for the vdom server's verdict.
+    vetoable: Cell<EventSet>,
+
+    /// The subscribed scroll and touch events the vdom server listens to passively, whose
+    /// default action doesn't wait for it.
+    passive: Cell<EventSet>,
+
     /// The maximum version of any inclusive descendant of this node.
     inclusive_descendants_version: Cell<u64>,
 
@@ -149,7 +161,10 @@ bitflags! {
         const SEQUENTIALLY_FOCUSABLE = 0x20,
 
         /// Whether any ancestor is a fragmentation container
-        const CAN_BE_FRAGMENTED = 0x40
+        const CAN_BE_FRAGMENTED = 0x40,
+        #[doc = "Specifies whether the vdom server froze the subtree rooted at this node, \
+                 so that patches can't mutate it."]
+        const IS_FROZEN = 0x80
     }
 }
 
@@ -420,6 +435,30 @@ impl Node {
         self.set_flag(HAS_CHANGED, state)
     }
 
+    /// Whether this node is in a subtree the vdom server froze. The ancestors are only walked
+    /// while the document has frozen nodes, so that mutations don't pay for it otherwise.
+    pub fn is_frozen(&self) -> bool {
+        self.owner_doc().has_vdom_frozen_nodes() &&
+            self.inclusive_ancestors().any(|node| node.get_flag(IS_FROZEN))
+    }
+
+    /// Freezes or unfreezes the subtree rooted at this node. Freezing only guards the subtree
+    /// against vdom patches; there is no tracer here that could skip it.
+    pub fn set_frozen(&self, frozen: bool) {
+        if self.get_flag(IS_FROZEN) != frozen {
+            self.owner_doc().count_vdom_frozen_node(frozen);
+            self.set_flag(IS_FROZEN, frozen)
+        }
+    }
+
+    /// The nodes of this subtree that the vdom server froze a subtree at.
+    pub fn frozen_roots(&self) -> Vec<Root<Node>> {
+        if !self.owner_doc().has_vdom_frozen_nodes() {
+            return vec![];
+        }
+        self.traverse_preorder().filter(|node| node.get_flag(IS_FROZEN)).collect()
+    }
+
     pub fn get_is_dirty(&self) -> bool {
         self.get_flag(IS_DIRTY)
     }
@@ -635,6 +674,15 @@ impl Node {
         Ok(NodeList::new_simple_list(iter))
     }
 
+    /// A live collection of the descendant elements matching a set of selectors, which only
+    /// recomputes after a mutation its selectors depend on; see `vdom::live`.
+    pub fn query_selector_live(&self, selectors: DOMString) -> Fallible<Root<HTMLCollection>> {
+        match parse_author_origin_selector_list_from_str(&selectors) {
+            Err(()) => Err(Error::Syntax),
+            Ok(selectors) => Ok(HTMLCollection::by_selectors(self, selectors))
+        }
+    }
+
     pub fn ancestors(&self) -> AncestorIterator {
         AncestorIterator {
             current: self.GetParentNode()
@@ -1161,7 +1209,7 @@ impl Node {
     fn new_(type_id: NodeTypeId, id: u64, flags: NodeFlags, doc: Option<&Document>) -> Node {
         Node {
             eventtarget: EventTarget::new_inherited(EventTargetTypeId::Node(type_id)),
-            id: id,
+            id: Cell::new(id),
 
             parent_node: Default::default(),
             first_child: Default::default(),
@@ -1172,6 +1220,9 @@ impl Node {
             child_list: Default::default(),
             children_count: Cell::new(0u32),
             flags: Cell::new(flags),
+            subscriptions: Cell::new(EventSet::empty()),
+            vetoable: Cell::new(EventSet::empty()),
+            passive: Cell::new(EventSet::empty()),
             inclusive_descendants_version: Cell::new(0),
 
             style_and_layout_data: Cell::new(None),
@@ -1182,7 +1233,74 @@ impl Node {
 
     /// Retrieves the id of the node.
     pub fn get_id(&self) -> u64 {
-        self.id
+        self.id.get()
+    }
+
+    /// Gives a node another id, i.e. the one of the node it replaces. The node must not be in a
+    /// document yet, so that it is registered under the new id when it is inserted.
+    pub fn set_id(&self, id: u64) {
+        debug_assert!(!self.is_in_doc());
+        self.id.set(id);
+    }
+
+    /// Detaches a node kept for reuse and clears its children, flags and subscriptions, giving
+    /// it a new id. See `Element::reset_shell`.
+    pub fn reset_shell(&self, id: u64) {
+        if let Some(parent) = self.GetParentNode() {
+            let _ = parent.RemoveChild(self);
+        }
+        Node::replace_all(None, self);
+        self.id.set(id);
+        self.set_frozen(false);
+        self.flags.set(NodeFlags::new());
+        self.drop_subscriptions();
+    }
+
+    /// Stops forwarding any events dispatched to this node to the vdom server.
+    pub fn drop_subscriptions(&self) {
+        self.subscriptions.set(EventSet::empty());
+        self.vetoable.set(EventSet::empty());
+        self.passive.set(EventSet::empty());
+    }
+
+    /// The events forwarded to the vdom server when dispatched to this node.
+    pub fn subscriptions(&self) -> EventSet {
+        self.subscriptions.get()
+    }
+
+    pub fn subscribe(&self, events: EventSet) {
+        self.subscriptions.set(self.subscriptions.get() | events);
+    }
+
+    pub fn unsubscribe(&self, events: EventSet) {
+        self.subscriptions.set(self.subscriptions.get() - events);
+    }
+
+    /// The size of the allocation holding this node. DOM objects are boxed with the fields of
+    /// their base types first, which `upcast` already relies on, so the node's address is the
+    /// start of its allocation.
+    #[allow(unsafe_code)]
+    pub fn heap_size(&self) -> usize {
+        unsafe { heap_size_of(self as *const Node as *const _) }
+    }
+
+    /// The events whose default action waits for the vdom server's verdict.
+    pub fn vetoable(&self) -> EventSet {
+        self.vetoable.get()
+    }
+
+    pub fn set_vetoable(&self, events: EventSet) {
+        self.vetoable.set(events);
+    }
+
+    /// The scroll and touch events whose default action proceeds without waiting for the vdom
+    /// server, which can't prevent it.
+    pub fn passive(&self) -> EventSet {
+        self.passive.get()
+    }
+
+    pub fn set_passive(&self, events: EventSet) {
+        self.passive.set(events & events::passive_events());
     }
 
     // https://dom.spec.whatwg.org/#concept-node-adopt
@@ -2045,6 +2163,7 @@ impl VirtualMethods for Node {
         if let Some(list) = self.child_list.get() {
             list.as_children_list().children_changed(mutation);
         }
+        self.owner_doc().note_live_mutation(live::STRUCTURE);
     }
 
     fn bind_to_tree(&self, tree_in_doc: bool) {
@@ -2057,13 +2176,22 @@ impl VirtualMethods for Node {
         }
 
         let doc = document_from_node(self);
-        doc.register_node_id(self.id, self);
+        doc.register_node_id(self.id.get(), self);
     }
 
     // This handles the ranges mentioned in steps 2-3 when removing a node.
     // https://dom.spec.whatwg.org/#concept-node-remove
     fn unbind_from_tree(&self, context: &UnbindContext) {
         self.super_type().unwrap().unbind_from_tree(context);
+
+        if !context.tree_in_doc {
+            return;
+        }
+
+        // A node leaving the document is no longer frozen, so that it isn't counted as such.
+        self.set_frozen(false);
+        let doc = document_from_node(self);
+        doc.unregister_node_id(self.id.get(), self);
     }
 }
 
@@ -9,7 +9,11 @@ use dom::node::{ChildrenMutation, Node};
 use std::cell::Cell;
 
 pub enum NodeListType {
+    /// A static snapshot, i.e. the result of `querySelectorAll()`, which later mutations of the
+    /// tree don't change.
     Simple(Vec<JS<Node>>),
+    /// The live list of the children of a node, i.e. `childNodes`, which always reflects its
+    /// current children.
     Children(ChildrenList),
 }
 
@@ -47,6 +51,14 @@ impl NodeList {
         NodeList::new(NodeListType::Simple(vec![]))
     }
 
+    /// Whether the list reflects mutations made to the tree after it was created.
+    pub fn is_live(&self) -> bool {
+        match self.list_type {
+            NodeListType::Simple(_) => false,
+            NodeListType::Children(_) => true,
+        }
+    }
+
     // https://dom.spec.whatwg.org/#dom-nodelist-length
     pub fn Length(&self) -> u32 {
         match self.list_type {
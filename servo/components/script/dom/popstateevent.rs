@@ -0,0 +1,48 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::inheritance::{Castable, EventTypeId};
+use dom::bindings::js::Root;
+use dom::event::{Event, EventBubbles, EventCancelable};
+use std::cell::Ref;
+use string_cache::Atom;
+use util::str::DOMString;
+
+// https://html.spec.whatwg.org/multipage/#the-popstateevent-interface
+
+pub struct PopStateEvent {
+    event: Event,
+    state: DOMRefCell<Option<String>>,
+}
+
+impl PopStateEvent {
+    fn new_inherited() -> PopStateEvent {
+        PopStateEvent {
+            event: Event::new_inherited(EventTypeId::PopStateEvent),
+            state: DOMRefCell::new(None),
+        }
+    }
+
+    pub fn new_uninitialized() -> Root<PopStateEvent> {
+        Root::new_box(box PopStateEvent::new_inherited())
+    }
+
+    pub fn new(type_: DOMString,
+               canBubble: EventBubbles,
+               cancelable: EventCancelable,
+               state: Option<String>) -> Root<PopStateEvent> {
+        let ev = PopStateEvent::new_uninitialized();
+        ev.upcast::<Event>().init_event(Atom::from(type_),
+                                        canBubble == EventBubbles::Bubbles,
+                                        cancelable == EventCancelable::Cancelable);
+        *ev.state.borrow_mut() = state;
+        ev
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-popstateevent-state
+    pub fn state(&self) -> Ref<Option<String>> {
+        self.state.borrow()
+    }
+}
@@ -2,27 +2,66 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::js::Root;
+use dom::bindings::js::{JS, Root};
+use dom::window::Window;
+use euclid::size::Size2D;
+use msg::constellation_msg::WindowSizeData;
 
-pub struct Screen {}
+pub struct Screen {
+    window: JS<Window>,
+}
 
 impl Screen {
-    fn new_inherited() -> Screen {
+    fn new_inherited(window: &Window) -> Screen {
         Screen {
+            window: JS::from_ref(window),
         }
     }
 
-    pub fn new() -> Root<Screen> {
-        Root::new_box(box Screen::new_inherited())
+    pub fn new(window: &Window) -> Root<Screen> {
+        Root::new_box(box Screen::new_inherited(window))
+    }
+
+    // https://drafts.csswg.org/cssom-view/#dom-screen-width
+    pub fn Width(&self) -> u32 {
+        screen_size(self.window.window_size()).width
+    }
+
+    // https://drafts.csswg.org/cssom-view/#dom-screen-height
+    pub fn Height(&self) -> u32 {
+        screen_size(self.window.window_size()).height
+    }
+
+    // https://drafts.csswg.org/cssom-view/#dom-screen-availwidth
+    pub fn AvailWidth(&self) -> u32 {
+        self.Width()
+    }
+
+    // https://drafts.csswg.org/cssom-view/#dom-screen-availheight
+    pub fn AvailHeight(&self) -> u32 {
+        self.Height()
     }
 
     // https://drafts.csswg.org/cssom-view/#dom-screen-colordepth
-    fn ColorDepth(&self) -> u32 {
+    pub fn ColorDepth(&self) -> u32 {
         24
     }
 
     // https://drafts.csswg.org/cssom-view/#dom-screen-pixeldepth
-    fn PixelDepth(&self) -> u32 {
+    pub fn PixelDepth(&self) -> u32 {
         24
     }
 }
+
+/// The size of the screen in CSS pixels. The size of the actual screen isn't known, so it is
+/// that of the viewport of the window, which is all of the screen the page gets; it is empty
+/// until the window has a size.
+pub fn screen_size(window_size: Option<WindowSizeData>) -> Size2D<u32> {
+    match window_size {
+        Some(window_size) => {
+            let viewport = window_size.visible_viewport;
+            Size2D::new(viewport.width.get().round() as u32, viewport.height.get().round() as u32)
+        },
+        None => Size2D::new(0, 0)
+    }
+}
@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::inheritance::{Castable, UIEventTypeId};
+use dom::bindings::js::Root;
+use dom::event::{EventBubbles, EventCancelable};
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use std::cell::Cell;
+use util::str::DOMString;
+
+pub mod WheelEventConstants {
+    pub const DOM_DELTA_PIXEL: u32 = 0;
+    pub const DOM_DELTA_LINE: u32 = 1;
+    pub const DOM_DELTA_PAGE: u32 = 2;
+}
+
+// https://w3c.github.io/uievents/#interface-wheelevent
+
+pub struct WheelEvent {
+    uievent: UIEvent,
+    client_x: Cell<i32>,
+    client_y: Cell<i32>,
+    delta_x: Cell<f64>,
+    delta_y: Cell<f64>,
+    delta_z: Cell<f64>,
+    delta_mode: Cell<u32>,
+}
+
+impl WheelEvent {
+    fn new_inherited() -> WheelEvent {
+        WheelEvent {
+            uievent: UIEvent::new_inherited(UIEventTypeId::WheelEvent),
+            client_x: Cell::new(0),
+            client_y: Cell::new(0),
+            delta_x: Cell::new(0.0),
+            delta_y: Cell::new(0.0),
+            delta_z: Cell::new(0.0),
+            delta_mode: Cell::new(WheelEventConstants::DOM_DELTA_PIXEL),
+        }
+    }
+
+    pub fn new_uninitialized() -> Root<WheelEvent> {
+        Root::new_box(box WheelEvent::new_inherited())
+    }
+
+    pub fn new(type_: DOMString,
+               canBubble: EventBubbles,
+               cancelable: EventCancelable,
+               view: Option<&Window>,
+               clientX: i32,
+               clientY: i32,
+               deltaX: f64,
+               deltaY: f64,
+               deltaZ: f64,
+               deltaMode: u32) -> Root<WheelEvent> {
+        let ev = WheelEvent::new_uninitialized();
+        ev.upcast::<UIEvent>().InitUIEvent(type_,
+                                           canBubble == EventBubbles::Bubbles,
+                                           cancelable == EventCancelable::Cancelable,
+                                           view, 0);
+        ev.client_x.set(clientX);
+        ev.client_y.set(clientY);
+        ev.delta_x.set(deltaX);
+        ev.delta_y.set(deltaY);
+        ev.delta_z.set(deltaZ);
+        ev.delta_mode.set(deltaMode);
+        ev
+    }
+
+    // https://w3c.github.io/uievents/#widl-MouseEvent-clientX
+    pub fn ClientX(&self) -> i32 {
+        self.client_x.get()
+    }
+
+    // https://w3c.github.io/uievents/#widl-MouseEvent-clientY
+    pub fn ClientY(&self) -> i32 {
+        self.client_y.get()
+    }
+
+    // https://w3c.github.io/uievents/#widl-WheelEvent-deltaX
+    pub fn DeltaX(&self) -> f64 {
+        self.delta_x.get()
+    }
+
+    // https://w3c.github.io/uievents/#widl-WheelEvent-deltaY
+    pub fn DeltaY(&self) -> f64 {
+        self.delta_y.get()
+    }
+
+    // https://w3c.github.io/uievents/#widl-WheelEvent-deltaZ
+    pub fn DeltaZ(&self) -> f64 {
+        self.delta_z.get()
+    }
+
+    // https://w3c.github.io/uievents/#widl-WheelEvent-deltaMode
+    pub fn DeltaMode(&self) -> u32 {
+        self.delta_mode.get()
+    }
+}
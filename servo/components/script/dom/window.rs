@@ -10,6 +10,7 @@ use dom::bindings::error::{Error, Fallible};
 use dom::bindings::inheritance::{Castable,EventTargetTypeId};
 use dom::bindings::js::{JS, MutNullableHeap, Root};
 use dom::bindings::num::Finite;
+use dom::bindings::refcounted::Trusted;
 use dom::browsingcontext::BrowsingContext;
 use dom::cssstyledeclaration::{CSSModificationAccess, CSSStyleDeclaration};
 use dom::document::{Document,DocumentReadyState};
@@ -18,10 +19,11 @@ use dom::eventtarget::EventTarget;
 use dom::node::{Node, TrustedNodeAddress, from_untrusted_node_address, window_from_node};
 use dom::screen::Screen;
 use euclid::{Point2D, Rect, Size2D};
-use gfx_traits::LayerId;
+use gfx_traits::{LayerId, LayerType};
 use ipc_channel::ipc::{self, IpcSender};
+use ipc_channel::router::ROUTER;
 use layout_interface::{ContentBoxResponse, ContentBoxesResponse, ResolvedStyleResponse, ScriptReflow};
-use layout_interface::{LayoutChan, LayoutRPC, Msg, Reflow, ReflowQueryType, MarginStyleResponse};
+use layout_interface::{BoxModelSides, LayoutChan, LayoutRPC, Msg, Reflow, ReflowQueryType, MarginStyleResponse};
 use msg::constellation_msg::{ConstellationChan, LoadData, PipelineId, SubpageId, WindowSizeData};
 use net_traits::ResourceThread;
 use net_traits::image_cache_thread::{ImageCacheChan, ImageCacheThread};
@@ -31,14 +33,15 @@ use page::Page;
 use profile_traits::mem;
 use reporter::CSSErrorReporter;
 use rustc_serialize::base64::{FromBase64, STANDARD, ToBase64};
-use script_thread::{MainThreadScriptChan, MainThreadScriptMsg, RunnableWrapper};
-use script_thread::{SendableMainThreadScriptChan, ScriptChan, ScriptPort};
+use script_thread::{CommonScriptMsg, MainThreadScriptChan, MainThreadScriptMsg, Runnable, RunnableWrapper};
+use script_thread::{ScriptThreadEventCategory, SendableMainThreadScriptChan, ScriptChan, ScriptPort};
 use script_traits::{ConstellationControlMsg, UntrustedNodeAddress};
 use script_traits::{DocumentState, ScriptToCompositorMsg};
-use script_traits::{ScriptMsg as ConstellationMsg, TimerEventRequest};
+use script_traits::{MsDuration, ScriptMsg as ConstellationMsg, TimerEvent, TimerEventId, TimerEventRequest};
+use script_traits::TimerSource;
 use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
-use std::cell::Cell;
+use std::cell::{Cell, RefMut};
 use std::collections::HashSet;
 use std::default::Default;
 use std::io::{Write, stderr, stdout};
@@ -48,9 +51,13 @@ use std::sync::mpsc::TryRecvError::{Disconnected, Empty};
 use std::sync::mpsc::{Sender, channel};
 use std::sync::{Arc, Mutex};
 use string_cache::Atom;
+use style::computed_values::font_family::FontFamily;
 use style::context::ReflowGoal;
 use style::error_reporting::ParseErrorReporter;
+use style::font_face::{Source, UrlSource};
+use style::media_queries::Device;
 use style::selector_impl::PseudoElement;
+use style_traits::color_scheme::ColorScheme;
 use task_source::TaskSource;
 use task_source::dom_manipulation::{DOMManipulationTaskSource, DOMManipulationTask};
 use task_source::file_reading::FileReadingTaskSource;
@@ -62,6 +69,8 @@ use url::Url;
 use util::geometry::{self, MAX_RECT};
 use util::str::{DOMString, HTML_SPACE_CHARACTERS};
 use util::{breakpoint, opts};
+use vdom::media::{self, MediaWatch};
+use vdom::msg::{ServerChan, ServerMsg};
 
 #[repr(usize)]
 #[derive(PartialEq, Copy, Clone, HeapSizeOf, Debug)]
@@ -91,7 +100,7 @@ enum WindowState {
 }
 
 /// Extra information concerning the reason for reflowing.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ReflowReason {
     CachedPageNeededReflow,
     RefreshTick,
@@ -111,6 +120,8 @@ pub enum ReflowReason {
     FramedContentChanged,
     IFrameLoadEvent,
     MissingExplicitReflow,
+    ColorSchemeChanged,
+    VdomPatch,
 }
 
 pub type ScrollPoint = Point2D<Au>;
@@ -143,6 +154,18 @@ pub struct Window {
     screen: MutNullableHeap<JS<Screen>>,
     #[ignore_heap_size_of = "channels are hard"]
     scheduler_chan: IpcSender<TimerEventRequest>,
+    /// The channel the scheduler fires the timer events of this window on.
+    #[ignore_heap_size_of = "channels are hard"]
+    timer_event_chan: IpcSender<TimerEvent>,
+    #[ignore_heap_size_of = "channels are hard"]
+    vdom_chan: ServerChan,
+    /// The media queries the vdom server is notified about when their result changes.
+    #[ignore_heap_size_of = "TODO(#6909) need to measure Vec"]
+    media_watch: DOMRefCell<MediaWatch>,
+    /// The color scheme the platform prefers.
+    platform_color_scheme: Cell<ColorScheme>,
+    /// The name of the browsing context, which navigations may target it by.
+    name: DOMRefCell<DOMString>,
 
     next_worker_id: Cell<WorkerId>,
 
@@ -374,8 +397,18 @@ impl Window {
     event_handler!(unload, GetOnunload, SetOnunload);
 
     // https://developer.mozilla.org/en-US/docs/Web/API/Window/screen
-    fn Screen(&self) -> Root<Screen> {
-        self.screen.or_init(|| Screen::new())
+    pub fn Screen(&self) -> Root<Screen> {
+        self.screen.or_init(|| Screen::new(self))
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-name
+    pub fn Name(&self) -> DOMString {
+        self.name.borrow().clone()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-name
+    pub fn SetName(&self, name: DOMString) {
+        *self.name.borrow_mut() = name;
     }
 
     // https://html.spec.whatwg.org/multipage/#dom-windowbase64-btoa
@@ -565,7 +598,7 @@ impl Window {
     }
 
     // https://drafts.csswg.org/cssom-view/#dom-window-devicepixelratio
-    fn DevicePixelRatio(&self) -> Finite<f64> {
+    pub fn DevicePixelRatio(&self) -> Finite<f64> {
         let dpr = self.window_size.get().map_or(1.0f32, |data| data.device_pixel_ratio.get());
         Finite::wrap(dpr as f64)
     }
@@ -722,6 +755,15 @@ impl Window {
                                                          self.pipeline(), LayerId::null(), point, smooth)).unwrap()
     }
 
+    /// Scrolls an overflow container of the page to `top` and `left`, which have to be in its
+    /// scroll range, by scrolling the layer layout gave its overflow.
+    pub fn scroll_element(&self, element: &Element, top: f32, left: f32) {
+        let address = element.upcast::<Node>().to_trusted_node_address();
+        let layer_id = LayerId::new_of_type(LayerType::OverflowScroll, address.0 as usize);
+        self.compositor.send(ScriptToCompositorMsg::ScrollFragmentPoint(
+                                                         self.pipeline(), layer_id, Point2D::new(left, top), false)).unwrap()
+    }
+
     pub fn client_window(&self) -> (Size2D<u32>, Point2D<i32>) {
         let (send, recv) = ipc::channel::<(Size2D<u32>, Point2D<i32>)>().unwrap();
         self.compositor.send(ScriptToCompositorMsg::GetClientWindow(send)).unwrap();
@@ -785,8 +827,10 @@ impl Window {
             document_stylesheets: document.stylesheets().clone(),
             stylesheets_changed: stylesheets_changed,
             window_size: window_size,
+            color_scheme: self.color_scheme(),
             script_join_chan: join_chan,
             query_type: query_type,
+            reason: reason,
         };
 
         let LayoutChan(ref chan) = self.layout_chan;
@@ -933,6 +977,13 @@ impl Window {
         self.layout_rpc.margin_style()
     }
 
+    pub fn box_model_query(&self, node: TrustedNodeAddress) -> Option<BoxModelSides> {
+        self.reflow(ReflowGoal::ForScriptQuery,
+                    ReflowQueryType::BoxModelQuery(node),
+                    ReflowReason::Query);
+        self.layout_rpc.box_model().0
+    }
+
     pub fn init_browsing_context(&self, browsing_context: &BrowsingContext) {
         assert!(self.browsing_context.get().is_none());
         self.browsing_context.set(Some(&browsing_context));
@@ -961,7 +1012,7 @@ impl Window {
     }
 
     pub fn get_url(&self) -> Url {
-        (*self.Document().url()).clone()
+        self.Document().url()
     }
 
     pub fn resource_thread(&self) -> ResourceThread {
@@ -988,6 +1039,67 @@ impl Window {
         self.scheduler_chan.clone()
     }
 
+    /// Asks the scheduler to fire the timer event `id` at this window after `duration`.
+    pub fn schedule_timer_event(&self, id: TimerEventId, duration: MsDuration) {
+        let source = TimerSource::FromWindow(self.id);
+        let request = TimerEventRequest(self.timer_event_chan.clone(), source, id, duration);
+        let _ = self.scheduler_chan.send(request);
+    }
+
+    pub fn vdom_chan(&self) -> &ServerChan {
+        &self.vdom_chan
+    }
+
+    /// Loads a web font into `family` for the vdom server. Layout reflows with the font once it
+    /// loaded, as it does for `@font-face` rules, and the server is sent `FontLoaded`, or
+    /// `FontError` if it couldn't be loaded.
+    pub fn preload_vdom_font(&self, family: String, url: Url) {
+        let (sender, receiver) = ipc::channel().unwrap();
+        let trusted_window = Trusted::new(self, self.networking_task_source());
+        let script_chan = self.networking_task_source();
+        let wrapper = self.get_runnable_wrapper();
+        let (font_family, font_url) = (family.clone(), url.serialize());
+        ROUTER.add_route(receiver.to_opaque(), box move |message| {
+            let runnable = FontPreloadRunnable {
+                window: trusted_window.clone(),
+                family: font_family.clone(),
+                url: font_url.clone(),
+                loaded: message.to().unwrap(),
+            };
+            let _ = script_chan.send(CommonScriptMsg::RunnableMsg(ScriptThreadEventCategory::NetworkEvent,
+                                                                  wrapper.wrap_runnable(runnable)));
+        });
+
+        let source = Source::Url(UrlSource {
+            url: url,
+            format_hints: vec![],
+        });
+        let LayoutChan(ref chan) = self.layout_chan;
+        chan.send(Msg::PreloadFont(FontFamily::FamilyName(Atom::from(&*family)), source, sender)).unwrap();
+    }
+
+    pub fn media_watch(&self) -> RefMut<MediaWatch> {
+        self.media_watch.borrow_mut()
+    }
+
+    /// The device media queries are evaluated against.
+    pub fn media_device(&self) -> Device {
+        let (width, height) = self.window_size.get().map_or((0.0, 0.0), |size| {
+            (size.initial_viewport.width.get(), size.initial_viewport.height.get())
+        });
+        media::device(width, height).with_color_scheme(self.color_scheme())
+    }
+
+    /// The color scheme `prefers-color-scheme` matches: the document's vdom preference, if the
+    /// server set one, or the platform's.
+    pub fn color_scheme(&self) -> ColorScheme {
+        self.Document().vdom_color_scheme().unwrap_or(self.platform_color_scheme.get())
+    }
+
+    pub fn set_platform_color_scheme(&self, color_scheme: ColorScheme) {
+        self.platform_color_scheme.set(color_scheme);
+    }
+
     pub fn get_next_subpage_id(&self) -> SubpageId {
         let subpage_id = self.next_subpage_id.get();
         let SubpageId(id_num) = subpage_id;
@@ -1013,6 +1125,11 @@ impl Window {
         event
     }
 
+    /// The part of the page the user is looking at.
+    pub fn current_viewport(&self) -> Rect<Au> {
+        self.current_viewport.get()
+    }
+
     pub fn set_page_clip_rect_with_new_viewport(&self, viewport: Rect<f32>) -> bool {
         let rect = geometry::f32_rect_to_au_rect(viewport.clone());
         self.current_viewport.set(rect);
@@ -1119,6 +1236,8 @@ impl Window {
                constellation_chan: ConstellationChan<ConstellationMsg>,
                control_chan: IpcSender<ConstellationControlMsg>,
                scheduler_chan: IpcSender<TimerEventRequest>,
+               timer_event_chan: IpcSender<TimerEvent>,
+               vdom_chan: ServerChan,
                layout_chan: LayoutChan,
                id: PipelineId,
                parent_info: Option<(PipelineId, SubpageId)>,
@@ -1153,6 +1272,11 @@ impl Window {
             navigation_start_precise: time::precise_time_ns() as f64,
             screen: Default::default(),
             scheduler_chan: scheduler_chan.clone(),
+            timer_event_chan: timer_event_chan,
+            vdom_chan: vdom_chan,
+            media_watch: DOMRefCell::new(MediaWatch::new()),
+            platform_color_scheme: Cell::new(ColorScheme::default()),
+            name: DOMRefCell::new(DOMString::new()),
             next_worker_id: Cell::new(WorkerId(0)),
             id: id,
             parent_info: parent_info,
@@ -1219,6 +1343,7 @@ fn debug_reflow_events(id: PipelineId, goal: &ReflowGoal, query_type: &ReflowQue
         ReflowQueryType::ResolvedStyleQuery(_, _, _) => "\tResolvedStyleQuery",
         ReflowQueryType::OffsetParentQuery(_n) => "\tOffsetParentQuery",
         ReflowQueryType::MarginStyleQuery(_n) => "\tMarginStyleQuery",
+        ReflowQueryType::BoxModelQuery(_n) => "\tBoxModelQuery",
     });
 
     debug_msg.push_str(match *reason {
@@ -1240,7 +1365,28 @@ fn debug_reflow_events(id: PipelineId, goal: &ReflowGoal, query_type: &ReflowQue
         ReflowReason::FramedContentChanged => "\tFramedContentChanged",
         ReflowReason::IFrameLoadEvent => "\tIFrameLoadEvent",
         ReflowReason::MissingExplicitReflow => "\tMissingExplicitReflow",
+        ReflowReason::ColorSchemeChanged => "\tColorSchemeChanged",
+        ReflowReason::VdomPatch => "\tVdomPatch",
     });
 
     println!("{}", debug_msg);
 }
+
+struct FontPreloadRunnable {
+    window: Trusted<Window>,
+    family: String,
+    url: String,
+    loaded: bool,
+}
+
+impl Runnable for FontPreloadRunnable {
+    fn handler(self: Box<FontPreloadRunnable>) {
+        let this = *self;
+        let msg = if this.loaded {
+            ServerMsg::FontLoaded(this.family, this.url)
+        } else {
+            ServerMsg::FontError(this.family, this.url)
+        };
+        this.window.root().vdom_chan().send(msg);
+    }
+}
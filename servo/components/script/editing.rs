@@ -0,0 +1,274 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Editing of the text of `contenteditable` elements from keyboard input. The text of an
+//! editing host is edited in place, in the text nodes that hold it, so the rest of its child
+//! structure survives.
+
+use dom::bindings::inheritance::Castable;
+use dom::bindings::js::Root;
+use dom::characterdata::CharacterData;
+use dom::element::Element;
+use dom::keyboardevent::key_value;
+use dom::node::Node;
+use dom::text::Text;
+use msg::constellation_msg::{Key, KeyModifiers, SHIFT};
+use textinput::{Direction, is_control_key, is_printable_key};
+use util::str::DOMString;
+
+/// An edit of the text of an editing host.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditCommand {
+    /// Inserts text at the caret.
+    Insert(String),
+    /// Deletes the character before or after the caret.
+    Delete(Direction),
+    /// Moves the caret by one character.
+    Move(Direction),
+    /// Moves the caret to the start of the text.
+    MoveToStart,
+    /// Moves the caret to the end of the text.
+    MoveToEnd,
+    /// Moves the caret by one character, extending the selection.
+    Select(Direction),
+    /// Moves the caret to the start of the text, extending the selection.
+    SelectToStart,
+    /// Moves the caret to the end of the text, extending the selection.
+    SelectToEnd,
+}
+
+impl EditCommand {
+    /// The edit a key press makes, if any. Shortcuts, like the clipboard ones, are left to the
+    /// code handling them.
+    pub fn from_key(key: Key, mods: KeyModifiers) -> Option<EditCommand> {
+        if is_control_key(mods) {
+            return None;
+        }
+        let select = mods.contains(SHIFT);
+        Some(match key {
+            _ if is_printable_key(key) => EditCommand::Insert(key_value(key, mods).to_owned()),
+            Key::Left if select => EditCommand::Select(Direction::Backward),
+            Key::Right if select => EditCommand::Select(Direction::Forward),
+            Key::Home if select => EditCommand::SelectToStart,
+            Key::End if select => EditCommand::SelectToEnd,
+            Key::Backspace => EditCommand::Delete(Direction::Backward),
+            Key::Delete => EditCommand::Delete(Direction::Forward),
+            Key::Left => EditCommand::Move(Direction::Backward),
+            Key::Right => EditCommand::Move(Direction::Forward),
+            Key::Home => EditCommand::MoveToStart,
+            Key::End => EditCommand::MoveToEnd,
+            _ => return None
+        })
+    }
+}
+
+/// An offset moved back into a text if it is past its end, and to the start of the character
+/// it falls in.
+fn clamp(text: &str, offset: usize) -> usize {
+    let mut offset = if offset > text.len() { text.len() } else { offset };
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// The text an offset in the concatenation of `texts` falls in, along with the offset within
+/// it. An offset between two texts is at the end of the first one.
+pub fn text_position(texts: &[String], offset: usize) -> Option<(usize, usize)> {
+    let mut start = 0;
+    for (index, text) in texts.iter().enumerate() {
+        if offset <= start + text.len() {
+            return Some((index, offset - start));
+        }
+        start += text.len();
+    }
+    None
+}
+
+/// The texts of an editing host in tree order, with a selection somewhere in their
+/// concatenation.
+pub struct EditBuffer {
+    pub texts: Vec<String>,
+    /// The offset of the caret in the concatenated text, in UTF-8 bytes.
+    pub caret: usize,
+    /// The offset of the other end of the selection, which is the caret unless the selection
+    /// was extended.
+    pub anchor: usize,
+}
+
+impl EditBuffer {
+    /// Creates a buffer with the caret moved back into the text if it is past its end, i.e.
+    /// after the server shortened it.
+    pub fn new(texts: Vec<String>, caret: usize) -> EditBuffer {
+        EditBuffer::with_selection(texts, caret, caret)
+    }
+
+    /// Creates a buffer selecting from `anchor` to `caret`, both moved back into the text like
+    /// the caret of `new`.
+    pub fn with_selection(texts: Vec<String>, anchor: usize, caret: usize) -> EditBuffer {
+        let text = texts.concat();
+        EditBuffer {
+            texts: texts,
+            caret: clamp(&text, caret),
+            anchor: clamp(&text, anchor),
+        }
+    }
+
+    /// The concatenated text.
+    pub fn text(&self) -> String {
+        self.texts.concat()
+    }
+
+    /// The text holding the character at `offset`, along with the offset within it.
+    fn text_at(&self, offset: usize) -> (usize, usize) {
+        let mut start = 0;
+        for (index, text) in self.texts.iter().enumerate() {
+            if offset < start + text.len() {
+                return (index, offset - start);
+            }
+            start += text.len();
+        }
+        unreachable!("offset past the end of the text")
+    }
+
+    /// The text the caret inserts into, along with the caret's offset within it. A caret
+    /// between two texts inserts at the end of the first one.
+    fn insertion_point(&self) -> (usize, usize) {
+        text_position(&self.texts, self.caret).expect("caret past the end of the text")
+    }
+
+    /// The length of the character next to the caret in a direction, if there is one.
+    fn char_len(&self, direction: Direction) -> Option<usize> {
+        let text = self.text();
+        let ch = match direction {
+            Direction::Backward => text[..self.caret].chars().next_back(),
+            Direction::Forward => text[self.caret..].chars().next(),
+        };
+        ch.map(|ch| ch.len_utf8())
+    }
+
+    /// Applies a command, returning the index of the text it changed, if it changed one. Edits
+    /// happen at the caret, collapsing the selection; commands other than the `Select` ones
+    /// collapse it too.
+    pub fn apply(&mut self, command: &EditCommand) -> Option<usize> {
+        let changed = self.apply_to_caret(command);
+        match *command {
+            EditCommand::Select(_) | EditCommand::SelectToStart | EditCommand::SelectToEnd => {},
+            _ => self.anchor = self.caret,
+        }
+        changed
+    }
+
+    fn apply_to_caret(&mut self, command: &EditCommand) -> Option<usize> {
+        match *command {
+            EditCommand::Insert(ref inserted) => {
+                if self.texts.is_empty() {
+                    self.texts.push(String::new());
+                }
+                let (index, offset) = self.insertion_point();
+                let text = format!("{}{}{}", &self.texts[index][..offset], inserted, &self.texts[index][offset..]);
+                self.texts[index] = text;
+                self.caret += inserted.len();
+                Some(index)
+            },
+            EditCommand::Delete(direction) => {
+                let len = match self.char_len(direction) {
+                    Some(len) => len,
+                    None => return None
+                };
+                if direction == Direction::Backward {
+                    self.caret -= len;
+                }
+                let (index, offset) = self.text_at(self.caret);
+                let text = format!("{}{}", &self.texts[index][..offset], &self.texts[index][offset + len..]);
+                self.texts[index] = text;
+                Some(index)
+            },
+            EditCommand::Move(direction) | EditCommand::Select(direction) => {
+                if let Some(len) = self.char_len(direction) {
+                    match direction {
+                        Direction::Backward => self.caret -= len,
+                        Direction::Forward => self.caret += len,
+                    }
+                }
+                None
+            },
+            EditCommand::MoveToStart | EditCommand::SelectToStart => {
+                self.caret = 0;
+                None
+            },
+            EditCommand::MoveToEnd | EditCommand::SelectToEnd => {
+                self.caret = self.text().len();
+                None
+            }
+        }
+    }
+}
+
+/// The offset in the concatenated text of an editing host of `offset` in its text at `index`,
+/// clamped to that text and moved back to the start of the character it falls in.
+pub fn host_offset(texts: &[String], index: usize, offset: usize) -> usize {
+    texts[..index].iter().fold(clamp(&texts[index], offset), |start, text| start + text.len())
+}
+
+/// The text nodes of an editing host in tree order, along with their data.
+fn host_texts(host: &Element) -> (Vec<Root<Text>>, Vec<String>) {
+    let texts = host.upcast::<Node>().traverse_preorder().filter_map(Root::downcast::<Text>).collect::<Vec<_>>();
+    let data = texts.iter().map(|text| String::from(text.upcast::<CharacterData>().Data())).collect();
+    (texts, data)
+}
+
+/// The offset in the text of an editing host of an end of a selection at `offset` in `node`,
+/// which is either one of the text nodes of the host or the host itself. Offsets past the end
+/// of the node's text are clamped to it.
+pub fn selection_offset(host: &Element, node: &Node, offset: usize) -> Option<usize> {
+    let (texts, data) = host_texts(host);
+    if node == host.upcast::<Node>() {
+        return Some(EditBuffer::new(data, offset).caret);
+    }
+    texts.iter().position(|text| text.upcast::<Node>() == node).map(|index| host_offset(&data, index, offset))
+}
+
+/// An offset in the text of an editing host clamped to the text, like the caret is.
+pub fn clamp_to_host(host: &Element, offset: usize) -> usize {
+    clamp(&host_texts(host).1.concat(), offset)
+}
+
+/// An end of a selection at `offset` in the text of an editing host, as the id of the text node
+/// it is in and the offset within that node. Offsets in text nodes the server doesn't know
+/// about, i.e. one an edit created, are given in the text of the host instead.
+pub fn selection_end(host: &Element, offset: usize) -> (u64, u32) {
+    let (texts, data) = host_texts(host);
+    match text_position(&data, offset) {
+        Some((index, offset)) if texts[index].upcast::<Node>().get_id() != 0 => {
+            (texts[index].upcast::<Node>().get_id(), offset as u32)
+        },
+        _ => (host.upcast::<Node>().get_id(), offset as u32)
+    }
+}
+
+/// Applies a command to an editing host selecting from `anchor` to `caret`, returning where the
+/// anchor and the caret end up and, if the command changed the text, the new text of the host.
+pub fn edit_host(host: &Element, anchor: usize, caret: usize, command: &EditCommand)
+                 -> (usize, usize, Option<String>) {
+    let node = host.upcast::<Node>();
+    let (texts, data) = host_texts(host);
+    let mut buffer = EditBuffer::with_selection(data, anchor, caret);
+    let index = match buffer.apply(command) {
+        Some(index) => index,
+        None => return (buffer.anchor, buffer.caret, None)
+    };
+
+    let data = DOMString::from(buffer.texts[index].clone());
+    match texts.get(index) {
+        Some(text) => text.upcast::<CharacterData>().set_data(data),
+        None => {
+            // The host had no text yet. The new node has no server id: the server learns about
+            // it from the edit report.
+            let text = Text::new(0, data, &node.owner_doc());
+            let _ = node.AppendChild(text.upcast());
+        }
+    }
+    (buffer.anchor, buffer.caret, Some(buffer.text()))
+}
@@ -8,8 +8,10 @@
 
 use app_units::Au;
 use dom::node::OpaqueStyleAndLayoutData;
+use dom::window::ReflowReason;
 use euclid::point::Point2D;
 use euclid::rect::Rect;
+use euclid::side_offsets::SideOffsets2D;
 use gfx_traits::{Epoch, LayerId};
 use ipc_channel::ipc::{IpcReceiver, IpcSender};
 use msg::constellation_msg::{ConstellationChan, Failure, PipelineId};
@@ -22,10 +24,13 @@ use std::any::Any;
 use std::sync::Arc;
 use std::sync::mpsc::{Receiver, Sender, channel};
 use string_cache::Atom;
+use style::computed_values::font_family::FontFamily;
 use style::context::ReflowGoal;
+use style::font_face::Source;
 use style::properties::longhands::{margin_top, margin_right, margin_bottom, margin_left};
 use style::selector_impl::PseudoElement;
 use style::servo::Stylesheet;
+use style_traits::color_scheme::ColorScheme;
 use url::Url;
 use util::ipc::OptionalOpaqueIpcSender;
 
@@ -51,6 +56,10 @@ pub enum Msg {
     /// Requests that the layout thread reflow with a newly-loaded Web font.
     ReflowWithNewlyLoadedWebFont,
 
+    /// Loads a Web font into the given family, as an `@font-face` rule would, and reports whether
+    /// it loaded on the supplied channel.
+    PreloadFont(FontFamily, Source, IpcSender<bool>),
+
     /// Updates the layout visible rects, affecting the area that display lists will be constructed
     /// for.
     SetVisibleRects(Vec<(LayerId, Rect<Au>)>),
@@ -113,6 +122,8 @@ pub trait LayoutRPC {
     fn offset_parent(&self) -> OffsetParentResponse;
     /// Query layout for the resolve values of the margin properties for an element.
     fn margin_style(&self) -> MarginStyleResponse;
+    /// Requests the border box of a node, with the widths of its border, padding and margin.
+    fn box_model(&self) -> BoxModelResponse;
 }
 
 #[derive(Clone)]
@@ -144,6 +155,17 @@ pub struct NodeGeometryResponse {
 }
 pub struct ResolvedStyleResponse(pub Option<String>);
 
+/// The box model of the first fragment of a node, if it has one, i.e. isn't `display: none`.
+pub struct BoxModelResponse(pub Option<BoxModelSides>);
+
+#[derive(Clone, Copy)]
+pub struct BoxModelSides {
+    pub border_box: Rect<Au>,
+    pub border: SideOffsets2D<Au>,
+    pub padding: SideOffsets2D<Au>,
+    pub margin: SideOffsets2D<Au>,
+}
+
 #[derive(Clone)]
 pub struct OffsetParentResponse {
     pub node_address: Option<UntrustedNodeAddress>,
@@ -171,6 +193,7 @@ pub enum ReflowQueryType {
     ResolvedStyleQuery(TrustedNodeAddress, Option<PseudoElement>, Atom),
     OffsetParentQuery(TrustedNodeAddress),
     MarginStyleQuery(TrustedNodeAddress),
+    BoxModelQuery(TrustedNodeAddress),
 }
 
 /// Information needed for a reflow.
@@ -193,10 +216,14 @@ pub struct ScriptReflow {
     pub stylesheets_changed: bool,
     /// The current window size.
     pub window_size: WindowSizeData,
+    /// The color scheme `prefers-color-scheme` media queries match.
+    pub color_scheme: ColorScheme,
     /// The channel that we send a notification to.
     pub script_join_chan: Sender<()>,
     /// The type of query if any to perform during this reflow.
     pub query_type: ReflowQueryType,
+    /// Why script asked for this reflow.
+    pub reason: ReflowReason,
 }
 
 impl Drop for ScriptReflow {
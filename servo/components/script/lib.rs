@@ -68,6 +68,7 @@ extern crate smallvec;
 #[macro_use(atom, ns)] extern crate string_cache;
 #[macro_use]
 extern crate style;
+extern crate style_traits;
 extern crate time;
 extern crate unicase;
 extern crate url;
@@ -82,6 +83,7 @@ mod devtools;
 pub mod document_loader;
 #[macro_use]
 pub mod dom;
+pub mod editing;
 pub mod layout_interface;
 mod network_listener;
 pub mod page;
@@ -33,6 +33,7 @@ use dom::create::create_element_simple;
 use dom::document::{Document, DocumentProgressHandler, DocumentReadyState, DocumentSource, IsHTMLDocument};
 use dom::element::{Element, ElementCreator};
 use dom::event::{Event, EventBubbles, EventCancelable};
+use dom::eventtarget::EventTarget;
 use dom::htmlanchorelement::HTMLAnchorElement;
 use dom::node::{Node, NodeDamage, window_from_node};
 use dom::text::Text;
@@ -53,18 +54,19 @@ use net_traits::image_cache_thread::{ImageCacheChan, ImageCacheResult, ImageCach
 use net_traits::storage_thread::StorageThread;
 use net_traits::{ResourceThread};
 use page::{Frame, IterablePage, Page};
-use profile_traits::mem::{self, OpaqueSender, ReportsChan};
+use profile_traits::mem::{self, OpaqueSender, Report, ReportKind, ReportsChan};
 use profile_traits::time::{self, ProfilerCategory, profile};
-use script_traits::CompositorEvent::{KeyEvent, MouseButtonEvent, MouseMoveEvent, ResizeEvent};
-use script_traits::CompositorEvent::{TouchEvent};
+use script_traits::CompositorEvent::{CompositionEvent, KeyEvent, MouseButtonEvent, MouseMoveEvent};
+use script_traits::CompositorEvent::{ResizeEvent};
+use script_traits::CompositorEvent::{TouchEvent, WheelEvent};
 use script_traits::{CompositorEvent, ConstellationControlMsg, EventResult};
 use script_traits::{InitialScriptState, MouseButton, MouseEventType};
 use script_traits::{LayoutMsg, OpaqueScriptLayoutChannel, ScriptMsg as ConstellationMsg};
-use script_traits::{ScriptThreadFactory, ScriptToCompositorMsg,TimerEventRequest};
+use script_traits::{ScriptThreadFactory, ScriptToCompositorMsg, TimerEvent, TimerEventRequest, TimerSource};
 use script_traits::{TouchEventType, TouchId};
 use servo_vdom_client::patch::*;
 use std::any::Any;
-use std::cell::{RefCell};
+use std::cell::{Cell, RefCell};
 use std::collections::HashSet;
 use std::io;
 use std::io::Cursor;
@@ -76,6 +78,7 @@ use std::sync::atomic::{Ordering, AtomicBool};
 use std::sync::mpsc::{Receiver, Select, Sender, channel};
 use std::sync::{Arc};
 use style::context::ReflowGoal;
+use style_traits::color_scheme::ColorScheme;
 use task_source::TaskSource;
 use task_source::dom_manipulation::{DOMManipulationTaskSource, DOMManipulationTask};
 use task_source::file_reading::FileReadingTaskSource;
@@ -88,6 +91,12 @@ use util::str::DOMString;
 use util::thread;
 use util::thread_state;
 use vdom::apply_patches;
+use vdom::echoes::EchoSuppression;
+use vdom::error::PatchError;
+use vdom::msg::{ClientCapabilities, ServerChan, ServerMsg};
+use vdom::undo::UndoLog;
+use vdom::verdict::{PendingVerdicts, Verdict};
+use vdom::wire::MAX_MESSAGE_SIZE;
 
 thread_local!(static SCRIPT_THREAD_ROOT: RefCell<Option<*const ScriptThread>> = RefCell::new(None));
 
@@ -176,6 +185,7 @@ enum MixedMessage {
     FromScript(MainThreadScriptMsg),
     FromDevtools(DevtoolScriptControlMsg),
     FromImageCache(ImageCacheResult),
+    FromScheduler(TimerEvent),
     FromVdom(Vec<u8>)
 }
 
@@ -188,6 +198,8 @@ pub enum CommonScriptMsg {
     RefcountCleanup(TrustedReference),
     /// Generic message that encapsulates event handling.
     RunnableMsg(ScriptThreadEventCategory, Box<Runnable + Send>),
+    /// The vdom server's verdict on a blocking event, delivered to the waiting dispatch.
+    VdomVerdict(u32, Verdict),
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -310,6 +322,46 @@ impl MainThreadScriptChan {
     }
 }
 
+/// The `dom-tree` memory report of a page, given the size of its own nodes only.
+pub fn dom_tree_report(url: &str, size: usize) -> Report {
+    Report {
+        path: path![format!("url({})", url), "dom-tree"],
+        kind: ReportKind::ExplicitJemallocHeapSize,
+        size: size,
+    }
+}
+
+/// Whether navigating from `current` to `target` only changes the fragment, so that the document
+/// stays and is scrolled instead.
+pub fn is_fragment_navigation(current: &Url, target: &Url) -> bool {
+    target.fragment.is_some() && current.scheme == target.scheme &&
+        current.scheme_data == target.scheme_data && current.query == target.query
+}
+
+/// A size computed from a tree, reused until the tree's version changes.
+pub struct SizeCache {
+    cached: Cell<Option<(u64, usize)>>,
+}
+
+impl SizeCache {
+    pub fn new() -> SizeCache {
+        SizeCache {
+            cached: Cell::new(None),
+        }
+    }
+
+    pub fn get_or_compute<F: FnOnce() -> usize>(&self, version: u64, compute: F) -> usize {
+        match self.cached.get() {
+            Some((cached_version, size)) if cached_version == version => size,
+            _ => {
+                let size = compute();
+                self.cached.set(Some((version, size)));
+                size
+            }
+        }
+    }
+}
+
 /// Information for an entire page. Pages are top-level browsing contexts and can contain multiple
 /// frames.
 // ScriptThread instances are rooted on creation, so this is okay
@@ -385,11 +437,19 @@ pub struct ScriptThread {
     closed_pipelines: DOMRefCell<HashSet<PipelineId>>,
 
     scheduler_chan: IpcSender<TimerEventRequest>,
+    /// The channel the scheduler fires the timer events of the windows of this thread on.
+    timer_event_chan: IpcSender<TimerEvent>,
+    /// The port on which the timer events of the windows of this thread are received.
+    timer_event_port: Receiver<TimerEvent>,
 
     content_process_shutdown_chan: IpcSender<()>,
 
-    vdom_chan: IpcSender<Vec<u8>>,
-    vdom_port: Receiver<Vec<u8>>
+    vdom_chan: ServerChan,
+    vdom_port: Receiver<Vec<u8>>,
+    /// The HTML id of the element the vdom is mounted under, instead of the body.
+    vdom_mount: Option<String>,
+    /// The suppressed regions the vdom ops being applied are in, whose echoes aren't sent.
+    vdom_echoes: Cell<EchoSuppression>
 }
 
 /// In the event of thread failure, all data on the stack runs its destructor. However, there
@@ -430,6 +490,27 @@ impl<'a> Drop for ScriptMemoryFailsafe<'a> {
     }
 }
 
+/// Connects to the vdom server given on the command line, returning the port its messages are
+/// received on and the channel ours are sent on. Without one, both lead nowhere.
+fn connect_vdom() -> (IpcReceiver<Vec<u8>>, IpcSender<Vec<u8>>) {
+    match opts::get().vdom_ipc {
+        Some(ref token) => {
+            println!("Token one servo: {}", token);
+            let chan = IpcSender::connect(token.clone()).unwrap();
+            let (oss,token2) = IpcOneShotServer::new().unwrap();
+            println!("Token two servo: {}", token);
+            chan.send(token2.into_bytes());
+            let (port,_) = oss.accept().unwrap();
+            (port, chan)
+        },
+        None => {
+            let (chan1,port1) = ipc::channel().unwrap();
+            let (chan2,port2) = ipc::channel().unwrap();
+            (port1, chan2)
+        }
+    }
+}
+
 impl ScriptThreadFactory for ScriptThread {
     fn create_layout_channel(_phantom: Option<&mut ScriptThread>) -> OpaqueScriptLayoutChannel {
         let (chan, port) = channel();
@@ -459,9 +540,12 @@ impl ScriptThreadFactory for ScriptThread {
             let parent_info = state.parent_info;
             let mem_profiler_chan = state.mem_profiler_chan.clone();
             let window_size = state.window_size;
+            let (vdom_port, vdom_chan) = connect_vdom();
             let script_thread = ScriptThread::new(state,
                                               script_port,
-                                              script_chan);
+                                              script_chan,
+                                              vdom_port,
+                                              vdom_chan);
 
             SCRIPT_THREAD_ROOT.with(|root| {
                 *root.borrow_mut() = Some(&script_thread as *const _);
@@ -494,6 +578,45 @@ impl ScriptThread {
         });
     }
 
+    /// Enters a region of vdom ops whose echoes aren't sent to the server.
+    pub fn begin_vdom_suppression() {
+        SCRIPT_THREAD_ROOT.with(|root| {
+            if let Some(script_thread) = *root.borrow() {
+                let script_thread = unsafe { &*script_thread };
+                let mut echoes = script_thread.vdom_echoes.get();
+                echoes.begin();
+                script_thread.vdom_echoes.set(echoes);
+            }
+        });
+    }
+
+    /// Leaves the innermost region of vdom ops whose echoes aren't sent. Fails if there is none.
+    pub fn end_vdom_suppression() -> io::Result<()> {
+        SCRIPT_THREAD_ROOT.with(|root| {
+            match *root.borrow() {
+                Some(script_thread) => {
+                    let script_thread = unsafe { &*script_thread };
+                    let mut echoes = script_thread.vdom_echoes.get();
+                    try!(echoes.end());
+                    script_thread.vdom_echoes.set(echoes);
+                    Ok(())
+                },
+                None => Ok(())
+            }
+        })
+    }
+
+    /// Whether the vdom ops being applied are in a suppressed region, so that the messages
+    /// echoing them to the server aren't sent. Code off the script thread never is.
+    pub fn suppressing_vdom_echoes() -> bool {
+        SCRIPT_THREAD_ROOT.with(|root| {
+            match *root.borrow() {
+                Some(script_thread) => unsafe { &*script_thread }.vdom_echoes.get().suppressing(),
+                None => false
+            }
+        })
+    }
+
     pub fn process_event(msg: CommonScriptMsg) {
         SCRIPT_THREAD_ROOT.with(|root| {
             if let Some(script_thread) = *root.borrow() {
@@ -503,10 +626,38 @@ impl ScriptThread {
         });
     }
 
-    /// Creates a new script thread.
+    /// Creates a script thread on the current thread, with the default content loaded, which
+    /// is driven by calling its handlers rather than `start`, i.e. by tests standing in for the
+    /// compositor and the vdom server. The thread must not be a script thread already.
+    pub fn new_headless(state: InitialScriptState,
+                        layout_chan: LayoutChan,
+                        vdom_port: IpcReceiver<Vec<u8>>,
+                        vdom_chan: IpcSender<Vec<u8>>,
+                        url: Url)
+                        -> Box<ScriptThread> {
+        thread_state::initialize(thread_state::SCRIPT);
+        PipelineNamespace::install(state.pipeline_namespace_id);
+        let (script_chan, script_port) = channel();
+        let id = state.id;
+        let parent_info = state.parent_info;
+        let window_size = state.window_size;
+        let script_thread = box ScriptThread::new(state, script_port, script_chan, vdom_port, vdom_chan);
+
+        SCRIPT_THREAD_ROOT.with(|root| {
+            *root.borrow_mut() = Some(&*script_thread as *const _);
+        });
+
+        let new_load = InProgressLoad::new(id, parent_info, layout_chan, window_size, url);
+        script_thread.initialize_default_content(new_load);
+        script_thread
+    }
+
+    /// Creates a new script thread, talking to the vdom server on `vdom_port` and `vdom_chan`.
     pub fn new(state: InitialScriptState,
                port: Receiver<MainThreadScriptMsg>,
-               chan: Sender<MainThreadScriptMsg>)
+               chan: Sender<MainThreadScriptMsg>,
+               vdom_port: IpcReceiver<Vec<u8>>,
+               vdom_chan: IpcSender<Vec<u8>>)
                -> ScriptThread {
 
         // Ask the router to proxy IPC messages from the devtools to us.
@@ -521,23 +672,23 @@ impl ScriptThread {
         // Ask the router to proxy IPC messages from the control port to us.
         let control_port = ROUTER.route_ipc_receiver_to_new_mpsc_receiver(state.control_port);
 
-        let (vdom_port, vdom_chan) = match opts::get().vdom_ipc {
-            Some(ref token) => {
-                println!("Token one servo: {}", token);
-                let chan = IpcSender::connect(token.clone()).unwrap();
-                let (oss,token2) = IpcOneShotServer::new().unwrap();
-                println!("Token two servo: {}", token);
-                chan.send(token2.into_bytes());
-                let (port,_) = oss.accept().unwrap();
-                (port, chan)
-            },
-            None => {
-                let (chan1,port1) = ipc::channel().unwrap();
-                let (chan2,port2) = ipc::channel().unwrap();
-                (port1, chan2)
+        // Ask the router to proxy the timer events fired by the scheduler to us.
+        let (timer_event_chan, ipc_timer_event_port) = ipc::channel().unwrap();
+        let timer_event_port = ROUTER.route_ipc_receiver_to_new_mpsc_receiver(ipc_timer_event_port);
+
+        // Verdicts on blocking events are delivered from the router thread, as the script
+        // thread is waiting for them rather than receiving from the vdom port.
+        let verdicts = PendingVerdicts::new();
+        let (vdom_sender, vdom_port_mpsc) = channel();
+        let router_verdicts = verdicts.clone();
+        ROUTER.add_route(vdom_port.to_opaque(), box move |message| {
+            if let Some(msg) = router_verdicts.route(message.to().unwrap()) {
+                let _ = vdom_sender.send(msg);
             }
-        };
-        let vdom_port = ROUTER.route_ipc_receiver_to_new_mpsc_receiver(vdom_port);
+        });
+        let vdom_port = vdom_port_mpsc;
+        let vdom_chan = ServerChan::new(vdom_chan, verdicts);
+        vdom_chan.send(ServerMsg::ClientCapabilities(ClientCapabilities::new()));
 
         ScriptThread {
             page: DOMRefCell::new(None),
@@ -574,11 +725,15 @@ impl ScriptThread {
             closed_pipelines: DOMRefCell::new(HashSet::new()),
 
             scheduler_chan: state.scheduler_chan,
+            timer_event_chan: timer_event_chan,
+            timer_event_port: timer_event_port,
 
             content_process_shutdown_chan: state.content_process_shutdown_chan,
 
             vdom_chan: vdom_chan,
-            vdom_port: vdom_port
+            vdom_port: vdom_port,
+            vdom_mount: state.vdom_mount,
+            vdom_echoes: Cell::new(EchoSuppression::new())
         }
     }
 
@@ -607,7 +762,8 @@ impl ScriptThread {
 
     /// Handle incoming control messages.
     fn handle_msgs(&self) -> bool {
-        use self::MixedMessage::{FromScript, FromConstellation, FromDevtools, FromImageCache, FromVdom};
+        use self::MixedMessage::{FromScript, FromConstellation, FromDevtools, FromImageCache, FromScheduler};
+        use self::MixedMessage::FromVdom;
 
         // Handle pending resize events.
         // Gather them first to avoid a double mut borrow on self.
@@ -642,6 +798,7 @@ impl ScriptThread {
             let mut control_port = sel.handle(&self.control_port);
             let mut devtools_port = sel.handle(&self.devtools_port);
             let mut image_cache_port = sel.handle(&self.image_cache_port);
+            let mut timer_event_port = sel.handle(&self.timer_event_port);
             let mut vdom_port = sel.handle(&self.vdom_port);
 
             unsafe {
@@ -651,6 +808,7 @@ impl ScriptThread {
                     devtools_port.add();
                 }
                 image_cache_port.add();
+                timer_event_port.add();
                 vdom_port.add();
             }
             let ret = sel.wait();
@@ -662,6 +820,8 @@ impl ScriptThread {
                 FromDevtools(self.devtools_port.recv().unwrap())
             } else if ret == image_cache_port.id() {
                 FromImageCache(self.image_cache_port.recv().unwrap())
+            } else if ret == timer_event_port.id() {
+                FromScheduler(self.timer_event_port.recv().unwrap())
             } else if ret == vdom_port.id() {
                 FromVdom(self.vdom_port.recv().unwrap())
             } else {
@@ -724,9 +884,12 @@ impl ScriptThread {
                 Err(_) => match self.port.try_recv() {
                     Err(_) => match self.devtools_port.try_recv() {
                         Err(_) => match self.image_cache_port.try_recv() {
-                            Err(_) => match self.vdom_port.try_recv() {
-                                Err(_) => break,
-                                Ok(ev) => event = FromVdom(ev)
+                            Err(_) => match self.timer_event_port.try_recv() {
+                                Err(_) => match self.vdom_port.try_recv() {
+                                    Err(_) => break,
+                                    Ok(ev) => event = FromVdom(ev)
+                                },
+                                Ok(ev) => event = FromScheduler(ev),
                             },
                             Ok(ev) => event = FromImageCache(ev),
                         },
@@ -753,6 +916,7 @@ impl ScriptThread {
                     FromScript(inner_msg) => self.handle_msg_from_script(inner_msg),
                     FromDevtools(inner_msg) => self.handle_msg_from_devtools(inner_msg),
                     FromImageCache(inner_msg) => self.handle_msg_from_image_cache(inner_msg),
+                    FromScheduler(inner_msg) => self.handle_timer_event(inner_msg),
                     FromVdom(inner_msg) => self.handle_msg_from_vdom(inner_msg)
                 }
 
@@ -764,12 +928,23 @@ impl ScriptThread {
             }
         }
 
+        self.finish_batch();
+
+        true
+    }
+
+    /// Reports the changes made by a batch of messages handled together, and issues the
+    /// reflows it requires.
+    pub fn finish_batch(&self) {
         // Issue batched reflows on any pages that require it (e.g. if images loaded)
         // TODO(gw): In the future we could probably batch other types of reflows
         // into this loop too, but for now it's only images.
         let page = self.page.borrow();
         if let Some(page) = page.as_ref() {
             for page in page.iter() {
+                // Report the selection changes of the events handled together at once.
+                page.document().flush_vdom_selection();
+                page.document().flush_vdom_mouse_moves(false);
                 let window = page.window();
                 let pending_reflows = window.get_pending_reflow_count();
                 if pending_reflows > 0 {
@@ -787,8 +962,21 @@ impl ScriptThread {
                 }
             }
         }
+    }
 
-        true
+    /// Handles the tasks already queued on the script thread's own port, such as the events a
+    /// default action fired, without waiting for more.
+    pub fn handle_queued_tasks(&self) {
+        while let Ok(msg) = self.port.try_recv() {
+            self.handle_msg_from_script(msg);
+        }
+    }
+
+    /// Waits for the next task queued for the script thread, i.e. by a route from another
+    /// thread, and handles it.
+    pub fn handle_next_task(&self) {
+        let msg = self.port.recv().unwrap();
+        self.handle_msg_from_script(msg);
     }
 
     fn categorize_msg(&self, msg: &MixedMessage) -> ScriptThreadEventCategory {
@@ -802,6 +990,7 @@ impl ScriptThread {
             },
             MixedMessage::FromDevtools(_) => ScriptThreadEventCategory::DevtoolsMsg,
             MixedMessage::FromImageCache(_) => ScriptThreadEventCategory::ImageCacheMsg,
+            MixedMessage::FromScheduler(_) => ScriptThreadEventCategory::TimerEvent,
             MixedMessage::FromScript(ref inner_msg) => {
                 match *inner_msg {
                     MainThreadScriptMsg::Common(CommonScriptMsg::RunnableMsg(ref category, _)) =>
@@ -881,6 +1070,8 @@ impl ScriptThread {
             ConstellationControlMsg::FramedContentChanged(_,_) => {},
             ConstellationControlMsg::ReportCSSError(pipeline_id, filename, line, column, msg) =>
                 self.handle_css_error_reporting(pipeline_id, filename, line, column, msg),
+            ConstellationControlMsg::ColorSchemeChanged(pipeline_id, color_scheme) =>
+                self.handle_color_scheme_changed(pipeline_id, color_scheme),
         }
     }
 
@@ -900,7 +1091,9 @@ impl ScriptThread {
                 }
             }
             MainThreadScriptMsg::Common(CommonScriptMsg::RefcountCleanup(addr)) => {},
-            MainThreadScriptMsg::Common(CommonScriptMsg::CollectReports(_)) => {},
+            MainThreadScriptMsg::Common(CommonScriptMsg::CollectReports(reports_chan)) =>
+                self.collect_reports(reports_chan),
+            MainThreadScriptMsg::Common(CommonScriptMsg::VdomVerdict(..)) => {},
             MainThreadScriptMsg::DOMManipulation(msg) =>
                 msg.handle_msg(self),
         }
@@ -936,26 +1129,78 @@ impl ScriptThread {
         }
     }
 
+    fn collect_reports(&self, reports_chan: ReportsChan) {
+        reports_chan.send(self.dom_tree_reports());
+    }
+
+    /// The `dom-tree` memory report of each page, in frame tree order.
+    pub fn dom_tree_reports(&self) -> Vec<Report> {
+        let mut reports = vec![];
+
+        if let Some(root_page) = self.page.borrow().as_ref() {
+            for it_page in root_page.iter() {
+                let document = it_page.document();
+                reports.push(dom_tree_report(&document.url().to_string(), document.dom_tree_size()));
+            }
+        }
+
+        reports
+    }
+
+    /// Fires a timer of the document of the window the scheduler fired it at.
+    pub fn handle_timer_event(&self, timer_event: TimerEvent) {
+        let TimerEvent(source, id) = timer_event;
+        let pipeline_id = match source {
+            TimerSource::FromWindow(pipeline_id) => pipeline_id,
+            TimerSource::FromWorker => panic!("Worker timeouts must not be sent to script thread"),
+        };
+        if let Some(page) = self.find_subpage(pipeline_id) {
+            page.document().handle_vdom_timer(id);
+        }
+    }
+
     fn handle_msg_from_image_cache(&self, msg: ImageCacheResult) {
         msg.responder.unwrap().respond(msg.image_response);
     }
 
-    fn try_apply_patches(&self, cursor: &mut Cursor<Vec<u8>>) -> io::Result<()> {
-        // assume MessageType == Patch for now
-        let _ = try!(cursor.read_msg_type());
-
+    /// Applies the body of a message from the vdom server with `apply`, reporting its failure
+    /// to the server, then brings the document up to date with it.
+    pub fn apply_vdom_message<F>(&self, apply: F)
+        where F: FnOnce(&Document, &mut UndoLog) -> io::Result<()> {
         let page = self.page.borrow();
         if let Some(page) = page.as_ref() {
             let doc = page.document();
-            apply_patches(cursor, &*doc);
+            if let Err(err) = apply(&*doc, &mut *doc.vdom_history()) {
+                warn!("failed to apply vdom patches: {}", err);
+                doc.window().vdom_chan().send(ServerMsg::PatchFailed(PatchError::of(&err), err.to_string()));
+            }
+            // A suppressed region doesn't outlast the message that started it.
+            self.vdom_echoes.set(EchoSuppression::new());
+            doc.compact_registry();
+            doc.prune_vdom_tombstones();
+            doc.fix_up_focus();
+            // Only the patched nodes are dirty, so this doesn't need to rebuild the layout tree.
+            doc.window().reflow(ReflowGoal::ForDisplay, ReflowQueryType::NoQuery, ReflowReason::VdomPatch);
+            doc.notify_vdom_visibility();
+            doc.notify_vdom_resizes();
         }
-
-        Ok(())
     }
 
-    fn handle_msg_from_vdom(&self, msg: Vec<u8>) {
+    /// Applies a message from the vdom server. A message that can't be applied, down to one
+    /// without a header, is reported to the server as a failed patch.
+    pub fn handle_msg_from_vdom(&self, msg: Vec<u8>) {
+        // The server learns the limit from the capabilities reported on connect.
+        if msg.len() > MAX_MESSAGE_SIZE {
+            let err = PatchError::LimitExceeded.error("message larger than the client applies");
+            self.apply_vdom_message(|_, _| Err(err));
+            return;
+        }
         let mut cursor = Cursor::new(msg);
-        self.try_apply_patches(&mut cursor).unwrap();
+        self.apply_vdom_message(|doc, log| {
+            // assume MessageType == Patch for now
+            let _ = try!(cursor.read_msg_type());
+            apply_patches(&mut cursor, doc, log)
+        });
     }
 
     fn handle_resize(&self, id: PipelineId, size: WindowSizeData) {
@@ -981,6 +1226,7 @@ impl ScriptThread {
                     let page = get_page(page, id);
                     self.rebuild_and_force_reflow(&*page, ReflowReason::Viewport);
                 }
+                window.Document().notify_vdom_visibility();
                 return;
             }
         }
@@ -1132,12 +1378,39 @@ impl ScriptThread {
     }
 
     /// Handles a Web font being loaded. Does nothing if the page no longer exists.
-    fn handle_web_font_loaded(&self, pipeline_id: PipelineId) {
+    pub fn handle_web_font_loaded(&self, pipeline_id: PipelineId) {
         if let Some(ref page) = self.find_subpage(pipeline_id)  {
             self.rebuild_and_force_reflow(page, ReflowReason::WebFontLoaded);
         }
     }
 
+    /// Tells the vdom server about the platform's new color scheme, and restyles if documents
+    /// that don't override it now match `prefers-color-scheme` differently.
+    fn handle_color_scheme_changed(&self, pipeline_id: PipelineId, color_scheme: ColorScheme) {
+        let page = match self.find_subpage(pipeline_id) {
+            Some(page) => page,
+            None => return
+        };
+        let window = page.window();
+        let old = window.color_scheme();
+        window.set_platform_color_scheme(color_scheme);
+        window.vdom_chan().send(ServerMsg::ColorSchemeChanged(color_scheme));
+
+        if window.color_scheme() != old {
+            page.document().invalidate_stylesheets();
+            self.notify_media_changes(&window);
+            self.rebuild_and_force_reflow(&page, ReflowReason::ColorSchemeChanged);
+        }
+    }
+
+    /// Tells the vdom server about the media queries it watches whose result changed.
+    fn notify_media_changes(&self, window: &Window) {
+        let device = window.media_device();
+        for (query, matches) in window.media_watch().reevaluate(&device) {
+            window.vdom_chan().send(ServerMsg::MediaQueryChanged(query, matches));
+        }
+    }
+
     /// Initializes the default window/document into a state that is ready to accept
     /// VDOM patches.
     fn initialize_default_content(&self, incomplete: InProgressLoad) {
@@ -1171,6 +1444,8 @@ impl ScriptThread {
                                  self.constellation_chan.clone(),
                                  self.control_chan.clone(),
                                  self.scheduler_chan.clone(),
+                                 self.timer_event_chan.clone(),
+                                 self.vdom_chan.clone(),
                                  incomplete.layout_chan,
                                  incomplete.pipeline_id,
                                  None,
@@ -1204,15 +1479,31 @@ impl ScriptThread {
             ElementCreator::ParserCreated);
         assert!(document.upcast::<Node>().InsertBefore(htmlel.upcast::<Node>(), None).is_ok());
 
+        // The server's root id refers to the element the vdom is mounted under: the body, or an
+        // element with the mount's HTML id inside it, which the server doesn't know about.
         let bodyel = create_element_simple(
-            2,
+            if self.vdom_mount.is_some() { 0 } else { 2 },
             atom!("body"),
             &document,
             ElementCreator::ParserCreated);
         assert!(htmlel.upcast::<Node>().InsertBefore(bodyel.upcast::<Node>(), None).is_ok());
 
+        let mountel = match self.vdom_mount {
+            Some(ref mount) => {
+                let mountel = create_element_simple(
+                    2,
+                    atom!("div"),
+                    &document,
+                    ElementCreator::ParserCreated);
+                mountel.set_atomic_attribute(&atom!("id"), DOMString::from(mount.clone()));
+                assert!(bodyel.upcast::<Node>().InsertBefore(mountel.upcast::<Node>(), None).is_ok());
+                mountel
+            },
+            None => bodyel
+        };
+
         let text = Text::new(3, DOMString::from("Hello World!"), &document);
-        assert!(bodyel.upcast::<Node>().InsertBefore(text.upcast(), None).is_ok());
+        assert!(mountel.upcast::<Node>().InsertBefore(text.upcast(), None).is_ok());
 
         document.set_ready_state(DocumentReadyState::Complete);
 
@@ -1260,7 +1551,10 @@ impl ScriptThread {
         // account the real device pixel ratio.
         let point = Point2D::new(rect.origin.x.to_nearest_px() as f32,
                                  rect.origin.y.to_nearest_px() as f32);
+        self.scroll_to_point(pipeline_id, point);
+    }
 
+    fn scroll_to_point(&self, pipeline_id: PipelineId, point: Point2D<f32>) {
         self.compositor.borrow_mut().send(ScriptToCompositorMsg::ScrollFragmentPoint(
                                                  pipeline_id, LayerId::null(), point, false)).unwrap();
     }
@@ -1276,7 +1570,7 @@ impl ScriptThread {
     /// This is the main entry point for receiving and dispatching DOM events.
     ///
     /// TODO: Actually perform DOM event dispatch.
-    fn handle_event(&self, pipeline_id: PipelineId, event: CompositorEvent) {
+    pub fn handle_event(&self, pipeline_id: PipelineId, event: CompositorEvent) {
 
         // DOM events can only be handled if there's a root page.
         if !self.root_page_exists() {
@@ -1366,12 +1660,30 @@ impl ScriptThread {
                 }
             }
 
+            WheelEvent(delta, point) => {
+                let page = get_page(&self.root_page(), pipeline_id);
+                let document = page.document();
+                let result = if document.handle_wheel_event(delta, point) {
+                    EventResult::DefaultAllowed
+                } else {
+                    EventResult::DefaultPrevented
+                };
+                self.compositor.borrow_mut()
+                    .send(ScriptToCompositorMsg::WheelEventProcessed(pipeline_id, result)).unwrap();
+            }
+
             KeyEvent(key, state, modifiers) => {
                 let page = get_page(&self.root_page(), pipeline_id);
                 let document = page.document();
                 document.dispatch_key_event(
                     key, state, modifiers, &mut self.compositor.borrow_mut());
             }
+
+            CompositionEvent(event_type, data) => {
+                let page = get_page(&self.root_page(), pipeline_id);
+                let document = page.document();
+                document.dispatch_composition_event(event_type, data);
+            }
         }
     }
 
@@ -1408,13 +1720,19 @@ impl ScriptThread {
                 let document = page.document();
                 let document = document.r();
                 let url = document.url();
-                if url.scheme == nurl.scheme && url.scheme_data == nurl.scheme_data &&
-                    url.query == nurl.query && load_data.method == Method::Get {
+                if is_fragment_navigation(&url, nurl) && load_data.method == Method::Get {
                     match document.find_fragment_node(&*fragment) {
                         Some(ref node) => {
                             self.scroll_fragment_point(pipeline_id, node.r());
                         }
-                        None => {}
+                        // There is nothing to scroll to, so the page goes back to its top.
+                        None => self.scroll_to_point(pipeline_id, Point2D::new(0.0, 0.0))
+                    }
+                    if url.fragment != nurl.fragment {
+                        document.set_url(nurl.clone());
+                        let window = page.window();
+                        window.upcast::<EventTarget>().fire_simple_event("hashchange");
+                        window.vdom_chan().send(ServerMsg::HashChanged(url.serialize(), nurl.serialize()));
                     }
                     return;
                 }
@@ -1438,7 +1756,11 @@ impl ScriptThread {
                             ReflowQueryType::NoQuery,
                             ReflowReason::WindowResize);
 
+        self.notify_media_changes(&window);
+
         let document = page.document();
+        document.notify_vdom_visibility();
+        document.notify_vdom_resizes();
         let fragment_node = window.steal_fragment_name()
                                   .and_then(|name| document.find_fragment_node(&*name));
         match fragment_node {
@@ -5,6 +5,10 @@
 //! Common handling of keyboard input and state management for text input controls
 
 use clipboard_provider::ClipboardProvider;
+use dom::bindings::inheritance::Castable;
+use dom::clipboardevent::ClipboardEvent;
+use dom::compositionevent::CompositionEvent;
+use dom::event::Event;
 use dom::keyboardevent::{KeyboardEvent, key_value};
 use msg::constellation_msg::{ALT, CONTROL, SHIFT, SUPER};
 use msg::constellation_msg::{Key, KeyModifiers};
@@ -41,7 +45,11 @@ pub struct TextInput<T: ClipboardProvider> {
     multiline: bool,
     #[ignore_heap_size_of = "Can't easily measure this generic type"]
     clipboard_provider: T,
-    pub max_length: Option<usize>
+    pub max_length: Option<usize>,
+    /// Absolute start and length in UTF-8 bytes of the text of an in-progress IME composition.
+    composition: Option<(usize, usize)>,
+    /// The content before the in-progress IME composition started.
+    precomposition_content: Option<DOMString>,
 }
 
 /// Resulting action to be taken by the owner of a text input that is handling an event.
@@ -69,7 +77,7 @@ pub enum Lines {
 }
 
 /// The direction in which to delete a character.
-#[derive(PartialEq, Eq, Copy, Clone)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum Direction {
     Forward,
     Backward
@@ -79,16 +87,41 @@ pub enum Direction {
 /// Was the keyboard event accompanied by the standard control modifier,
 /// i.e. cmd on Mac OS or ctrl on other platforms.
 #[cfg(target_os = "macos")]
-fn is_control_key(mods: KeyModifiers) -> bool {
+pub fn is_control_key(mods: KeyModifiers) -> bool {
     mods.contains(SUPER) && !mods.contains(CONTROL | ALT)
 }
 
 #[cfg(not(target_os = "macos"))]
-fn is_control_key(mods: KeyModifiers) -> bool {
+pub fn is_control_key(mods: KeyModifiers) -> bool {
     mods.contains(CONTROL) && !mods.contains(SUPER | ALT)
 }
 
-fn is_printable_key(key: Key) -> bool {
+/// Replaces the \r\n and lone \r line breaks of a value with \n.
+/// https://infra.spec.whatwg.org/#normalize-newlines
+pub fn normalize_newlines(value: &str) -> String {
+    value.replace("\r\n", "\n").replace("\r", "\n")
+}
+
+/// Removes the line breaks of a value, as single line controls do.
+/// https://infra.spec.whatwg.org/#strip-newlines
+pub fn strip_newlines(value: &str) -> String {
+    value.chars().filter(|&c| c != '\n' && c != '\r').collect()
+}
+
+/// The clipboard event type fired for a keyboard shortcut, if the shortcut is a clipboard one.
+pub fn clipboard_event_type(key: Key, mods: KeyModifiers) -> Option<&'static str> {
+    if !is_control_key(mods) {
+        return None;
+    }
+    match key {
+        Key::C => Some("copy"),
+        Key::X => Some("cut"),
+        Key::V => Some("paste"),
+        _ => None,
+    }
+}
+
+pub fn is_printable_key(key: Key) -> bool {
     match key {
         Key::Space | Key::Apostrophe | Key::Comma | Key::Minus |
         Key::Period | Key::Slash | Key::GraveAccent | Key::Num0 |
@@ -115,7 +148,9 @@ impl<T: ClipboardProvider> TextInput<T> {
             selection_begin: None,
             multiline: lines == Lines::Multiple,
             clipboard_provider: clipboard_provider,
-            max_length: max_length
+            max_length: max_length,
+            composition: None,
+            precomposition_content: None,
         };
         i.set_content(initial);
         i
@@ -189,6 +224,7 @@ impl<T: ClipboardProvider> TextInput<T> {
     }
 
     pub fn replace_selection(&mut self, insert: DOMString) {
+        let insert = self.sanitize(&insert);
         if let Some((begin, end)) = self.get_sorted_selection() {
             let allowed_to_insert_count = if let Some(max_length) = self.max_length {
                 let len_after_selection_replaced = self.len() - self.selection_len();
@@ -389,10 +425,127 @@ impl<T: ClipboardProvider> TextInput<T> {
         self.selection_begin = None;
     }
 
+    /// Is an IME composition in progress?
+    pub fn is_composing(&self) -> bool {
+        self.composition.is_some()
+    }
+
+    /// Begin an IME composition at the editing point, replacing the current selection.
+    pub fn start_composition(&mut self) {
+        self.precomposition_content = Some(self.get_content());
+        if self.selection_begin.is_some() {
+            self.replace_selection(DOMString::new());
+        }
+        self.composition = Some((self.get_absolute_insertion_point(), 0));
+    }
+
+    /// Replace the text of the in-progress composition.
+    pub fn update_composition(&mut self, data: &str) {
+        if let Some((start, len)) = self.composition {
+            self.selection_begin = Some(self.get_text_point_for_absolute_point(start));
+            self.edit_point = self.get_text_point_for_absolute_point(start + len);
+            self.replace_selection(DOMString::from(data));
+            let inserted = self.get_absolute_insertion_point() - start;
+            self.composition = Some((start, inserted));
+        }
+    }
+
+    /// Commit the composition with its final text. Returns whether the content differs from
+    /// what it was before the composition started.
+    pub fn end_composition(&mut self, data: &str) -> bool {
+        self.update_composition(data);
+        self.composition = None;
+        match self.precomposition_content.take() {
+            Some(content) => content != self.get_content(),
+            None => false,
+        }
+    }
+
+    /// Abandon the composition, removing any text it inserted. Returns whether the content
+    /// differs from what it was before the composition started, i.e. if it replaced a selection.
+    pub fn cancel_composition(&mut self) -> bool {
+        self.end_composition("")
+    }
+
+    /// Process a given `CompositionEvent` and return an action for the caller to execute.
+    pub fn handle_composition(&mut self, event: &CompositionEvent) -> KeyReaction {
+        match &*event.upcast::<Event>().type_() {
+            "compositionstart" => {
+                self.start_composition();
+                KeyReaction::RedrawSelection
+            }
+            "compositionupdate" => {
+                self.update_composition(&event.Data());
+                KeyReaction::RedrawSelection
+            }
+            "compositionend" => {
+                // A canceled composition ends with no data, and only changed the content if it
+                // replaced a selection.
+                if self.end_composition(&event.Data()) {
+                    KeyReaction::DispatchInput
+                } else {
+                    KeyReaction::RedrawSelection
+                }
+            }
+            _ => KeyReaction::Nothing,
+        }
+    }
+
+    /// Copy the selected text to the clipboard.
+    pub fn copy_selection(&mut self) {
+        match self.get_selection_text() {
+            Some(ref text) if text.is_empty() => (),
+            Some(text) => self.clipboard_provider.set_clipboard_contents(text),
+            None => (),
+        }
+    }
+
+    /// Move the selected text to the clipboard.
+    pub fn cut_selection(&mut self) -> KeyReaction {
+        match self.get_selection_text() {
+            Some(ref text) if text.is_empty() => KeyReaction::Nothing,
+            Some(text) => {
+                self.clipboard_provider.set_clipboard_contents(text);
+                self.replace_selection(DOMString::new());
+                KeyReaction::DispatchInput
+            }
+            None => KeyReaction::Nothing,
+        }
+    }
+
+    /// Process a given `ClipboardEvent` whose default action was not prevented and return an
+    /// action for the caller to execute. Pasted text is taken from the event's data rather than
+    /// the clipboard, so that the document can supply it.
+    pub fn handle_clipboard(&mut self, event: &ClipboardEvent) -> KeyReaction {
+        match &*event.upcast::<Event>().type_() {
+            "copy" => {
+                self.copy_selection();
+                KeyReaction::Nothing
+            }
+            "cut" => self.cut_selection(),
+            "paste" => {
+                match event.text() {
+                    Some(text) => {
+                        self.insert_string(text);
+                        KeyReaction::DispatchInput
+                    }
+                    None => KeyReaction::Nothing,
+                }
+            }
+            _ => KeyReaction::Nothing,
+        }
+    }
+
     /// Process a given `KeyboardEvent` and return an action for the caller to execute.
+    /// Clipboard shortcuts are left to the `copy`, `cut` and `paste` events fired by the
+    /// document after the key event.
     pub fn handle_keydown(&mut self, event: &KeyboardEvent) -> KeyReaction {
         if let Some(key) = event.get_key() {
-            self.handle_keydown_aux(key, event.get_key_modifiers())
+            let mods = event.get_key_modifiers();
+            if clipboard_event_type(key, mods).is_some() {
+                return KeyReaction::Nothing;
+            }
+            self.handle_keydown_aux(key, mods)
         } else {
             KeyReaction::Nothing
         }
@@ -405,11 +558,10 @@ impl<T: ClipboardProvider> TextInput<T> {
                 KeyReaction::RedrawSelection
             },
             Key::C if is_control_key(mods) => {
-                if let Some(text) = self.get_selection_text() {
-                    self.clipboard_provider.set_clipboard_contents(text);
-                }
+                self.copy_selection();
                 KeyReaction::DispatchInput
             },
+            Key::X if is_control_key(mods) => self.cut_selection(),
             Key::V if is_control_key(mods) => {
                 let contents = self.clipboard_provider.clipboard_contents();
                 self.insert_string(contents);
@@ -490,6 +642,7 @@ impl<T: ClipboardProvider> TextInput<T> {
     /// Set the current contents of the text input. If this is control supports multiple lines,
     /// any \n encountered will be stripped and force a new logical line.
     pub fn set_content(&mut self, content: DOMString) {
+        let content = self.sanitize(&content);
         self.lines = if self.multiline {
             content.split('\n').map(DOMString::from).collect()
         } else {
@@ -498,6 +651,19 @@ impl<T: ClipboardProvider> TextInput<T> {
         self.edit_point.line = min(self.edit_point.line, self.lines.len() - 1);
         self.edit_point.index = min(self.edit_point.index, self.current_line_length());
         self.selection_begin = None;
+        self.composition = None;
+        self.precomposition_content = None;
+    }
+
+    /// Sanitizes text entering the control, whose line breaks are normalized to \n if it
+    /// supports multiple lines and stripped otherwise.
+    /// https://html.spec.whatwg.org/multipage/#value-sanitization-algorithm
+    fn sanitize(&self, value: &str) -> DOMString {
+        DOMString::from(if self.multiline {
+            normalize_newlines(value)
+        } else {
+            strip_newlines(value)
+        })
     }
 
     pub fn get_absolute_insertion_point(&self) -> usize {
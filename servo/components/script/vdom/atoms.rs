@@ -0,0 +1,62 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A cache of the atoms made from names in patches, so a name the server keeps sending is only
+//! interned once.
+
+use string_cache::Atom;
+
+/// The most names a cache holds; adding another one evicts the least recently used.
+pub const MAX_CACHED_ATOMS: usize = 64;
+
+/// The recently used names and their atoms, least recently used first.
+pub struct AtomCache {
+	entries: Vec<(String, Atom)>,
+	hits: usize,
+	misses: usize,
+}
+
+impl AtomCache {
+	pub fn new() -> AtomCache {
+		AtomCache {
+			entries: vec![],
+			hits: 0,
+			misses: 0,
+		}
+	}
+
+	/// The atom for `name`, interning it if it isn't cached.
+	pub fn intern(&mut self, name: &str) -> Atom {
+		let entry = match self.entries.iter().position(|&(ref cached, _)| cached == name) {
+			Some(index) => {
+				self.hits += 1;
+				self.entries.remove(index)
+			},
+			None => {
+				self.misses += 1;
+				if self.entries.len() == MAX_CACHED_ATOMS {
+					self.entries.remove(0);
+				}
+				(name.to_owned(), Atom::from(name))
+			}
+		};
+		let atom = entry.1.clone();
+		self.entries.push(entry);
+		atom
+	}
+
+	/// How many lookups found their name cached.
+	pub fn hits(&self) -> usize {
+		self.hits
+	}
+
+	/// How many lookups had to intern their name.
+	pub fn misses(&self) -> usize {
+		self.misses
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+}
@@ -0,0 +1,53 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Patches the server pipelined ahead of the one creating their target, i.e. a child inserted
+//! into a parent whose own insertion comes later in the message.
+
+use std::io::Result;
+use vdom::error::PatchError;
+
+/// The patches of a message still waiting for their target, in the order they were read.
+pub struct DeferredPatches<P> {
+	patches: Vec<(u64, P)>,
+}
+
+impl<P> DeferredPatches<P> {
+	pub fn new() -> DeferredPatches<P> {
+		DeferredPatches {
+			patches: vec![],
+		}
+	}
+
+	/// Applies a patch to the node with `id` if it `exists`, or defers it until it does. Every
+	/// applied patch may have created the targets of deferred ones, which are then retried in
+	/// the order they were deferred.
+	pub fn apply<E, A>(&mut self, id: u64, patch: P, exists: E, mut apply: A) -> Result<()>
+		where E: Fn(u64) -> bool, A: FnMut(u64, P) -> Result<()> {
+		if !exists(id) {
+			self.patches.push((id, patch));
+			return Ok(());
+		}
+		try!(apply(id, patch));
+		while let Some(index) = self.patches.iter().position(|&(id, _)| exists(id)) {
+			let (id, patch) = self.patches.remove(index);
+			try!(apply(id, patch));
+		}
+		Ok(())
+	}
+
+	/// The targets of the patches still waiting, in the order they were deferred.
+	pub fn unresolved(&self) -> Vec<u64> {
+		self.patches.iter().map(|&(id, _)| id).collect()
+	}
+
+	/// Fails if patches are still waiting for their target at the end of the message.
+	pub fn finish(self) -> Result<()> {
+		if self.patches.is_empty() {
+			Ok(())
+		} else {
+			Err(PatchError::UnknownId.error("patch targets a missing node"))
+		}
+	}
+}
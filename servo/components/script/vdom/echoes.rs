@@ -0,0 +1,57 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Suppression of the messages that echo a mutation the server made itself back to it, like the
+//! `focus` event and `ActiveElementChanged` of a `Focus` op. The server brackets the ops whose
+//! echoes it doesn't want with `BeginSuppress` and `EndSuppress`, which may nest.
+
+use std::io::Result;
+use vdom::error::PatchError;
+use vdom::msg::ServerMsg;
+
+/// Whether a message reports a change the page can also see, so that it echoes a server-driven
+/// mutation when one caused it. Replies and errors are answers the server waits for instead.
+pub fn is_echo(msg: &ServerMsg) -> bool {
+	match *msg {
+		ServerMsg::Event(_) |
+		ServerMsg::BlockingEvent(_, _) |
+		ServerMsg::ContentEdited(_, _) |
+		ServerMsg::ActiveElementChanged(_) |
+		ServerMsg::HoverChanged(_, _) |
+		ServerMsg::SelectionChanged(_, _, _, _) => true,
+		_ => false
+	}
+}
+
+/// How many suppressed regions the ops being applied are in.
+#[derive(Clone, Copy)]
+pub struct EchoSuppression {
+	depth: u32,
+}
+
+impl EchoSuppression {
+	pub fn new() -> EchoSuppression {
+		EchoSuppression {
+			depth: 0,
+		}
+	}
+
+	pub fn begin(&mut self) {
+		self.depth += 1;
+	}
+
+	/// Leaves the innermost suppressed region. Fails if there is none.
+	pub fn end(&mut self) -> Result<()> {
+		if self.depth == 0 {
+			return Err(PatchError::Malformed.error("EndSuppress outside a suppressed region"));
+		}
+		self.depth -= 1;
+		Ok(())
+	}
+
+	/// Whether the ops being applied are in a suppressed region, so echoes aren't sent.
+	pub fn suppressing(&self) -> bool {
+		self.depth > 0
+	}
+}
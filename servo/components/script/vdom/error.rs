@@ -0,0 +1,93 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The reasons a patch can fail for, which the vdom server is told along with a description, so
+//! it can handle them without matching on the text of the description.
+
+use std::error;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+
+/// Why a patch failed. The codes of the variants are part of the protocol and must not change.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PatchError {
+	/// The patch targets a node that isn't in the document.
+	UnknownId,
+	/// The patch would make an invalid node tree, or mutates a frozen subtree.
+	HierarchyViolation,
+	/// The patch targets a node of the wrong type for it, or inserts one.
+	TypeMismatch,
+	/// The message can't be decoded, or holds a value the op doesn't accept.
+	Malformed,
+	/// The patch exceeds a limit of the client, i.e. nests nodes too deeply.
+	LimitExceeded,
+	/// The op isn't known to the client, or can't be used where it is.
+	UnsupportedOp,
+	/// The patch targets a node that was removed from the document recently, which is only
+	/// told apart from an unknown one while tombstones are kept.
+	RemovedId,
+}
+
+/// The error of a failed patch, with the reason it failed for.
+#[derive(Debug)]
+struct Failure {
+	kind: PatchError,
+	description: String,
+}
+
+impl fmt::Display for Failure {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.description)
+	}
+}
+
+impl error::Error for Failure {
+	fn description(&self) -> &str {
+		&self.description
+	}
+}
+
+impl PatchError {
+	/// An error for a patch that failed for this reason.
+	pub fn error(self, description: &str) -> Error {
+		Error::new(ErrorKind::InvalidData, Failure {
+			kind: self,
+			description: description.to_owned(),
+		})
+	}
+
+	/// The reason `err` was returned for. Errors that weren't made by `error`, i.e. those of a
+	/// payload that ends early, are `Malformed`.
+	pub fn of(err: &Error) -> PatchError {
+		match err.get_ref().and_then(|inner| inner.downcast_ref::<Failure>()) {
+			Some(failure) => failure.kind,
+			None => PatchError::Malformed
+		}
+	}
+
+	pub fn to_wire(self) -> u8 {
+		match self {
+			PatchError::UnknownId => 1,
+			PatchError::HierarchyViolation => 2,
+			PatchError::TypeMismatch => 3,
+			PatchError::Malformed => 4,
+			PatchError::LimitExceeded => 5,
+			PatchError::UnsupportedOp => 6,
+			PatchError::RemovedId => 7,
+		}
+	}
+
+	pub fn from_wire(code: u8) -> Result<PatchError> {
+		match code {
+			1 => Ok(PatchError::UnknownId),
+			2 => Ok(PatchError::HierarchyViolation),
+			3 => Ok(PatchError::TypeMismatch),
+			4 => Ok(PatchError::Malformed),
+			5 => Ok(PatchError::LimitExceeded),
+			6 => Ok(PatchError::UnsupportedOp),
+			7 => Ok(PatchError::RemovedId),
+			_ => Err(Error::new(ErrorKind::InvalidData, "unknown patch error"))
+		}
+	}
+}
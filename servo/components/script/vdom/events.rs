@@ -0,0 +1,334 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Forwarding of DOM events to the vdom server.
+
+use dom::bindings::inheritance::Castable;
+use dom::bindings::js::{Root, RootedReference};
+use dom::clipboardevent::ClipboardEvent;
+use dom::compositionevent::CompositionEvent;
+use dom::dragevent::DragEvent;
+use dom::element::Element;
+use dom::event::Event;
+use dom::eventtarget::EventTarget;
+use dom::focusevent::FocusEvent;
+use dom::keyboardevent::KeyboardEvent;
+use dom::mouseevent::MouseEvent;
+use dom::node::Node;
+use dom::wheelevent::WheelEvent;
+use std::io::Result;
+use vdom::error::PatchError;
+use vdom::msg::{EventDetail, EventMsg, ServerMsg};
+use vdom::verdict::Verdict;
+
+/// The attribute listing, separated by commas, the events a node wants forwarded, i.e.
+/// `data-vdom-on="click,input"`.
+pub const DECLARATIVE_ATTR: &'static str = "data-vdom-on";
+
+bitflags! {
+	#[doc = "The set of event types a node has asked to be forwarded to the server."]
+	flags EventSet: u32 {
+		const CLICK = 1 << 0,
+		const MOUSEDOWN = 1 << 1,
+		const MOUSEUP = 1 << 2,
+		const MOUSEMOVE = 1 << 3,
+		const MOUSEOVER = 1 << 4,
+		const MOUSEOUT = 1 << 5,
+		const KEYDOWN = 1 << 6,
+		const KEYUP = 1 << 7,
+		const KEYPRESS = 1 << 8,
+		const FOCUS = 1 << 9,
+		const BLUR = 1 << 10,
+		const INPUT = 1 << 11,
+		const CHANGE = 1 << 12,
+		const SUBMIT = 1 << 13,
+		const TOUCHSTART = 1 << 14,
+		const TOUCHMOVE = 1 << 15,
+		const TOUCHEND = 1 << 16,
+		const TOUCHCANCEL = 1 << 17,
+		const WHEEL = 1 << 18,
+		const COMPOSITIONSTART = 1 << 19,
+		const COMPOSITIONUPDATE = 1 << 20,
+		const COMPOSITIONEND = 1 << 21,
+		const DRAGSTART = 1 << 22,
+		const DRAGOVER = 1 << 23,
+		const DROP = 1 << 24,
+		const DRAGEND = 1 << 25,
+		const COPY = 1 << 26,
+		const CUT = 1 << 27,
+		const PASTE = 1 << 28,
+		const FOCUSOUT = 1 << 29,
+		#[doc = "Not an event: asks for `HoverChanged` messages when the node starts or stops matching `:hover`."]
+		const HOVER = 1 << 30
+	}
+}
+
+/// The names of the event types that can be forwarded, in the order of their flags.
+static EVENT_NAMES: [(&'static str, EventSet); 31] = [
+	("click", CLICK),
+	("mousedown", MOUSEDOWN),
+	("mouseup", MOUSEUP),
+	("mousemove", MOUSEMOVE),
+	("mouseover", MOUSEOVER),
+	("mouseout", MOUSEOUT),
+	("keydown", KEYDOWN),
+	("keyup", KEYUP),
+	("keypress", KEYPRESS),
+	("focus", FOCUS),
+	("blur", BLUR),
+	("input", INPUT),
+	("change", CHANGE),
+	("submit", SUBMIT),
+	("touchstart", TOUCHSTART),
+	("touchmove", TOUCHMOVE),
+	("touchend", TOUCHEND),
+	("touchcancel", TOUCHCANCEL),
+	("wheel", WHEEL),
+	("compositionstart", COMPOSITIONSTART),
+	("compositionupdate", COMPOSITIONUPDATE),
+	("compositionend", COMPOSITIONEND),
+	("dragstart", DRAGSTART),
+	("dragover", DRAGOVER),
+	("drop", DROP),
+	("dragend", DRAGEND),
+	("copy", COPY),
+	("cut", CUT),
+	("paste", PASTE),
+	("focusout", FOCUSOUT),
+	("hover", HOVER),
+];
+
+impl EventSet {
+	/// Maps an event type name to its flag, if the event can be forwarded.
+	pub fn from_name(name: &str) -> Option<EventSet> {
+		EVENT_NAMES.iter().find(|&&(other, _)| other == name).map(|&(_, events)| events)
+	}
+
+	/// The names of the event types in the set, in the order of their flags.
+	pub fn names(&self) -> Vec<&'static str> {
+		EVENT_NAMES.iter().filter(|&&(_, events)| self.contains(events)).map(|&(name, _)| name).collect()
+	}
+
+	/// Parses a comma separated list of event type names, ignoring unsupported ones.
+	pub fn from_list(list: &str) -> EventSet {
+		let mut set = EventSet::empty();
+		for name in list.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+			match EventSet::from_name(name) {
+				Some(events) => set.insert(events),
+				None => warn!("{} lists unsupported event {}", DECLARATIVE_ATTR, name)
+			}
+		}
+		set
+	}
+}
+
+/// How the events of a type are delivered to the server.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delivery {
+	/// Events wait for a verdict if they are cancelable and their listener marked them vetoable.
+	Vetoable,
+	/// Events always wait for a verdict, i.e. so the server can process them in order with the
+	/// patches it sends in response.
+	Synchronous,
+	/// Events are sent without waiting for a verdict, even if their listener marked them
+	/// vetoable, i.e. frequent ones like `mousemove`.
+	Asynchronous,
+}
+
+impl Delivery {
+	pub fn to_wire(self) -> u8 {
+		match self {
+			Delivery::Vetoable => 0,
+			Delivery::Synchronous => 1,
+			Delivery::Asynchronous => 2,
+		}
+	}
+
+	pub fn from_wire(mode: u8) -> Result<Delivery> {
+		match mode {
+			0 => Ok(Delivery::Vetoable),
+			1 => Ok(Delivery::Synchronous),
+			2 => Ok(Delivery::Asynchronous),
+			_ => Err(PatchError::Malformed.error("unknown event delivery"))
+		}
+	}
+}
+
+/// The event types the server chose a delivery other than `Vetoable` for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EventDelivery {
+	synchronous: EventSet,
+	asynchronous: EventSet,
+}
+
+impl EventDelivery {
+	pub fn new() -> EventDelivery {
+		EventDelivery {
+			synchronous: EventSet::empty(),
+			asynchronous: EventSet::empty(),
+		}
+	}
+
+	/// Delivers the events of the types in `events` the given way.
+	pub fn set(&mut self, events: EventSet, delivery: Delivery) {
+		self.synchronous.remove(events);
+		self.asynchronous.remove(events);
+		match delivery {
+			Delivery::Vetoable => {},
+			Delivery::Synchronous => self.synchronous.insert(events),
+			Delivery::Asynchronous => self.asynchronous.insert(events),
+		}
+	}
+
+	/// How the events of a type are delivered.
+	pub fn of(&self, event_set: EventSet) -> Delivery {
+		if self.synchronous.contains(event_set) {
+			Delivery::Synchronous
+		} else if self.asynchronous.contains(event_set) {
+			Delivery::Asynchronous
+		} else {
+			Delivery::Vetoable
+		}
+	}
+
+	/// Whether dispatch of an event waits for the server's verdict, given whether it is
+	/// cancelable and the events its listener marked vetoable.
+	pub fn blocks(&self, event_set: EventSet, cancelable: bool, vetoable: EventSet) -> bool {
+		match self.of(event_set) {
+			Delivery::Vetoable => cancelable && vetoable.contains(event_set),
+			Delivery::Synchronous => true,
+			Delivery::Asynchronous => false,
+		}
+	}
+}
+
+/// The events a listener can be passive for: the scroll and touch events, whose default action
+/// the compositor would otherwise wait on.
+pub fn passive_events() -> EventSet {
+	TOUCHSTART | TOUCHMOVE | WHEEL
+}
+
+/// Whether dispatch of an event to a listener waits for the server's verdict. Events the
+/// listener is passive for never do, whatever their delivery.
+pub fn waits_for_verdict(delivery: &EventDelivery, event_set: EventSet, cancelable: bool, vetoable: EventSet,
+                         passive: EventSet) -> bool {
+	!passive.contains(event_set) && delivery.blocks(event_set, cancelable, vetoable)
+}
+
+/// Whether the default action of an event the compositor waits on proceeds, given whether the
+/// event was prevented and the events its listener, if any, is passive for. Preventing an event
+/// a listener is passive for has no effect.
+pub fn default_allowed(event_set: EventSet, prevented: bool, passive: EventSet) -> bool {
+	!prevented || passive.contains(event_set)
+}
+
+/// The events the listener an event dispatched to `target` goes to, if any, is passive for.
+pub fn listener_passive(target: &Node, event_set: EventSet) -> EventSet {
+	find_listener(target, event_set, true).map_or(EventSet::empty(), |listener| listener.passive())
+}
+
+/// Builds the forwarded payload for an event.
+pub fn event_detail(event: &Event) -> EventDetail {
+	if let Some(wheel) = event.downcast::<WheelEvent>() {
+		EventDetail::Wheel {
+			client_x: wheel.ClientX(),
+			client_y: wheel.ClientY(),
+			delta_x: wheel.DeltaX(),
+			delta_y: wheel.DeltaY(),
+			delta_z: wheel.DeltaZ(),
+			delta_mode: wheel.DeltaMode(),
+		}
+	} else if let Some(mouse) = event.downcast::<MouseEvent>() {
+		EventDetail::Mouse {
+			client_x: mouse.ClientX(),
+			client_y: mouse.ClientY(),
+			button: mouse.Button(),
+		}
+	} else if let Some(drag) = event.downcast::<DragEvent>() {
+		EventDetail::Drag {
+			client_x: drag.ClientX(),
+			client_y: drag.ClientY(),
+			items: drag.data_transfer().items().to_vec(),
+		}
+	} else if let Some(composition) = event.downcast::<CompositionEvent>() {
+		EventDetail::Composition {
+			data: String::from(composition.Data()),
+		}
+	} else if let Some(clipboard) = event.downcast::<ClipboardEvent>() {
+		EventDetail::Clipboard {
+			data: clipboard.text().unwrap_or(String::new()),
+		}
+	} else if event.is::<FocusEvent>() {
+		let target = event.GetTarget();
+		EventDetail::Focus {
+			visible: target.r().and_then(|target| target.downcast::<Element>())
+			               .map_or(false, |element| element.get_focus_visible_state()),
+		}
+	} else if let Some(key) = event.downcast::<KeyboardEvent>() {
+		EventDetail::Key {
+			key: String::from(key.Key()),
+			code: String::from(key.Code()),
+			modifiers: key_modifiers(key),
+		}
+	} else {
+		EventDetail::None
+	}
+}
+
+fn key_modifiers(key: &KeyboardEvent) -> u8 {
+	(key.ShiftKey() as u8) | (key.CtrlKey() as u8) << 1 | (key.AltKey() as u8) << 2 | (key.MetaKey() as u8) << 3
+}
+
+/// Finds the node that will receive an event dispatched to `target`: the nearest subscribed
+/// inclusive ancestor for bubbling events, and the target itself otherwise.
+pub fn find_listener(target: &Node, event_set: EventSet, bubbles: bool) -> Option<Root<Node>> {
+	if bubbles {
+		target.inclusive_ancestors().find(|node| node.subscriptions().contains(event_set))
+	} else if target.subscriptions().contains(event_set) {
+		Some(Root::from_ref(target))
+	} else {
+		None
+	}
+}
+
+/// Sends an event to the server if its target, or an ancestor it bubbles to, is subscribed.
+/// Whether dispatch waits for the server's verdict depends on the delivery the server chose for
+/// the event type, and by default on whether the event is cancelable and the listener marked it
+/// vetoable. Events the listener is passive for are never waited on.
+pub fn forward_event(target: &EventTarget, event: &Event) {
+	let node = match target.downcast::<Node>() {
+		Some(node) => node,
+		None => return
+	};
+	let kind = event.type_();
+	let event_set = match EventSet::from_name(&kind) {
+		Some(event_set) => event_set,
+		None => return
+	};
+
+	if let Some(listener) = find_listener(node, event_set, event.bubbles()) {
+		let doc = node.owner_doc();
+		let window = doc.window();
+		let msg = EventMsg {
+			listener: listener.get_id(),
+			target: node.get_id(),
+			kind: String::from(&*kind),
+			detail: event_detail(event),
+		};
+
+		if waits_for_verdict(&doc.vdom_event_delivery(), event_set, event.Cancelable(), listener.vetoable(),
+		                     listener.passive()) {
+			doc.flush_vdom_mouse_moves(true);
+			let (chan, port) = window.new_script_pair();
+			if window.vdom_chan().send_blocking(msg, chan, port) == Verdict::Prevent {
+				event.PreventDefault();
+			}
+		} else if event_set == MOUSEMOVE {
+			doc.forward_vdom_mouse_move(msg);
+		} else {
+			doc.flush_vdom_mouse_moves(true);
+			window.vdom_chan().send(ServerMsg::Event(msg));
+		}
+	}
+}
@@ -0,0 +1,173 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Formatting of ranges of the text of editing hosts, which the vdom server asks for with ops
+//! since there is no `execCommand`. Ranges are in UTF-8 bytes of the concatenated text of the
+//! host, like its caret.
+
+use dom::bindings::inheritance::Castable;
+use dom::bindings::js::{Root, RootedReference};
+use dom::characterdata::CharacterData;
+use dom::create::create_element_named;
+use dom::element::{Element, ElementCreator};
+use dom::node::Node;
+use dom::text::Text;
+use servo_vdom_client::patch::ElementName;
+use std::io::Result;
+use util::str::DOMString;
+use vdom::{check_mutation, remove_node};
+use vdom::msg::TextRun;
+use vdom::undo::UndoLog;
+
+/// Whether `start` to `end` is a range of `text` that starts and ends between characters.
+pub fn valid_range(text: &str, start: usize, end: usize) -> bool {
+	start <= end && end <= text.len() && text.is_char_boundary(start) && text.is_char_boundary(end)
+}
+
+/// The non-empty parts of texts, given by their lengths, that the range from `start` to `end`
+/// of their concatenation covers, as the index of a text and the range within it.
+pub fn text_ranges(lens: &[usize], start: usize, end: usize) -> Vec<(usize, usize, usize)> {
+	let mut ranges = vec![];
+	let mut offset = 0;
+	for (index, &len) in lens.iter().enumerate() {
+		let from = if start > offset { start - offset } else { 0 };
+		let to = if end < offset + len { end.saturating_sub(offset) } else { len };
+		if from < to {
+			ranges.push((index, from, to));
+		}
+		offset += len;
+	}
+	ranges
+}
+
+/// Joins neighbouring runs with the same formatting, dropping empty ones.
+pub fn merge_runs(runs: Vec<TextRun>) -> Vec<TextRun> {
+	let mut merged: Vec<TextRun> = vec![];
+	for run in runs.into_iter().filter(|run| !run.text.is_empty()) {
+		if let Some(last) = merged.last_mut() {
+			if last.bold == run.bold {
+				last.text.push_str(&run.text);
+				continue;
+			}
+		}
+		merged.push(run);
+	}
+	merged
+}
+
+fn texts(host: &Node) -> Vec<Root<Text>> {
+	host.traverse_preorder().filter_map(Root::downcast::<Text>).collect()
+}
+
+fn data(text: &Text) -> String {
+	String::from(text.upcast::<CharacterData>().Data())
+}
+
+/// The `b` elements between `node` and `host`.
+fn bold_ancestors(host: &Node, node: &Node) -> Vec<Root<Element>> {
+	node.ancestors()
+	    .take_while(|ancestor| &**ancestor != host)
+	    .filter_map(Root::downcast::<Element>)
+	    .filter(|element| *element.local_name() == atom!("b"))
+	    .collect()
+}
+
+/// The text of the host, in runs that are bold or not.
+pub fn text_runs(host: &Element) -> Vec<TextRun> {
+	let node = host.upcast::<Node>();
+	merge_runs(texts(node).iter().map(|text| TextRun {
+		text: data(text),
+		bold: !bold_ancestors(node, text.upcast()).is_empty(),
+	}).collect())
+}
+
+/// The texts of the host the range covers, split so that the range covers each of them whole.
+fn split_range(host: &Node, start: usize, end: usize, log: &mut UndoLog) -> Result<Vec<Root<Text>>> {
+	let texts = texts(host);
+	let lens = texts.iter().map(|text| data(text).len()).collect::<Vec<_>>();
+	let mut parts = vec![];
+	for (index, from, to) in text_ranges(&lens, start, end) {
+		parts.push(try!(split_text(&texts[index], from, to, log)));
+	}
+	Ok(parts)
+}
+
+/// Splits a text node so that its data from `from` to `to` is a text node of its own, which is
+/// returned. The new nodes have no server id: the server learns about them from the format
+/// report.
+fn split_text(text: &Text, from: usize, to: usize, log: &mut UndoLog) -> Result<Root<Text>> {
+	let data = data(text);
+	if from == 0 && to == data.len() {
+		return Ok(Root::from_ref(text));
+	}
+	let node = text.upcast::<Node>();
+	let parent = node.GetParentNode().unwrap();
+	let doc = node.owner_doc();
+	let mut kept = data.len();
+	let mut new_nodes = vec![];
+	if to < data.len() {
+		kept = to;
+		new_nodes.push(Text::new(0, DOMString::from(&data[to..]), &doc));
+	}
+	let part = if from == 0 {
+		Root::from_ref(text)
+	} else {
+		kept = from;
+		let part = Text::new(0, DOMString::from(&data[from..to]), &doc);
+		new_nodes.push(Root::from_ref(&*part));
+		part
+	};
+	log.changing_data(text.upcast());
+	text.upcast::<CharacterData>().set_data(DOMString::from(&data[..kept]));
+	for new in new_nodes {
+		try!(check_mutation(parent.InsertBefore(new.upcast(), node.GetNextSibling().r())));
+		log.inserted(new.upcast());
+	}
+	Ok(part)
+}
+
+/// Wraps the text of an editing host from `start` to `end` in `b` elements, one for each text
+/// node in the range that isn't bold already.
+pub fn wrap_bold(host: &Element, start: usize, end: usize, log: &mut UndoLog) -> Result<()> {
+	let node = host.upcast::<Node>();
+	for part in try!(split_range(node, start, end, log)) {
+		let part = part.upcast::<Node>();
+		if !bold_ancestors(node, part).is_empty() {
+			continue;
+		}
+		let parent = part.GetParentNode().unwrap();
+		let bold = create_element_named(0, ElementName::B, &node.owner_doc(), ElementCreator::ScriptCreated);
+		try!(check_mutation(parent.InsertBefore(bold.upcast(), Some(part))));
+		log.inserted(bold.upcast());
+		log.removing(part);
+		try!(check_mutation(bold.upcast::<Node>().AppendChild(part)));
+	}
+	Ok(())
+}
+
+/// Removes the `b` elements the text of an editing host from `start` to `end` is in, keeping
+/// their children. Each element is unwrapped whole, even if the range only covers part of it.
+pub fn unwrap_bold(host: &Element, start: usize, end: usize, log: &mut UndoLog) -> Result<()> {
+	let node = host.upcast::<Node>();
+	let texts = texts(node);
+	let lens = texts.iter().map(|text| data(text).len()).collect::<Vec<_>>();
+	let mut bolds: Vec<Root<Element>> = vec![];
+	for (index, _, _) in text_ranges(&lens, start, end) {
+		for bold in bold_ancestors(node, texts[index].upcast()) {
+			if !bolds.contains(&bold) {
+				bolds.push(bold);
+			}
+		}
+	}
+	for bold in bolds {
+		let bold = bold.upcast::<Node>();
+		let parent = bold.GetParentNode().unwrap();
+		for child in bold.children().collect::<Vec<_>>() {
+			log.removing(&child);
+			try!(check_mutation(parent.InsertBefore(&child, Some(bold))));
+		}
+		try!(remove_node(&parent.owner_doc(), &parent, bold, None, log));
+	}
+	Ok(())
+}
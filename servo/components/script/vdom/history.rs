@@ -0,0 +1,63 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The session history the vdom server builds for a document, so that going back restores the
+//! URL and state of an entry without a navigation.
+
+/// A session history entry: the URL of the document, and the state the server attached to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+	pub url: String,
+	pub state: Option<String>,
+}
+
+/// The entries of the session history, oldest first, and which one is current.
+pub struct SessionHistory {
+	entries: Vec<HistoryEntry>,
+	index: usize,
+}
+
+impl SessionHistory {
+	/// Creates a history with a single entry for the document as it was loaded.
+	pub fn new(url: String) -> SessionHistory {
+		SessionHistory {
+			entries: vec![HistoryEntry {
+				url: url,
+				state: None,
+			}],
+			index: 0,
+		}
+	}
+
+	pub fn current(&self) -> &HistoryEntry {
+		&self.entries[self.index]
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Adds an entry after the current one and makes it current, forgetting the entries that
+	/// were after the current one.
+	pub fn push(&mut self, entry: HistoryEntry) {
+		self.entries.truncate(self.index + 1);
+		self.entries.push(entry);
+		self.index += 1;
+	}
+
+	/// Replaces the current entry.
+	pub fn replace(&mut self, entry: HistoryEntry) {
+		self.entries[self.index] = entry;
+	}
+
+	/// Makes the entry before the current one current and returns it, unless the current one is
+	/// the first.
+	pub fn back(&mut self) -> Option<&HistoryEntry> {
+		if self.index == 0 {
+			return None;
+		}
+		self.index -= 1;
+		Some(&self.entries[self.index])
+	}
+}
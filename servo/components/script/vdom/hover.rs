@@ -0,0 +1,40 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The `:hover` changes of nodes the server subscribed to with `hover`, which it is told about
+//! with `HoverChanged` rather than the events that caused them.
+
+use std::mem;
+
+/// The hover changes made while handling one mouse move. A node whose hover state flips and
+/// flips back in the meantime, like a common ancestor of the element the mouse left and the
+/// one it entered, isn't reported.
+pub struct HoverChanges {
+	changes: Vec<(u64, bool)>,
+}
+
+impl HoverChanges {
+	pub fn new() -> HoverChanges {
+		HoverChanges {
+			changes: vec![],
+		}
+	}
+
+	/// Records that the node with `id` started or stopped being hovered, which it must not
+	/// already have been.
+	pub fn record(&mut self, id: u64, hovered: bool) {
+		match self.changes.iter().position(|&(other, _)| other == id) {
+			// The node is back to the state it was in before the move.
+			Some(index) => {
+				self.changes.remove(index);
+			},
+			None => self.changes.push((id, hovered))
+		}
+	}
+
+	/// The nodes whose hover state changed, with their new state, in the order they changed.
+	pub fn take(&mut self) -> Vec<(u64, bool)> {
+		mem::replace(&mut self.changes, vec![])
+	}
+}
@@ -0,0 +1,116 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Invalidation of live collections created from selectors. Each collection depends on the
+//! kinds of mutations its selectors can observe, and only recomputes after one of those, so a
+//! `.active` collection survives edits of text but not changes of `class` attributes.
+
+use selectors::parser::{Selector, SimpleSelector, parse_author_origin_selector_list_from_str};
+use string_cache::Atom;
+use style::selector_impl::ServoSelectorImpl;
+
+bitflags! {
+	#[doc = "The kinds of mutations that can change which elements match a selector."]
+	flags MutationKinds: u8 {
+		#[doc = "Elements are inserted or removed, or move."]
+		const STRUCTURE = 1 << 0,
+		const CLASSES = 1 << 1,
+		const IDS = 1 << 2,
+		#[doc = "Any attribute changes, including `class` and `id`."]
+		const ATTRIBUTES = 1 << 3,
+		#[doc = "The state of an element changes, i.e. it becomes hovered or focused."]
+		const STATE = 1 << 4,
+		#[doc = "The data of a text node changes, which only `:empty` observes."]
+		const TEXT = 1 << 5
+	}
+}
+
+/// The kinds of mutations in order of their flags, for `MutationVersions`.
+static KINDS: [MutationKinds; 6] = [STRUCTURE, CLASSES, IDS, ATTRIBUTES, STATE, TEXT];
+
+fn simple_selector_dependencies(selector: &SimpleSelector<ServoSelectorImpl>) -> MutationKinds {
+	match *selector {
+		SimpleSelector::Class(_) => CLASSES,
+		SimpleSelector::ID(_) => IDS,
+		SimpleSelector::AttrExists(_) |
+		SimpleSelector::AttrEqual(_, _, _) |
+		SimpleSelector::AttrIncludes(_, _) |
+		SimpleSelector::AttrDashMatch(_, _, _) |
+		SimpleSelector::AttrPrefixMatch(_, _) |
+		SimpleSelector::AttrSubstringMatch(_, _) |
+		SimpleSelector::AttrSuffixMatch(_, _) => ATTRIBUTES,
+		SimpleSelector::NonTSPseudoClass(_) => STATE,
+		SimpleSelector::Empty => TEXT,
+		SimpleSelector::Negation(ref selectors) => {
+			selectors.iter().fold(MutationKinds::empty(), |kinds, selector| {
+				kinds | simple_selector_dependencies(selector)
+			})
+		},
+		// Names, namespaces and the structural pseudo-classes only change with the structure.
+		_ => MutationKinds::empty()
+	}
+}
+
+/// The kinds of mutations that can change which elements match any of `selectors`. Elements
+/// entering or leaving the tree always can.
+pub fn dependencies(selectors: &[Selector<ServoSelectorImpl>]) -> MutationKinds {
+	let mut kinds = STRUCTURE;
+	for selector in selectors {
+		let mut compound = Some(&*selector.compound_selectors);
+		while let Some(current) = compound {
+			for simple in &current.simple_selectors {
+				kinds.insert(simple_selector_dependencies(simple));
+			}
+			compound = current.next.as_ref().map(|&(ref next, _)| &**next);
+		}
+	}
+	kinds
+}
+
+/// Parses a selector list and returns the kinds of mutations it depends on, or `None` if it
+/// doesn't parse.
+pub fn selector_dependencies(selectors: &str) -> Option<MutationKinds> {
+	parse_author_origin_selector_list_from_str(selectors).ok().map(|selectors| dependencies(&selectors))
+}
+
+/// The kinds of mutations a change of an attribute is.
+pub fn attribute_mutation(name: &Atom) -> MutationKinds {
+	match *name {
+		atom!("class") => CLASSES | ATTRIBUTES,
+		atom!("id") => IDS | ATTRIBUTES,
+		_ => ATTRIBUTES
+	}
+}
+
+/// Counts the mutations of a document by kind, so a collection can tell whether one it
+/// depends on happened since it last computed its elements.
+#[derive(Clone, Copy)]
+pub struct MutationVersions {
+	counts: [u64; 6],
+}
+
+impl MutationVersions {
+	pub fn new() -> MutationVersions {
+		MutationVersions {
+			counts: [0; 6],
+		}
+	}
+
+	/// Records a mutation of the given kinds.
+	pub fn note(&mut self, kinds: MutationKinds) {
+		for (count, kind) in self.counts.iter_mut().zip(KINDS.iter()) {
+			if kinds.contains(*kind) {
+				*count += 1;
+			}
+		}
+	}
+
+	/// A version that changes whenever a mutation of one of the given kinds is noted, and only
+	/// then.
+	pub fn version(&self, kinds: MutationKinds) -> u64 {
+		self.counts.iter().zip(KINDS.iter())
+		           .filter(|&(_, kind)| kinds.contains(*kind))
+		           .fold(0, |version, (count, _)| version + count)
+	}
+}
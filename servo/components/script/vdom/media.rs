@@ -0,0 +1,94 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Media queries the vdom server asked about, so it can be told when their result changes.
+
+use cssparser::Parser as CssParser;
+use euclid::size::TypedSize2D;
+use std::io::Result;
+use style::media_queries::{Device, MediaQueryList, MediaType, parse_media_query_list};
+use style_traits::color_scheme::ColorScheme;
+use vdom::error::PatchError;
+
+/// A screen with a viewport of the given size in CSS pixels, and the default color scheme.
+pub fn device(width: f32, height: f32) -> Device {
+	Device::new(MediaType::Screen, TypedSize2D::new(width, height))
+}
+
+fn parse(query: &str) -> MediaQueryList {
+	parse_media_query_list(&mut CssParser::new(query))
+}
+
+/// Evaluates a media query list against a device.
+pub fn evaluate(query: &str, device: &Device) -> bool {
+	parse(query).evaluate(device)
+}
+
+/// Encodes a color scheme preference, where `None` defers to the platform's.
+pub fn color_scheme_to_wire(color_scheme: Option<ColorScheme>) -> u8 {
+	match color_scheme {
+		None => 0,
+		Some(ColorScheme::Light) => 1,
+		Some(ColorScheme::Dark) => 2,
+	}
+}
+
+pub fn color_scheme_from_wire(code: u8) -> Result<Option<ColorScheme>> {
+	match code {
+		0 => Ok(None),
+		1 => Ok(Some(ColorScheme::Light)),
+		2 => Ok(Some(ColorScheme::Dark)),
+		_ => Err(PatchError::Malformed.error("unknown color scheme"))
+	}
+}
+
+struct WatchedQuery {
+	query: String,
+	list: MediaQueryList,
+	matches: bool,
+}
+
+/// The media queries the server is watching, with their last result.
+pub struct MediaWatch {
+	queries: Vec<WatchedQuery>,
+}
+
+impl MediaWatch {
+	pub fn new() -> MediaWatch {
+		MediaWatch {
+			queries: vec![],
+		}
+	}
+
+	/// Starts watching a query, if it isn't already, and returns whether it currently matches.
+	pub fn watch(&mut self, query: String, device: &Device) -> bool {
+		let matches = evaluate(&query, device);
+		match self.queries.iter_mut().find(|watched| watched.query == query) {
+			Some(watched) => watched.matches = matches,
+			None => {
+				let list = parse(&query);
+				self.queries.push(WatchedQuery {
+					query: query,
+					list: list,
+					matches: matches,
+				});
+			}
+		}
+		matches
+	}
+
+	/// Re-evaluates the watched queries after the device changed, i.e. on resize, returning the
+	/// ones whose result changed along with their new result.
+	pub fn reevaluate(&mut self, device: &Device) -> Vec<(String, bool)> {
+		let mut changed = vec![];
+		for watched in &mut self.queries {
+			let matches = watched.list.evaluate(device);
+			if matches != watched.matches {
+				watched.matches = matches;
+				changed.push((watched.query.clone(), matches));
+			}
+		}
+		changed
+	}
+}
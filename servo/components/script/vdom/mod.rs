@@ -0,0 +1,452 @@
+use dom::bindings::error::{Error as DomError, Fallible};
+use dom::create::create_element_named;
+use dom::bindings::js::Root;
+use dom::characterdata::CharacterData;
+use dom::bindings::inheritance::Castable;
+use dom::document::Document;
+use dom::element::{Element,ElementCreator,StylePriority};
+use dom::htmlelement::HTMLElement;
+use dom::node::{Node,NodeDamage,document_from_node,window_from_node};
+use dom::text::Text;
+use servo_vdom_client::patch::*;
+use std::ascii::AsciiExt;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor,Read,Result};
+use style::properties::parse_one_declaration_with_priority;
+use util::str::DOMString;
+use vdom::deferred::DeferredPatches;
+use vdom::error::PatchError;
+use vdom::events::EventSet;
+use vdom::ops::{OpTarget, PatchOp, TargetKind, apply_ops};
+use vdom::tombstones::LookupError;
+use vdom::tree::{NodeToken, build_tree};
+use vdom::undo::UndoLog;
+
+pub mod atoms;
+pub mod deferred;
+pub mod echoes;
+pub mod error;
+pub mod events;
+pub mod format;
+pub mod history;
+pub mod hover;
+pub mod live;
+pub mod media;
+pub mod mousemoves;
+pub mod moves;
+pub mod msg;
+pub mod ops;
+pub mod outgoing;
+pub mod pool;
+pub mod registry;
+pub mod resize;
+pub mod selection;
+pub mod stylesheets;
+pub mod ticks;
+pub mod timers;
+pub mod tooltips;
+pub mod tombstones;
+pub mod tree;
+pub mod undo;
+pub mod verdict;
+pub mod visibility;
+pub mod wire;
+
+/// Reads a text node from a reader.
+pub fn read_text_node<T:Read>(reader: &mut T, doc: &Document) -> Result<Root<Text>> {
+	let (id,text) = try!(reader.read_text());
+	Ok(Text::new(id, DOMString::from(text), doc))
+}
+
+/// Reads an attribute list into a node.
+pub fn read_attrs_into<T:Read>(reader: &mut T, el: &Element) -> Result<()> {
+	while let Some(attr) = try!(reader.read_attr()) {
+		set_attr(el, attr);
+	}
+	Ok(())
+}
+
+/// Sets an attribute of an element.
+fn set_attr(el: &Element, attr: AttributeVal) {
+	match attr {
+		AttributeVal::Class(val) => {
+			el.set_tokenlist_attribute(&atom!("class"), DOMString::from(val));
+		},
+		AttributeVal::Style(key, val) => set_inline_style(el, &key, &val),
+		AttributeVal::Event(name,_) => {
+			match EventSet::from_name(&name) {
+				Some(events) => el.upcast::<Node>().subscribe(events),
+				None => warn!("vdom server subscribed to unsupported event {}", name)
+			}
+		}
+	}
+}
+
+/// Sets a property of the inline style of an element, whose value may end with `!important`
+/// for the declaration to win over the non-important ones of stylesheets. An empty value
+/// removes the property.
+pub fn set_inline_style(el: &Element, name: &str, value: &str) {
+	let htmlel = match el.downcast::<HTMLElement>() {
+		Some(htmlel) => htmlel,
+		None => return
+	};
+	if value.is_empty() {
+		let _ = htmlel.Style().RemoveProperty(DOMString::from(name));
+		return;
+	}
+	let window = window_from_node(el);
+	match parse_one_declaration_with_priority(&name.to_ascii_lowercase(), value, &window.get_url(),
+	                                          window.css_error_reporter()) {
+		Ok((declarations, important)) => {
+			let priority = if important { StylePriority::Important } else { StylePriority::Normal };
+			for declaration in declarations {
+				el.update_inline_style(declaration, priority);
+			}
+			document_from_node(el).content_changed(el.upcast(), NodeDamage::NodeStyleDamaged);
+		},
+		Err(()) => warn!("vdom server set invalid style {}: {}", name, value)
+	}
+}
+
+/// Reads the next token of a node payload from a reader. An element is read along with its
+/// attributes, but not its children.
+fn read_token<T:Read>(reader: &mut T, doc: &Document) -> Result<NodeToken<Root<Node>>> {
+	match try!(reader.read_node_type()) {
+		Some(NodeType::Text) => read_text_node(reader, doc).map(|t| NodeToken::Leaf(Root::from_ref(t.upcast()))),
+		Some(NodeType::Element) => {
+			let (id,name) = try!(reader.read_el());
+			let element = create_element_named(id, name, doc, ElementCreator::ParserCreated);
+			try!(read_attrs_into(reader, &*element));
+			Ok(NodeToken::Open(Root::from_ref(element.upcast())))
+		},
+		None => Ok(NodeToken::Close)
+	}
+}
+
+/// Reads a node from a reader.
+pub fn read_node<T:Read>(reader: &mut T, doc: &Document) -> Result<Option<Root<Node>>> {
+	build_tree(|| read_token(reader, doc), |parent, child| {
+		parent.AppendChild(child);
+	})
+}
+
+/// Fails if `node` is in a frozen subtree, which patches may not mutate until it is unfrozen.
+pub fn check_mutable(node: &Node) -> Result<()> {
+	if node.is_frozen() {
+		return Err(PatchError::HierarchyViolation.error("patch mutates a frozen subtree"));
+	}
+	Ok(())
+}
+
+/// Turns the failure of a DOM tree mutation into a patch error.
+pub fn check_mutation<T>(result: Fallible<T>) -> Result<T> {
+	result.map_err(|err| match err {
+		DomError::NotFound => PatchError::UnknownId.error("patch refers to a node that isn't a child"),
+		DomError::InvalidCharacter => PatchError::Malformed.error("patch uses an invalid name"),
+		_ => PatchError::HierarchyViolation.error("patch makes an invalid node tree")
+	})
+}
+
+/// A patch read from a message, along with its payload.
+enum Patch {
+	Replace(Root<Node>),
+	ModifyAttrs(Vec<AttributeVal>),
+	Remove,
+	/// The nodes of an `Append` or `AppendMultiple` patch.
+	Append(Vec<Root<Node>>),
+}
+
+/// Reads the payload of a patch, building the nodes it inserts.
+fn read_patch<T:Read>(reader: &mut T, patch_ty: PatchType, doc: &Document) -> Result<Patch> {
+	Ok(match patch_ty {
+		PatchType::Replace => {
+			Patch::Replace(try!(try!(read_node(reader, doc)).ok_or_else(|| PatchError::Malformed.error("replace patch without a node"))))
+		},
+		PatchType::ModifyAttrs => {
+			let mut attrs = vec![];
+			while let Some(attr) = try!(reader.read_attr()) {
+				attrs.push(attr);
+			}
+			Patch::ModifyAttrs(attrs)
+		},
+		PatchType::Remove => Patch::Remove,
+		PatchType::Append => {
+			Patch::Append(vec![try!(try!(read_node(reader, doc)).ok_or_else(|| PatchError::Malformed.error("append patch without a node")))])
+		},
+		PatchType::AppendMultiple => {
+			let mut nodes = vec![];
+			while let Some(new) = try!(read_node(reader, doc)) {
+				nodes.push(new);
+			}
+			Patch::Append(nodes)
+		}
+	})
+}
+
+/// Removes `old` from `parent`, putting `new` in its place if there is one, and cleans up after
+/// the removed subtree with `Document::vdom_subtree_removed`. Every op removing nodes removes
+/// them with this; `new` is left for the caller to log as inserted.
+pub fn remove_node(doc: &Document, parent: &Node, old: &Node, new: Option<&Node>, log: &mut UndoLog) -> Result<()> {
+	log.removing(old);
+	match new {
+		Some(new) => try!(check_mutation(parent.ReplaceChild(new, old))),
+		None => try!(check_mutation(parent.RemoveChild(old)))
+	};
+	doc.vdom_subtree_removed(old, parent, log.recording());
+	Ok(())
+}
+
+fn apply_patch(doc: &Document, id: u64, patch: Patch, log: &mut UndoLog) -> Result<()> {
+	let target = try!(doc.get_node_by_id(id).ok_or_else(|| PatchError::UnknownId.error("patch targets a missing node")));
+	try!(check_mutable(&target));
+
+	match patch {
+		Patch::Replace(new) => {
+			let parent = try!(target.GetParent().ok_or_else(|| PatchError::HierarchyViolation.error("replace patch targets a root node")));
+			try!(remove_node(doc, &parent, &target, Some(&*new), log));
+			log.inserted(&*new);
+		},
+		Patch::ModifyAttrs(attrs) => {
+			if let Some(el) = target.downcast::<Element>() {
+				log.modifying(el);
+				for attr in attrs {
+					set_attr(el, attr);
+				}
+			}
+		},
+		Patch::Remove => {
+			let parent = try!(target.GetParent().ok_or_else(|| PatchError::HierarchyViolation.error("remove patch targets a root node")));
+			try!(remove_node(doc, &parent, &target, None, log));
+		},
+		Patch::Append(nodes) => {
+			for new in nodes {
+				try!(check_mutation(target.AppendChild(&*new)));
+				log.inserted(&*new);
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Applies a list of patches to a document, followed by any trailing operations, recording
+/// the mutations made into `log`. A patch targeting an id no node has yet waits for a later
+/// patch of the list to insert it; the list fails if one is still waiting at its end.
+pub fn apply_patches<T:Read>(reader: &mut T, doc: &Document, log: &mut UndoLog) -> Result<()> {
+	let mut deferred = DeferredPatches::new();
+	while let Some((patch_ty, id)) = try!(reader.read_patch_type()) {
+		if let Err(LookupError::Removed) = doc.lookup_node_by_id(id) {
+			return Err(PatchError::RemovedId.error("patch targets a removed node"));
+		}
+		let patch = try!(read_patch(reader, patch_ty, doc));
+		try!(deferred.apply(id, patch, |id| doc.get_node_by_id(id).is_some(),
+		                    |id, patch| apply_patch(doc, id, patch, log)));
+	}
+	let unresolved = deferred.unresolved();
+	if !unresolved.is_empty() {
+		warn!("vdom patches target missing nodes {:?}", unresolved);
+	}
+	try!(deferred.finish());
+
+	apply_ops(reader, doc, log)
+}
+
+/// A patch read for a dry run, with the nodes it inserts as their id and whether each one is a
+/// text node.
+enum DryPatch {
+	Replace(Vec<(u64, bool)>),
+	ModifyAttrs,
+	Remove,
+	Append(Vec<(u64, bool)>),
+}
+
+/// Reads the next token of a node payload without creating its node.
+fn skip_token<T:Read>(reader: &mut T) -> Result<NodeToken<(u64, bool)>> {
+	match try!(reader.read_node_type()) {
+		Some(NodeType::Text) => Ok(NodeToken::Leaf((try!(reader.read_text()).0, true))),
+		Some(NodeType::Element) => {
+			let (id,_) = try!(reader.read_el());
+			while let Some(_) = try!(reader.read_attr()) {}
+			Ok(NodeToken::Open((id, false)))
+		},
+		None => Ok(NodeToken::Close)
+	}
+}
+
+/// Reads a node payload without creating its nodes, returning the nodes it holds.
+fn skip_node<T:Read>(reader: &mut T) -> Result<Option<Vec<(u64, bool)>>> {
+	let mut nodes = vec![];
+	let root = try!(build_tree(|| skip_token(reader), |_, &child| nodes.push(child)));
+	Ok(root.map(|root| {
+		nodes.push(root);
+		nodes
+	}))
+}
+
+/// Reads the payload of a patch like `read_patch`, without creating its nodes.
+fn skip_patch<T:Read>(reader: &mut T, patch_ty: PatchType) -> Result<DryPatch> {
+	Ok(match patch_ty {
+		PatchType::Replace => {
+			DryPatch::Replace(try!(try!(skip_node(reader)).ok_or_else(|| PatchError::Malformed.error("replace patch without a node"))))
+		},
+		PatchType::ModifyAttrs => {
+			while let Some(_) = try!(reader.read_attr()) {}
+			DryPatch::ModifyAttrs
+		},
+		PatchType::Remove => DryPatch::Remove,
+		PatchType::Append => {
+			DryPatch::Append(try!(try!(skip_node(reader)).ok_or_else(|| PatchError::Malformed.error("append patch without a node"))))
+		},
+		PatchType::AppendMultiple => {
+			let mut nodes = vec![];
+			while let Some(new) = try!(skip_node(reader)) {
+				nodes.extend(new);
+			}
+			DryPatch::Append(nodes)
+		}
+	})
+}
+
+/// The changes a dry run of patches would have made to the ids of a document so far.
+struct DryRun<'a> {
+	doc: &'a Document,
+	/// The ids inserted by the patches, along with whether each one is a text node.
+	inserted: HashMap<u64, bool>,
+	/// The ids of the document removed by the patches.
+	removed: HashSet<u64>,
+}
+
+impl<'a> DryRun<'a> {
+	fn exists(&self, id: u64) -> bool {
+		self.inserted.contains_key(&id) || (!self.removed.contains(&id) && self.doc.get_node_by_id(id).is_some())
+	}
+
+	/// Checks a patch targeting an existing id as `apply_patch` would, then records the ids it
+	/// would insert and remove.
+	fn check(&mut self, id: u64, patch: DryPatch) -> Option<PatchError> {
+		let (removes, inserts) = match patch {
+			DryPatch::Replace(nodes) => (true, nodes),
+			DryPatch::ModifyAttrs => (false, vec![]),
+			DryPatch::Remove => (true, vec![]),
+			DryPatch::Append(nodes) => (false, nodes),
+		};
+		let appends = !removes && !inserts.is_empty();
+		match self.inserted.get(&id).cloned() {
+			Some(is_text) => {
+				if appends && is_text {
+					return Some(PatchError::HierarchyViolation);
+				}
+				if removes {
+					self.inserted.remove(&id);
+				}
+			},
+			None => {
+				let target = self.doc.get_node_by_id(id).unwrap();
+				if target.is_frozen() || (removes && target.GetParentNode().is_none()) ||
+				   (appends && target.is::<CharacterData>()) {
+					return Some(PatchError::HierarchyViolation);
+				}
+				if removes {
+					self.removed.extend(target.traverse_preorder().map(|node| node.get_id()));
+				}
+			}
+		}
+		self.inserted.extend(inserts);
+		None
+	}
+
+	/// Checks a node a trailing op targets as `apply_ops` would: it must exist, be of the type
+	/// the op requires, and be mutable if the op mutates it. Nodes the patches inserted are only
+	/// known to be text or not, and aren't frozen.
+	fn check_target(&self, target: &OpTarget) -> Option<PatchError> {
+		if !self.exists(target.id) {
+			return Some(match self.doc.lookup_node_by_id(target.id) {
+				Err(LookupError::Removed) => PatchError::RemovedId,
+				_ => PatchError::UnknownId
+			});
+		}
+		match self.inserted.get(&target.id).cloned() {
+			Some(is_text) => {
+				let matches = match target.kind {
+					TargetKind::Node => true,
+					TargetKind::CharacterData | TargetKind::Text => is_text,
+					TargetKind::Element | TargetKind::Canvas => !is_text,
+				};
+				if !matches { Some(PatchError::TypeMismatch) } else { None }
+			},
+			None => {
+				let node = self.doc.get_node_by_id(target.id).unwrap();
+				if !target.kind.matches(&node) {
+					Some(PatchError::TypeMismatch)
+				} else if target.mutates && check_mutable(&node).is_err() {
+					Some(PatchError::HierarchyViolation)
+				} else {
+					None
+				}
+			}
+		}
+	}
+}
+
+/// Checks a buffer of patches and trailing ops against a document without applying it,
+/// returning the errors applying it would fail with, in order. The targets of the patches must
+/// exist and the trees they make be valid; a malformed payload ends the check, so its error is
+/// the last one. The nodes trailing ops target are checked like those of the patches, without
+/// following the nodes the ops themselves insert or remove.
+pub fn validate_patch(buffer: &[u8], doc: &Document) -> Vec<PatchError> {
+	let mut reader = Cursor::new(buffer);
+	let mut errors = vec![];
+	let run = RefCell::new(DryRun {
+		doc: doc,
+		inserted: HashMap::new(),
+		removed: HashSet::new(),
+	});
+	let mut deferred = DeferredPatches::new();
+	loop {
+		let (patch_ty, id) = match reader.read_patch_type() {
+			Ok(Some(patch)) => patch,
+			Ok(None) => break,
+			Err(err) => {
+				errors.push(PatchError::of(&err));
+				return errors;
+			}
+		};
+		let patch = match skip_patch(&mut reader, patch_ty) {
+			Ok(patch) => patch,
+			Err(err) => {
+				errors.push(PatchError::of(&err));
+				return errors;
+			}
+		};
+		if !run.borrow().exists(id) {
+			if let Err(LookupError::Removed) = doc.lookup_node_by_id(id) {
+				errors.push(PatchError::RemovedId);
+				continue;
+			}
+		}
+		let _ = deferred.apply(id, patch, |id| run.borrow().exists(id), |id, patch| {
+			if let Some(error) = run.borrow_mut().check(id, patch) {
+				errors.push(error);
+			}
+			Ok(())
+		});
+	}
+	for _ in deferred.unresolved() {
+		errors.push(PatchError::UnknownId);
+	}
+
+	loop {
+		match PatchOp::read_from(&mut reader) {
+			Ok(Some(op)) => {
+				let error = op.targets().iter().filter_map(|target| run.borrow().check_target(target)).next();
+				errors.extend(error);
+			},
+			Ok(None) => break,
+			Err(err) => {
+				errors.push(PatchError::of(&err));
+				break;
+			}
+		}
+	}
+	errors
+}
\ No newline at end of file
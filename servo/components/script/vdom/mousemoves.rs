@@ -0,0 +1,78 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Coalescing of the `mousemove` events forwarded to the vdom server, which asked for at most
+//! one per listener in each batch of events the script thread handles, or per interval. Each
+//! move forwarded carries the latest position. This is independent of the squashing of pending
+//! mouse moves before they are handled.
+
+use vdom::msg::EventMsg;
+
+/// The moves held back until the next flush, the latest of each listener.
+pub struct MouseMoves {
+	interval_ns: Option<u64>,
+	pending: Vec<EventMsg>,
+	last_flushed: Option<u64>,
+	scheduled: bool,
+}
+
+impl MouseMoves {
+	/// Moves that aren't coalesced, until `set_interval` says otherwise.
+	pub fn new() -> MouseMoves {
+		MouseMoves {
+			interval_ns: None,
+			pending: vec![],
+			last_flushed: None,
+			scheduled: false,
+		}
+	}
+
+	/// Coalesces the moves of each batch of events, also forwarding them at most once per
+	/// `interval` in milliseconds if it isn't 0, or stops coalescing them if it is `None`.
+	pub fn set_interval(&mut self, interval_ms: Option<u32>) {
+		self.interval_ns = interval_ms.map(|interval_ms| interval_ms as u64 * 1000 * 1000);
+	}
+
+	/// Holds back a move until the next flush, replacing the one held back for its listener.
+	/// Returns it to be forwarded right away if moves aren't coalesced.
+	pub fn coalesce(&mut self, msg: EventMsg) -> Option<EventMsg> {
+		if self.interval_ns.is_none() {
+			return Some(msg);
+		}
+		match self.pending.iter().position(|pending| pending.listener == msg.listener) {
+			Some(index) => self.pending[index] = msg,
+			None => self.pending.push(msg)
+		}
+		None
+	}
+
+	/// The moves to forward at `now`, in nanoseconds, once a batch of events is handled: those
+	/// held back, unless moves were forwarded less than the interval ago. When another event is
+	/// forwarded, the moves before it are forced out first so it doesn't overtake them.
+	pub fn flush(&mut self, now: u64, force: bool) -> Vec<EventMsg> {
+		let interval_ns = self.interval_ns.unwrap_or(0);
+		let due = self.last_flushed.map_or(true, |last_flushed| now - last_flushed >= interval_ns);
+		if self.pending.is_empty() || !(force || due) {
+			return vec![];
+		}
+		self.last_flushed = Some(now);
+		self.pending.drain(..).collect()
+	}
+
+	/// How long after `now` the moves held back by a flush that wasn't due are due, in
+	/// nanoseconds, unless there are none or a flush of them is already scheduled.
+	pub fn schedule(&mut self, now: u64) -> Option<u64> {
+		if self.pending.is_empty() || self.scheduled {
+			return None;
+		}
+		self.scheduled = true;
+		let due = self.last_flushed.unwrap_or(now) + self.interval_ns.unwrap_or(0);
+		Some(if due > now { due - now } else { 0 })
+	}
+
+	/// Records that the scheduled flush runs, or is no longer needed.
+	pub fn scheduled_flush(&mut self) {
+		self.scheduled = false;
+	}
+}
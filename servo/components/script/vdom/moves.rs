@@ -0,0 +1,57 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Batches of nodes moved among parents, played out on their ids before the DOM is mutated.
+
+use std::io::Result;
+use vdom::error::PatchError;
+
+/// The ids of the children of the parents a batch of moves involves, in tree order.
+#[derive(Debug, PartialEq)]
+pub struct Arrangement {
+	parents: Vec<Vec<u64>>,
+}
+
+impl Arrangement {
+	pub fn new(parents: Vec<Vec<u64>>) -> Arrangement {
+		Arrangement {
+			parents: parents,
+		}
+	}
+
+	/// Moves `node` to the parent at index `parent`, before the child `before` or at the end,
+	/// as `insertBefore` would. Fails without changing anything if `before` isn't a child of
+	/// that parent.
+	pub fn move_node(&mut self, node: u64, parent: usize, before: Option<u64>) -> Result<()> {
+		// Inserting a node before itself inserts it before its next sibling.
+		let before = match before {
+			Some(before) if before == node => self.next_sibling(node),
+			before => before
+		};
+		if let Some(before) = before {
+			if !self.parents[parent].contains(&before) {
+				return Err(PatchError::HierarchyViolation.error("node moved before a node of another parent"));
+			}
+		}
+		for children in &mut self.parents {
+			children.retain(|&child| child != node);
+		}
+		let children = &mut self.parents[parent];
+		let index = before.and_then(|before| children.iter().position(|&child| child == before))
+		                  .unwrap_or(children.len());
+		children.insert(index, node);
+		Ok(())
+	}
+
+	fn next_sibling(&self, node: u64) -> Option<u64> {
+		self.parents.iter().filter_map(|children| {
+			children.iter().position(|&child| child == node).and_then(|index| children.get(index + 1).cloned())
+		}).next()
+	}
+
+	/// The ids of the children of the parent at index `parent`.
+	pub fn children(&self, parent: usize) -> &[u64] {
+		&self.parents[parent]
+	}
+}
@@ -0,0 +1,1179 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Messages sent from the client back to the vdom server.
+
+use ipc_channel::ipc::IpcSender;
+use script_thread::{ScriptChan, ScriptPort, ScriptThread};
+use std::cmp::{max, min};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use style_traits::color_scheme::ColorScheme;
+use std::sync::{Arc, Condvar, Mutex};
+use util::thread::spawn_named;
+use vdom::echoes::is_echo;
+use vdom::error::PatchError;
+use vdom::media::{color_scheme_from_wire, color_scheme_to_wire};
+use vdom::ops::{PATCH_PROTOCOL_VERSION, supported_ops};
+use vdom::outgoing::{DEFAULT_QUEUE_BOUND, DropPolicy, OutgoingQueue};
+use vdom::tombstones::LookupError;
+use vdom::tree::MAX_NODE_DEPTH;
+use vdom::undo::MAX_CHECKPOINTS;
+use vdom::verdict::{PendingVerdicts, Verdict};
+use vdom::wire::{MAX_MESSAGE_SIZE, ReadWire, WriteWire};
+
+const MSG_EVENT: u8 = 1;
+const MSG_BLOCKING_EVENT: u8 = 2;
+const MSG_REPLY: u8 = 3;
+const MSG_MEDIA_QUERY_CHANGED: u8 = 4;
+const MSG_COLOR_SCHEME_CHANGED: u8 = 5;
+const MSG_CONTENT_EDITED: u8 = 6;
+const MSG_ACTIVE_ELEMENT_CHANGED: u8 = 7;
+const MSG_POP_STATE: u8 = 8;
+const MSG_HASH_CHANGED: u8 = 9;
+const MSG_FONT_LOADED: u8 = 10;
+const MSG_FONT_ERROR: u8 = 11;
+const MSG_HOVER_CHANGED: u8 = 12;
+const MSG_VISIBILITY_CHANGED: u8 = 13;
+const MSG_RESIZE_OBSERVED: u8 = 14;
+const MSG_CONTENT_FORMATTED: u8 = 15;
+const MSG_TICK: u8 = 16;
+const MSG_TEXTS_NOT_SET: u8 = 17;
+const MSG_PATCH_FAILED: u8 = 18;
+const MSG_SELECTION_CHANGED: u8 = 19;
+const MSG_TOOLTIP: u8 = 20;
+const MSG_DROPPED_MESSAGES: u8 = 21;
+const MSG_CLIENT_CAPABILITIES: u8 = 22;
+
+const DETAIL_NONE: u8 = 0;
+const DETAIL_MOUSE: u8 = 1;
+const DETAIL_KEY: u8 = 2;
+const DETAIL_WHEEL: u8 = 3;
+const DETAIL_COMPOSITION: u8 = 4;
+const DETAIL_DRAG: u8 = 5;
+const DETAIL_CLIPBOARD: u8 = 6;
+const DETAIL_FOCUS: u8 = 7;
+
+const REPLY_STATE: u8 = 0;
+const REPLY_DATASET: u8 = 1;
+const REPLY_ATTRIBUTE_NAMES: u8 = 2;
+const REPLY_MEDIA_MATCH: u8 = 3;
+const REPLY_DEVICE_PIXEL_RATIO: u8 = 4;
+const REPLY_GEOMETRY: u8 = 5;
+const REPLY_IDS: u8 = 6;
+const REPLY_ACTIVE_ELEMENT: u8 = 7;
+const REPLY_BOX_MODEL: u8 = 8;
+const REPLY_REFLOW_TIMING: u8 = 9;
+const REPLY_CANVAS_PIXELS: u8 = 10;
+const REPLY_SCREEN: u8 = 11;
+const REPLY_WINDOW_NAME: u8 = 12;
+const REPLY_LONGHANDS: u8 = 13;
+const REPLY_RESOLVED_STYLE: u8 = 14;
+const REPLY_SUBSCRIPTIONS: u8 = 15;
+const REPLY_CONTENT_TYPE: u8 = 16;
+const REPLY_PATCH_ERRORS: u8 = 17;
+const REPLY_SELECTION: u8 = 18;
+const REPLY_CONNECTION: u8 = 19;
+
+/// A message for the vdom server.
+#[derive(Debug, PartialEq)]
+pub enum ServerMsg {
+	/// A DOM event was dispatched to a node the server subscribed to.
+	Event(EventMsg),
+	/// A vetoable event is waiting for the server's verdict, to be sent back with the given id.
+	BlockingEvent(u32, EventMsg),
+	/// The answer to a query op, sent back with the id the op carried.
+	Reply(u32, Reply),
+	/// A media query the server watches started or stopped matching after a resize.
+	MediaQueryChanged(String, bool),
+	/// The platform's preferred color scheme changed.
+	ColorSchemeChanged(ColorScheme),
+	/// The user edited the text of a `contenteditable` element, which now has the given text.
+	ContentEdited(u64, String),
+	/// Focus moved, and `document.activeElement` is now the element with the given id; see
+	/// `Reply::ActiveElement`.
+	ActiveElementChanged(u64),
+	/// The document went back to a session history entry, and now has its URL and state.
+	PopState(String, Option<String>),
+	/// A navigation only changed the fragment of the URL of the document, from the first URL
+	/// to the second, so the document was scrolled instead of replaced.
+	HashChanged(String, String),
+	/// The font a `PreloadFont` op loaded into a family, from a URL, is ready to be used.
+	FontLoaded(String, String),
+	/// The font a `PreloadFont` op asked for couldn't be loaded, i.e. because it isn't a
+	/// supported font type.
+	FontError(String, String),
+	/// The node with the given id, which subscribed to `hover`, started or stopped matching
+	/// `:hover`. Changes undone within the same mouse move aren't sent.
+	HoverChanged(u64, bool),
+	/// The fraction of the node with the given id inside the viewport crossed one of the
+	/// thresholds of an `ObserveVisibility` op, and is now the given ratio.
+	VisibilityChanged(u64, f64),
+	/// The content box of the node with the given id, which an `ObserveResize` op watches, has a
+	/// new width and height after a reflow.
+	ResizeObserved(u64, i32, i32),
+	/// A `FormatBold` op changed the structure of the `contenteditable` element with the given
+	/// id, whose text is now in the given runs. The nodes the op created have no ids.
+	ContentFormatted(u64, Vec<TextRun>),
+	/// An animation frame ran at the given time in milliseconds, which the server subscribed to
+	/// with `SubscribeTicks`.
+	Tick(f64),
+	/// The ids a `SetTexts` op skipped, because they aren't text nodes in the document.
+	TextsNotSet(Vec<u64>),
+	/// A message of patches failed to apply, for the given reason. The patches before the failing
+	/// one were applied, unless it was in a transaction.
+	PatchFailed(PatchError, String),
+	/// The selection of the focused editing host moved, i.e. because of shift+arrow, and now goes
+	/// from an anchor to a focus, where the caret is, each a node and an offset in UTF-8 bytes
+	/// into its text. The node is a text node, or the host for text the server doesn't know
+	/// about. The changes made by the events handled together are sent at once.
+	SelectionChanged(u64, u32, u64, u32),
+	/// The mouse dwelt on an element with a title, given along with the id of the element, so
+	/// the server can render its own tooltip.
+	Tooltip(u64, String),
+	/// The given number of droppable messages, like `mousemove` events, were dropped because the
+	/// server didn't read the ones before them fast enough.
+	DroppedMessages(u32),
+	/// What the client supports, sent once on connect so the server can adapt to it.
+	ClientCapabilities(ClientCapabilities),
+}
+
+/// The answer to a query op.
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+	/// The value stored in the document state under the queried key.
+	State(Option<String>),
+	/// The queried `dataset` entry, if the element has it.
+	Dataset(Option<String>),
+	/// The qualified attribute names of the queried element, in insertion order.
+	AttributeNames(Vec<String>),
+	/// Whether the queried media query currently matches.
+	MediaMatch(bool),
+	/// The current `devicePixelRatio` of the window.
+	DevicePixelRatio(f64),
+	/// The scroll and client dimensions of the queried element, if it exists.
+	Geometry(Option<BoxGeometry>),
+	/// The ids of the nodes in the queried subtree, in tree order, or of the queried propagation
+	/// path, from the node to the document. Nodes created by the client, i.e. by editing, have
+	/// the id 0.
+	Ids(Vec<u64>),
+	/// The id of `document.activeElement`: the focused element, or the body when none is. The
+	/// id is 0 when there is no such element, or it was created by the client.
+	ActiveElement(u64),
+	/// The box model of the queried element, unless it doesn't exist or isn't rendered.
+	BoxModel(Option<BoxModel>),
+	/// How long a forced reflow took, in nanoseconds, and how many nodes were dirty before it.
+	ReflowTiming(u64, u32),
+	/// The pixels read back from the queried canvas, unless the rectangle doesn't overlap it.
+	CanvasPixels(Option<CanvasPixels>),
+	/// The metrics of `window.screen`.
+	Screen(ScreenMetrics),
+	/// The `name` of the window.
+	WindowName(String),
+	/// The names and values of the longhands of the queried inline style property, in the order
+	/// of the shorthand. Longhands that aren't set are left out.
+	Longhands(Vec<(String, String)>),
+	/// The resolved value of the queried property, unless the element doesn't exist or isn't in
+	/// the document, or the property is a custom one it doesn't have.
+	ResolvedStyle(Option<String>),
+	/// The event types the queried node has asked to be forwarded, in a fixed order, along with
+	/// whether each one waits for a verdict.
+	Subscriptions(Vec<(String, bool)>),
+	/// The `contentType` and `characterSet` of the document.
+	ContentType(String, String),
+	/// The errors the validated patches would fail with, in order; empty if they would apply.
+	PatchErrors(Vec<PatchError>),
+	/// The anchor and focus of the selection of the focused editing host, like those of
+	/// `SelectionChanged`, unless no editing host is focused.
+	Selection(Option<(u64, u32, u64, u32)>),
+	/// Whether the queried node is connected to the document.
+	Connection(Connection),
+}
+
+/// Whether a node the server has an id for is still in the document. Only the nodes in the
+/// document are registered by id, so there is no detached node the client still resolves.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Connection {
+	Connected,
+	/// The node was removed from the document, and may still be put back, i.e. by a `Revert`.
+	/// Only told apart from an unknown id while the server keeps tombstones.
+	Disconnected,
+	/// The id was never registered, or was removed long enough ago to be forgotten.
+	Unknown,
+}
+
+impl Connection {
+	/// The connection of a node from looking up its id.
+	pub fn of<T>(lookup: ::std::result::Result<T, LookupError>) -> Connection {
+		match lookup {
+			Ok(_) => Connection::Connected,
+			Err(LookupError::Removed) => Connection::Disconnected,
+			Err(LookupError::NotFound) => Connection::Unknown
+		}
+	}
+
+	fn to_wire(self) -> u8 {
+		match self {
+			Connection::Connected => 0,
+			Connection::Disconnected => 1,
+			Connection::Unknown => 2,
+		}
+	}
+
+	fn from_wire(connection: u8) -> Result<Connection> {
+		match connection {
+			0 => Ok(Connection::Connected),
+			1 => Ok(Connection::Disconnected),
+			2 => Ok(Connection::Unknown),
+			_ => Err(Error::new(ErrorKind::InvalidData, "unknown connection"))
+		}
+	}
+}
+
+/// The `scrollWidth`, `scrollHeight`, `clientWidth` and `clientHeight` of an element, in CSS
+/// pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoxGeometry {
+	pub scroll_width: i32,
+	pub scroll_height: i32,
+	pub client_width: i32,
+	pub client_height: i32,
+}
+
+impl BoxGeometry {
+	/// Combines the scroll area and client box layout reported. The scroll area only covers the
+	/// content, so it is never smaller than the client box: it is larger when the content
+	/// overflows, and the same size otherwise.
+	pub fn new(scroll_width: i32, scroll_height: i32, client_width: i32, client_height: i32) -> BoxGeometry {
+		BoxGeometry {
+			scroll_width: max(scroll_width, client_width),
+			scroll_height: max(scroll_height, client_height),
+			client_width: client_width,
+			client_height: client_height,
+		}
+	}
+
+	/// Whether the content is larger than the client box, i.e. the element can be scrolled.
+	pub fn overflows(&self) -> bool {
+		self.scroll_width > self.client_width || self.scroll_height > self.client_height
+	}
+
+	/// Clamps a `scrollTop` and `scrollLeft` to the range the element can be scrolled in, which
+	/// is empty if it doesn't overflow. Offsets that aren't finite are 0.
+	pub fn clamp_scroll(&self, top: f64, left: f64) -> (f64, f64) {
+		let clamp = |offset: f64, max: i32| {
+			if offset.is_finite() { offset.max(0.0).min(max as f64) } else { 0.0 }
+		};
+		(clamp(top, self.scroll_height - self.client_height), clamp(left, self.scroll_width - self.client_width))
+	}
+}
+
+/// A rectangle in CSS pixels, relative to the origin of the document, or in pixels of the bitmap
+/// of a canvas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoxRect {
+	pub x: i32,
+	pub y: i32,
+	pub width: i32,
+	pub height: i32,
+}
+
+impl BoxRect {
+	pub fn new(x: i32, y: i32, width: i32, height: i32) -> BoxRect {
+		BoxRect {
+			x: x,
+			y: y,
+			width: width,
+			height: height,
+		}
+	}
+
+	/// The rectangle shrunk by the edges, which can't make it smaller than empty.
+	pub fn inset(&self, edges: &BoxEdges) -> BoxRect {
+		BoxRect::new(self.x + edges.left,
+		             self.y + edges.top,
+		             max(self.width - edges.left - edges.right, 0),
+		             max(self.height - edges.top - edges.bottom, 0))
+	}
+
+	/// The rectangle grown by the edges.
+	pub fn outset(&self, edges: &BoxEdges) -> BoxRect {
+		BoxRect::new(self.x - edges.left,
+		             self.y - edges.top,
+		             self.width + edges.left + edges.right,
+		             self.height + edges.top + edges.bottom)
+	}
+
+	/// The part of the rectangle inside `bounds`. It is empty, but still inside `bounds`, if they
+	/// don't overlap.
+	pub fn intersection(&self, bounds: &BoxRect) -> BoxRect {
+		let x = min(max(self.x, bounds.x), bounds.x.saturating_add(bounds.width));
+		let y = min(max(self.y, bounds.y), bounds.y.saturating_add(bounds.height));
+		let right = min(self.x.saturating_add(self.width), bounds.x.saturating_add(bounds.width));
+		let bottom = min(self.y.saturating_add(self.height), bounds.y.saturating_add(bounds.height));
+		BoxRect::new(x, y, max(right - x, 0), max(bottom - y, 0))
+	}
+
+	pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+		try!(writer.put_i32(self.x));
+		try!(writer.put_i32(self.y));
+		try!(writer.put_i32(self.width));
+		writer.put_i32(self.height)
+	}
+
+	pub fn read_from<R: Read>(reader: &mut R) -> Result<BoxRect> {
+		Ok(BoxRect {
+			x: try!(reader.get_i32()),
+			y: try!(reader.get_i32()),
+			width: try!(reader.get_i32()),
+			height: try!(reader.get_i32()),
+		})
+	}
+}
+
+/// The widths of the four sides of a border, padding or margin, in CSS pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoxEdges {
+	pub top: i32,
+	pub right: i32,
+	pub bottom: i32,
+	pub left: i32,
+}
+
+impl BoxEdges {
+	pub fn new(top: i32, right: i32, bottom: i32, left: i32) -> BoxEdges {
+		BoxEdges {
+			top: top,
+			right: right,
+			bottom: bottom,
+			left: left,
+		}
+	}
+}
+
+/// The content, padding, border and margin boxes of an element, each nested in the next.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoxModel {
+	pub content: BoxRect,
+	pub padding: BoxRect,
+	pub border: BoxRect,
+	pub margin: BoxRect,
+}
+
+impl BoxModel {
+	/// Derives the boxes from the border box layout reported and the widths around it.
+	pub fn new(border_box: BoxRect, border: BoxEdges, padding: BoxEdges, margin: BoxEdges) -> BoxModel {
+		let padding_box = border_box.inset(&border);
+		BoxModel {
+			content: padding_box.inset(&padding),
+			padding: padding_box,
+			border: border_box,
+			margin: border_box.outset(&margin),
+		}
+	}
+}
+
+/// Pixels read back from a canvas: the rectangle of its bitmap they cover, and their RGBA values
+/// in rows, as in an `ImageData`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanvasPixels {
+	pub rect: BoxRect,
+	pub data: Vec<u8>,
+}
+
+/// A piece of the text of an editing host, and whether it is in a `b` element.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun {
+	pub text: String,
+	pub bold: bool,
+}
+
+/// The size of the screen in CSS pixels, the part of it pages may use, and its color depth in
+/// bits per pixel, as given by `window.screen`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScreenMetrics {
+	pub width: u32,
+	pub height: u32,
+	pub avail_width: u32,
+	pub avail_height: u32,
+	pub color_depth: u32,
+	pub pixel_depth: u32,
+}
+
+/// The protocol version, ops and limits of the client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientCapabilities {
+	pub protocol_version: u32,
+	/// The tags of the ops the client applies.
+	pub ops: Vec<u8>,
+	/// How deeply elements may be nested in a node payload.
+	pub max_node_depth: u32,
+	/// How many checkpoints are kept before the oldest is dropped.
+	pub max_checkpoints: u32,
+	/// How many messages are queued for the server before droppable ones are dropped.
+	pub queue_bound: u32,
+	/// The size in bytes of the largest message the client applies.
+	pub max_message_size: u32,
+}
+
+impl ClientCapabilities {
+	/// The capabilities of this client, with the limits it starts with.
+	pub fn new() -> ClientCapabilities {
+		ClientCapabilities {
+			protocol_version: PATCH_PROTOCOL_VERSION,
+			ops: supported_ops(),
+			max_node_depth: MAX_NODE_DEPTH as u32,
+			max_checkpoints: MAX_CHECKPOINTS as u32,
+			queue_bound: DEFAULT_QUEUE_BOUND as u32,
+			max_message_size: MAX_MESSAGE_SIZE as u32,
+		}
+	}
+}
+
+impl Reply {
+	fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+		match *self {
+			Reply::State(ref value) => {
+				try!(writer.put_u8(REPLY_STATE));
+				writer.put_opt_str(value.as_ref().map(|value| &**value))
+			},
+			Reply::Dataset(ref value) => {
+				try!(writer.put_u8(REPLY_DATASET));
+				writer.put_opt_str(value.as_ref().map(|value| &**value))
+			},
+			Reply::AttributeNames(ref names) => {
+				try!(writer.put_u8(REPLY_ATTRIBUTE_NAMES));
+				try!(writer.put_u32(names.len() as u32));
+				for name in names {
+					try!(writer.put_str(name));
+				}
+				Ok(())
+			},
+			Reply::MediaMatch(matches) => {
+				try!(writer.put_u8(REPLY_MEDIA_MATCH));
+				writer.put_bool(matches)
+			},
+			Reply::DevicePixelRatio(ratio) => {
+				try!(writer.put_u8(REPLY_DEVICE_PIXEL_RATIO));
+				writer.put_f64(ratio)
+			},
+			Reply::Geometry(geometry) => {
+				try!(writer.put_u8(REPLY_GEOMETRY));
+				match geometry {
+					Some(geometry) => {
+						try!(writer.put_bool(true));
+						try!(writer.put_i32(geometry.scroll_width));
+						try!(writer.put_i32(geometry.scroll_height));
+						try!(writer.put_i32(geometry.client_width));
+						writer.put_i32(geometry.client_height)
+					},
+					None => writer.put_bool(false)
+				}
+			},
+			Reply::Ids(ref ids) => {
+				try!(writer.put_u8(REPLY_IDS));
+				try!(writer.put_u32(ids.len() as u32));
+				for &id in ids {
+					try!(writer.put_u64(id));
+				}
+				Ok(())
+			},
+			Reply::ActiveElement(id) => {
+				try!(writer.put_u8(REPLY_ACTIVE_ELEMENT));
+				writer.put_u64(id)
+			},
+			Reply::BoxModel(box_model) => {
+				try!(writer.put_u8(REPLY_BOX_MODEL));
+				match box_model {
+					Some(box_model) => {
+						try!(writer.put_bool(true));
+						try!(box_model.content.write_to(writer));
+						try!(box_model.padding.write_to(writer));
+						try!(box_model.border.write_to(writer));
+						box_model.margin.write_to(writer)
+					},
+					None => writer.put_bool(false)
+				}
+			},
+			Reply::ReflowTiming(elapsed_ns, dirty_nodes) => {
+				try!(writer.put_u8(REPLY_REFLOW_TIMING));
+				try!(writer.put_u64(elapsed_ns));
+				writer.put_u32(dirty_nodes)
+			},
+			Reply::CanvasPixels(ref pixels) => {
+				try!(writer.put_u8(REPLY_CANVAS_PIXELS));
+				match *pixels {
+					Some(ref pixels) => {
+						try!(writer.put_bool(true));
+						try!(pixels.rect.write_to(writer));
+						writer.put_bytes(&pixels.data)
+					},
+					None => writer.put_bool(false)
+				}
+			},
+			Reply::Screen(screen) => {
+				try!(writer.put_u8(REPLY_SCREEN));
+				try!(writer.put_u32(screen.width));
+				try!(writer.put_u32(screen.height));
+				try!(writer.put_u32(screen.avail_width));
+				try!(writer.put_u32(screen.avail_height));
+				try!(writer.put_u32(screen.color_depth));
+				writer.put_u32(screen.pixel_depth)
+			},
+			Reply::WindowName(ref name) => {
+				try!(writer.put_u8(REPLY_WINDOW_NAME));
+				writer.put_str(name)
+			},
+			Reply::Longhands(ref longhands) => {
+				try!(writer.put_u8(REPLY_LONGHANDS));
+				try!(writer.put_u32(longhands.len() as u32));
+				for &(ref name, ref value) in longhands {
+					try!(writer.put_str(name));
+					try!(writer.put_str(value));
+				}
+				Ok(())
+			},
+			Reply::ResolvedStyle(ref value) => {
+				try!(writer.put_u8(REPLY_RESOLVED_STYLE));
+				writer.put_opt_str(value.as_ref().map(|value| &**value))
+			},
+			Reply::Subscriptions(ref subscriptions) => {
+				try!(writer.put_u8(REPLY_SUBSCRIPTIONS));
+				try!(writer.put_u32(subscriptions.len() as u32));
+				for &(ref name, vetoable) in subscriptions {
+					try!(writer.put_str(name));
+					try!(writer.put_bool(vetoable));
+				}
+				Ok(())
+			},
+			Reply::ContentType(ref content_type, ref charset) => {
+				try!(writer.put_u8(REPLY_CONTENT_TYPE));
+				try!(writer.put_str(content_type));
+				writer.put_str(charset)
+			},
+			Reply::PatchErrors(ref errors) => {
+				try!(writer.put_u8(REPLY_PATCH_ERRORS));
+				try!(writer.put_u32(errors.len() as u32));
+				for error in errors {
+					try!(writer.put_u8(error.to_wire()));
+				}
+				Ok(())
+			},
+			Reply::Selection(selection) => {
+				try!(writer.put_u8(REPLY_SELECTION));
+				match selection {
+					Some((anchor, anchor_offset, focus, focus_offset)) => {
+						try!(writer.put_bool(true));
+						try!(writer.put_u64(anchor));
+						try!(writer.put_u32(anchor_offset));
+						try!(writer.put_u64(focus));
+						writer.put_u32(focus_offset)
+					},
+					None => writer.put_bool(false)
+				}
+			},
+			Reply::Connection(connection) => {
+				try!(writer.put_u8(REPLY_CONNECTION));
+				writer.put_u8(connection.to_wire())
+			}
+		}
+	}
+
+	fn read_from<R: Read>(reader: &mut R) -> Result<Reply> {
+		match try!(reader.get_u8()) {
+			REPLY_STATE => reader.get_opt_string().map(Reply::State),
+			REPLY_DATASET => reader.get_opt_string().map(Reply::Dataset),
+			REPLY_ATTRIBUTE_NAMES => {
+				let len = try!(reader.get_u32());
+				let mut names = vec![];
+				for _ in 0..len {
+					names.push(try!(reader.get_string()));
+				}
+				Ok(Reply::AttributeNames(names))
+			},
+			REPLY_MEDIA_MATCH => reader.get_bool().map(Reply::MediaMatch),
+			REPLY_DEVICE_PIXEL_RATIO => reader.get_f64().map(Reply::DevicePixelRatio),
+			REPLY_GEOMETRY => {
+				if !try!(reader.get_bool()) {
+					return Ok(Reply::Geometry(None));
+				}
+				Ok(Reply::Geometry(Some(BoxGeometry {
+					scroll_width: try!(reader.get_i32()),
+					scroll_height: try!(reader.get_i32()),
+					client_width: try!(reader.get_i32()),
+					client_height: try!(reader.get_i32()),
+				})))
+			},
+			REPLY_IDS => {
+				let len = try!(reader.get_u32());
+				let mut ids = vec![];
+				for _ in 0..len {
+					ids.push(try!(reader.get_u64()));
+				}
+				Ok(Reply::Ids(ids))
+			},
+			REPLY_ACTIVE_ELEMENT => reader.get_u64().map(Reply::ActiveElement),
+			REPLY_BOX_MODEL => {
+				if !try!(reader.get_bool()) {
+					return Ok(Reply::BoxModel(None));
+				}
+				Ok(Reply::BoxModel(Some(BoxModel {
+					content: try!(BoxRect::read_from(reader)),
+					padding: try!(BoxRect::read_from(reader)),
+					border: try!(BoxRect::read_from(reader)),
+					margin: try!(BoxRect::read_from(reader)),
+				})))
+			},
+			REPLY_REFLOW_TIMING => {
+				let elapsed_ns = try!(reader.get_u64());
+				reader.get_u32().map(|dirty_nodes| Reply::ReflowTiming(elapsed_ns, dirty_nodes))
+			},
+			REPLY_CANVAS_PIXELS => {
+				if !try!(reader.get_bool()) {
+					return Ok(Reply::CanvasPixels(None));
+				}
+				Ok(Reply::CanvasPixels(Some(CanvasPixels {
+					rect: try!(BoxRect::read_from(reader)),
+					data: try!(reader.get_bytes()),
+				})))
+			},
+			REPLY_SCREEN => {
+				Ok(Reply::Screen(ScreenMetrics {
+					width: try!(reader.get_u32()),
+					height: try!(reader.get_u32()),
+					avail_width: try!(reader.get_u32()),
+					avail_height: try!(reader.get_u32()),
+					color_depth: try!(reader.get_u32()),
+					pixel_depth: try!(reader.get_u32()),
+				}))
+			},
+			REPLY_WINDOW_NAME => reader.get_string().map(Reply::WindowName),
+			REPLY_LONGHANDS => {
+				let len = try!(reader.get_u32());
+				let mut longhands = vec![];
+				for _ in 0..len {
+					let name = try!(reader.get_string());
+					longhands.push((name, try!(reader.get_string())));
+				}
+				Ok(Reply::Longhands(longhands))
+			},
+			REPLY_RESOLVED_STYLE => reader.get_opt_string().map(Reply::ResolvedStyle),
+			REPLY_SUBSCRIPTIONS => {
+				let len = try!(reader.get_u32());
+				let mut subscriptions = vec![];
+				for _ in 0..len {
+					let name = try!(reader.get_string());
+					subscriptions.push((name, try!(reader.get_bool())));
+				}
+				Ok(Reply::Subscriptions(subscriptions))
+			},
+			REPLY_CONTENT_TYPE => {
+				let content_type = try!(reader.get_string());
+				Ok(Reply::ContentType(content_type, try!(reader.get_string())))
+			},
+			REPLY_PATCH_ERRORS => {
+				let len = try!(reader.get_u32());
+				let mut errors = vec![];
+				for _ in 0..len {
+					errors.push(try!(PatchError::from_wire(try!(reader.get_u8()))));
+				}
+				Ok(Reply::PatchErrors(errors))
+			},
+			REPLY_SELECTION => {
+				if !try!(reader.get_bool()) {
+					return Ok(Reply::Selection(None));
+				}
+				let anchor = try!(reader.get_u64());
+				let anchor_offset = try!(reader.get_u32());
+				let focus = try!(reader.get_u64());
+				Ok(Reply::Selection(Some((anchor, anchor_offset, focus, try!(reader.get_u32())))))
+			},
+			REPLY_CONNECTION => Connection::from_wire(try!(reader.get_u8())).map(Reply::Connection),
+			_ => Err(Error::new(ErrorKind::InvalidData, "unknown reply"))
+		}
+	}
+}
+
+/// A forwarded DOM event.
+#[derive(Debug, PartialEq)]
+pub struct EventMsg {
+	/// The id of the subscribed node, which may be an ancestor of the target.
+	pub listener: u64,
+	/// The id of the node the event was dispatched to.
+	pub target: u64,
+	/// The event type, i.e. `click`.
+	pub kind: String,
+	/// Event type specific data.
+	pub detail: EventDetail,
+}
+
+/// The data carried by a forwarded event.
+#[derive(Debug, PartialEq)]
+pub enum EventDetail {
+	None,
+	Mouse {
+		client_x: i32,
+		client_y: i32,
+		button: i16,
+	},
+	Key {
+		key: String,
+		code: String,
+		modifiers: u8,
+	},
+	Wheel {
+		client_x: i32,
+		client_y: i32,
+		delta_x: f64,
+		delta_y: f64,
+		delta_z: f64,
+		delta_mode: u32,
+	},
+	Composition {
+		data: String,
+	},
+	Drag {
+		client_x: i32,
+		client_y: i32,
+		/// The drag data as `(format, data)` pairs.
+		items: Vec<(String, String)>,
+	},
+	Clipboard {
+		/// The selected text for `copy` and `cut`, the clipboard contents for `paste`.
+		data: String,
+	},
+	Focus {
+		/// Whether the focused element matches `:focus-visible`; always false for `blur`.
+		visible: bool,
+	},
+}
+
+impl EventMsg {
+	fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+		try!(writer.put_u64(self.listener));
+		try!(writer.put_u64(self.target));
+		try!(writer.put_str(&self.kind));
+		self.detail.write_to(writer)
+	}
+
+	fn read_from<R: Read>(reader: &mut R) -> Result<EventMsg> {
+		Ok(EventMsg {
+			listener: try!(reader.get_u64()),
+			target: try!(reader.get_u64()),
+			kind: try!(reader.get_string()),
+			detail: try!(EventDetail::read_from(reader)),
+		})
+	}
+}
+
+impl EventDetail {
+	fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+		match *self {
+			EventDetail::None => writer.put_u8(DETAIL_NONE),
+			EventDetail::Mouse { client_x, client_y, button } => {
+				try!(writer.put_u8(DETAIL_MOUSE));
+				try!(writer.put_i32(client_x));
+				try!(writer.put_i32(client_y));
+				writer.put_i32(button as i32)
+			},
+			EventDetail::Key { ref key, ref code, modifiers } => {
+				try!(writer.put_u8(DETAIL_KEY));
+				try!(writer.put_str(key));
+				try!(writer.put_str(code));
+				writer.put_u8(modifiers)
+			},
+			EventDetail::Wheel { client_x, client_y, delta_x, delta_y, delta_z, delta_mode } => {
+				try!(writer.put_u8(DETAIL_WHEEL));
+				try!(writer.put_i32(client_x));
+				try!(writer.put_i32(client_y));
+				try!(writer.put_f64(delta_x));
+				try!(writer.put_f64(delta_y));
+				try!(writer.put_f64(delta_z));
+				writer.put_u32(delta_mode)
+			},
+			EventDetail::Composition { ref data } => {
+				try!(writer.put_u8(DETAIL_COMPOSITION));
+				writer.put_str(data)
+			},
+			EventDetail::Drag { client_x, client_y, ref items } => {
+				try!(writer.put_u8(DETAIL_DRAG));
+				try!(writer.put_i32(client_x));
+				try!(writer.put_i32(client_y));
+				try!(writer.put_u32(items.len() as u32));
+				for &(ref format, ref data) in items {
+					try!(writer.put_str(format));
+					try!(writer.put_str(data));
+				}
+				Ok(())
+			},
+			EventDetail::Clipboard { ref data } => {
+				try!(writer.put_u8(DETAIL_CLIPBOARD));
+				writer.put_str(data)
+			},
+			EventDetail::Focus { visible } => {
+				try!(writer.put_u8(DETAIL_FOCUS));
+				writer.put_bool(visible)
+			}
+		}
+	}
+
+	fn read_from<R: Read>(reader: &mut R) -> Result<EventDetail> {
+		Ok(match try!(reader.get_u8()) {
+			DETAIL_NONE => EventDetail::None,
+			DETAIL_MOUSE => EventDetail::Mouse {
+				client_x: try!(reader.get_i32()),
+				client_y: try!(reader.get_i32()),
+				button: try!(reader.get_i32()) as i16,
+			},
+			DETAIL_KEY => EventDetail::Key {
+				key: try!(reader.get_string()),
+				code: try!(reader.get_string()),
+				modifiers: try!(reader.get_u8()),
+			},
+			DETAIL_WHEEL => EventDetail::Wheel {
+				client_x: try!(reader.get_i32()),
+				client_y: try!(reader.get_i32()),
+				delta_x: try!(reader.get_f64()),
+				delta_y: try!(reader.get_f64()),
+				delta_z: try!(reader.get_f64()),
+				delta_mode: try!(reader.get_u32()),
+			},
+			DETAIL_COMPOSITION => EventDetail::Composition {
+				data: try!(reader.get_string()),
+			},
+			DETAIL_DRAG => {
+				let client_x = try!(reader.get_i32());
+				let client_y = try!(reader.get_i32());
+				let len = try!(reader.get_u32());
+				let mut items = vec![];
+				for _ in 0..len {
+					let format = try!(reader.get_string());
+					items.push((format, try!(reader.get_string())));
+				}
+				EventDetail::Drag {
+					client_x: client_x,
+					client_y: client_y,
+					items: items,
+				}
+			},
+			DETAIL_CLIPBOARD => EventDetail::Clipboard {
+				data: try!(reader.get_string()),
+			},
+			DETAIL_FOCUS => EventDetail::Focus {
+				visible: try!(reader.get_bool()),
+			},
+			_ => return Err(Error::new(ErrorKind::InvalidData, "unknown event detail"))
+		})
+	}
+}
+
+impl ServerMsg {
+	/// Writes the message in the vdom wire format.
+	pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+		match *self {
+			ServerMsg::Event(ref event) => {
+				try!(writer.put_u8(MSG_EVENT));
+				event.write_to(writer)
+			},
+			ServerMsg::BlockingEvent(id, ref event) => {
+				try!(writer.put_u8(MSG_BLOCKING_EVENT));
+				try!(writer.put_u32(id));
+				event.write_to(writer)
+			},
+			ServerMsg::Reply(id, ref reply) => {
+				try!(writer.put_u8(MSG_REPLY));
+				try!(writer.put_u32(id));
+				reply.write_to(writer)
+			},
+			ServerMsg::MediaQueryChanged(ref query, matches) => {
+				try!(writer.put_u8(MSG_MEDIA_QUERY_CHANGED));
+				try!(writer.put_str(query));
+				writer.put_bool(matches)
+			},
+			ServerMsg::ColorSchemeChanged(color_scheme) => {
+				try!(writer.put_u8(MSG_COLOR_SCHEME_CHANGED));
+				writer.put_u8(color_scheme_to_wire(Some(color_scheme)))
+			},
+			ServerMsg::ContentEdited(id, ref text) => {
+				try!(writer.put_u8(MSG_CONTENT_EDITED));
+				try!(writer.put_u64(id));
+				writer.put_str(text)
+			},
+			ServerMsg::ActiveElementChanged(id) => {
+				try!(writer.put_u8(MSG_ACTIVE_ELEMENT_CHANGED));
+				writer.put_u64(id)
+			},
+			ServerMsg::PopState(ref url, ref state) => {
+				try!(writer.put_u8(MSG_POP_STATE));
+				try!(writer.put_str(url));
+				writer.put_opt_str(state.as_ref().map(|state| &**state))
+			},
+			ServerMsg::HashChanged(ref old_url, ref new_url) => {
+				try!(writer.put_u8(MSG_HASH_CHANGED));
+				try!(writer.put_str(old_url));
+				writer.put_str(new_url)
+			},
+			ServerMsg::FontLoaded(ref family, ref url) => {
+				try!(writer.put_u8(MSG_FONT_LOADED));
+				try!(writer.put_str(family));
+				writer.put_str(url)
+			},
+			ServerMsg::FontError(ref family, ref url) => {
+				try!(writer.put_u8(MSG_FONT_ERROR));
+				try!(writer.put_str(family));
+				writer.put_str(url)
+			},
+			ServerMsg::HoverChanged(id, hovered) => {
+				try!(writer.put_u8(MSG_HOVER_CHANGED));
+				try!(writer.put_u64(id));
+				writer.put_bool(hovered)
+			},
+			ServerMsg::VisibilityChanged(id, ratio) => {
+				try!(writer.put_u8(MSG_VISIBILITY_CHANGED));
+				try!(writer.put_u64(id));
+				writer.put_f64(ratio)
+			},
+			ServerMsg::ResizeObserved(id, width, height) => {
+				try!(writer.put_u8(MSG_RESIZE_OBSERVED));
+				try!(writer.put_u64(id));
+				try!(writer.put_i32(width));
+				writer.put_i32(height)
+			},
+			ServerMsg::ContentFormatted(id, ref runs) => {
+				try!(writer.put_u8(MSG_CONTENT_FORMATTED));
+				try!(writer.put_u64(id));
+				try!(writer.put_u32(runs.len() as u32));
+				for run in runs {
+					try!(writer.put_str(&run.text));
+					try!(writer.put_bool(run.bold));
+				}
+				Ok(())
+			},
+			ServerMsg::Tick(timestamp) => {
+				try!(writer.put_u8(MSG_TICK));
+				writer.put_f64(timestamp)
+			},
+			ServerMsg::TextsNotSet(ref ids) => {
+				try!(writer.put_u8(MSG_TEXTS_NOT_SET));
+				try!(writer.put_u32(ids.len() as u32));
+				for &id in ids {
+					try!(writer.put_u64(id));
+				}
+				Ok(())
+			},
+			ServerMsg::PatchFailed(error, ref description) => {
+				try!(writer.put_u8(MSG_PATCH_FAILED));
+				try!(writer.put_u8(error.to_wire()));
+				writer.put_str(description)
+			},
+			ServerMsg::SelectionChanged(anchor, anchor_offset, focus, focus_offset) => {
+				try!(writer.put_u8(MSG_SELECTION_CHANGED));
+				try!(writer.put_u64(anchor));
+				try!(writer.put_u32(anchor_offset));
+				try!(writer.put_u64(focus));
+				writer.put_u32(focus_offset)
+			},
+			ServerMsg::Tooltip(id, ref title) => {
+				try!(writer.put_u8(MSG_TOOLTIP));
+				try!(writer.put_u64(id));
+				writer.put_str(title)
+			},
+			ServerMsg::DroppedMessages(count) => {
+				try!(writer.put_u8(MSG_DROPPED_MESSAGES));
+				writer.put_u32(count)
+			},
+			ServerMsg::ClientCapabilities(ref capabilities) => {
+				try!(writer.put_u8(MSG_CLIENT_CAPABILITIES));
+				try!(writer.put_u32(capabilities.protocol_version));
+				try!(writer.put_bytes(&capabilities.ops));
+				try!(writer.put_u32(capabilities.max_node_depth));
+				try!(writer.put_u32(capabilities.max_checkpoints));
+				try!(writer.put_u32(capabilities.queue_bound));
+				writer.put_u32(capabilities.max_message_size)
+			}
+		}
+	}
+
+	/// Reads a message in the vdom wire format, as the server does.
+	pub fn read_from<R: Read>(reader: &mut R) -> Result<ServerMsg> {
+		match try!(reader.get_u8()) {
+			MSG_EVENT => EventMsg::read_from(reader).map(ServerMsg::Event),
+			MSG_BLOCKING_EVENT => {
+				let id = try!(reader.get_u32());
+				EventMsg::read_from(reader).map(|event| ServerMsg::BlockingEvent(id, event))
+			},
+			MSG_REPLY => {
+				let id = try!(reader.get_u32());
+				Reply::read_from(reader).map(|reply| ServerMsg::Reply(id, reply))
+			},
+			MSG_MEDIA_QUERY_CHANGED => {
+				let query = try!(reader.get_string());
+				reader.get_bool().map(|matches| ServerMsg::MediaQueryChanged(query, matches))
+			},
+			MSG_COLOR_SCHEME_CHANGED => {
+				match try!(color_scheme_from_wire(try!(reader.get_u8()))) {
+					Some(color_scheme) => Ok(ServerMsg::ColorSchemeChanged(color_scheme)),
+					None => Err(Error::new(ErrorKind::InvalidData, "missing color scheme"))
+				}
+			},
+			MSG_CONTENT_EDITED => {
+				let id = try!(reader.get_u64());
+				reader.get_string().map(|text| ServerMsg::ContentEdited(id, text))
+			},
+			MSG_ACTIVE_ELEMENT_CHANGED => reader.get_u64().map(ServerMsg::ActiveElementChanged),
+			MSG_POP_STATE => {
+				let url = try!(reader.get_string());
+				reader.get_opt_string().map(|state| ServerMsg::PopState(url, state))
+			},
+			MSG_HASH_CHANGED => {
+				let old_url = try!(reader.get_string());
+				reader.get_string().map(|new_url| ServerMsg::HashChanged(old_url, new_url))
+			},
+			MSG_FONT_LOADED => {
+				let family = try!(reader.get_string());
+				reader.get_string().map(|url| ServerMsg::FontLoaded(family, url))
+			},
+			MSG_FONT_ERROR => {
+				let family = try!(reader.get_string());
+				reader.get_string().map(|url| ServerMsg::FontError(family, url))
+			},
+			MSG_HOVER_CHANGED => {
+				let id = try!(reader.get_u64());
+				reader.get_bool().map(|hovered| ServerMsg::HoverChanged(id, hovered))
+			},
+			MSG_VISIBILITY_CHANGED => {
+				let id = try!(reader.get_u64());
+				reader.get_f64().map(|ratio| ServerMsg::VisibilityChanged(id, ratio))
+			},
+			MSG_RESIZE_OBSERVED => {
+				let id = try!(reader.get_u64());
+				let width = try!(reader.get_i32());
+				reader.get_i32().map(|height| ServerMsg::ResizeObserved(id, width, height))
+			},
+			MSG_CONTENT_FORMATTED => {
+				let id = try!(reader.get_u64());
+				let len = try!(reader.get_u32());
+				let mut runs = vec![];
+				for _ in 0..len {
+					runs.push(TextRun {
+						text: try!(reader.get_string()),
+						bold: try!(reader.get_bool()),
+					});
+				}
+				Ok(ServerMsg::ContentFormatted(id, runs))
+			},
+			MSG_TICK => reader.get_f64().map(ServerMsg::Tick),
+			MSG_TEXTS_NOT_SET => {
+				let len = try!(reader.get_u32());
+				let mut ids = vec![];
+				for _ in 0..len {
+					ids.push(try!(reader.get_u64()));
+				}
+				Ok(ServerMsg::TextsNotSet(ids))
+			},
+			MSG_PATCH_FAILED => {
+				let error = try!(PatchError::from_wire(try!(reader.get_u8())));
+				Ok(ServerMsg::PatchFailed(error, try!(reader.get_string())))
+			},
+			MSG_SELECTION_CHANGED => {
+				let anchor = try!(reader.get_u64());
+				let anchor_offset = try!(reader.get_u32());
+				let focus = try!(reader.get_u64());
+				Ok(ServerMsg::SelectionChanged(anchor, anchor_offset, focus, try!(reader.get_u32())))
+			},
+			MSG_TOOLTIP => {
+				let id = try!(reader.get_u64());
+				Ok(ServerMsg::Tooltip(id, try!(reader.get_string())))
+			},
+			MSG_DROPPED_MESSAGES => Ok(ServerMsg::DroppedMessages(try!(reader.get_u32()))),
+			MSG_CLIENT_CAPABILITIES => {
+				Ok(ServerMsg::ClientCapabilities(ClientCapabilities {
+					protocol_version: try!(reader.get_u32()),
+					ops: try!(reader.get_bytes()),
+					max_node_depth: try!(reader.get_u32()),
+					max_checkpoints: try!(reader.get_u32()),
+					queue_bound: try!(reader.get_u32()),
+					max_message_size: try!(reader.get_u32()),
+				}))
+			},
+			_ => Err(Error::new(ErrorKind::InvalidData, "unknown server message"))
+		}
+	}
+
+	/// Encodes the message into a buffer ready to be sent to the server.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut buf = vec![];
+		self.write_to(&mut buf).unwrap();
+		buf
+	}
+}
+
+/// The channel on which messages are delivered to the vdom server.
+#[derive(Clone)]
+pub struct ServerChan {
+	queue: Arc<(Mutex<OutgoingQueue>, Condvar)>,
+	verdicts: PendingVerdicts,
+}
+
+impl ServerChan {
+	/// A channel whose messages are sent on `sender` by a thread of its own, from a bounded
+	/// queue; see `vdom::outgoing`.
+	pub fn new(sender: IpcSender<Vec<u8>>, verdicts: PendingVerdicts) -> ServerChan {
+		let queue = Arc::new((Mutex::new(OutgoingQueue::new()), Condvar::new()));
+		let sending = queue.clone();
+		spawn_named("VdomSender".to_owned(), move || send_queued(sender, &sending));
+		ServerChan {
+			queue: queue,
+			verdicts: verdicts,
+		}
+	}
+
+	pub fn send(&self, msg: ServerMsg) {
+		if is_echo(&msg) && ScriptThread::suppressing_vdom_echoes() {
+			return;
+		}
+		let (ref queue, ref queued) = *self.queue;
+		queue.lock().unwrap().push(&msg);
+		queued.notify_one();
+	}
+
+	/// Bounds the queue of messages waiting to be sent to `bound`, dropping with `policy` beyond
+	/// it.
+	pub fn configure_queue(&self, bound: usize, policy: DropPolicy) {
+		self.queue.0.lock().unwrap().configure(bound, policy);
+	}
+
+	/// Sends a vetoable event and blocks until the server allows or prevents its default
+	/// action, using `chan` and `port` to receive the verdict. A suppressed echo is allowed
+	/// without being sent.
+	pub fn send_blocking(&self,
+	                     event: EventMsg,
+	                     chan: Box<ScriptChan + Send>,
+	                     port: Box<ScriptPort + Send>) -> Verdict {
+		if ScriptThread::suppressing_vdom_echoes() {
+			return Verdict::Allow;
+		}
+		let id = self.verdicts.register(chan.clone());
+		self.send(ServerMsg::BlockingEvent(id, event));
+		self.verdicts.wait(id, chan, port)
+	}
+}
+
+/// Sends the messages of a queue as they are queued, blocking while the server isn't reading.
+fn send_queued(sender: IpcSender<Vec<u8>>, queue: &(Mutex<OutgoingQueue>, Condvar)) {
+	let (ref queue, ref queued) = *queue;
+	loop {
+		let msg = {
+			let mut waiting = queue.lock().unwrap();
+			let mut msg = waiting.pop();
+			while msg.is_none() {
+				waiting = queued.wait(waiting).unwrap();
+				msg = waiting.pop();
+			}
+			msg.unwrap()
+		};
+		if sender.send(msg).is_err() {
+			warn!("vdom server channel closed, dropping message");
+		}
+	}
+}
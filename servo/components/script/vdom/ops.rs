@@ -0,0 +1,1981 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Operations the vdom server can send after the patch list of a message, for client state
+//! that isn't part of the node tree. The section runs until the end of the message.
+
+use app_units::Au;
+use canvas_traits::{Canvas2dMsg, CanvasMsg};
+use clipboard_provider::ClipboardProvider;
+use dom::bindings::inheritance::Castable;
+use dom::bindings::js::{Root, RootedReference};
+use dom::characterdata::CharacterData;
+use dom::document::Document;
+use dom::documentfragment::DocumentFragment;
+use dom::element::{Element, dataset_attr_name};
+use dom::htmlcanvaselement::HTMLCanvasElement;
+use dom::node::{Node, NodeDamage};
+use dom::text::Text;
+use dom::window::ReflowReason;
+use editing::selection_offset;
+use encoding::label::encoding_from_whatwg_label;
+use euclid::point::Point2D;
+use euclid::rect::Rect;
+use euclid::side_offsets::SideOffsets2D;
+use euclid::size::Size2D;
+use hyper::mime::{Attr, Mime};
+use ipc_channel::ipc::IpcSender;
+use layout_interface::ReflowQueryType;
+use script_thread::ScriptThread;
+use std::ascii::AsciiExt;
+use std::io::{Cursor, Read, Result, Write};
+use string_cache::Atom;
+use style::context::ReflowGoal;
+use style::properties::Shorthand;
+use style_traits::color_scheme::ColorScheme;
+use time;
+use url::Url;
+use util::str::DOMString;
+use vdom::error::PatchError;
+use vdom::events::{Delivery, EventSet};
+use vdom::format;
+use vdom::media::{color_scheme_from_wire, color_scheme_to_wire};
+use vdom::moves::Arrangement;
+use vdom::{apply_patches, check_mutable, check_mutation, read_attrs_into, read_node, remove_node,
+           set_inline_style, validate_patch};
+use vdom::undo::UndoLog;
+use vdom::msg::{BoxEdges, BoxGeometry, BoxModel, BoxRect, CanvasPixels, Connection, Reply, ScreenMetrics};
+use vdom::msg::ServerMsg;
+use vdom::outgoing::DropPolicy;
+use vdom::tombstones::LookupError;
+use vdom::wire::{ReadWire, WriteWire};
+
+/// The version of the patch protocol, which the server is told on connect.
+pub const PATCH_PROTOCOL_VERSION: u32 = 1;
+
+const OP_SET_CLIPBOARD: u8 = 1;
+const OP_SET_VETOABLE: u8 = 2;
+const OP_SET_STATE: u8 = 3;
+const OP_GET_STATE: u8 = 4;
+const OP_SET_DATASET: u8 = 5;
+const OP_GET_DATASET: u8 = 6;
+const OP_GET_ATTRIBUTE_NAMES: u8 = 7;
+const OP_CLEAR_CHILDREN: u8 = 8;
+const OP_REPLACE_CHILDREN: u8 = 9;
+const OP_TRANSACTION: u8 = 10;
+const OP_CHECKPOINT: u8 = 11;
+const OP_REVERT: u8 = 12;
+const OP_MATCH_MEDIA: u8 = 13;
+const OP_GET_DEVICE_PIXEL_RATIO: u8 = 14;
+const OP_SET_COLOR_SCHEME: u8 = 15;
+const OP_GET_GEOMETRY: u8 = 16;
+const OP_SET_TOMBSTONES: u8 = 17;
+const OP_APPEND_TEXT: u8 = 18;
+const OP_SET_DOCUMENT_LANG: u8 = 19;
+const OP_SET_DOCUMENT_DIR: u8 = 20;
+const OP_SWAP_NODES: u8 = 21;
+const OP_ENUMERATE_IDS: u8 = 22;
+const OP_GET_ACTIVE_ELEMENT: u8 = 23;
+const OP_QUERY_BOX_MODEL: u8 = 24;
+const OP_FORCE_REFLOW: u8 = 25;
+const OP_SET_FROZEN: u8 = 26;
+const OP_SET_ELEMENT_POOL: u8 = 27;
+const OP_MOVE_NODE: u8 = 28;
+const OP_SET_HTML_ID: u8 = 29;
+const OP_CANVAS_BLIT: u8 = 30;
+const OP_CANVAS_READBACK: u8 = 31;
+const OP_GET_SCREEN: u8 = 32;
+const OP_SET_WINDOW_NAME: u8 = 33;
+const OP_GET_WINDOW_NAME: u8 = 34;
+const OP_PUSH_STATE: u8 = 35;
+const OP_REPLACE_STATE: u8 = 36;
+const OP_POP_STATE: u8 = 37;
+const OP_PRELOAD_FONT: u8 = 38;
+const OP_OBSERVE_VISIBILITY: u8 = 39;
+const OP_OBSERVE_RESIZE: u8 = 40;
+const OP_SET_DESIGN_MODE: u8 = 41;
+const OP_FORMAT_BOLD: u8 = 42;
+const OP_SUBSCRIBE_TICKS: u8 = 43;
+const OP_GET_INLINE_LONGHANDS: u8 = 44;
+const OP_REORDER_STYLESHEETS: u8 = 45;
+const OP_SET_CUSTOM_PROPERTY: u8 = 46;
+const OP_GET_RESOLVED_STYLE: u8 = 47;
+const OP_CREATE_CHILDREN: u8 = 48;
+const OP_PATCH_CHILDREN: u8 = 49;
+const OP_SET_TEXTS: u8 = 50;
+const OP_GET_SUBSCRIPTIONS: u8 = 51;
+const OP_REPLACE_DOCUMENT_ELEMENT: u8 = 52;
+const OP_SET_CONTENT_TYPE: u8 = 53;
+const OP_GET_CONTENT_TYPE: u8 = 54;
+const OP_UPGRADE_TEXT_TO_ELEMENT: u8 = 55;
+const OP_VALIDATE_PATCHES: u8 = 56;
+const OP_SET_SELECTION: u8 = 57;
+const OP_GET_SELECTION: u8 = 58;
+const OP_INSERT_AT: u8 = 59;
+const OP_SET_EVENT_DELIVERY: u8 = 60;
+const OP_SET_TITLE: u8 = 61;
+const OP_SET_OUTGOING_QUEUE: u8 = 62;
+const OP_SET_PASSIVE: u8 = 63;
+const OP_SET_DRAG_DATA: u8 = 64;
+const OP_BEGIN_SUPPRESS: u8 = 65;
+const OP_END_SUPPRESS: u8 = 66;
+const OP_QUERY_PROPAGATION_PATH: u8 = 67;
+const OP_SET_MOVE_COALESCING: u8 = 68;
+const OP_SET_ELEMENT_SCROLL: u8 = 69;
+const OP_IS_CONNECTED: u8 = 70;
+const OP_DIFF_ATTRIBUTES: u8 = 71;
+const OP_INVALIDATE_LAYOUT: u8 = 72;
+
+const CHILD_KEEP: u8 = 0;
+const CHILD_CREATE: u8 = 1;
+
+/// What a `PatchChildren` op puts at a position among the children of a node.
+#[derive(Debug, PartialEq)]
+pub enum ChildSpec {
+	/// Keeps the existing child at this position, which preserves its state, i.e. focus or the
+	/// caret of an editing host. The attributes of a kept element can be modified with a payload
+	/// in the encoding of a `ModifyAttrs` patch, and the data of a kept text node replaced.
+	Keep(Option<Vec<u8>>, Option<String>),
+	/// Puts a new node, in the encoding of an `Append` patch, in place of the existing child at
+	/// this position, if there is one.
+	Create(Vec<u8>),
+}
+
+impl ChildSpec {
+	fn read_from<R: Read>(reader: &mut R) -> Result<ChildSpec> {
+		match try!(reader.get_u8()) {
+			CHILD_KEEP => {
+				let attrs = if try!(reader.get_bool()) { Some(try!(reader.get_bytes())) } else { None };
+				Ok(ChildSpec::Keep(attrs, try!(reader.get_opt_string())))
+			},
+			CHILD_CREATE => reader.get_bytes().map(ChildSpec::Create),
+			_ => Err(PatchError::Malformed.error("unknown child spec"))
+		}
+	}
+
+	fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+		match *self {
+			ChildSpec::Keep(ref attrs, ref text) => {
+				try!(writer.put_u8(CHILD_KEEP));
+				try!(writer.put_bool(attrs.is_some()));
+				if let Some(ref attrs) = *attrs {
+					try!(writer.put_bytes(attrs));
+				}
+				writer.put_opt_str(text.as_ref().map(|text| &**text))
+			},
+			ChildSpec::Create(ref node) => {
+				try!(writer.put_u8(CHILD_CREATE));
+				writer.put_bytes(node)
+			}
+		}
+	}
+}
+
+/// An operation sent by the vdom server.
+#[derive(Debug, PartialEq)]
+pub enum PatchOp {
+	/// Replaces the clipboard contents, i.e. to override what a forwarded `copy` put there.
+	SetClipboard(String),
+	/// Sets which of a node's subscribed events wait for a verdict before their default action.
+	SetVetoable(u64, EventSet),
+	/// Stores a value in the document state, or clears it if there is none.
+	SetState(String, Option<String>),
+	/// Replies with the value stored in the document state under a key.
+	GetState(String, u32),
+	/// Sets a `dataset` entry of an element, i.e. `userId` for its `data-user-id` attribute.
+	SetDataset(u64, String, String),
+	/// Replies with a `dataset` entry of an element.
+	GetDataset(u64, String, u32),
+	/// Replies with the qualified names of an element's attributes, in insertion order.
+	GetAttributeNames(u64, u32),
+	/// Removes all the children of a node at once.
+	ClearChildren(u64),
+	/// Replaces all the children of a node at once with the nodes in the payload, which uses the
+	/// encoding of an `AppendMultiple` patch.
+	ReplaceChildren(u64, Vec<u8>),
+	/// Applies a nested message, patches and operations, as a whole: if any part of it fails,
+	/// the DOM mutations already made by the others are rolled back.
+	Transaction(Vec<u8>),
+	/// Creates a named checkpoint of the DOM, which the server can later revert to.
+	Checkpoint(String),
+	/// Reverts the DOM to a named checkpoint, undoing every mutation made since.
+	Revert(String),
+	/// Replies with whether a media query matches, and watches it for changes from then on.
+	MatchMedia(String, u32),
+	/// Replies with the window's `devicePixelRatio`.
+	GetDevicePixelRatio(u32),
+	/// Sets the color scheme `prefers-color-scheme` matches in the document, or defers to the
+	/// platform's preference again.
+	SetColorScheme(Option<ColorScheme>),
+	/// Replies with the scroll and client dimensions of an element, which forces a reflow.
+	GetGeometry(u64, u32),
+	/// Starts or stops remembering removed node ids for a while, so that patches targeting them
+	/// fail with a distinct error.
+	SetTombstones(bool),
+	/// Appends to the data of a text or comment node, i.e. for a streamed log, without sending
+	/// what it already has.
+	AppendText(u64, String),
+	/// Sets the `lang` attribute of the document element, which its descendants inherit.
+	SetDocumentLang(String),
+	/// Sets the `dir` attribute of the document element, i.e. `rtl`.
+	SetDocumentDir(String),
+	/// Exchanges the positions of two nodes in the tree, i.e. for an animated list swap. The
+	/// nodes are moved, not recreated, and neither may contain the other.
+	SwapNodes(u64, u64),
+	/// Replies with the ids of a node and all its descendants in tree order, so the server can
+	/// reconcile its id map after changes it didn't make.
+	EnumerateIds(u64, u32),
+	/// Replies with the id of `document.activeElement`.
+	GetActiveElement(u32),
+	/// Replies with the content, padding, border and margin boxes of an element, for debugging
+	/// layout. This forces a reflow.
+	QueryBoxModel(u64, u32),
+	/// Reflows the document right away, even if nothing changed, and replies with how long it
+	/// took and how many nodes were dirty, so the server can measure the layout cost of a batch.
+	ForceReflow(u32),
+	/// Freezes or unfreezes the subtree rooted at a node, i.e. for large static content. Patches
+	/// and operations mutating a frozen subtree fail until it is unfrozen; only the node it was
+	/// frozen at can unfreeze it. Freezing doesn't change how the subtree is traced or kept alive.
+	SetFrozen(u64, bool),
+	/// Starts or stops keeping the elements removed by later patches for reuse by the elements
+	/// they create. Only removals that can't be undone feed the pool.
+	SetElementPool(bool),
+	/// Moves a node to a new parent, before one of its children or at the end. The node keeps
+	/// its subscriptions, and focus if it has it. Consecutive moves of a message are applied in
+	/// order as a batch, which fails before mutating the DOM if a move is invalid once the
+	/// earlier ones are made, i.e. before a node that was moved away.
+	MoveNode(u64, u64, Option<u64>),
+	/// Sets the HTML `id` attribute of an element, which `getElementById` finds it by, or
+	/// removes it. This is unrelated to the id patches refer to the element by.
+	SetHtmlId(u64, Option<String>),
+	/// Draws an image, given as its width, height and RGBA pixels in rows, at the origin of a
+	/// canvas. The pixels past the edges of the canvas are dropped. The drawing can't be undone.
+	CanvasBlit(u64, u32, u32, Vec<u8>),
+	/// Replies with the pixels of a canvas in a rectangle of its bitmap, i.e. to snapshot what
+	/// was drawn into it. The rectangle is clamped to the bitmap, and there are no pixels if it
+	/// doesn't overlap it.
+	CanvasReadback(u64, BoxRect, u32),
+	/// Replies with the metrics of `window.screen`, i.e. so the server can adapt its layout.
+	GetScreen(u32),
+	/// Sets the `name` of the window, which navigations may target it by. The name belongs to
+	/// the window, so it outlives the patches and can't be undone.
+	SetWindowName(String),
+	/// Replies with the `name` of the window.
+	GetWindowName(u32),
+	/// Adds a session history entry with a URL, resolved against the document's, and a state,
+	/// and gives the document that URL without a navigation. The URL must have the origin of
+	/// the document.
+	PushState(String, Option<String>),
+	/// Like `PushState`, but replaces the current session history entry.
+	ReplaceState(String, Option<String>),
+	/// Goes back to the previous session history entry, if there is one, in a history traversal
+	/// task. This restores its URL, fires `popstate` at the window and sends `PopState`.
+	PopState,
+	/// Loads a web font from a URL, resolved against the document's, into a font family, as an
+	/// `@font-face` rule would, so that text can use it without first rendering with a fallback.
+	/// The server is sent `FontLoaded` or `FontError` once the load finishes.
+	PreloadFont(String, String),
+	/// Watches the fraction of a node inside the viewport against thresholds between 0 and 1,
+	/// sending `VisibilityChanged` whenever it crosses one of them, and once to begin with. No
+	/// thresholds stop watching the node.
+	ObserveVisibility(u64, Vec<f64>),
+	/// Starts or stops watching the size of the content box of a node, sending `ResizeObserved`
+	/// after every reflow that changed it, and once to begin with.
+	ObserveResize(u64, bool),
+	/// Turns the `designMode` of the document on or off. In design mode, the body is the editing
+	/// host of the whole document, and edits to it are reported with `ContentEdited` like those
+	/// to `contenteditable` elements. The mode belongs to the document, so it can't be undone.
+	SetDesignMode(bool),
+	/// Makes a range of the text of an editing host bold, by wrapping it in `b` elements, or not
+	/// bold, by unwrapping the ones it is in, then sends `ContentFormatted`. The range is in
+	/// UTF-8 bytes of the text of the host, like its caret.
+	FormatBold(u64, u32, u32, bool),
+	/// Subscribes to animation frames, which are sent as `Tick`s at most once per the given
+	/// number of milliseconds, or unsubscribes from them.
+	SubscribeTicks(Option<u32>),
+	/// Replies with the longhands a property of the inline style of an element expands to, i.e.
+	/// the four `margin-*` ones for `margin`, so the server can reconcile its model after setting
+	/// a shorthand.
+	GetInlineLonghands(u64, String, u32),
+	/// Puts the stylesheets of the `style` and `link` elements with the given ids in that
+	/// cascade order, so later ones win ties between equally specific rules. The sheets of other
+	/// elements keep their place in tree order.
+	ReorderStylesheets(Vec<u64>),
+	/// Sets a custom property, i.e. `--accent`, in the inline style of an element, or removes it
+	/// if there is no value. Descendants inherit it, and can use it with `var()`.
+	SetCustomProperty(u64, String, Option<String>),
+	/// Replies with the resolved value of a property of an element, as `getComputedStyle` would
+	/// give it, which forces a reflow. Custom properties resolve to the value they inherit, and
+	/// the `var()` substitutions of other properties to their fallback if the variable isn't set.
+	GetResolvedStyle(u64, String, u32),
+	/// Appends the nodes in the payload, which uses the encoding of an `AppendMultiple` patch, to
+	/// a node at once, i.e. for the first render of a long list, then replies with their ids in
+	/// order. The nodes are built in a fragment, so the document is only restyled for the whole
+	/// batch, in the reflow after the message.
+	CreateChildren(u64, Vec<u8>, u32),
+	/// Diffs the children of a node by position, without keys: the child at each position is
+	/// kept or replaced as its spec says, new children are appended past the existing ones, and
+	/// the existing children past the last spec are removed.
+	PatchChildren(u64, Vec<ChildSpec>),
+	/// Sets the data of many text nodes at once, i.e. the cells of a table, which are reflowed
+	/// together after the message. Ids that aren't text nodes in the document are skipped, and
+	/// sent back with `TextsNotSet`.
+	SetTexts(Vec<(u64, String)>),
+	/// Replies with the event types a node has asked to be forwarded, and whether each waits
+	/// for a verdict, i.e. to debug event delegation.
+	GetSubscriptions(u64, u32),
+	/// Replaces the document element with the element in the payload, which uses the encoding
+	/// of an `Append` patch, for a full re-render. The document and window are kept, but the ids
+	/// of the old tree are forgotten; `readystatechange` and `load` fire again once the document
+	/// is complete.
+	ReplaceDocumentElement(Vec<u8>),
+	/// Declares the content type of the document, i.e. `text/html; charset=shift_jis`, which
+	/// sets its `contentType` and, if there is a `charset` parameter, its `characterSet`. Strings
+	/// on the wire stay UTF-8 whatever the charset is.
+	SetContentType(String),
+	/// Asks for the content type and character set of the document.
+	GetContentType(u32),
+	/// Replaces a text node with the element in the payload, which uses the encoding of an
+	/// `Append` patch, i.e. to hydrate a placeholder. The element takes over the id of the text
+	/// node, whatever id the payload gives it.
+	UpgradeTextToElement(u64, Vec<u8>),
+	/// Asks for the errors the patches and ops in the payload, encoded like a patch message,
+	/// would fail with against the current document, without applying them.
+	ValidatePatches(Vec<u8>, u32),
+	/// Selects text of an editing host from an anchor to a focus, each a node and an offset in
+	/// UTF-8 bytes into its text, and moves the caret to the focus, i.e. to follow a cursor of
+	/// another user. The nodes are text nodes of the host or the host itself; offsets past their
+	/// text are clamped to it. The host is focused if it isn't, and the server is sent
+	/// `SelectionChanged` once the message is applied.
+	SetSelection(u64, u32, u64, u32),
+	/// Asks where the selection of the focused editing host is.
+	GetSelection(u32),
+	/// Inserts the node in the payload, which uses the encoding of an `Append` patch, as the
+	/// child of a node at an index, so that the server needn't know the id of its next sibling.
+	/// Indices past the last child append the node.
+	InsertAt(u64, u32, Vec<u8>),
+	/// Sets how the events of the given types are delivered to the server, for every node.
+	SetEventDelivery(EventSet, Delivery),
+	/// Sets the `title` attribute of an element, or removes it if there is none. The server is
+	/// sent a `Tooltip` when the mouse dwells on an element with a title.
+	SetTitle(u64, Option<String>),
+	/// Bounds the queue of messages waiting to be sent to the server to a number of messages,
+	/// dropping droppable ones beyond it with a policy. The server is sent `DroppedMessages` once
+	/// some were dropped.
+	SetOutgoingQueue(u32, DropPolicy),
+	/// Sets which of a node's subscribed `touchstart`, `touchmove` and `wheel` events it listens
+	/// to passively: they are forwarded without blocking, and the compositor scrolls without
+	/// waiting to know whether they were prevented. Other events are ignored.
+	SetPassive(u64, EventSet),
+	/// Primes a draggable element with data in a MIME type, replacing the data it had in that
+	/// type. A drag starting from the element carries the data of every type it was primed
+	/// with, which the `dragstart` event forwarded to the server includes.
+	SetDragData(u64, String, String),
+	/// Starts a region of ops whose echoes aren't sent to the server: the events they fire and
+	/// the focus, hover, selection and content changes they cause. Regions nest, and one left
+	/// open is closed at the end of the message of patches.
+	BeginSuppress,
+	/// Ends the innermost region a `BeginSuppress` started. Fails outside of one.
+	EndSuppress,
+	/// Replies with the ids of the nodes an event dispatched to a node propagates through, from
+	/// the node up to the document, so the server can delegate events the way dispatch does.
+	QueryPropagationPath(u64, u32),
+	/// Coalesces the `mousemove` events forwarded to the server, so that it is sent at most one
+	/// per listener, carrying the latest position, for each batch of events handled together
+	/// and per interval in milliseconds unless it is 0. `None` forwards every move again.
+	SetMoveCoalescing(Option<u32>),
+	/// Scrolls an overflow container to a `scrollTop` and `scrollLeft` in CSS pixels, clamped to
+	/// the range it can be scrolled in. Elements that don't overflow stay where they are.
+	SetElementScroll(u64, f64, f64),
+	/// Replies with whether a node is still connected to the document, so a server can tell
+	/// whether an optimistic update of it still applies. Removed nodes are disconnected while
+	/// the server keeps tombstones, and unknown otherwise.
+	IsConnected(u64, u32),
+	/// Applies the diff of the attribute map of an element: sets the attributes of the first
+	/// list to their values, then removes those of the second. Attributes that already have the
+	/// value, and removed ones the element doesn't have, aren't touched.
+	DiffAttributes(u64, Vec<(String, String)>, Vec<String>),
+	/// Marks the subtree of a node dirty, for the reflow after the patches to lay it out again
+	/// although the server changed it out of band, i.e. by drawing to a canvas. The rest of the
+	/// document stays clean.
+	InvalidateLayout(u64),
+}
+
+impl PatchOp {
+	/// Reads the next operation, returning `None` at the end of the message.
+	pub fn read_from<R: Read>(reader: &mut R) -> Result<Option<PatchOp>> {
+		let tag = match try!(reader.get_tag()) {
+			Some(tag) => tag,
+			None => return Ok(None)
+		};
+		Ok(Some(match tag {
+			OP_SET_CLIPBOARD => PatchOp::SetClipboard(try!(reader.get_string())),
+			OP_SET_VETOABLE => {
+				let id = try!(reader.get_u64());
+				PatchOp::SetVetoable(id, EventSet::from_bits_truncate(try!(reader.get_u32())))
+			},
+			OP_SET_STATE => {
+				let key = try!(reader.get_string());
+				PatchOp::SetState(key, try!(reader.get_opt_string()))
+			},
+			OP_GET_STATE => {
+				let key = try!(reader.get_string());
+				PatchOp::GetState(key, try!(reader.get_u32()))
+			},
+			OP_SET_DATASET => {
+				let id = try!(reader.get_u64());
+				let key = try!(reader.get_string());
+				PatchOp::SetDataset(id, key, try!(reader.get_string()))
+			},
+			OP_GET_DATASET => {
+				let id = try!(reader.get_u64());
+				let key = try!(reader.get_string());
+				PatchOp::GetDataset(id, key, try!(reader.get_u32()))
+			},
+			OP_GET_ATTRIBUTE_NAMES => {
+				let id = try!(reader.get_u64());
+				PatchOp::GetAttributeNames(id, try!(reader.get_u32()))
+			},
+			OP_CLEAR_CHILDREN => PatchOp::ClearChildren(try!(reader.get_u64())),
+			OP_REPLACE_CHILDREN => {
+				let id = try!(reader.get_u64());
+				PatchOp::ReplaceChildren(id, try!(reader.get_bytes()))
+			},
+			OP_TRANSACTION => PatchOp::Transaction(try!(reader.get_bytes())),
+			OP_CHECKPOINT => PatchOp::Checkpoint(try!(reader.get_string())),
+			OP_REVERT => PatchOp::Revert(try!(reader.get_string())),
+			OP_MATCH_MEDIA => {
+				let query = try!(reader.get_string());
+				PatchOp::MatchMedia(query, try!(reader.get_u32()))
+			},
+			OP_GET_DEVICE_PIXEL_RATIO => PatchOp::GetDevicePixelRatio(try!(reader.get_u32())),
+			OP_SET_COLOR_SCHEME => PatchOp::SetColorScheme(try!(color_scheme_from_wire(try!(reader.get_u8())))),
+			OP_GET_GEOMETRY => {
+				let id = try!(reader.get_u64());
+				PatchOp::GetGeometry(id, try!(reader.get_u32()))
+			},
+			OP_SET_TOMBSTONES => PatchOp::SetTombstones(try!(reader.get_bool())),
+			OP_APPEND_TEXT => {
+				let id = try!(reader.get_u64());
+				PatchOp::AppendText(id, try!(reader.get_string()))
+			},
+			OP_SET_DOCUMENT_LANG => PatchOp::SetDocumentLang(try!(reader.get_string())),
+			OP_SET_DOCUMENT_DIR => PatchOp::SetDocumentDir(try!(reader.get_string())),
+			OP_SWAP_NODES => {
+				let a = try!(reader.get_u64());
+				PatchOp::SwapNodes(a, try!(reader.get_u64()))
+			},
+			OP_ENUMERATE_IDS => {
+				let id = try!(reader.get_u64());
+				PatchOp::EnumerateIds(id, try!(reader.get_u32()))
+			},
+			OP_GET_ACTIVE_ELEMENT => PatchOp::GetActiveElement(try!(reader.get_u32())),
+			OP_QUERY_BOX_MODEL => {
+				let id = try!(reader.get_u64());
+				PatchOp::QueryBoxModel(id, try!(reader.get_u32()))
+			},
+			OP_FORCE_REFLOW => PatchOp::ForceReflow(try!(reader.get_u32())),
+			OP_SET_FROZEN => {
+				let id = try!(reader.get_u64());
+				PatchOp::SetFrozen(id, try!(reader.get_bool()))
+			},
+			OP_SET_ELEMENT_POOL => PatchOp::SetElementPool(try!(reader.get_bool())),
+			OP_MOVE_NODE => {
+				let id = try!(reader.get_u64());
+				let parent = try!(reader.get_u64());
+				let before = if try!(reader.get_bool()) { Some(try!(reader.get_u64())) } else { None };
+				PatchOp::MoveNode(id, parent, before)
+			},
+			OP_SET_HTML_ID => {
+				let id = try!(reader.get_u64());
+				PatchOp::SetHtmlId(id, try!(reader.get_opt_string()))
+			},
+			OP_CANVAS_BLIT => {
+				let id = try!(reader.get_u64());
+				let width = try!(reader.get_u32());
+				let height = try!(reader.get_u32());
+				PatchOp::CanvasBlit(id, width, height, try!(reader.get_bytes()))
+			},
+			OP_CANVAS_READBACK => {
+				let id = try!(reader.get_u64());
+				let rect = try!(BoxRect::read_from(reader));
+				PatchOp::CanvasReadback(id, rect, try!(reader.get_u32()))
+			},
+			OP_GET_SCREEN => PatchOp::GetScreen(try!(reader.get_u32())),
+			OP_SET_WINDOW_NAME => PatchOp::SetWindowName(try!(reader.get_string())),
+			OP_GET_WINDOW_NAME => PatchOp::GetWindowName(try!(reader.get_u32())),
+			OP_PUSH_STATE => {
+				let url = try!(reader.get_string());
+				PatchOp::PushState(url, try!(reader.get_opt_string()))
+			},
+			OP_REPLACE_STATE => {
+				let url = try!(reader.get_string());
+				PatchOp::ReplaceState(url, try!(reader.get_opt_string()))
+			},
+			OP_POP_STATE => PatchOp::PopState,
+			OP_PRELOAD_FONT => {
+				let family = try!(reader.get_string());
+				PatchOp::PreloadFont(family, try!(reader.get_string()))
+			},
+			OP_OBSERVE_VISIBILITY => {
+				let id = try!(reader.get_u64());
+				let len = try!(reader.get_u32());
+				let mut thresholds = vec![];
+				for _ in 0..len {
+					thresholds.push(try!(reader.get_f64()));
+				}
+				PatchOp::ObserveVisibility(id, thresholds)
+			},
+			OP_OBSERVE_RESIZE => {
+				let id = try!(reader.get_u64());
+				PatchOp::ObserveResize(id, try!(reader.get_bool()))
+			},
+			OP_SET_DESIGN_MODE => PatchOp::SetDesignMode(try!(reader.get_bool())),
+			OP_FORMAT_BOLD => {
+				let id = try!(reader.get_u64());
+				let start = try!(reader.get_u32());
+				let end = try!(reader.get_u32());
+				PatchOp::FormatBold(id, start, end, try!(reader.get_bool()))
+			},
+			OP_SUBSCRIBE_TICKS => {
+				let subscribed = try!(reader.get_bool());
+				PatchOp::SubscribeTicks(if subscribed { Some(try!(reader.get_u32())) } else { None })
+			},
+			OP_GET_INLINE_LONGHANDS => {
+				let id = try!(reader.get_u64());
+				let name = try!(reader.get_string());
+				PatchOp::GetInlineLonghands(id, name, try!(reader.get_u32()))
+			},
+			OP_REORDER_STYLESHEETS => {
+				let len = try!(reader.get_u32());
+				let mut ids = vec![];
+				for _ in 0..len {
+					ids.push(try!(reader.get_u64()));
+				}
+				PatchOp::ReorderStylesheets(ids)
+			},
+			OP_SET_CUSTOM_PROPERTY => {
+				let id = try!(reader.get_u64());
+				let name = try!(reader.get_string());
+				PatchOp::SetCustomProperty(id, name, try!(reader.get_opt_string()))
+			},
+			OP_GET_RESOLVED_STYLE => {
+				let id = try!(reader.get_u64());
+				let name = try!(reader.get_string());
+				PatchOp::GetResolvedStyle(id, name, try!(reader.get_u32()))
+			},
+			OP_CREATE_CHILDREN => {
+				let id = try!(reader.get_u64());
+				let children = try!(reader.get_bytes());
+				PatchOp::CreateChildren(id, children, try!(reader.get_u32()))
+			},
+			OP_PATCH_CHILDREN => {
+				let id = try!(reader.get_u64());
+				let len = try!(reader.get_u32());
+				let mut specs = vec![];
+				for _ in 0..len {
+					specs.push(try!(ChildSpec::read_from(reader)));
+				}
+				PatchOp::PatchChildren(id, specs)
+			},
+			OP_SET_TEXTS => {
+				let len = try!(reader.get_u32());
+				let mut texts = vec![];
+				for _ in 0..len {
+					let id = try!(reader.get_u64());
+					texts.push((id, try!(reader.get_string())));
+				}
+				PatchOp::SetTexts(texts)
+			},
+			OP_GET_SUBSCRIPTIONS => {
+				let id = try!(reader.get_u64());
+				PatchOp::GetSubscriptions(id, try!(reader.get_u32()))
+			},
+			OP_REPLACE_DOCUMENT_ELEMENT => PatchOp::ReplaceDocumentElement(try!(reader.get_bytes())),
+			OP_SET_CONTENT_TYPE => PatchOp::SetContentType(try!(reader.get_string())),
+			OP_GET_CONTENT_TYPE => PatchOp::GetContentType(try!(reader.get_u32())),
+			OP_UPGRADE_TEXT_TO_ELEMENT => {
+				let id = try!(reader.get_u64());
+				PatchOp::UpgradeTextToElement(id, try!(reader.get_bytes()))
+			},
+			OP_VALIDATE_PATCHES => {
+				let patches = try!(reader.get_bytes());
+				PatchOp::ValidatePatches(patches, try!(reader.get_u32()))
+			},
+			OP_SET_SELECTION => {
+				let anchor = try!(reader.get_u64());
+				let anchor_offset = try!(reader.get_u32());
+				let focus = try!(reader.get_u64());
+				PatchOp::SetSelection(anchor, anchor_offset, focus, try!(reader.get_u32()))
+			},
+			OP_GET_SELECTION => PatchOp::GetSelection(try!(reader.get_u32())),
+			OP_INSERT_AT => {
+				let id = try!(reader.get_u64());
+				let index = try!(reader.get_u32());
+				PatchOp::InsertAt(id, index, try!(reader.get_bytes()))
+			},
+			OP_SET_EVENT_DELIVERY => {
+				let events = EventSet::from_bits_truncate(try!(reader.get_u32()));
+				PatchOp::SetEventDelivery(events, try!(Delivery::from_wire(try!(reader.get_u8()))))
+			},
+			OP_SET_TITLE => {
+				let id = try!(reader.get_u64());
+				PatchOp::SetTitle(id, try!(reader.get_opt_string()))
+			},
+			OP_SET_OUTGOING_QUEUE => {
+				let bound = try!(reader.get_u32());
+				PatchOp::SetOutgoingQueue(bound, try!(DropPolicy::from_wire(try!(reader.get_u8()))))
+			},
+			OP_SET_PASSIVE => {
+				let id = try!(reader.get_u64());
+				PatchOp::SetPassive(id, EventSet::from_bits_truncate(try!(reader.get_u32())))
+			},
+			OP_SET_DRAG_DATA => {
+				let id = try!(reader.get_u64());
+				let mime = try!(reader.get_string());
+				PatchOp::SetDragData(id, mime, try!(reader.get_string()))
+			},
+			OP_BEGIN_SUPPRESS => PatchOp::BeginSuppress,
+			OP_END_SUPPRESS => PatchOp::EndSuppress,
+			OP_QUERY_PROPAGATION_PATH => {
+				let id = try!(reader.get_u64());
+				PatchOp::QueryPropagationPath(id, try!(reader.get_u32()))
+			},
+			OP_SET_MOVE_COALESCING => {
+				let coalesced = try!(reader.get_bool());
+				PatchOp::SetMoveCoalescing(if coalesced { Some(try!(reader.get_u32())) } else { None })
+			},
+			OP_SET_ELEMENT_SCROLL => {
+				let id = try!(reader.get_u64());
+				let top = try!(reader.get_f64());
+				PatchOp::SetElementScroll(id, top, try!(reader.get_f64()))
+			},
+			OP_IS_CONNECTED => {
+				let id = try!(reader.get_u64());
+				PatchOp::IsConnected(id, try!(reader.get_u32()))
+			},
+			OP_DIFF_ATTRIBUTES => {
+				let id = try!(reader.get_u64());
+				let len = try!(reader.get_u32());
+				let mut set = vec![];
+				for _ in 0..len {
+					let name = try!(reader.get_string());
+					set.push((name, try!(reader.get_string())));
+				}
+				let len = try!(reader.get_u32());
+				let mut remove = vec![];
+				for _ in 0..len {
+					remove.push(try!(reader.get_string()));
+				}
+				PatchOp::DiffAttributes(id, set, remove)
+			},
+			OP_INVALIDATE_LAYOUT => PatchOp::InvalidateLayout(try!(reader.get_u64())),
+			_ => return Err(PatchError::UnsupportedOp.error("unknown vdom op"))
+		}))
+	}
+
+	/// Writes the operation in the vdom wire format.
+	pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+		match *self {
+			PatchOp::SetClipboard(ref contents) => {
+				try!(writer.put_u8(OP_SET_CLIPBOARD));
+				writer.put_str(contents)
+			},
+			PatchOp::SetVetoable(id, events) => {
+				try!(writer.put_u8(OP_SET_VETOABLE));
+				try!(writer.put_u64(id));
+				writer.put_u32(events.bits())
+			},
+			PatchOp::SetState(ref key, ref value) => {
+				try!(writer.put_u8(OP_SET_STATE));
+				try!(writer.put_str(key));
+				writer.put_opt_str(value.as_ref().map(|value| &**value))
+			},
+			PatchOp::GetState(ref key, reply) => {
+				try!(writer.put_u8(OP_GET_STATE));
+				try!(writer.put_str(key));
+				writer.put_u32(reply)
+			},
+			PatchOp::SetDataset(id, ref key, ref value) => {
+				try!(writer.put_u8(OP_SET_DATASET));
+				try!(writer.put_u64(id));
+				try!(writer.put_str(key));
+				writer.put_str(value)
+			},
+			PatchOp::GetDataset(id, ref key, reply) => {
+				try!(writer.put_u8(OP_GET_DATASET));
+				try!(writer.put_u64(id));
+				try!(writer.put_str(key));
+				writer.put_u32(reply)
+			},
+			PatchOp::GetAttributeNames(id, reply) => {
+				try!(writer.put_u8(OP_GET_ATTRIBUTE_NAMES));
+				try!(writer.put_u64(id));
+				writer.put_u32(reply)
+			},
+			PatchOp::ClearChildren(id) => {
+				try!(writer.put_u8(OP_CLEAR_CHILDREN));
+				writer.put_u64(id)
+			},
+			PatchOp::ReplaceChildren(id, ref children) => {
+				try!(writer.put_u8(OP_REPLACE_CHILDREN));
+				try!(writer.put_u64(id));
+				writer.put_bytes(children)
+			},
+			PatchOp::Transaction(ref patches) => {
+				try!(writer.put_u8(OP_TRANSACTION));
+				writer.put_bytes(patches)
+			},
+			PatchOp::Checkpoint(ref label) => {
+				try!(writer.put_u8(OP_CHECKPOINT));
+				writer.put_str(label)
+			},
+			PatchOp::Revert(ref label) => {
+				try!(writer.put_u8(OP_REVERT));
+				writer.put_str(label)
+			},
+			PatchOp::MatchMedia(ref query, reply) => {
+				try!(writer.put_u8(OP_MATCH_MEDIA));
+				try!(writer.put_str(query));
+				writer.put_u32(reply)
+			},
+			PatchOp::GetDevicePixelRatio(reply) => {
+				try!(writer.put_u8(OP_GET_DEVICE_PIXEL_RATIO));
+				writer.put_u32(reply)
+			},
+			PatchOp::SetColorScheme(color_scheme) => {
+				try!(writer.put_u8(OP_SET_COLOR_SCHEME));
+				writer.put_u8(color_scheme_to_wire(color_scheme))
+			},
+			PatchOp::GetGeometry(id, reply) => {
+				try!(writer.put_u8(OP_GET_GEOMETRY));
+				try!(writer.put_u64(id));
+				writer.put_u32(reply)
+			},
+			PatchOp::SetTombstones(enabled) => {
+				try!(writer.put_u8(OP_SET_TOMBSTONES));
+				writer.put_bool(enabled)
+			},
+			PatchOp::AppendText(id, ref text) => {
+				try!(writer.put_u8(OP_APPEND_TEXT));
+				try!(writer.put_u64(id));
+				writer.put_str(text)
+			},
+			PatchOp::SetDocumentLang(ref lang) => {
+				try!(writer.put_u8(OP_SET_DOCUMENT_LANG));
+				writer.put_str(lang)
+			},
+			PatchOp::SetDocumentDir(ref dir) => {
+				try!(writer.put_u8(OP_SET_DOCUMENT_DIR));
+				writer.put_str(dir)
+			},
+			PatchOp::SwapNodes(a, b) => {
+				try!(writer.put_u8(OP_SWAP_NODES));
+				try!(writer.put_u64(a));
+				writer.put_u64(b)
+			},
+			PatchOp::EnumerateIds(id, reply) => {
+				try!(writer.put_u8(OP_ENUMERATE_IDS));
+				try!(writer.put_u64(id));
+				writer.put_u32(reply)
+			},
+			PatchOp::GetActiveElement(reply) => {
+				try!(writer.put_u8(OP_GET_ACTIVE_ELEMENT));
+				writer.put_u32(reply)
+			},
+			PatchOp::QueryBoxModel(id, reply) => {
+				try!(writer.put_u8(OP_QUERY_BOX_MODEL));
+				try!(writer.put_u64(id));
+				writer.put_u32(reply)
+			},
+			PatchOp::ForceReflow(reply) => {
+				try!(writer.put_u8(OP_FORCE_REFLOW));
+				writer.put_u32(reply)
+			},
+			PatchOp::SetFrozen(id, frozen) => {
+				try!(writer.put_u8(OP_SET_FROZEN));
+				try!(writer.put_u64(id));
+				writer.put_bool(frozen)
+			},
+			PatchOp::SetElementPool(enabled) => {
+				try!(writer.put_u8(OP_SET_ELEMENT_POOL));
+				writer.put_bool(enabled)
+			},
+			PatchOp::MoveNode(id, parent, before) => {
+				try!(writer.put_u8(OP_MOVE_NODE));
+				try!(writer.put_u64(id));
+				try!(writer.put_u64(parent));
+				match before {
+					Some(before) => {
+						try!(writer.put_bool(true));
+						writer.put_u64(before)
+					},
+					None => writer.put_bool(false)
+				}
+			},
+			PatchOp::SetHtmlId(id, ref html_id) => {
+				try!(writer.put_u8(OP_SET_HTML_ID));
+				try!(writer.put_u64(id));
+				writer.put_opt_str(html_id.as_ref().map(|html_id| &**html_id))
+			},
+			PatchOp::CanvasBlit(id, width, height, ref rgba) => {
+				try!(writer.put_u8(OP_CANVAS_BLIT));
+				try!(writer.put_u64(id));
+				try!(writer.put_u32(width));
+				try!(writer.put_u32(height));
+				writer.put_bytes(rgba)
+			},
+			PatchOp::CanvasReadback(id, ref rect, reply) => {
+				try!(writer.put_u8(OP_CANVAS_READBACK));
+				try!(writer.put_u64(id));
+				try!(rect.write_to(writer));
+				writer.put_u32(reply)
+			},
+			PatchOp::GetScreen(reply) => {
+				try!(writer.put_u8(OP_GET_SCREEN));
+				writer.put_u32(reply)
+			},
+			PatchOp::SetWindowName(ref name) => {
+				try!(writer.put_u8(OP_SET_WINDOW_NAME));
+				writer.put_str(name)
+			},
+			PatchOp::GetWindowName(reply) => {
+				try!(writer.put_u8(OP_GET_WINDOW_NAME));
+				writer.put_u32(reply)
+			},
+			PatchOp::PushState(ref url, ref state) => {
+				try!(writer.put_u8(OP_PUSH_STATE));
+				try!(writer.put_str(url));
+				writer.put_opt_str(state.as_ref().map(|state| &**state))
+			},
+			PatchOp::ReplaceState(ref url, ref state) => {
+				try!(writer.put_u8(OP_REPLACE_STATE));
+				try!(writer.put_str(url));
+				writer.put_opt_str(state.as_ref().map(|state| &**state))
+			},
+			PatchOp::PopState => writer.put_u8(OP_POP_STATE),
+			PatchOp::PreloadFont(ref family, ref url) => {
+				try!(writer.put_u8(OP_PRELOAD_FONT));
+				try!(writer.put_str(family));
+				writer.put_str(url)
+			},
+			PatchOp::ObserveVisibility(id, ref thresholds) => {
+				try!(writer.put_u8(OP_OBSERVE_VISIBILITY));
+				try!(writer.put_u64(id));
+				try!(writer.put_u32(thresholds.len() as u32));
+				for &threshold in thresholds {
+					try!(writer.put_f64(threshold));
+				}
+				Ok(())
+			},
+			PatchOp::ObserveResize(id, observe) => {
+				try!(writer.put_u8(OP_OBSERVE_RESIZE));
+				try!(writer.put_u64(id));
+				writer.put_bool(observe)
+			},
+			PatchOp::SetDesignMode(enabled) => {
+				try!(writer.put_u8(OP_SET_DESIGN_MODE));
+				writer.put_bool(enabled)
+			},
+			PatchOp::FormatBold(id, start, end, bold) => {
+				try!(writer.put_u8(OP_FORMAT_BOLD));
+				try!(writer.put_u64(id));
+				try!(writer.put_u32(start));
+				try!(writer.put_u32(end));
+				writer.put_bool(bold)
+			},
+			PatchOp::SubscribeTicks(interval) => {
+				try!(writer.put_u8(OP_SUBSCRIBE_TICKS));
+				try!(writer.put_bool(interval.is_some()));
+				match interval {
+					Some(interval) => writer.put_u32(interval),
+					None => Ok(())
+				}
+			},
+			PatchOp::GetInlineLonghands(id, ref name, reply) => {
+				try!(writer.put_u8(OP_GET_INLINE_LONGHANDS));
+				try!(writer.put_u64(id));
+				try!(writer.put_str(name));
+				writer.put_u32(reply)
+			},
+			PatchOp::ReorderStylesheets(ref ids) => {
+				try!(writer.put_u8(OP_REORDER_STYLESHEETS));
+				try!(writer.put_u32(ids.len() as u32));
+				for &id in ids {
+					try!(writer.put_u64(id));
+				}
+				Ok(())
+			},
+			PatchOp::SetCustomProperty(id, ref name, ref value) => {
+				try!(writer.put_u8(OP_SET_CUSTOM_PROPERTY));
+				try!(writer.put_u64(id));
+				try!(writer.put_str(name));
+				writer.put_opt_str(value.as_ref().map(|value| &**value))
+			},
+			PatchOp::GetResolvedStyle(id, ref name, reply) => {
+				try!(writer.put_u8(OP_GET_RESOLVED_STYLE));
+				try!(writer.put_u64(id));
+				try!(writer.put_str(name));
+				writer.put_u32(reply)
+			},
+			PatchOp::CreateChildren(id, ref children, reply) => {
+				try!(writer.put_u8(OP_CREATE_CHILDREN));
+				try!(writer.put_u64(id));
+				try!(writer.put_bytes(children));
+				writer.put_u32(reply)
+			},
+			PatchOp::PatchChildren(id, ref specs) => {
+				try!(writer.put_u8(OP_PATCH_CHILDREN));
+				try!(writer.put_u64(id));
+				try!(writer.put_u32(specs.len() as u32));
+				for spec in specs {
+					try!(spec.write_to(writer));
+				}
+				Ok(())
+			},
+			PatchOp::SetTexts(ref texts) => {
+				try!(writer.put_u8(OP_SET_TEXTS));
+				try!(writer.put_u32(texts.len() as u32));
+				for &(id, ref text) in texts {
+					try!(writer.put_u64(id));
+					try!(writer.put_str(text));
+				}
+				Ok(())
+			},
+			PatchOp::GetSubscriptions(id, reply) => {
+				try!(writer.put_u8(OP_GET_SUBSCRIPTIONS));
+				try!(writer.put_u64(id));
+				writer.put_u32(reply)
+			},
+			PatchOp::ReplaceDocumentElement(ref element) => {
+				try!(writer.put_u8(OP_REPLACE_DOCUMENT_ELEMENT));
+				writer.put_bytes(element)
+			},
+			PatchOp::SetContentType(ref content_type) => {
+				try!(writer.put_u8(OP_SET_CONTENT_TYPE));
+				writer.put_str(content_type)
+			},
+			PatchOp::GetContentType(reply) => {
+				try!(writer.put_u8(OP_GET_CONTENT_TYPE));
+				writer.put_u32(reply)
+			},
+			PatchOp::UpgradeTextToElement(id, ref element) => {
+				try!(writer.put_u8(OP_UPGRADE_TEXT_TO_ELEMENT));
+				try!(writer.put_u64(id));
+				writer.put_bytes(element)
+			},
+			PatchOp::ValidatePatches(ref patches, reply) => {
+				try!(writer.put_u8(OP_VALIDATE_PATCHES));
+				try!(writer.put_bytes(patches));
+				writer.put_u32(reply)
+			},
+			PatchOp::SetSelection(anchor, anchor_offset, focus, focus_offset) => {
+				try!(writer.put_u8(OP_SET_SELECTION));
+				try!(writer.put_u64(anchor));
+				try!(writer.put_u32(anchor_offset));
+				try!(writer.put_u64(focus));
+				writer.put_u32(focus_offset)
+			},
+			PatchOp::GetSelection(reply) => {
+				try!(writer.put_u8(OP_GET_SELECTION));
+				writer.put_u32(reply)
+			},
+			PatchOp::InsertAt(id, index, ref node) => {
+				try!(writer.put_u8(OP_INSERT_AT));
+				try!(writer.put_u64(id));
+				try!(writer.put_u32(index));
+				writer.put_bytes(node)
+			},
+			PatchOp::SetEventDelivery(events, delivery) => {
+				try!(writer.put_u8(OP_SET_EVENT_DELIVERY));
+				try!(writer.put_u32(events.bits()));
+				writer.put_u8(delivery.to_wire())
+			},
+			PatchOp::SetTitle(id, ref title) => {
+				try!(writer.put_u8(OP_SET_TITLE));
+				try!(writer.put_u64(id));
+				writer.put_opt_str(title.as_ref().map(|title| &**title))
+			},
+			PatchOp::SetOutgoingQueue(bound, policy) => {
+				try!(writer.put_u8(OP_SET_OUTGOING_QUEUE));
+				try!(writer.put_u32(bound));
+				writer.put_u8(policy.to_wire())
+			},
+			PatchOp::SetPassive(id, events) => {
+				try!(writer.put_u8(OP_SET_PASSIVE));
+				try!(writer.put_u64(id));
+				writer.put_u32(events.bits())
+			},
+			PatchOp::SetDragData(id, ref mime, ref data) => {
+				try!(writer.put_u8(OP_SET_DRAG_DATA));
+				try!(writer.put_u64(id));
+				try!(writer.put_str(mime));
+				writer.put_str(data)
+			},
+			PatchOp::BeginSuppress => writer.put_u8(OP_BEGIN_SUPPRESS),
+			PatchOp::EndSuppress => writer.put_u8(OP_END_SUPPRESS),
+			PatchOp::QueryPropagationPath(id, reply) => {
+				try!(writer.put_u8(OP_QUERY_PROPAGATION_PATH));
+				try!(writer.put_u64(id));
+				writer.put_u32(reply)
+			},
+			PatchOp::SetMoveCoalescing(interval) => {
+				try!(writer.put_u8(OP_SET_MOVE_COALESCING));
+				try!(writer.put_bool(interval.is_some()));
+				match interval {
+					Some(interval) => writer.put_u32(interval),
+					None => Ok(())
+				}
+			},
+			PatchOp::SetElementScroll(id, top, left) => {
+				try!(writer.put_u8(OP_SET_ELEMENT_SCROLL));
+				try!(writer.put_u64(id));
+				try!(writer.put_f64(top));
+				writer.put_f64(left)
+			},
+			PatchOp::IsConnected(id, reply) => {
+				try!(writer.put_u8(OP_IS_CONNECTED));
+				try!(writer.put_u64(id));
+				writer.put_u32(reply)
+			},
+			PatchOp::DiffAttributes(id, ref set, ref remove) => {
+				try!(writer.put_u8(OP_DIFF_ATTRIBUTES));
+				try!(writer.put_u64(id));
+				try!(writer.put_u32(set.len() as u32));
+				for &(ref name, ref value) in set {
+					try!(writer.put_str(name));
+					try!(writer.put_str(value));
+				}
+				try!(writer.put_u32(remove.len() as u32));
+				for name in remove {
+					try!(writer.put_str(name));
+				}
+				Ok(())
+			},
+			PatchOp::InvalidateLayout(id) => {
+				try!(writer.put_u8(OP_INVALIDATE_LAYOUT));
+				writer.put_u64(id)
+			}
+		}
+	}
+
+	/// Performs the operation on a document, recording its mutations into `log`. Fails if a node
+	/// payload it carries is malformed, if a transaction it carries fails, if it refers to a
+	/// checkpoint `log` doesn't have, or if it targets a node that isn't in the document, in
+	/// which case a query isn't replied to.
+	pub fn apply(self, doc: &Document, log: &mut UndoLog) -> Result<()> {
+		match self {
+			PatchOp::SetClipboard(contents) => {
+				doc.window().constellation_chan().set_clipboard_contents(contents);
+			},
+			PatchOp::SetVetoable(id, events) => try!(target_node(doc, id)).set_vetoable(events),
+			PatchOp::SetState(key, value) => doc.set_vdom_state(key, value),
+			PatchOp::GetState(key, reply) => {
+				let value = doc.vdom_state(&key);
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::State(value)));
+			},
+			PatchOp::SetDataset(id, key, value) => {
+				let element = try!(target_element(doc, id));
+				try!(check_mutable(element.upcast()));
+				log.modifying(&element);
+				let name = doc.vdom_atom(&dataset_attr_name(&key));
+				if element.dataset_set_named(&key, name, DOMString::from(value)).is_err() {
+					warn!("vdom server set invalid dataset key {}", key);
+				}
+			},
+			PatchOp::GetDataset(id, key, reply) => {
+				let element = try!(target_element(doc, id));
+				let value = element.dataset_get_named(&doc.vdom_atom(&dataset_attr_name(&key))).map(String::from);
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Dataset(value)));
+			},
+			PatchOp::GetAttributeNames(id, reply) => {
+				let element = try!(target_element(doc, id));
+				let names = element.GetAttributeNames().into_iter().map(String::from).collect();
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::AttributeNames(names)));
+			},
+			PatchOp::ClearChildren(id) => {
+				let node = try!(target_node(doc, id));
+				try!(check_mutable(&node));
+				for child in node.children().collect::<Vec<_>>() {
+					try!(remove_node(doc, &node, &child, None, log));
+				}
+			},
+			PatchOp::ReplaceChildren(id, children) => {
+				let node = try!(target_node(doc, id));
+				let fragment = try!(read_fragment(doc, children));
+				try!(replace_children(doc, &node, &fragment, log));
+			},
+			PatchOp::Transaction(patches) => {
+				try!(log.transaction(|transaction| apply_patches(&mut Cursor::new(patches), doc, transaction)))
+			},
+			PatchOp::Checkpoint(label) => try!(log.checkpoint(label)),
+			PatchOp::Revert(label) => try!(log.revert(&label)),
+			PatchOp::MatchMedia(query, reply) => {
+				let window = doc.window();
+				let device = window.media_device();
+				let matches = window.media_watch().watch(query, &device);
+				window.vdom_chan().send(ServerMsg::Reply(reply, Reply::MediaMatch(matches)));
+			},
+			PatchOp::GetDevicePixelRatio(reply) => {
+				let window = doc.window();
+				let ratio = *window.DevicePixelRatio();
+				window.vdom_chan().send(ServerMsg::Reply(reply, Reply::DevicePixelRatio(ratio)));
+			},
+			PatchOp::SetColorScheme(color_scheme) => doc.set_vdom_color_scheme(color_scheme),
+			PatchOp::GetGeometry(id, reply) => {
+				let element = try!(target_element(doc, id));
+				let node = element.upcast::<Node>();
+				let scroll_area = node.get_scroll_area();
+				let client_rect = node.get_client_rect();
+				let geometry = BoxGeometry::new(scroll_area.size.width, scroll_area.size.height,
+				                                client_rect.size.width, client_rect.size.height);
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Geometry(Some(geometry))));
+			},
+			PatchOp::SetTombstones(enabled) => doc.set_vdom_tombstones(enabled),
+			PatchOp::AppendText(id, text) => {
+				let data = try!(Root::downcast::<CharacterData>(try!(target_node(doc, id))).ok_or_else(|| {
+					PatchError::TypeMismatch.error("appended text targets a node without data")
+				}));
+				try!(check_mutable(data.upcast()));
+				log.changing_data(&data);
+				data.append_data(&text);
+			},
+			PatchOp::SetDocumentLang(lang) => try!(set_document_attribute(doc, log, atom!("lang"), lang)),
+			PatchOp::SetDocumentDir(dir) => try!(set_document_attribute(doc, log, atom!("dir"), dir)),
+			PatchOp::SwapNodes(a, b) => {
+				let (a, b) = (try!(target_node(doc, a)), try!(target_node(doc, b)));
+				try!(swap_nodes(doc, &a, &b, log));
+			},
+			PatchOp::EnumerateIds(id, reply) => {
+				let ids = try!(target_node(doc, id)).traverse_preorder().map(|node| node.get_id()).collect();
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Ids(ids)));
+			},
+			PatchOp::GetActiveElement(reply) => {
+				let id = doc.active_element_id();
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::ActiveElement(id)));
+			},
+			PatchOp::QueryBoxModel(id, reply) => {
+				let element = try!(target_element(doc, id));
+				let node = element.upcast::<Node>();
+				let box_model = doc.window().box_model_query(node.to_trusted_node_address()).map(|sides| {
+					BoxModel::new(box_rect(&sides.border_box), box_edges(&sides.border),
+					              box_edges(&sides.padding), box_edges(&sides.margin))
+				});
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::BoxModel(box_model)));
+			},
+			PatchOp::ForceReflow(reply) => {
+				let window = doc.window();
+				let dirty_nodes = doc.upcast::<Node>().traverse_preorder()
+				                     .filter(|node| node.get_is_dirty())
+				                     .count();
+				let start = time::precise_time_ns();
+				window.force_reflow(ReflowGoal::ForDisplay, ReflowQueryType::NoQuery, ReflowReason::VdomPatch);
+				let elapsed_ns = time::precise_time_ns() - start;
+				window.vdom_chan().send(ServerMsg::Reply(reply, Reply::ReflowTiming(elapsed_ns, dirty_nodes as u32)));
+			},
+			PatchOp::SetFrozen(id, frozen) => try!(target_node(doc, id)).set_frozen(frozen),
+			PatchOp::SetElementPool(enabled) => doc.set_vdom_pool(enabled),
+			PatchOp::MoveNode(id, parent, before) => try!(move_nodes(doc, log, vec![(id, parent, before)])),
+			PatchOp::SetHtmlId(id, html_id) => {
+				let element = try!(target_element(doc, id));
+				try!(check_mutable(element.upcast()));
+				log.modifying(&element);
+				// Changing the attribute moves the element in the index of the document, among
+				// other elements with the same id in tree order.
+				match html_id {
+					Some(html_id) => element.set_atomic_attribute(&atom!("id"), DOMString::from(html_id)),
+					None => {
+						element.remove_attribute(&ns!(), &atom!("id"));
+					}
+				}
+			},
+			PatchOp::CanvasBlit(id, width, height, rgba) => {
+				let canvas = try!(Root::downcast::<HTMLCanvasElement>(try!(target_node(doc, id))).ok_or_else(|| {
+					PatchError::TypeMismatch.error("blit targets a node that isn't a canvas")
+				}));
+				try!(check_mutable(canvas.upcast()));
+				let msg = try!(blit_msg(width, height, rgba));
+				try!(send_blit(&canvas.renderer(), msg));
+				canvas.mark_as_dirty();
+			},
+			PatchOp::CanvasReadback(id, rect, reply) => {
+				let canvas = try!(Root::downcast::<HTMLCanvasElement>(try!(target_node(doc, id))).ok_or_else(|| {
+					PatchError::TypeMismatch.error("readback targets a node that isn't a canvas")
+				}));
+				let size = canvas.get_size();
+				let rect = rect.intersection(&BoxRect::new(0, 0, size.width, size.height));
+				let pixels = if rect.width == 0 || rect.height == 0 {
+					None
+				} else {
+					let image_rect = Rect::new(Point2D::new(rect.x, rect.y), Size2D::new(rect.width, rect.height));
+					let image = canvas.get_image_data(image_rect);
+					Some(CanvasPixels {
+						rect: rect,
+						data: image.get_data_array(),
+					})
+				};
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::CanvasPixels(pixels)));
+			},
+			PatchOp::GetScreen(reply) => {
+				let window = doc.window();
+				let screen = window.Screen();
+				let metrics = ScreenMetrics {
+					width: screen.Width(),
+					height: screen.Height(),
+					avail_width: screen.AvailWidth(),
+					avail_height: screen.AvailHeight(),
+					color_depth: screen.ColorDepth(),
+					pixel_depth: screen.PixelDepth(),
+				};
+				window.vdom_chan().send(ServerMsg::Reply(reply, Reply::Screen(metrics)));
+			},
+			PatchOp::SetWindowName(name) => doc.window().SetName(DOMString::from(name)),
+			PatchOp::GetWindowName(reply) => {
+				let window = doc.window();
+				let name = String::from(window.Name());
+				window.vdom_chan().send(ServerMsg::Reply(reply, Reply::WindowName(name)));
+			},
+			PatchOp::PushState(url, state) => {
+				let url = try!(history_url(doc, &url));
+				doc.push_vdom_history(url, state);
+			},
+			PatchOp::ReplaceState(url, state) => {
+				let url = try!(history_url(doc, &url));
+				doc.replace_vdom_history(url, state);
+			},
+			PatchOp::PopState => doc.queue_vdom_history_back(),
+			PatchOp::PreloadFont(family, url) => {
+				let url = match doc.url().join(&url) {
+					Ok(url) => url,
+					Err(_) => return Err(PatchError::Malformed.error("invalid font URL"))
+				};
+				doc.window().preload_vdom_font(family, url);
+			},
+			PatchOp::ObserveVisibility(id, thresholds) => {
+				if thresholds.iter().any(|&threshold| !(threshold >= 0.0 && threshold <= 1.0)) {
+					return Err(PatchError::Malformed.error("visibility threshold outside of 0 to 1"));
+				}
+				// The visibility is reported after the reflow that follows the patches.
+				doc.observe_vdom_visibility(id, thresholds);
+			},
+			// Like visibility, sizes are reported after the reflow that follows the patches.
+			PatchOp::ObserveResize(id, observe) => doc.observe_vdom_resize(id, observe),
+			PatchOp::SetDesignMode(enabled) => doc.SetDesignMode(DOMString::from(if enabled { "on" } else { "off" })),
+			PatchOp::FormatBold(id, start, end, bold) => {
+				let node = try!(target_node(doc, id));
+				try!(format_bold(doc, &node, start as usize, end as usize, bold, log));
+			},
+			PatchOp::SubscribeTicks(interval) => doc.subscribe_vdom_ticks(interval),
+			PatchOp::GetInlineLonghands(id, name, reply) => {
+				let longhands = inline_longhands(&*try!(target_element(doc, id)), &name);
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Longhands(longhands)));
+			},
+			PatchOp::ReorderStylesheets(ids) => doc.reorder_vdom_stylesheets(ids),
+			PatchOp::SetCustomProperty(id, name, value) => {
+				if !name.starts_with("--") {
+					return Err(PatchError::Malformed.error("custom property name without --"));
+				}
+				let element = try!(target_element(doc, id));
+				try!(check_mutable(element.upcast()));
+				set_inline_style(&element, &name, value.as_ref().map_or("", |value| &**value));
+			},
+			PatchOp::GetResolvedStyle(id, name, reply) => {
+				let element = try!(target_element(doc, id));
+				let value = doc.window().resolved_style_query(element.upcast::<Node>().to_trusted_node_address(), None,
+				                                              &Atom::from(&*name.to_ascii_lowercase()))
+				               .map(String::from);
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::ResolvedStyle(value)));
+			},
+			PatchOp::CreateChildren(id, children, reply) => {
+				let node = try!(target_node(doc, id));
+				let fragment = try!(read_fragment(doc, children));
+				try!(create_children(doc, &node, &fragment, reply, log));
+			},
+			PatchOp::PatchChildren(id, specs) => {
+				let node = try!(target_node(doc, id));
+				try!(patch_children(doc, &node, specs, log));
+			},
+			PatchOp::SetTexts(texts) => {
+				let mut not_set = vec![];
+				for (id, text) in texts {
+					match doc.get_node_by_id(id).as_ref().and_then(|node| node.downcast::<Text>()) {
+						Some(node) => {
+							let data = node.upcast::<CharacterData>();
+							try!(check_mutable(data.upcast()));
+							log.changing_data(data);
+							data.set_data(DOMString::from(text));
+						},
+						None => not_set.push(id)
+					}
+				}
+				if !not_set.is_empty() {
+					doc.window().vdom_chan().send(ServerMsg::TextsNotSet(not_set));
+				}
+			},
+			PatchOp::GetSubscriptions(id, reply) => {
+				let node = try!(target_node(doc, id));
+				let subscriptions = subscription_list(node.subscriptions(), node.vetoable());
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Subscriptions(subscriptions)));
+			},
+			PatchOp::ReplaceDocumentElement(payload) => {
+				let new = try!(try!(read_node(&mut Cursor::new(payload), doc)).ok_or_else(|| {
+					PatchError::Malformed.error("document element payload without a node")
+				}));
+				let new = try!(Root::downcast::<Element>(new).ok_or_else(|| {
+					PatchError::TypeMismatch.error("document element payload isn't an element")
+				}));
+				try!(check_mutation(doc.replace_vdom_document_element(&new, log)));
+			},
+			PatchOp::SetContentType(value) => {
+				let (content_type, charset) = try!(parse_content_type(&value));
+				doc.set_content_type(DOMString::from(content_type));
+				if let Some(charset) = charset {
+					doc.set_encoding_name(DOMString::from(charset));
+				}
+			},
+			PatchOp::GetContentType(reply) => {
+				let content_type = String::from(doc.content_type().clone());
+				let charset = String::from(doc.encoding_name().clone());
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::ContentType(content_type, charset)));
+			},
+			PatchOp::UpgradeTextToElement(id, payload) => {
+				let text = try!(target_node(doc, id));
+				let new = try!(try!(read_node(&mut Cursor::new(payload), doc)).ok_or_else(|| {
+					PatchError::Malformed.error("upgrade payload without a node")
+				}));
+				try!(upgrade_text_to_element(doc, &text, &new, log));
+			},
+			PatchOp::ValidatePatches(patches, reply) => {
+				let errors = validate_patch(&patches, doc);
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::PatchErrors(errors)));
+			},
+			PatchOp::SetSelection(anchor_id, anchor_offset, focus_id, focus_offset) => {
+				let (anchor, focus) = (try!(target_node(doc, anchor_id)), try!(target_node(doc, focus_id)));
+				let host = try!(selection_host(&anchor).ok_or_else(|| {
+					PatchError::TypeMismatch.error("selection outside of an editing host")
+				}));
+				match (selection_offset(&host, &anchor, anchor_offset as usize),
+				       selection_offset(&host, &focus, focus_offset as usize)) {
+					(Some(anchor), Some(focus)) => doc.set_editing_selection(&host, anchor, focus),
+					_ => return Err(PatchError::TypeMismatch.error("selection ends outside of the text of its editing host"))
+				}
+			},
+			PatchOp::GetSelection(reply) => {
+				let selection = doc.editing_selection_ends().map(|((anchor, anchor_offset), (focus, focus_offset))| {
+					(anchor, anchor_offset, focus, focus_offset)
+				});
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Selection(selection)));
+			},
+			PatchOp::InsertAt(id, index, payload) => {
+				let parent = try!(target_node(doc, id));
+				let new = try!(try!(read_node(&mut Cursor::new(payload), doc)).ok_or_else(|| {
+					PatchError::Malformed.error("insert payload without a node")
+				}));
+				try!(insert_child_at(&parent, index, &new, log));
+			},
+			PatchOp::SetEventDelivery(events, delivery) => doc.set_vdom_event_delivery(events, delivery),
+			PatchOp::SetTitle(id, title) => {
+				let element = try!(target_element(doc, id));
+				try!(check_mutable(element.upcast()));
+				log.modifying(&element);
+				match title {
+					Some(title) => element.set_string_attribute(&atom!("title"), DOMString::from(title)),
+					None => {
+						element.remove_attribute(&ns!(), &atom!("title"));
+					}
+				}
+			},
+			PatchOp::SetOutgoingQueue(bound, policy) => doc.window().vdom_chan().configure_queue(bound as usize, policy),
+			PatchOp::SetPassive(id, events) => try!(target_node(doc, id)).set_passive(events),
+			PatchOp::SetDragData(id, mime, data) => {
+				let element = try!(target_element(doc, id));
+				try!(check_mutable(element.upcast()));
+				element.set_vdom_drag_data(&mime, &data);
+			},
+			PatchOp::BeginSuppress => ScriptThread::begin_vdom_suppression(),
+			PatchOp::EndSuppress => try!(ScriptThread::end_vdom_suppression()),
+			PatchOp::QueryPropagationPath(id, reply) => {
+				let path = try!(target_node(doc, id)).inclusive_ancestors().map(|node| node.get_id()).collect();
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Ids(path)));
+			},
+			PatchOp::SetMoveCoalescing(interval) => doc.set_vdom_move_coalescing(interval),
+			PatchOp::SetElementScroll(id, top, left) => {
+				let element = try!(target_element(doc, id));
+				let node = element.upcast::<Node>();
+				let scroll_area = node.get_scroll_area();
+				let client_rect = node.get_client_rect();
+				let geometry = BoxGeometry::new(scroll_area.size.width, scroll_area.size.height,
+				                                client_rect.size.width, client_rect.size.height);
+				if geometry.overflows() {
+					let (top, left) = geometry.clamp_scroll(top, left);
+					doc.window().scroll_element(&element, top as f32, left as f32);
+				}
+			},
+			PatchOp::IsConnected(id, reply) => {
+				let connection = Connection::of(doc.lookup_node_by_id(id));
+				doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Connection(connection)));
+			},
+			PatchOp::DiffAttributes(id, set, remove) => {
+				let element = try!(target_element(doc, id));
+				try!(check_mutable(element.upcast()));
+				log.modifying(&element);
+				let value_of = |name: &str| {
+					element.get_attribute_by_name(DOMString::from(name)).map(|attr| String::from(&**attr.value()))
+				};
+				// Each change is an attribute mutation of its own; the reflow after the patches
+				// covers them all.
+				for (name, value) in attribute_changes(value_of, set, remove) {
+					match value {
+						Some(value) => {
+							if element.SetAttribute(DOMString::from(&*name), DOMString::from(value)).is_err() {
+								warn!("vdom server set invalid attribute {}", name);
+							}
+						},
+						None => element.RemoveAttribute(DOMString::from(name))
+					}
+				}
+			},
+			PatchOp::InvalidateLayout(id) => try!(target_node(doc, id)).dirty(NodeDamage::OtherNodeDamage)
+		}
+		Ok(())
+	}
+
+	/// The nodes the operation targets by id, in the order `apply` looks them up, with what it
+	/// requires of each. Ops that tolerate missing nodes, like `SetTexts`, target none.
+	pub fn targets(&self) -> Vec<OpTarget> {
+		let target = |id, kind, mutates| OpTarget {
+			id: id,
+			kind: kind,
+			mutates: mutates,
+		};
+		match *self {
+			PatchOp::SetVetoable(id, _) |
+			PatchOp::EnumerateIds(id, _) |
+			PatchOp::SetFrozen(id, _) |
+			PatchOp::GetSubscriptions(id, _) |
+			PatchOp::SetPassive(id, _) |
+			PatchOp::QueryPropagationPath(id, _) |
+			PatchOp::InvalidateLayout(id) => vec![target(id, TargetKind::Node, false)],
+			PatchOp::ClearChildren(id) |
+			PatchOp::ReplaceChildren(id, _) |
+			PatchOp::FormatBold(id, _, _, _) |
+			PatchOp::CreateChildren(id, _, _) |
+			PatchOp::PatchChildren(id, _) |
+			PatchOp::InsertAt(id, _, _) => vec![target(id, TargetKind::Node, true)],
+			PatchOp::GetDataset(id, _, _) |
+			PatchOp::GetAttributeNames(id, _) |
+			PatchOp::GetGeometry(id, _) |
+			PatchOp::QueryBoxModel(id, _) |
+			PatchOp::GetInlineLonghands(id, _, _) |
+			PatchOp::GetResolvedStyle(id, _, _) |
+			PatchOp::SetElementScroll(id, _, _) => vec![target(id, TargetKind::Element, false)],
+			PatchOp::SetDataset(id, _, _) |
+			PatchOp::SetHtmlId(id, _) |
+			PatchOp::SetCustomProperty(id, _, _) |
+			PatchOp::SetTitle(id, _) |
+			PatchOp::SetDragData(id, _, _) |
+			PatchOp::DiffAttributes(id, _, _) => vec![target(id, TargetKind::Element, true)],
+			PatchOp::AppendText(id, _) => vec![target(id, TargetKind::CharacterData, true)],
+			PatchOp::UpgradeTextToElement(id, _) => vec![target(id, TargetKind::Text, true)],
+			PatchOp::CanvasBlit(id, _, _, _) => vec![target(id, TargetKind::Canvas, true)],
+			PatchOp::CanvasReadback(id, _, _) => vec![target(id, TargetKind::Canvas, false)],
+			PatchOp::SwapNodes(a, b) => vec![target(a, TargetKind::Node, true), target(b, TargetKind::Node, true)],
+			PatchOp::MoveNode(id, parent, before) => {
+				let mut targets = vec![target(id, TargetKind::Node, true), target(parent, TargetKind::Node, true)];
+				targets.extend(before.map(|before| target(before, TargetKind::Node, false)));
+				targets
+			},
+			PatchOp::SetSelection(anchor, _, focus, _) => {
+				vec![target(anchor, TargetKind::Node, false), target(focus, TargetKind::Node, false)]
+			},
+			_ => vec![]
+		}
+	}
+}
+
+/// What an operation requires of a node it targets.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TargetKind {
+	Node,
+	Element,
+	CharacterData,
+	Text,
+	Canvas,
+}
+
+impl TargetKind {
+	/// Whether `node` is of the type an operation requires, which fails with `TypeMismatch`
+	/// otherwise.
+	pub fn matches(self, node: &Node) -> bool {
+		match self {
+			TargetKind::Node => true,
+			TargetKind::Element => node.is::<Element>(),
+			TargetKind::CharacterData => node.is::<CharacterData>(),
+			TargetKind::Text => node.is::<Text>(),
+			TargetKind::Canvas => node.is::<HTMLCanvasElement>(),
+		}
+	}
+}
+
+/// A node an operation targets: its id, what the operation requires it to be, and whether the
+/// operation mutates it, which fails with `HierarchyViolation` in a frozen subtree.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OpTarget {
+	pub id: u64,
+	pub kind: TargetKind,
+	pub mutates: bool,
+}
+
+/// The node with an id in the document, which an operation targets. Fails if there is none,
+/// telling a recently removed node apart from an unknown id when tombstones are kept.
+fn target_node(doc: &Document, id: u64) -> Result<Root<Node>> {
+	doc.lookup_node_by_id(id).map_err(|err| match err {
+		LookupError::Removed => PatchError::RemovedId.error("operation targets a removed node"),
+		LookupError::NotFound => PatchError::UnknownId.error("operation targets an unknown node"),
+	})
+}
+
+/// The element with an id in the document, which an operation targets. Fails like
+/// `target_node`, or if the node isn't an element.
+fn target_element(doc: &Document, id: u64) -> Result<Root<Element>> {
+	Root::downcast::<Element>(try!(target_node(doc, id))).ok_or_else(|| {
+		PatchError::TypeMismatch.error("operation targets a node that isn't an element")
+	})
+}
+
+/// Sends the message of a blit to the paint thread of a canvas. Fails if that thread is gone,
+/// so the canvas can't be drawn on.
+pub fn send_blit(renderer: &IpcSender<CanvasMsg>, msg: CanvasMsg) -> Result<()> {
+	renderer.send(msg).map_err(|_| PatchError::UnsupportedOp.error("blit to a canvas whose paint thread is gone"))
+}
+
+/// The message drawing an image of `width` by `height` RGBA pixels at the origin of a canvas.
+/// Fails if there aren't as many pixels as the size says.
+pub fn blit_msg(width: u32, height: u32, rgba: Vec<u8>) -> Result<CanvasMsg> {
+	let len = (width as u64).checked_mul(height as u64).and_then(|pixels| pixels.checked_mul(4));
+	if len != Some(rgba.len() as u64) {
+		return Err(PatchError::Malformed.error("canvas blit of the wrong length"));
+	}
+	let size = Size2D::new(width as f64, height as f64);
+	let dirty_rect = Rect::new(Point2D::new(0.0, 0.0), size);
+	Ok(CanvasMsg::Canvas2d(Canvas2dMsg::PutImageData(rgba, Point2D::new(0.0, 0.0), size, dirty_rect)))
+}
+
+/// Resolves the URL of a session history entry against the document's. Like `pushState`, the
+/// server can't make the document look like it comes from another origin.
+fn history_url(doc: &Document, url: &str) -> Result<Url> {
+	let base = doc.url();
+	let url = match base.join(url) {
+		Ok(url) => url,
+		Err(_) => return Err(PatchError::Malformed.error("invalid session history URL"))
+	};
+	if url.scheme != base.scheme || url.host() != base.host() || url.port_or_default() != base.port_or_default() {
+		return Err(PatchError::Malformed.error("session history URL of another origin"));
+	}
+	Ok(url)
+}
+
+/// A layout rectangle in whole CSS pixels, as the vdom wire format has it.
+pub fn box_rect(rect: &Rect<Au>) -> BoxRect {
+	BoxRect::new(rect.origin.x.to_px(), rect.origin.y.to_px(), rect.size.width.to_px(), rect.size.height.to_px())
+}
+
+fn box_edges(edges: &SideOffsets2D<Au>) -> BoxEdges {
+	BoxEdges::new(edges.top.to_px(), edges.right.to_px(), edges.bottom.to_px(), edges.left.to_px())
+}
+
+/// Sets an attribute of the document element. Changing it restyles the element, and with it
+/// the descendants inheriting from it.
+fn set_document_attribute(doc: &Document, log: &mut UndoLog, name: Atom, value: String) -> Result<()> {
+	let root = try!(doc.GetDocumentElement().ok_or_else(|| {
+		PatchError::UnknownId.error("operation targets a missing document element")
+	}));
+	try!(check_mutable(root.upcast()));
+	log.modifying(&root);
+	root.set_string_attribute(&name, DOMString::from(value));
+	Ok(())
+}
+
+/// The names of the subscribed event types, along with whether each one is vetoable.
+pub fn subscription_list(subscriptions: EventSet, vetoable: EventSet) -> Vec<(String, bool)> {
+	subscriptions.names().into_iter().map(|name| {
+		(name.to_owned(), vetoable.contains(EventSet::from_name(name).unwrap()))
+	}).collect()
+}
+
+/// The index of the child a node inserted at `index` among `child_count` children goes before,
+/// or `None` if it goes after the last one.
+pub fn insertion_index(index: u32, child_count: u32) -> Option<usize> {
+	if index < child_count { Some(index as usize) } else { None }
+}
+
+/// Inserts the node read from an `InsertAt` payload among the children of `parent`, at the
+/// position `insertion_index` gives.
+pub fn insert_child_at(parent: &Node, index: u32, new: &Node, log: &mut UndoLog) -> Result<()> {
+	try!(check_mutable(parent));
+	let next = insertion_index(index, parent.children_count()).and_then(|index| parent.children().nth(index));
+	try!(check_mutation(parent.InsertBefore(new, next.r())));
+	log.inserted(new);
+	Ok(())
+}
+
+/// The editing host a selection ending in `node` would select text of.
+fn selection_host(node: &Node) -> Option<Root<Element>> {
+	match node.downcast::<Element>() {
+		Some(element) => element.editing_host(),
+		None => node.GetParentElement().and_then(|parent| parent.editing_host())
+	}
+}
+
+/// Splits a content type into its lowercased essence, i.e. `text/html`, and the name of the
+/// encoding its `charset` parameter labels, if it has one. Unparsable types and unknown charsets
+/// are errors.
+pub fn parse_content_type(value: &str) -> Result<(String, Option<String>)> {
+	let Mime(top, sub, params) = try!(value.parse::<Mime>().map_err(|_| {
+		PatchError::Malformed.error("invalid content type")
+	}));
+	let essence = format!("{}/{}", top, sub).to_ascii_lowercase();
+	let charset = match params.iter().find(|&&(ref attr, _)| *attr == Attr::Charset) {
+		Some(&(_, ref value)) => match encoding_from_whatwg_label(&value.to_string()) {
+			Some(encoding) => Some(encoding.whatwg_name().unwrap_or(encoding.name()).to_owned()),
+			None => return Err(PatchError::Malformed.error("unknown charset"))
+		},
+		None => None
+	};
+	Ok((essence, charset))
+}
+
+/// Applies the specs of a `PatchChildren` op to the children of `node`.
+fn patch_children(doc: &Document, node: &Node, specs: Vec<ChildSpec>, log: &mut UndoLog) -> Result<()> {
+	try!(check_mutable(node));
+	let existing = node.children().collect::<Vec<_>>();
+	let kept_past_end = specs.iter().enumerate().any(|(index, spec)| match *spec {
+		ChildSpec::Keep(..) => index >= existing.len(),
+		ChildSpec::Create(_) => false,
+	});
+	if kept_past_end {
+		return Err(PatchError::HierarchyViolation.error("child spec keeps a missing child"));
+	}
+
+	let len = specs.len();
+	for (index, spec) in specs.into_iter().enumerate() {
+		match spec {
+			ChildSpec::Keep(attrs, text) => {
+				let child = &existing[index];
+				try!(check_mutable(child));
+				if let Some(attrs) = attrs {
+					let element = try!(child.downcast::<Element>().ok_or_else(|| {
+						PatchError::TypeMismatch.error("child spec modifies attributes of a non-element")
+					}));
+					log.modifying(element);
+					try!(read_attrs_into(&mut Cursor::new(attrs), element));
+				}
+				if let Some(text) = text {
+					let data = try!(child.downcast::<CharacterData>().ok_or_else(|| {
+						PatchError::TypeMismatch.error("child spec sets the text of a non-text node")
+					}));
+					log.changing_data(data);
+					data.set_data(DOMString::from(text));
+				}
+			},
+			ChildSpec::Create(payload) => {
+				let new = try!(try!(read_node(&mut Cursor::new(payload), doc)).ok_or_else(|| {
+					PatchError::Malformed.error("child spec without a node")
+				}));
+				match existing.get(index) {
+					Some(old) => try!(remove_node(doc, node, old, Some(&*new), log)),
+					None => try!(check_mutation(node.AppendChild(&new)))
+				}
+				log.inserted(&new);
+			}
+		}
+	}
+
+	for old in existing.iter().skip(len) {
+		try!(remove_node(doc, node, old, None, log));
+	}
+	Ok(())
+}
+
+/// Builds the nodes of a payload in the encoding of an `AppendMultiple` patch into a fragment.
+fn read_fragment(doc: &Document, payload: Vec<u8>) -> Result<Root<DocumentFragment>> {
+	// The fragment is never inserted itself, so it doesn't need a server id.
+	let fragment = DocumentFragment::new(0, doc);
+	let mut reader = Cursor::new(payload);
+	while let Some(child) = try!(read_node(&mut reader, doc)) {
+		try!(check_mutation(fragment.upcast::<Node>().AppendChild(&*child)));
+	}
+	Ok(fragment)
+}
+
+/// Puts the element read from an `UpgradeTextToElement` payload in place of a text node, under
+/// the id of the text node.
+pub fn upgrade_text_to_element(doc: &Document, text: &Node, new: &Node, log: &mut UndoLog) -> Result<()> {
+	if text.is::<Element>() {
+		return Err(PatchError::TypeMismatch.error("upgrade targets an element"));
+	}
+	if !text.is::<Text>() {
+		return Err(PatchError::TypeMismatch.error("upgrade targets a node that isn't text"));
+	}
+	let parent = try!(text.GetParentNode().ok_or_else(|| {
+		PatchError::HierarchyViolation.error("upgrade targets a root node")
+	}));
+	try!(check_mutable(&parent));
+	if !new.is::<Element>() {
+		return Err(PatchError::TypeMismatch.error("upgrade payload isn't an element"));
+	}
+	// The element is registered under the id when it is inserted, after the text node is
+	// unregistered.
+	new.set_id(text.get_id());
+	try!(remove_node(doc, &parent, text, Some(new), log));
+	log.inserted(new);
+	Ok(())
+}
+
+/// Appends the children of the fragment read from a `CreateChildren` payload to a node, then
+/// replies with their ids in order.
+pub fn create_children(doc: &Document, node: &Node, fragment: &DocumentFragment, reply: u32, log: &mut UndoLog)
+                       -> Result<()> {
+	try!(check_mutable(node));
+	let children = fragment.upcast::<Node>().children().collect::<Vec<_>>();
+	try!(check_mutation(node.AppendChild(fragment.upcast())));
+	for child in &children {
+		log.inserted(child);
+	}
+	let ids = children.iter().map(|child| child.get_id()).collect();
+	doc.window().vdom_chan().send(ServerMsg::Reply(reply, Reply::Ids(ids)));
+	Ok(())
+}
+
+/// Replaces the children of a node with those of the fragment read from a `ReplaceChildren`
+/// payload, unregistering the ids of the removed subtrees.
+pub fn replace_children(doc: &Document, node: &Node, fragment: &DocumentFragment, log: &mut UndoLog)
+                        -> Result<()> {
+	try!(check_mutable(node));
+	for child in node.children().collect::<Vec<_>>() {
+		try!(remove_node(doc, node, &child, None, log));
+	}
+	try!(check_mutation(node.AppendChild(fragment.upcast())));
+	for child in node.children() {
+		log.inserted(&child);
+	}
+	Ok(())
+}
+
+/// The names and serialized values of the longhands of the inline style of an element that a
+/// property expands to, in the order of the shorthand, leaving out those that aren't set.
+fn inline_longhands(element: &Element, name: &str) -> Vec<(String, String)> {
+	let name = name.to_ascii_lowercase();
+	let longhands = match Shorthand::from_name(&name) {
+		Some(shorthand) => shorthand.longhands().iter().map(|&longhand| Atom::from(longhand)).collect(),
+		None => vec![Atom::from(&*name)]
+	};
+	longhands.into_iter().filter_map(|longhand| {
+		element.get_inline_style_declaration(&longhand)
+		       .map(|declaration| (String::from(&*longhand), declaration.value()))
+	}).collect()
+}
+
+/// Exchanges the positions of two nodes, which must both have a parent and not contain one
+/// another.
+fn swap_nodes(doc: &Document, a: &Node, b: &Node, log: &mut UndoLog) -> Result<()> {
+	if a == b {
+		return Ok(());
+	}
+	try!(check_mutable(a));
+	try!(check_mutable(b));
+	if a.is_ancestor_of(b) || b.is_ancestor_of(a) {
+		return Err(PatchError::HierarchyViolation.error("swap of a node with its ancestor"));
+	}
+	let (a_parent, b_parent) = match (a.GetParentNode(), b.GetParentNode()) {
+		(Some(a_parent), Some(b_parent)) => (a_parent, b_parent),
+		_ => return Err(PatchError::HierarchyViolation.error("swap of a node without a parent"))
+	};
+	let a_next = a.GetNextSibling();
+	let b_next = b.GetNextSibling();
+
+	// Adjacent siblings only need the second one moved before the first; otherwise each node is
+	// moved to where the other was, which the other's old next sibling still marks.
+	if a_next.as_ref().map_or(false, |next| &**next == b) {
+		move_node(doc, log, b, &a_parent, Some(a))
+	} else if b_next.as_ref().map_or(false, |next| &**next == a) {
+		move_node(doc, log, a, &b_parent, Some(b))
+	} else {
+		try!(move_node(doc, log, a, &b_parent, b_next.r()));
+		move_node(doc, log, b, &a_parent, a_next.r())
+	}
+}
+
+fn format_bold(doc: &Document, node: &Node, start: usize, end: usize, bold: bool, log: &mut UndoLog)
+               -> Result<()> {
+	let host = match node.downcast::<Element>() {
+		Some(host) if host.editing_host().r() == Some(host) => host,
+		_ => return Err(PatchError::TypeMismatch.error("formatting of a node that isn't an editing host"))
+	};
+	try!(check_mutable(node));
+	let text = format::text_runs(host).into_iter().map(|run| run.text).collect::<String>();
+	if !format::valid_range(&text, start, end) {
+		return Err(PatchError::Malformed.error("formatting range outside of the text"));
+	}
+	if bold {
+		try!(format::wrap_bold(host, start, end, log));
+	} else {
+		try!(format::unwrap_bold(host, start, end, log));
+	}
+	doc.window().vdom_chan().send(ServerMsg::ContentFormatted(node.get_id(), format::text_runs(host)));
+	Ok(())
+}
+
+/// Applies a batch of moves, as (node, parent, before) ids, in order. A move involving a missing
+/// node fails the batch. The batch is first played out on the ids of the children of the parents it
+/// involves, so that it fails before mutating the DOM if a move is invalid once the earlier
+/// ones are made; debug builds check the children of those parents against that arrangement
+/// afterwards. The ids of the moved nodes stay registered throughout, since a node moved within
+/// the document is registered under its id again as soon as it is inserted.
+fn move_nodes(doc: &Document, log: &mut UndoLog, moves: Vec<(u64, u64, Option<u64>)>) -> Result<()> {
+	let mut resolved = vec![];
+	for (id, parent, before) in moves {
+		let (node, parent) = (try!(target_node(doc, id)), try!(target_node(doc, parent)));
+		let before = match before {
+			Some(before) => Some(try!(target_node(doc, before))),
+			None => None
+		};
+		try!(check_mutable(&node));
+		try!(check_mutable(&parent));
+		resolved.push((node, parent, before));
+	}
+
+	let mut parents: Vec<Root<Node>> = vec![];
+	for &(ref node, ref parent, _) in &resolved {
+		for involved in node.GetParentNode().into_iter().chain(Some(Root::from_ref(&**parent))) {
+			if !parents.contains(&involved) {
+				parents.push(involved);
+			}
+		}
+	}
+	let child_ids = |parent: &Node| parent.children().map(|child| child.get_id()).collect::<Vec<_>>();
+	let mut arrangement = Arrangement::new(parents.iter().map(|parent| child_ids(parent)).collect());
+	for &(ref node, ref parent, ref before) in &resolved {
+		if node.is_inclusive_ancestor_of(parent) {
+			return Err(PatchError::HierarchyViolation.error("node moved into itself"));
+		}
+		let index = parents.iter().position(|involved| involved == parent).unwrap();
+		try!(arrangement.move_node(node.get_id(), index, before.as_ref().map(|before| before.get_id())));
+	}
+
+	for (node, parent, before) in resolved {
+		try!(move_node(doc, log, &node, &parent, before.r()));
+	}
+	debug_assert!(parents.iter().enumerate().all(|(index, parent)| child_ids(parent) == arrangement.children(index)),
+	              "moves left the children of a parent out of order");
+	Ok(())
+}
+
+/// Moves a node within the document. Its subscriptions are kept like the rest of its state, and
+/// if it is or contains the focused element, that element stays focused rather than being
+/// blurred as a removed one is. Likewise, the subtrees frozen under it stay frozen.
+fn move_node(doc: &Document, log: &mut UndoLog, node: &Node, parent: &Node, before: Option<&Node>) -> Result<()> {
+	let frozen = node.frozen_roots();
+	let focused = doc.get_focused_element().and_then(|focused| {
+		if node.is_inclusive_ancestor_of(focused.upcast()) { Some(focused) } else { None }
+	});
+	let focus_visible = focused.as_ref().map_or(false, |focused| focused.get_focus_visible_state());
+	log.removing(node);
+	try!(check_mutation(parent.InsertBefore(node, before)));
+	if let Some(ref focused) = focused {
+		doc.keep_focus(focused, focus_visible);
+	}
+	for frozen in frozen {
+		frozen.set_frozen(true);
+	}
+	Ok(())
+}
+
+/// The tags of the ops this client applies, which are numbered from 1 without gaps.
+pub fn supported_ops() -> Vec<u8> {
+	(OP_SET_CLIPBOARD..OP_INVALIDATE_LAYOUT + 1).collect()
+}
+
+/// The changes a `DiffAttributes` op makes to an element whose attributes have the values
+/// `value_of` gives, in order: the attributes of `set` whose value differs, to their new values,
+/// then the attributes of `remove` the element has or `set` gave it, to `None`.
+pub fn attribute_changes<F>(mut value_of: F, set: Vec<(String, String)>, remove: Vec<String>)
+                            -> Vec<(String, Option<String>)>
+	where F: FnMut(&str) -> Option<String> {
+	let mut changes = vec![];
+	for (name, value) in set {
+		if value_of(&name).as_ref() != Some(&value) {
+			changes.push((name, Some(value)));
+		}
+	}
+	for name in remove {
+		let set_by_diff = changes.iter().any(|&(ref changed, _)| *changed == name);
+		if set_by_diff || value_of(&name).is_some() {
+			changes.push((name, None));
+		}
+	}
+	changes
+}
+
+/// Merges consecutive `AppendText` operations on the same node into one, so that a node
+/// streamed to is only changed once per message.
+pub fn coalesce_appends(ops: Vec<PatchOp>) -> Vec<PatchOp> {
+	let mut coalesced: Vec<PatchOp> = vec![];
+	for op in ops {
+		if let PatchOp::AppendText(id, ref text) = op {
+			if let Some(&mut PatchOp::AppendText(last_id, ref mut last_text)) = coalesced.last_mut() {
+				if last_id == id {
+					last_text.push_str(text);
+					continue;
+				}
+			}
+		}
+		coalesced.push(op);
+	}
+	coalesced
+}
+
+/// Reads operations until the end of the message, then applies them.
+pub fn apply_ops<R: Read>(reader: &mut R, doc: &Document, log: &mut UndoLog) -> Result<()> {
+	let mut ops = vec![];
+	while let Some(op) = try!(PatchOp::read_from(reader)) {
+		ops.push(op);
+	}
+	let mut ops = coalesce_appends(ops).into_iter().peekable();
+	while let Some(op) = ops.next() {
+		match op {
+			PatchOp::MoveNode(id, parent, before) => {
+				let mut moves = vec![(id, parent, before)];
+				while let Some(&PatchOp::MoveNode(id, parent, before)) = ops.peek() {
+					moves.push((id, parent, before));
+					ops.next();
+				}
+				try!(move_nodes(doc, log, moves));
+			},
+			op => try!(op.apply(doc, log))
+		}
+	}
+	Ok(())
+}
+
+/// Builds the operations section of a message, as the server sends it.
+pub struct PatchWriter {
+	buf: Vec<u8>,
+}
+
+impl PatchWriter {
+	pub fn new() -> PatchWriter {
+		PatchWriter {
+			buf: vec![],
+		}
+	}
+
+	pub fn push(&mut self, op: &PatchOp) {
+		op.write_to(&mut self.buf).unwrap();
+	}
+
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.buf
+	}
+}
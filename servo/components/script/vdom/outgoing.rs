@@ -0,0 +1,122 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The messages waiting to be sent to the vdom server. Sending blocks once the server stops
+//! reading, so messages are queued and sent from another thread, and the queue is bounded so
+//! that a stalled server doesn't make the client buffer without end. Frequent messages that a
+//! later one supersedes, like `mousemove` events, are dropped to stay within the bound; replies,
+//! errors and the other messages the server relies on never are.
+
+use std::collections::VecDeque;
+use std::io::Result;
+use vdom::error::PatchError;
+use vdom::msg::ServerMsg;
+
+/// The number of messages queued before droppable ones are dropped, unless the server set
+/// another with `SetOutgoingQueue`.
+pub const DEFAULT_QUEUE_BOUND: usize = 1024;
+
+/// The event types whose forwarded events may be dropped.
+static DROPPABLE_EVENTS: [&'static str; 4] = ["mousemove", "touchmove", "wheel", "dragover"];
+
+/// Which droppable messages are dropped when the queue is full.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DropPolicy {
+	/// The oldest queued droppable message, so the server gets the latest state.
+	DropOldest,
+	/// The droppable message being queued.
+	DropNewest,
+}
+
+impl DropPolicy {
+	pub fn to_wire(self) -> u8 {
+		match self {
+			DropPolicy::DropOldest => 0,
+			DropPolicy::DropNewest => 1,
+		}
+	}
+
+	pub fn from_wire(policy: u8) -> Result<DropPolicy> {
+		match policy {
+			0 => Ok(DropPolicy::DropOldest),
+			1 => Ok(DropPolicy::DropNewest),
+			_ => Err(PatchError::Malformed.error("unknown drop policy"))
+		}
+	}
+}
+
+/// Whether a message may be dropped when the queue is full. Blocking events wait for a verdict,
+/// so they are never dropped.
+pub fn is_droppable(msg: &ServerMsg) -> bool {
+	match *msg {
+		ServerMsg::Event(ref event) => DROPPABLE_EVENTS.contains(&&*event.kind),
+		ServerMsg::Tick(_) => true,
+		_ => false
+	}
+}
+
+/// The encoded messages waiting to be sent, each with whether it may be dropped.
+pub struct OutgoingQueue {
+	messages: VecDeque<(bool, Vec<u8>)>,
+	bound: usize,
+	policy: DropPolicy,
+	dropped: u32,
+}
+
+impl OutgoingQueue {
+	pub fn new() -> OutgoingQueue {
+		OutgoingQueue {
+			messages: VecDeque::new(),
+			bound: DEFAULT_QUEUE_BOUND,
+			policy: DropPolicy::DropOldest,
+			dropped: 0,
+		}
+	}
+
+	/// Bounds the queue to `bound` messages, dropping with `policy` beyond it. Messages already
+	/// queued past a lower bound stay.
+	pub fn configure(&mut self, bound: usize, policy: DropPolicy) {
+		self.bound = bound;
+		self.policy = policy;
+	}
+
+	pub fn len(&self) -> usize {
+		self.messages.len()
+	}
+
+	/// Queues a message. If the queue is full, a droppable message is dropped according to the
+	/// policy; a message that can't be dropped is queued past the bound if none is.
+	pub fn push(&mut self, msg: &ServerMsg) {
+		let droppable = is_droppable(msg);
+		if self.messages.len() >= self.bound {
+			if droppable && self.policy == DropPolicy::DropNewest {
+				self.dropped += 1;
+				return;
+			}
+			match self.messages.iter().position(|&(droppable, _)| droppable) {
+				Some(index) => {
+					self.messages.remove(index);
+					self.dropped += 1;
+				},
+				None if droppable => {
+					self.dropped += 1;
+					return;
+				},
+				None => {}
+			}
+		}
+		self.messages.push_back((droppable, msg.to_bytes()));
+	}
+
+	/// Takes the next message to send. Once messages were dropped, the next one is a
+	/// `DroppedMessages` notice counting them.
+	pub fn pop(&mut self) -> Option<Vec<u8>> {
+		if self.dropped > 0 {
+			let notice = ServerMsg::DroppedMessages(self.dropped);
+			self.dropped = 0;
+			return Some(notice.to_bytes());
+		}
+		self.messages.pop_front().map(|(_, msg)| msg)
+	}
+}
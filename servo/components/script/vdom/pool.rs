@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Shells of elements removed by patches, kept for reuse by later patches creating elements of
+//! the same kind, so that a churning list, i.e. a virtualized one, doesn't keep allocating.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The most shells kept for a single element name.
+pub const MAX_POOLED_PER_NAME: usize = 32;
+
+/// The reusable shells of a document, by element name. Nothing is kept until the server asks
+/// for it.
+pub struct ElementPool<K: Hash + Eq, T> {
+	enabled: bool,
+	shells: HashMap<K, Vec<T>>,
+	reused: usize,
+}
+
+impl<K: Hash + Eq, T> ElementPool<K, T> {
+	pub fn new() -> ElementPool<K, T> {
+		ElementPool {
+			enabled: false,
+			shells: HashMap::new(),
+			reused: 0,
+		}
+	}
+
+	/// Starts or stops keeping shells; stopping drops the ones kept so far.
+	pub fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+		if !enabled {
+			self.shells.clear();
+		}
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	/// Keeps a shell for reuse, returning whether there was room for it.
+	pub fn put(&mut self, name: K, shell: T) -> bool {
+		if !self.enabled {
+			return false;
+		}
+		let shells = self.shells.entry(name).or_insert_with(Vec::new);
+		if shells.len() == MAX_POOLED_PER_NAME {
+			return false;
+		}
+		shells.push(shell);
+		true
+	}
+
+	/// Takes a shell for an element with the given name, if one is kept.
+	pub fn take(&mut self, name: &K) -> Option<T> {
+		let shell = self.shells.get_mut(name).and_then(|shells| shells.pop());
+		if shell.is_some() {
+			self.reused += 1;
+		}
+		shell
+	}
+
+	/// How many shells were taken for reuse.
+	pub fn reused(&self) -> usize {
+		self.reused
+	}
+
+	/// How many shells are kept.
+	pub fn len(&self) -> usize {
+		self.shells.values().map(|shells| shells.len()).sum()
+	}
+}
@@ -0,0 +1,28 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Compaction of the id registry of a document, whose map keeps the capacity it grew to after
+//! the server removed large subtrees.
+
+use std::collections::HashMap;
+
+/// The capacity below which a registry isn't worth compacting.
+pub const MIN_COMPACTED_CAPACITY: usize = 256;
+
+/// Whether a registry of `len` ids with room for `capacity` should be shrunk, after `removed`
+/// ids were removed from it since it last was: once more were removed than remain.
+pub fn should_compact(len: usize, capacity: usize, removed: usize) -> bool {
+	capacity > MIN_COMPACTED_CAPACITY && removed > len
+}
+
+/// Shrinks a registry to fit the ids it still holds if `should_compact` says so, returning
+/// whether it did. `removed` counts the removals since the last compaction, and is reset by it.
+pub fn compact<V>(registry: &mut HashMap<u64, V>, removed: &mut usize) -> bool {
+	if !should_compact(registry.len(), registry.capacity(), *removed) {
+		return false;
+	}
+	registry.shrink_to_fit();
+	*removed = 0;
+	true
+}
@@ -0,0 +1,47 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Elements the vdom server watches the size of, so it can be told when it changes.
+
+/// The nodes the server is watching the size of, with the size it was last told about.
+pub struct ResizeWatch {
+	nodes: Vec<(u64, Option<(i32, i32)>)>,
+}
+
+impl ResizeWatch {
+	pub fn new() -> ResizeWatch {
+		ResizeWatch {
+			nodes: vec![],
+		}
+	}
+
+	/// Starts or stops watching the size of the node with `id`. The first size of a newly
+	/// watched node is reported.
+	pub fn observe(&mut self, id: u64, observe: bool) {
+		let watched = self.nodes.iter().any(|&(other, _)| other == id);
+		if observe && !watched {
+			self.nodes.push((id, None));
+		} else if !observe {
+			self.nodes.retain(|&(other, _)| other != id);
+		}
+	}
+
+	/// The ids of the watched nodes.
+	pub fn ids(&self) -> Vec<u64> {
+		self.nodes.iter().map(|&(id, _)| id).collect()
+	}
+
+	/// Records the size of a watched node after a reflow, returning whether it differs from the
+	/// one the server was last told about, so it should be told.
+	pub fn update(&mut self, id: u64, width: i32, height: i32) -> bool {
+		match self.nodes.iter_mut().find(|&&mut (other, _)| other == id) {
+			Some(&mut (_, ref mut size)) => {
+				let changed = *size != Some((width, height));
+				*size = Some((width, height));
+				changed
+			},
+			None => false
+		}
+	}
+}
@@ -0,0 +1,48 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Changes of the selection of the focused editing host, debounced so that a burst of key
+//! presses handled together, i.e. a held shift+arrow, is reported to the vdom server once.
+
+/// An end of a selection, as the id of a node and an offset in UTF-8 bytes into its text.
+pub type SelectionEnd = (u64, u32);
+
+/// The selection last reported to the server, and the one to report next.
+pub struct SelectionReports {
+	reported: Option<(SelectionEnd, SelectionEnd)>,
+	pending: Option<(SelectionEnd, SelectionEnd)>,
+}
+
+impl SelectionReports {
+	pub fn new() -> SelectionReports {
+		SelectionReports {
+			reported: None,
+			pending: None,
+		}
+	}
+
+	/// Records the selection after it may have changed, from `anchor` to `focus`.
+	pub fn changed(&mut self, anchor: SelectionEnd, focus: SelectionEnd) {
+		self.pending = Some((anchor, focus));
+	}
+
+	/// The selection to report once the events handled together are done, unless it ended up
+	/// where it was last reported.
+	pub fn flush(&mut self) -> Option<(SelectionEnd, SelectionEnd)> {
+		match self.pending.take() {
+			Some(selection) if self.reported != Some(selection) => {
+				self.reported = Some(selection);
+				Some(selection)
+			},
+			_ => None
+		}
+	}
+
+	/// Forgets the selection after the editing host it was in was removed, so that one in a
+	/// host focused next is reported even if it has the same ends.
+	pub fn forget(&mut self) {
+		self.reported = None;
+		self.pending = None;
+	}
+}
@@ -0,0 +1,24 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The cascade order the vdom server gave the stylesheets it pushed.
+
+/// Orders the stylesheets of a document, given in tree order along with the id of the node
+/// they belong to. Later sheets win ties between equally specific rules, so the sheets of the
+/// nodes in `order` are moved to be in that order, within the places they held between
+/// themselves; the other sheets keep their place. Ids without a sheet are ignored.
+pub fn cascade_order<T>(sheets: Vec<(u64, T)>, order: &[u64]) -> Vec<T> {
+	let ranks = sheets.iter()
+	                  .map(|&(id, _)| order.iter().position(|&other| other == id))
+	                  .collect::<Vec<_>>();
+	let mut listed = (0..ranks.len()).filter(|&index| ranks[index].is_some()).collect::<Vec<_>>();
+	listed.sort_by_key(|&index| ranks[index]);
+	let mut listed = listed.into_iter();
+
+	let mut sheets = sheets.into_iter().map(|(_, sheet)| Some(sheet)).collect::<Vec<_>>();
+	(0..ranks.len()).map(|index| {
+		let source = if ranks[index].is_some() { listed.next().unwrap() } else { index };
+		sheets[source].take().unwrap()
+	}).collect()
+}
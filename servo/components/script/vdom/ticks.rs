@@ -0,0 +1,32 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Animation frame ticks the vdom server subscribed to, throttled so that a slow server isn't
+//! flooded with them.
+
+/// Lets through at most one tick per interval, whatever the frame rate. The ticks in between
+/// are dropped, so each tick sent has the latest timestamp.
+pub struct TickThrottle {
+	interval_ns: u64,
+	last_sent: Option<u64>,
+}
+
+impl TickThrottle {
+	/// A throttle for a minimum interval between ticks in milliseconds; 0 lets every tick through.
+	pub fn new(interval_ms: u32) -> TickThrottle {
+		TickThrottle {
+			interval_ns: interval_ms as u64 * 1000 * 1000,
+			last_sent: None,
+		}
+	}
+
+	/// Whether a tick at `now`, in nanoseconds, should be sent.
+	pub fn tick(&mut self, now: u64) -> bool {
+		let send = self.last_sent.map_or(true, |last_sent| now - last_sent >= self.interval_ns);
+		if send {
+			self.last_sent = Some(now);
+		}
+		send
+	}
+}
@@ -0,0 +1,64 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The timers of a document's vdom client, which are fired by the constellation's timer
+//! scheduler like those of a window. At most one timer of each kind is armed; arming it again
+//! replaces it, and the event of a timer that was replaced or cancelled is ignored when it fires.
+
+use script_traits::TimerEventId;
+
+/// What a vdom timer does when it fires.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VdomTimer {
+	/// Reports the tooltip of the element the mouse moved onto at a generation of the dwell.
+	TooltipDwell(u32),
+	/// Forwards the `mousemove` events held back once they are due.
+	FlushMouseMoves,
+}
+
+impl VdomTimer {
+	fn same_kind(&self, other: &VdomTimer) -> bool {
+		match (*self, *other) {
+			(VdomTimer::TooltipDwell(_), VdomTimer::TooltipDwell(_)) => true,
+			(VdomTimer::FlushMouseMoves, VdomTimer::FlushMouseMoves) => true,
+			_ => false,
+		}
+	}
+}
+
+/// The armed vdom timers of a document, by the id of their timer event.
+pub struct VdomTimers {
+	next_id: u32,
+	armed: Vec<(TimerEventId, VdomTimer)>,
+}
+
+impl VdomTimers {
+	pub fn new() -> VdomTimers {
+		VdomTimers {
+			next_id: 0,
+			armed: vec![],
+		}
+	}
+
+	/// Arms a timer in place of the one of its kind, and returns the id of the timer event to
+	/// ask the scheduler for.
+	pub fn arm(&mut self, timer: VdomTimer) -> TimerEventId {
+		self.cancel(timer);
+		let id = TimerEventId(self.next_id);
+		self.next_id = self.next_id.wrapping_add(1);
+		self.armed.push((id, timer));
+		id
+	}
+
+	/// Disarms the timer of the same kind as `timer`, if one is armed.
+	pub fn cancel(&mut self, timer: VdomTimer) {
+		self.armed.retain(|&(_, ref armed)| !armed.same_kind(&timer));
+	}
+
+	/// The timer the event `id` fired for, which is disarmed, unless it was replaced or
+	/// cancelled since it was armed.
+	pub fn fired(&mut self, id: TimerEventId) -> Option<VdomTimer> {
+		self.armed.iter().position(|&(armed, _)| armed == id).map(|index| self.armed.remove(index).1)
+	}
+}
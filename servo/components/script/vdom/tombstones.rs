@@ -0,0 +1,71 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Ids of recently removed nodes, kept so that a server patching a node it no longer has in
+//! the document can be told so, rather than that the id was never seen.
+
+use std::collections::HashMap;
+
+/// How long a removed id is remembered, in nanoseconds.
+pub const TOMBSTONE_LIFETIME_NS: u64 = 30 * 1000 * 1000 * 1000;
+
+/// Why a node id isn't in the document.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LookupError {
+	/// The id was never registered, or was removed long enough ago to be forgotten.
+	NotFound,
+	/// The node was removed from the document recently.
+	Removed,
+}
+
+/// The tombstones of a document, which are only kept once the server asked for them.
+pub struct Tombstones {
+	enabled: bool,
+	/// The removed ids, with the time they were removed at.
+	removed: HashMap<u64, u64>,
+}
+
+impl Tombstones {
+	pub fn new() -> Tombstones {
+		Tombstones {
+			enabled: false,
+			removed: HashMap::new(),
+		}
+	}
+
+	/// Starts or stops keeping tombstones; stopping forgets the ones kept so far.
+	pub fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+		if !enabled {
+			self.removed.clear();
+		}
+	}
+
+	/// Records that the node with `id` was removed from the document at `now`.
+	pub fn bury(&mut self, id: u64, now: u64) {
+		if self.enabled {
+			self.removed.insert(id, now);
+		}
+	}
+
+	/// Forgets the tombstones that expired by `now`. Expired ones are already ignored by
+	/// `lookup_error`, so this only bounds their memory, and is done once per message rather
+	/// than per removal.
+	pub fn prune(&mut self, now: u64) {
+		self.removed.retain(|_, removed_at| now - *removed_at < TOMBSTONE_LIFETIME_NS);
+	}
+
+	/// Forgets the tombstone of `id`, i.e. because a node with that id was inserted again.
+	pub fn forget(&mut self, id: u64) {
+		self.removed.remove(&id);
+	}
+
+	/// Explains why `id`, which isn't in the document, can't be found at `now`.
+	pub fn lookup_error(&self, id: u64, now: u64) -> LookupError {
+		match self.removed.get(&id) {
+			Some(&removed_at) if now - removed_at < TOMBSTONE_LIFETIME_NS => LookupError::Removed,
+			_ => LookupError::NotFound
+		}
+	}
+}
@@ -0,0 +1,52 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The tooltips of elements with a `title`, which the server is sent as `Tooltip` once the
+//! mouse dwelt on one long enough, so it can render its own.
+
+/// How long the mouse has to rest on an element before its tooltip is reported.
+pub const TOOLTIP_DWELL_MS: u64 = 500;
+
+/// The element with a title the mouse is on, if any, and since when.
+pub struct TooltipDwell {
+	hovered: Option<u64>,
+	since: u64,
+	generation: u32,
+	reported: bool,
+}
+
+impl TooltipDwell {
+	pub fn new() -> TooltipDwell {
+		TooltipDwell {
+			hovered: None,
+			since: 0,
+			generation: 0,
+			reported: false,
+		}
+	}
+
+	/// Records that the mouse moved onto the element with `id` at `now`, in nanoseconds, or off
+	/// any element with a title. Returns the generation to pass to `dwelt` once the dwell time
+	/// passed, unless the mouse is still on the same element or on none.
+	pub fn hover(&mut self, id: Option<u64>, now: u64) -> Option<u32> {
+		if id == self.hovered {
+			return None;
+		}
+		self.hovered = id;
+		self.since = now;
+		self.generation = self.generation.wrapping_add(1);
+		self.reported = false;
+		id.map(|_| self.generation)
+	}
+
+	/// The element whose tooltip to report at `now`, if the mouse is still where it was at
+	/// `generation`, rested there for the dwell time, and its tooltip wasn't reported yet.
+	pub fn dwelt(&mut self, generation: u32, now: u64) -> Option<u64> {
+		if generation != self.generation || self.reported || now - self.since < TOOLTIP_DWELL_MS * 1000 * 1000 {
+			return None;
+		}
+		self.reported = self.hovered.is_some();
+		self.hovered
+	}
+}
@@ -0,0 +1,52 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Building the trees of node payloads with an explicit stack, so that a deeply nested payload
+//! fails to read instead of overflowing the stack of the script thread.
+
+use std::io::Result;
+use vdom::error::PatchError;
+
+/// The deepest elements may be nested in a node payload.
+pub const MAX_NODE_DEPTH: usize = 512;
+
+/// A step of a node payload.
+pub enum NodeToken<N> {
+	/// A node without children, i.e. a text node.
+	Leaf(N),
+	/// An element, whose children follow until the matching `Close`.
+	Open(N),
+	/// The end of the children of the innermost open element, or of the payload.
+	Close,
+}
+
+/// Builds the next node of a payload from its tokens, appending children to their parent once
+/// they are complete. Returns `None` if the payload ends before a node; fails if elements are
+/// nested deeper than `MAX_NODE_DEPTH`.
+pub fn build_tree<N, F, A>(mut next: F, mut append: A) -> Result<Option<N>>
+	where F: FnMut() -> Result<NodeToken<N>>, A: FnMut(&N, &N) {
+	let mut open = match try!(next()) {
+		NodeToken::Leaf(node) => return Ok(Some(node)),
+		NodeToken::Open(node) => vec![node],
+		NodeToken::Close => return Ok(None)
+	};
+	loop {
+		match try!(next()) {
+			NodeToken::Leaf(node) => append(open.last().unwrap(), &node),
+			NodeToken::Open(node) => {
+				if open.len() >= MAX_NODE_DEPTH {
+					return Err(PatchError::LimitExceeded.error("node payload nested too deeply"));
+				}
+				open.push(node);
+			},
+			NodeToken::Close => {
+				let node = open.pop().unwrap();
+				match open.last() {
+					Some(parent) => append(parent, &node),
+					None => return Ok(Some(node))
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,267 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A log of the DOM mutations made while applying patches, so that they can be undone.
+
+use dom::bindings::inheritance::Castable;
+use dom::bindings::js::{Root, RootedReference};
+use dom::characterdata::CharacterData;
+use dom::element::Element;
+use dom::node::Node;
+use std::io::Result;
+use string_cache::{Atom, Namespace};
+use util::str::DOMString;
+use vdom::error::PatchError;
+use vdom::events::EventSet;
+
+/// An attribute as it was before a mutation.
+struct SavedAttr {
+	local_name: Atom,
+	name: Atom,
+	namespace: Namespace,
+	prefix: Option<Atom>,
+	value: DOMString,
+}
+
+/// The state needed to undo a single mutation.
+enum UndoEntry {
+	/// A node was inserted, undone by removing it.
+	Inserted(Root<Node>),
+	/// A node was removed from its parent, undone by inserting it back before its old next
+	/// sibling and freezing the subtrees that were frozen under it again.
+	Removed {
+		node: Root<Node>,
+		parent: Root<Node>,
+		next: Option<Root<Node>>,
+		frozen: Vec<Root<Node>>,
+	},
+	/// The attributes or subscriptions of an element were modified, undone by restoring both.
+	Attributes {
+		element: Root<Element>,
+		attrs: Vec<SavedAttr>,
+		subscriptions: EventSet,
+	},
+	/// The data of a text or comment node was changed, undone by restoring it.
+	Data {
+		node: Root<CharacterData>,
+		data: DOMString,
+	},
+}
+
+impl UndoEntry {
+	fn undo(self) {
+		match self {
+			UndoEntry::Inserted(node) => {
+				if let Some(parent) = node.GetParentNode() {
+					if let Err(err) = parent.RemoveChild(&node) {
+						warn!("couldn't undo the insertion of node {}: {:?}", node.get_id(), err);
+					}
+				}
+			},
+			UndoEntry::Removed { node, parent, next, frozen } => {
+				if let Err(err) = parent.InsertBefore(&node, next.r()) {
+					warn!("couldn't undo the removal of node {}: {:?}", node.get_id(), err);
+				}
+				for frozen in frozen {
+					frozen.set_frozen(true);
+				}
+			},
+			UndoEntry::Attributes { element, attrs, subscriptions } => {
+				let current = element.attrs().iter()
+				                     .map(|attr| (attr.namespace().clone(), attr.local_name().clone()))
+				                     .collect::<Vec<_>>();
+				for (namespace, local_name) in current {
+					element.remove_attribute(&namespace, &local_name);
+				}
+				for attr in attrs {
+					let value = element.parse_attribute(&attr.namespace, &attr.local_name, attr.value);
+					element.push_new_attribute(attr.local_name, value, attr.name, attr.namespace, attr.prefix);
+				}
+				let node = element.upcast::<Node>();
+				node.unsubscribe(EventSet::all());
+				node.subscribe(subscriptions);
+			},
+			UndoEntry::Data { node, data } => node.set_data(data)
+		}
+	}
+}
+
+/// The most checkpoints a history keeps; creating another one forgets the oldest.
+pub const MAX_CHECKPOINTS: usize = 8;
+
+#[derive(Copy, Clone, PartialEq)]
+enum LogMode {
+	/// Nothing is recorded.
+	Ignored,
+	/// Everything is recorded, to be rolled back if the transaction fails.
+	Transaction,
+	/// Mutations are recorded while there is a checkpoint to revert to.
+	History,
+}
+
+/// The mutations made by a batch of patches, most recent last.
+pub struct UndoLog {
+	entries: Vec<UndoEntry>,
+	mode: LogMode,
+	/// The named checkpoints of a history, as the length of the log when each was created.
+	checkpoints: Vec<(String, usize)>,
+}
+
+impl UndoLog {
+	fn with_mode(mode: LogMode) -> UndoLog {
+		UndoLog {
+			entries: vec![],
+			mode: mode,
+			checkpoints: vec![],
+		}
+	}
+
+	/// Creates a log that records every mutation, for a transaction.
+	pub fn new() -> UndoLog {
+		UndoLog::with_mode(LogMode::Transaction)
+	}
+
+	/// Creates a log that records nothing, for patches that can't be rolled back.
+	pub fn ignored() -> UndoLog {
+		UndoLog::with_mode(LogMode::Ignored)
+	}
+
+	/// Creates the long-lived log of a document, which records mutations only while the server
+	/// has checkpoints it may revert to.
+	pub fn history() -> UndoLog {
+		UndoLog::with_mode(LogMode::History)
+	}
+
+	/// Whether mutations are being recorded, so that the nodes they remove may be put back.
+	pub fn recording(&self) -> bool {
+		match self.mode {
+			LogMode::Ignored => false,
+			LogMode::Transaction => true,
+			LogMode::History => !self.checkpoints.is_empty(),
+		}
+	}
+
+	/// Records that `node` was inserted.
+	pub fn inserted(&mut self, node: &Node) {
+		if self.recording() {
+			self.entries.push(UndoEntry::Inserted(Root::from_ref(node)));
+		}
+	}
+
+	/// Records that `node` is about to be removed from its parent.
+	pub fn removing(&mut self, node: &Node) {
+		if !self.recording() {
+			return;
+		}
+		if let Some(parent) = node.GetParentNode() {
+			self.entries.push(UndoEntry::Removed {
+				node: Root::from_ref(node),
+				parent: parent,
+				next: node.GetNextSibling(),
+				frozen: node.frozen_roots(),
+			});
+		}
+	}
+
+	/// Records that the attributes or subscriptions of `element` are about to be modified.
+	pub fn modifying(&mut self, element: &Element) {
+		if !self.recording() {
+			return;
+		}
+		let attrs = element.attrs().iter().map(|attr| SavedAttr {
+			local_name: attr.local_name().clone(),
+			name: attr.name().clone(),
+			namespace: attr.namespace().clone(),
+			prefix: attr.prefix().clone(),
+			value: attr.Value(),
+		}).collect();
+		self.entries.push(UndoEntry::Attributes {
+			element: Root::from_ref(element),
+			attrs: attrs,
+			subscriptions: element.upcast::<Node>().subscriptions(),
+		});
+	}
+
+	/// Records that the data of `node` is about to be changed.
+	pub fn changing_data(&mut self, node: &CharacterData) {
+		if self.recording() {
+			self.entries.push(UndoEntry::Data {
+				node: Root::from_ref(node),
+				data: node.Data(),
+			});
+		}
+	}
+
+	/// Moves the entries of `other` to the end of this log.
+	pub fn append(&mut self, other: UndoLog) {
+		if self.recording() {
+			self.entries.extend(other.entries);
+		}
+	}
+
+	/// Applies mutations as a transaction, recorded in a log of their own that is rolled back if
+	/// `apply` fails and appended to this one otherwise.
+	pub fn transaction<F>(&mut self, apply: F) -> Result<()>
+		where F: FnOnce(&mut UndoLog) -> Result<()> {
+		let mut transaction = UndoLog::new();
+		match apply(&mut transaction) {
+			Ok(()) => {
+				self.append(transaction);
+				Ok(())
+			},
+			Err(err) => {
+				transaction.rollback();
+				Err(err)
+			}
+		}
+	}
+
+	/// Undoes every recorded mutation, most recent first.
+	pub fn rollback(mut self) {
+		self.undo_to(0);
+	}
+
+	fn undo_to(&mut self, len: usize) {
+		while self.entries.len() > len {
+			self.entries.pop().unwrap().undo();
+		}
+	}
+
+	/// Creates a checkpoint of a history, replacing any other one with the same label.
+	pub fn checkpoint(&mut self, label: String) -> Result<()> {
+		if self.mode != LogMode::History {
+			return Err(PatchError::UnsupportedOp.error("checkpoint outside of the document history"));
+		}
+		self.checkpoints.retain(|&(ref other, _)| *other != label);
+		self.checkpoints.push((label, self.entries.len()));
+
+		if self.checkpoints.len() > MAX_CHECKPOINTS {
+			self.checkpoints.remove(0);
+			// Nothing can be reverted past the oldest remaining checkpoint any more.
+			let forgotten = self.checkpoints[0].1;
+			self.entries.drain(..forgotten);
+			for checkpoint in &mut self.checkpoints {
+				checkpoint.1 -= forgotten;
+			}
+		}
+		Ok(())
+	}
+
+	/// Reverts the DOM to a checkpoint of a history, forgetting the checkpoints created after it.
+	pub fn revert(&mut self, label: &str) -> Result<()> {
+		let index = match self.checkpoints.iter().position(|&(ref other, _)| other == label) {
+			Some(index) => index,
+			None => return Err(PatchError::Malformed.error("revert to an unknown checkpoint"))
+		};
+		let len = self.checkpoints[index].1;
+		self.checkpoints.truncate(index + 1);
+		self.undo_to(len);
+		Ok(())
+	}
+
+	/// The labels of the checkpoints that can be reverted to, oldest first.
+	pub fn checkpoints(&self) -> Vec<&str> {
+		self.checkpoints.iter().map(|&(ref label, _)| &**label).collect()
+	}
+}
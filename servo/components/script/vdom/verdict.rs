@@ -0,0 +1,116 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Synchronous server verdicts on forwarded events.
+//!
+//! Events the server marked vetoable are sent as blocking events, and dispatch waits for the
+//! server to allow or prevent their default action. The script thread isn't receiving from its
+//! vdom port while it waits, so verdicts are picked out of the server's messages by the router
+//! and delivered straight to the waiting dispatch on a pair from `Window::new_script_pair`.
+
+use script_thread::{CommonScriptMsg, ScriptChan, ScriptPort};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Result, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use vdom::error::PatchError;
+use vdom::wire::{ReadWire, WriteWire};
+
+/// The tag of verdict messages from the server, outside the range of patch message types.
+pub const MSG_VERDICT: u8 = 0x80;
+
+/// How long dispatch waits for a verdict before allowing the default action.
+pub const VERDICT_TIMEOUT_MS: u64 = 200;
+
+/// The outcome of waiting for a verdict.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+	Allow,
+	Prevent,
+	/// The server didn't answer in time; the default action is allowed.
+	TimedOut,
+}
+
+/// Reads a verdict message, without its tag.
+pub fn read_verdict<R: Read>(reader: &mut R) -> Result<(u32, Verdict)> {
+	let id = try!(reader.get_u32());
+	match try!(reader.get_u8()) {
+		0 => Ok((id, Verdict::Allow)),
+		1 => Ok((id, Verdict::Prevent)),
+		_ => Err(PatchError::Malformed.error("invalid verdict"))
+	}
+}
+
+/// Writes a verdict message as the server would send it.
+pub fn write_verdict<W: Write>(writer: &mut W, id: u32, prevent: bool) -> Result<()> {
+	try!(writer.put_u8(MSG_VERDICT));
+	try!(writer.put_u32(id));
+	writer.put_bool(prevent)
+}
+
+struct Waiting {
+	next_id: u32,
+	chans: HashMap<u32, Box<ScriptChan + Send>>,
+}
+
+/// The dispatches waiting for a verdict, shared between the script thread and the router.
+#[derive(Clone)]
+pub struct PendingVerdicts(Arc<Mutex<Waiting>>);
+
+impl PendingVerdicts {
+	pub fn new() -> PendingVerdicts {
+		PendingVerdicts(Arc::new(Mutex::new(Waiting {
+			next_id: 0,
+			chans: HashMap::new(),
+		})))
+	}
+
+	/// Registers a channel to deliver a verdict on, returning the id the server must answer to.
+	pub fn register(&self, chan: Box<ScriptChan + Send>) -> u32 {
+		let mut waiting = self.0.lock().unwrap();
+		let id = waiting.next_id;
+		waiting.next_id = waiting.next_id.wrapping_add(1);
+		waiting.chans.insert(id, chan);
+		id
+	}
+
+	/// Stops waiting for a verdict; one arriving later is dropped.
+	pub fn forget(&self, id: u32) {
+		self.0.lock().unwrap().chans.remove(&id);
+	}
+
+	/// Delivers a message from the server if it is a verdict, or hands it back otherwise.
+	pub fn route(&self, msg: Vec<u8>) -> Option<Vec<u8>> {
+		if msg.first() != Some(&MSG_VERDICT) {
+			return Some(msg);
+		}
+		match read_verdict(&mut Cursor::new(&msg[1..])) {
+			Ok((id, verdict)) => {
+				if let Some(chan) = self.0.lock().unwrap().chans.remove(&id) {
+					let _ = chan.send(CommonScriptMsg::VdomVerdict(id, verdict));
+				}
+			},
+			Err(_) => warn!("vdom server sent a malformed verdict")
+		}
+		None
+	}
+
+	/// Blocks until the verdict for `id` is delivered on `port`, or the timeout expires.
+	pub fn wait(&self, id: u32, chan: Box<ScriptChan + Send>, port: Box<ScriptPort + Send>) -> Verdict {
+		thread::spawn(move || {
+			thread::sleep(Duration::from_millis(VERDICT_TIMEOUT_MS));
+			let _ = chan.send(CommonScriptMsg::VdomVerdict(id, Verdict::TimedOut));
+		});
+
+		loop {
+			if let CommonScriptMsg::VdomVerdict(verdict_id, verdict) = port.recv() {
+				if verdict_id == id {
+					self.forget(id);
+					return verdict;
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,79 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Elements the vdom server watches the visibility of, so it can be told whenever the part of
+//! one inside the viewport crosses one of the thresholds it asked about.
+
+use vdom::msg::BoxRect;
+
+/// The fraction of `rect` inside `viewport`. An empty rect, like that of an element that isn't
+/// rendered, is never visible.
+pub fn visible_ratio(rect: &BoxRect, viewport: &BoxRect) -> f64 {
+	let area = rect.width as f64 * rect.height as f64;
+	if area == 0.0 {
+		return 0.0;
+	}
+	let visible = rect.intersection(viewport);
+	visible.width as f64 * visible.height as f64 / area
+}
+
+/// How many of the thresholds a ratio reached. A threshold of 0 is reached as soon as any of the
+/// element is visible.
+fn thresholds_reached(thresholds: &[f64], ratio: f64) -> usize {
+	thresholds.iter().filter(|&&threshold| {
+		if threshold == 0.0 { ratio > 0.0 } else { ratio >= threshold }
+	}).count()
+}
+
+struct WatchedNode {
+	id: u64,
+	thresholds: Vec<f64>,
+	/// The number of thresholds reached as of the last update, if there was one.
+	reached: Option<usize>,
+}
+
+/// The nodes the server is watching the visibility of.
+pub struct VisibilityWatch {
+	nodes: Vec<WatchedNode>,
+}
+
+impl VisibilityWatch {
+	pub fn new() -> VisibilityWatch {
+		VisibilityWatch {
+			nodes: vec![],
+		}
+	}
+
+	/// Starts watching the node with `id` against a list of thresholds, replacing the ones it was
+	/// watched against, or stops watching it if there are none. The next update of the node is
+	/// reported either way.
+	pub fn observe(&mut self, id: u64, thresholds: Vec<f64>) {
+		self.nodes.retain(|node| node.id != id);
+		if !thresholds.is_empty() {
+			self.nodes.push(WatchedNode {
+				id: id,
+				thresholds: thresholds,
+				reached: None,
+			});
+		}
+	}
+
+	/// The ids of the watched nodes.
+	pub fn ids(&self) -> Vec<u64> {
+		self.nodes.iter().map(|node| node.id).collect()
+	}
+
+	/// Records the visible ratio of a watched node, returning whether it crossed one of its
+	/// thresholds since the last update, or had none yet, so the server should be told.
+	pub fn update(&mut self, id: u64, ratio: f64) -> bool {
+		let node = match self.nodes.iter_mut().find(|node| node.id == id) {
+			Some(node) => node,
+			None => return false
+		};
+		let reached = thresholds_reached(&node.thresholds, ratio);
+		let crossed = node.reached != Some(reached);
+		node.reached = Some(reached);
+		crossed
+	}
+}
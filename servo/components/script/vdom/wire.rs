@@ -0,0 +1,132 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Primitive encoding used for the messages exchanged with the vdom server. Integers are
+//! little endian, floats are sent as their raw bits and strings are length-prefixed utf-8.
+
+use std::io::{Read, Result, Write};
+use std::mem;
+use vdom::error::PatchError;
+
+/// The size in bytes of the largest message the client applies, which also bounds the length
+/// of every byte string read from one.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Writes primitive values in the vdom wire format.
+pub trait WriteWire: Write {
+	fn put_u8(&mut self, val: u8) -> Result<()> {
+		self.write_all(&[val])
+	}
+
+	fn put_bool(&mut self, val: bool) -> Result<()> {
+		self.put_u8(val as u8)
+	}
+
+	fn put_u32(&mut self, val: u32) -> Result<()> {
+		self.write_all(&[val as u8, (val >> 8) as u8, (val >> 16) as u8, (val >> 24) as u8])
+	}
+
+	fn put_i32(&mut self, val: i32) -> Result<()> {
+		self.put_u32(val as u32)
+	}
+
+	fn put_u64(&mut self, val: u64) -> Result<()> {
+		try!(self.put_u32(val as u32));
+		self.put_u32((val >> 32) as u32)
+	}
+
+	#[allow(unsafe_code)]
+	fn put_f64(&mut self, val: f64) -> Result<()> {
+		self.put_u64(unsafe { mem::transmute::<f64, u64>(val) })
+	}
+
+	fn put_str(&mut self, val: &str) -> Result<()> {
+		self.put_bytes(val.as_bytes())
+	}
+
+	fn put_bytes(&mut self, val: &[u8]) -> Result<()> {
+		try!(self.put_u32(val.len() as u32));
+		self.write_all(val)
+	}
+
+	fn put_opt_str(&mut self, val: Option<&str>) -> Result<()> {
+		match val {
+			Some(val) => {
+				try!(self.put_bool(true));
+				self.put_str(val)
+			},
+			None => self.put_bool(false)
+		}
+	}
+}
+
+impl<W: Write> WriteWire for W {}
+
+/// Reads primitive values in the vdom wire format.
+pub trait ReadWire: Read {
+	/// Reads a single tag byte, returning `None` if the end of the stream was reached.
+	fn get_tag(&mut self) -> Result<Option<u8>> {
+		let mut buf = [0u8; 1];
+		match try!(self.read(&mut buf)) {
+			0 => Ok(None),
+			_ => Ok(Some(buf[0]))
+		}
+	}
+
+	fn get_u8(&mut self) -> Result<u8> {
+		let mut buf = [0u8; 1];
+		try!(self.read_exact(&mut buf));
+		Ok(buf[0])
+	}
+
+	fn get_bool(&mut self) -> Result<bool> {
+		self.get_u8().map(|val| val != 0)
+	}
+
+	fn get_u32(&mut self) -> Result<u32> {
+		let mut buf = [0u8; 4];
+		try!(self.read_exact(&mut buf));
+		Ok((buf[0] as u32) | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24)
+	}
+
+	fn get_i32(&mut self) -> Result<i32> {
+		self.get_u32().map(|val| val as i32)
+	}
+
+	fn get_u64(&mut self) -> Result<u64> {
+		let low = try!(self.get_u32()) as u64;
+		let high = try!(self.get_u32()) as u64;
+		Ok(low | high << 32)
+	}
+
+	#[allow(unsafe_code)]
+	fn get_f64(&mut self) -> Result<f64> {
+		self.get_u64().map(|val| unsafe { mem::transmute::<u64, f64>(val) })
+	}
+
+	fn get_string(&mut self) -> Result<String> {
+		let buf = try!(self.get_bytes());
+		String::from_utf8(buf).map_err(|_| PatchError::Malformed.error("invalid utf-8 string"))
+	}
+
+	fn get_bytes(&mut self) -> Result<Vec<u8>> {
+		let len = try!(self.get_u32()) as usize;
+		if len > MAX_MESSAGE_SIZE {
+			return Err(PatchError::LimitExceeded.error("byte string longer than the largest message"));
+		}
+		let mut buf = vec![0u8; len];
+		try!(self.read_exact(&mut buf));
+		Ok(buf)
+	}
+
+	fn get_opt_string(&mut self) -> Result<Option<String>> {
+		if try!(self.get_bool()) {
+			self.get_string().map(Some)
+		} else {
+			Ok(None)
+		}
+	}
+}
+
+impl<R: Read> ReadWire for R {}
@@ -50,6 +50,7 @@ use net_traits::response::HttpsState;
 use net_traits::storage_thread::StorageThread;
 use profile_traits::mem;
 use std::any::Any;
+use style_traits::color_scheme::ColorScheme;
 use url::Url;
 use util::ipc::OptionalOpaqueIpcSender;
 
@@ -148,6 +149,8 @@ pub enum ConstellationControlMsg {
     FramedContentChanged(PipelineId, SubpageId),
     /// Report an error from a CSS parser for the given pipeline
     ReportCSSError(PipelineId, String, usize, usize, String),
+    /// Notifies script that the platform's preferred color scheme changed.
+    ColorSchemeChanged(PipelineId, ColorScheme),
 }
 
 /// Used to determine if a script has any pending asynchronous activity.
@@ -186,6 +189,19 @@ pub enum TouchEventType {
     Cancel,
 }
 
+/// The stage of an IME composition session.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum CompositionEventType {
+    /// A new composition was started.
+    Start,
+    /// The text being composed changed.
+    Update,
+    /// The composition was committed with its final text.
+    End,
+    /// The composition was abandoned without committing any text.
+    Cancel,
+}
+
 /// An opaque identifier for a touch point.
 ///
 /// http://w3c.github.io/touch-events/#widl-Touch-identifier
@@ -225,8 +241,12 @@ pub enum CompositorEvent {
     MouseMoveEvent(Option<Point2D<f32>>),
     /// A touch event was generated with a touch ID and location.
     TouchEvent(TouchEventType, TouchId, Point2D<f32>),
+    /// A wheel was scrolled by a delta over a point.
+    WheelEvent(Point2D<f32>, Point2D<f32>),
     /// A key was pressed.
     KeyEvent(Key, KeyState, KeyModifiers),
+    /// An IME composition session progressed, carrying the current composition text.
+    CompositionEvent(CompositionEventType, String),
 }
 
 /// An opaque wrapper around script<->layout channels to avoid leaking message types into
@@ -322,6 +342,9 @@ pub struct InitialScriptState {
     pub pipeline_namespace_id: PipelineNamespaceId,
     /// A ping will be sent on this channel once the script thread shuts down.
     pub content_process_shutdown_chan: IpcSender<()>,
+    /// The HTML id of the element the vdom is mounted under. If `None`, it is mounted under the
+    /// body.
+    pub vdom_mount: Option<String>,
 }
 
 /// Encapsulates external communication with the script thread.
@@ -361,6 +384,9 @@ pub enum ScriptToCompositorMsg {
     ResizeTo(Size2D<u32>),
     /// Script has handled a touch event, and either prevented or allowed default actions.
     TouchEventProcessed(EventResult),
+    /// Script has handled a wheel event sent to a pipeline, and either prevented or allowed the
+    /// default scroll.
+    WheelEventProcessed(PipelineId, EventResult),
     /// Requests that the compositor shut down.
     Exit,
 }
@@ -27,5 +27,8 @@ bitflags! {
         const IN_CHECKED_STATE = 0x20,
         #[doc = "https://html.spec.whatwg.org/multipage/#selector-indeterminate"]
         const IN_INDETERMINATE_STATE = 0x40,
+        #[doc = "This element has focus, and the user agent shows it. \
+                 https://drafts.csswg.org/selectors-4/#the-focus-visible-pseudo"]
+        const IN_FOCUS_VISIBLE_STATE = 0x80,
     }
 }
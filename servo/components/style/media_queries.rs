@@ -7,6 +7,7 @@ use cssparser::{Delimiter, Parser, Token};
 use euclid::size::{Size2D, TypedSize2D};
 use properties::longhands;
 use std::ascii::AsciiExt;
+use style_traits::color_scheme::ColorScheme;
 use util::geometry::ViewportPx;
 use values::specified;
 
@@ -63,6 +64,8 @@ impl<T: Ord> Range<T> {
 pub enum Expression {
     /// http://dev.w3.org/csswg/mediaqueries-3/#width
     Width(Range<specified::Length>),
+    /// https://drafts.csswg.org/mediaqueries-5/#prefers-color-scheme
+    PrefersColorScheme(ColorScheme),
 }
 
 /// http://dev.w3.org/csswg/mediaqueries-3/#media0
@@ -108,6 +111,7 @@ pub enum MediaType {
 pub struct Device {
     pub media_type: MediaType,
     pub viewport_size: TypedSize2D<ViewportPx, f32>,
+    pub color_scheme: ColorScheme,
 }
 
 impl Device {
@@ -115,6 +119,14 @@ impl Device {
         Device {
             media_type: media_type,
             viewport_size: viewport_size,
+            color_scheme: ColorScheme::default(),
+        }
+    }
+
+    pub fn with_color_scheme(self, color_scheme: ColorScheme) -> Device {
+        Device {
+            color_scheme: color_scheme,
+            .. self
         }
     }
 }
@@ -133,6 +145,9 @@ impl Expression {
                 "max-width" => {
                     Ok(Expression::Width(Range::Max(try!(specified::Length::parse_non_negative(input)))))
                 },
+                "prefers-color-scheme" => {
+                    ColorScheme::from_css_keyword(&try!(input.expect_ident())).map(Expression::PrefersColorScheme)
+                },
                 _ => Err(())
             }
         })
@@ -220,6 +235,7 @@ impl MediaQueryList {
                 match *expression {
                     Expression::Width(ref value) =>
                         value.to_computed_range(viewport_size).evaluate(viewport_size.width),
+                    Expression::PrefersColorScheme(color_scheme) => color_scheme == device.color_scheme,
                 }
             });
 
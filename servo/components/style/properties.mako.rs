@@ -5742,6 +5742,23 @@ pub fn parse_one_declaration(name: &str, input: &str, base_url: &Url, error_repo
     }
 }
 
+/// Like `parse_one_declaration`, but the value may end with `!important`. Returns the
+/// declarations along with whether they are important.
+pub fn parse_one_declaration_with_priority(name: &str, input: &str, base_url: &Url,
+                                           error_reporter: Box<ParseErrorReporter + Send>)
+                                           -> Result<(Vec<PropertyDeclaration>, bool), ()> {
+    let context = ParserContext::new(Origin::Author, base_url, error_reporter);
+    let parser = PropertyDeclarationParser {
+        context: &context,
+    };
+    let mut input = Parser::new(input);
+    let declaration = try!(parser.parse_value(name, &mut input));
+    if !input.is_exhausted() {
+        return Err(());
+    }
+    Ok(declaration)
+}
+
 struct PropertyDeclarationParser<'a, 'b: 'a> {
     context: &'a ParserContext<'b>,
 }
@@ -35,6 +35,7 @@ pub enum NonTSPseudoClass {
     Visited,
     Active,
     Focus,
+    FocusVisible,
     Hover,
     Enabled,
     Disabled,
@@ -50,6 +51,7 @@ impl NonTSPseudoClass {
         match *self {
             Active => IN_ACTIVE_STATE,
             Focus => IN_FOCUS_STATE,
+            FocusVisible => IN_FOCUS_VISIBLE_STATE,
             Hover => IN_HOVER_STATE,
             Enabled => IN_ENABLED_STATE,
             Disabled => IN_DISABLED_STATE,
@@ -80,6 +82,7 @@ impl SelectorImpl for ServoSelectorImpl {
             "visited" => Visited,
             "active" => Active,
             "focus" => Focus,
+            "focus-visible" => FocusVisible,
             "hover" => Hover,
             "enabled" => Enabled,
             "disabled" => Disabled,
@@ -224,7 +224,7 @@ impl<Impl: SelectorImplExt> Stylist<Impl> {
 
         self.viewport_constraints = ViewportConstraints::maybe_new(self.device.viewport_size, &cascaded_rule);
         if let Some(ref constraints) = self.viewport_constraints {
-            device = Device::new(MediaType::Screen, constraints.size);
+            device = Device::new(MediaType::Screen, constraints.size).with_color_scheme(device.color_scheme);
         }
         let is_device_dirty = self.is_device_dirty || stylesheets.iter()
             .flat_map(|stylesheet| stylesheet.rules().media())
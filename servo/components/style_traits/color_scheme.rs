@@ -0,0 +1,30 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The color scheme preference matched by the `prefers-color-scheme` media feature.
+
+use std::ascii::AsciiExt;
+
+/// https://drafts.csswg.org/mediaqueries-5/#prefers-color-scheme
+#[derive(Clone, Copy, Debug, Deserialize, Eq, HeapSizeOf, PartialEq, Serialize)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    pub fn from_css_keyword(keyword: &str) -> Result<ColorScheme, ()> {
+        match_ignore_ascii_case! { keyword,
+            "light" => Ok(ColorScheme::Light),
+            "dark" => Ok(ColorScheme::Dark),
+            _ => Err(())
+        }
+    }
+}
+
+impl Default for ColorScheme {
+    fn default() -> ColorScheme {
+        ColorScheme::Light
+    }
+}
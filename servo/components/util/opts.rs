@@ -191,7 +191,10 @@ pub struct Opts {
     pub render_api: RenderApi,
 
     // The path to the vdom ipc file
-    pub vdom_ipc: Option<String>
+    pub vdom_ipc: Option<String>,
+
+    // The HTML id of the element the vdom is mounted under, instead of the body
+    pub vdom_mount: Option<String>
 }
 
 fn print_usage(app: &str, opts: &Options) {
@@ -502,7 +505,8 @@ pub fn default_opts() -> Opts {
         webrender_stats: false,
         use_msaa: false,
         render_api: DEFAULT_RENDER_API,
-        vdom_ipc: None
+        vdom_ipc: None,
+        vdom_mount: None
     }
 }
 
@@ -549,6 +553,7 @@ pub fn from_cmdline_args(args: &[String]) -> ArgumentParsingResult {
     opts.optflag("w", "webrender", "Use webrender backend");
     opts.optopt("G", "graphics", "Select graphics backend (gl or es2)", "gl");
     opts.optopt("", "vdom-ipc", "File containing vdom ipc", "PATH");
+    opts.optopt("", "vdom-mount", "Id of the element to mount the vdom under", "ID");
 
     let opt_match = match opts.parse(args) {
         Ok(m) => m,
@@ -705,6 +710,14 @@ pub fn from_cmdline_args(args: &[String]) -> ArgumentParsingResult {
         None => None
     };
 
+    let vdom_mount = match opt_match.opt_str("vdom-mount") {
+        Some(ref id) => match parse_vdom_mount(id) {
+            Some(id) => Some(id),
+            None => args_fail(&format!("error: the vdom mount must be an HTML id: {}", id)),
+        },
+        None => None
+    };
+
     let opts = Opts {
         is_running_problem_test: is_running_problem_test,
         url: Some(url),
@@ -756,7 +769,8 @@ pub fn from_cmdline_args(args: &[String]) -> ArgumentParsingResult {
         use_webrender: use_webrender,
         webrender_stats: debug_options.webrender_stats,
         use_msaa: debug_options.use_msaa,
-        vdom_ipc: vdom_ipc
+        vdom_ipc: vdom_ipc,
+        vdom_mount: vdom_mount
     };
 
     set_defaults(opts);
@@ -828,3 +842,13 @@ pub fn parse_url_or_filename(cwd: &Path, input: &str) -> Result<Url, ()> {
         Err(_) => Err(()),
     }
 }
+
+/// Parses the id of the element to mount the vdom under, which may be written as an id
+/// selector. Returns `None` if it isn't a valid HTML id, i.e. empty or containing whitespace.
+pub fn parse_vdom_mount(input: &str) -> Option<String> {
+    let id = if input.starts_with('#') { &input[1..] } else { input };
+    if id.is_empty() || id.chars().any(char::is_whitespace) {
+        return None;
+    }
+    Some(id.to_owned())
+}
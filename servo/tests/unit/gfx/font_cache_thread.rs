@@ -4,8 +4,10 @@
 
 use gfx::font_cache_thread::FontCacheThread;
 use ipc_channel::ipc;
+use net_traits::{ControlMsg, LoadConsumer, ResponseAction};
 use style::computed_values::font_family::FontFamily;
-use style::font_face::Source;
+use style::font_face::{Source, UrlSource};
+use url::Url;
 
 #[test]
 fn test_local_web_font() {
@@ -17,5 +19,27 @@ fn test_local_web_font() {
 
   font_cache_thread.add_web_font(family_name, Source::Local(variant_name), out_chan);
 
-  assert_eq!(out_receiver.recv().unwrap(), ());
+  assert_eq!(out_receiver.recv().unwrap(), true);
+}
+
+#[test]
+fn test_failed_web_font() {
+  let (resource_chan, resource_receiver) = ipc::channel().unwrap();
+  let (out_chan, out_receiver) = ipc::channel().unwrap();
+  let font_cache_thread = FontCacheThread::new(resource_chan, None);
+  let family_name = FontFamily::FamilyName(From::from("test family"));
+  let source = Source::Url(UrlSource {
+    url: Url::parse("http://example.com/missing.ttf").unwrap(),
+    format_hints: vec![],
+  });
+
+  font_cache_thread.add_web_font(family_name, source, out_chan);
+
+  match resource_receiver.recv().unwrap() {
+    ControlMsg::Load(_, LoadConsumer::Listener(target), _) => {
+      target.invoke_with_listener(ResponseAction::ResponseComplete(Err("not found".to_owned())));
+    },
+    _ => panic!("expected the font to be loaded"),
+  }
+  assert_eq!(out_receiver.recv().unwrap(), false);
 }
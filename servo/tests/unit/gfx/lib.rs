@@ -4,7 +4,9 @@
 
 extern crate gfx;
 extern crate ipc_channel;
+extern crate net_traits;
 extern crate style;
+extern crate url;
 
 #[cfg(test)] mod font_cache_thread;
 #[cfg(test)] mod text_util;
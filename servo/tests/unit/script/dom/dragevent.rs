@@ -0,0 +1,28 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use script::dom::dragevent::DragData;
+
+#[test]
+fn test_drag_data_keeps_insertion_order() {
+    let mut data = DragData::new();
+    data.set_data("text/plain", "hello");
+    data.set_data("text/uri-list", "http://example.com/");
+    data.set_data("Text/Plain", "bye");
+
+    assert_eq!(data.get_data("text/plain"), Some("bye"));
+    assert_eq!(data.get_data("TEXT/URI-LIST"), Some("http://example.com/"));
+    assert_eq!(data.get_data("text/html"), None);
+    assert_eq!(data.items(), &[("text/plain".to_owned(), "bye".to_owned()),
+                               ("text/uri-list".to_owned(), "http://example.com/".to_owned())]);
+}
+
+#[test]
+fn test_drag_data_clear() {
+    let mut data = DragData::new();
+    data.set_data("text/plain", "hello");
+    data.clear();
+    assert_eq!(data.get_data("text/plain"), None);
+    assert!(data.items().is_empty());
+}
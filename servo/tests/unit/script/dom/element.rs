@@ -0,0 +1,27 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use script::dom::element::{dataset_attr_name, dataset_key};
+
+#[test]
+fn test_dataset_attr_name() {
+    assert_eq!(dataset_attr_name("userId"), "data-user-id");
+    assert_eq!(dataset_attr_name("id"), "data-id");
+    assert_eq!(dataset_attr_name(""), "data-");
+}
+
+#[test]
+fn test_dataset_key() {
+    assert_eq!(dataset_key("data-user-id"), Some("userId".to_owned()));
+    assert_eq!(dataset_key("data-id"), Some("id".to_owned()));
+    assert_eq!(dataset_key("data-x-1"), Some("x-1".to_owned()));
+    assert_eq!(dataset_key("user-id"), None);
+}
+
+#[test]
+fn test_dataset_round_trip() {
+    for key in &["userId", "aBC", "plain"] {
+        assert_eq!(dataset_key(&dataset_attr_name(key)).as_ref().map(|key| &**key), Some(*key));
+    }
+}
@@ -0,0 +1,47 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use script::dom::htmlareaelement::{Area, Shape};
+
+#[test]
+fn test_shape_from_attribute() {
+    assert_eq!(Shape::from_attribute("CIRCLE"), Shape::Circle);
+    assert_eq!(Shape::from_attribute("poly"), Shape::Polygon);
+    assert_eq!(Shape::from_attribute("default"), Shape::Default);
+    assert_eq!(Shape::from_attribute(""), Shape::Rectangle);
+    assert_eq!(Shape::from_attribute("triangle"), Shape::Rectangle);
+}
+
+#[test]
+fn test_parse_coords() {
+    assert_eq!(Area::parse("10, 20, 5", Shape::Circle),
+               Some(Area::Circle { left: 10.0, top: 20.0, radius: 5.0 }));
+    assert_eq!(Area::parse("50,40 0;0", Shape::Rectangle),
+               Some(Area::Rectangle { top_left: (0.0, 0.0), bottom_right: (50.0, 40.0) }));
+    assert_eq!(Area::parse("0,0,10px,0,10,10,7", Shape::Polygon),
+               Some(Area::Polygon { points: vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)] }));
+    assert_eq!(Area::parse("", Shape::Default), Some(Area::Default));
+    assert_eq!(Area::parse("1,2,3", Shape::Rectangle), None);
+    assert_eq!(Area::parse("1,2,0", Shape::Circle), None);
+}
+
+#[test]
+fn test_rect_area_activates_only_inside() {
+    let area = Area::parse("10,10,60,40", Shape::Rectangle).unwrap();
+    assert!(area.hit_test(20.0, 20.0));
+    assert!(area.hit_test(60.0, 40.0));
+    assert!(!area.hit_test(5.0, 20.0));
+    assert!(!area.hit_test(20.0, 45.0));
+}
+
+#[test]
+fn test_circle_and_polygon_hit_test() {
+    let circle = Area::parse("50,50,10", Shape::Circle).unwrap();
+    assert!(circle.hit_test(55.0, 55.0));
+    assert!(!circle.hit_test(59.0, 59.0));
+
+    let triangle = Area::parse("0,0,20,0,0,20", Shape::Polygon).unwrap();
+    assert!(triangle.hit_test(5.0, 5.0));
+    assert!(!triangle.hit_test(15.0, 15.0));
+}
@@ -0,0 +1,85 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use script::dom::bindings::inheritance::Castable;
+use script::dom::bindings::js::Root;
+use script::dom::document::Document;
+use script::dom::htmlbodyelement::HTMLBodyElement;
+use script::dom::htmldivelement::HTMLDivElement;
+use script::dom::htmlhtmlelement::HTMLHtmlElement;
+use script::dom::node::Node;
+use script::dom::text::Text;
+use script::vdom::events::EventSet;
+use script::vdom::ops::PatchOp;
+use std::mem::size_of;
+use vdom::harness::Client;
+
+fn id(node: Option<Root<Node>>) -> Option<u64> {
+    node.map(|node| node.get_id())
+}
+
+#[test]
+fn test_navigate_built_tree() {
+    let client = Client::new();
+    let list = client.create(2, 10, "ul", EventSet::empty());
+    for child in 11..14 {
+        client.create(10, child, "li", EventSet::empty());
+    }
+
+    assert_eq!(id(list.GetParentNode()), Some(2));
+    assert_eq!(id(list.GetPreviousSibling()), Some(3));
+    assert_eq!(id(list.GetNextSibling()), None);
+    assert_eq!(id(list.GetFirstChild()), Some(11));
+    assert_eq!(id(list.GetLastChild()), Some(13));
+
+    let middle = client.node(12);
+    assert_eq!(id(middle.GetPreviousSibling()), Some(11));
+    assert_eq!(id(middle.GetNextSibling()), Some(13));
+    assert_eq!(id(middle.GetParentNode()), Some(10));
+    assert_eq!(id(middle.GetFirstChild()), None);
+
+    let children = list.ChildNodes();
+    assert_eq!(children.Length(), 3);
+    assert_eq!((0..3).map(|index| id(children.Item(index)).unwrap()).collect::<Vec<_>>(), vec![11, 12, 13]);
+    assert_eq!(id(children.Item(3)), None);
+
+    // The child list is live.
+    client.create(10, 14, "li", EventSet::empty());
+    assert_eq!(children.Length(), 4);
+    assert_eq!(id(children.Item(3)), Some(14));
+}
+
+#[test]
+fn test_navigated_nodes_stay_rooted_once_removed() {
+    let client = Client::new();
+    client.create(2, 10, "ul", EventSet::empty());
+    client.create(10, 11, "li", EventSet::empty());
+    client.create_text(11, 12, "item");
+
+    let item = client.node(10).GetFirstChild().unwrap();
+    let children = client.node(10).ChildNodes();
+    client.apply(&[PatchOp::ClearChildren(10)]);
+
+    // The removed node is still usable through the roots held on to, and keeps its subtree.
+    assert_eq!(children.Length(), 0);
+    assert!(!client.has_node(11));
+    assert_eq!(item.get_id(), 11);
+    assert_eq!(id(item.GetParentNode()), None);
+    assert_eq!(id(item.GetFirstChild()), Some(12));
+    assert_eq!(id(item.GetFirstChild().unwrap().GetParentNode()), Some(11));
+}
+
+#[test]
+fn test_heap_size_covers_the_whole_object() {
+    let client = Client::new();
+    client.create(2, 10, "div", EventSet::empty());
+
+    // A node's address is the start of the allocation of its most derived type, so the
+    // allocation it measures holds all of that type's fields.
+    assert!(client.document().upcast::<Node>().heap_size() >= size_of::<Document>());
+    assert!(client.node(1).heap_size() >= size_of::<HTMLHtmlElement>());
+    assert!(client.node(2).heap_size() >= size_of::<HTMLBodyElement>());
+    assert!(client.node(3).heap_size() >= size_of::<Text>());
+    assert!(client.node(10).heap_size() >= size_of::<HTMLDivElement>());
+}
@@ -0,0 +1,43 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use script::dom::bindings::js::Root;
+use script::dom::nodelist::NodeList;
+use script::vdom::events::EventSet;
+use script::vdom::ops::PatchOp;
+use util::str::DOMString;
+use vdom::harness::Client;
+
+fn ids(list: &NodeList) -> Vec<u64> {
+    (0..list.Length()).map(|index| list.Item(index).unwrap().get_id()).collect()
+}
+
+#[test]
+fn test_query_results_are_static_while_child_nodes_are_live() {
+    let client = Client::new();
+    let list = client.create(2, 10, "ul", EventSet::empty());
+    client.create(10, 11, "li", EventSet::empty());
+    client.create(10, 12, "li", EventSet::empty());
+
+    let items: Root<NodeList> = list.query_selector_all(DOMString::from("li")).unwrap();
+    let children = list.ChildNodes();
+    assert!(!items.is_live());
+    assert!(children.is_live());
+    assert_eq!(ids(&items), vec![11, 12]);
+    assert_eq!(ids(&children), vec![11, 12]);
+
+    // Appending and reordering children changes the live list only, and so does removing them.
+    client.create(10, 13, "li", EventSet::empty());
+    client.apply(&[PatchOp::SwapNodes(11, 13)]);
+    assert_eq!(ids(&items), vec![11, 12]);
+    assert_eq!(ids(&children), vec![13, 12, 11]);
+
+    client.apply(&[PatchOp::ClearChildren(10)]);
+    assert_eq!(ids(&items), vec![11, 12]);
+    assert_eq!(children.Length(), 0);
+    assert!(children.Item(0).is_none());
+
+    // A new query sees the tree as it is now.
+    assert_eq!(list.query_selector_all(DOMString::from("li")).unwrap().Length(), 0);
+}
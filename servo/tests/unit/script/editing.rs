@@ -0,0 +1,107 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use msg::constellation_msg::{CONTROL, Key, KeyModifiers, SHIFT};
+use script::editing::{EditBuffer, EditCommand, host_offset, text_position};
+use script::textinput::Direction;
+
+fn type_keys(buffer: &mut EditBuffer, keys: &[Key]) {
+    for &key in keys {
+        let command = EditCommand::from_key(key, KeyModifiers::empty()).unwrap();
+        buffer.apply(&command);
+    }
+}
+
+#[test]
+fn test_edit_command_from_key() {
+    assert_eq!(EditCommand::from_key(Key::A, SHIFT), Some(EditCommand::Insert("A".to_owned())));
+    assert_eq!(EditCommand::from_key(Key::Space, KeyModifiers::empty()),
+               Some(EditCommand::Insert(" ".to_owned())));
+    assert_eq!(EditCommand::from_key(Key::Backspace, KeyModifiers::empty()),
+               Some(EditCommand::Delete(Direction::Backward)));
+    assert_eq!(EditCommand::from_key(Key::Escape, KeyModifiers::empty()), None);
+    // Shortcuts are left to their own handlers.
+    assert_eq!(EditCommand::from_key(Key::V, CONTROL), None);
+}
+
+#[test]
+fn test_typing_into_editable_text() {
+    let mut buffer = EditBuffer::new(vec!["Hello".to_owned()], usize::max_value());
+    assert_eq!(buffer.caret, 5);
+    type_keys(&mut buffer, &[Key::Space, Key::W, Key::O]);
+    assert_eq!(buffer.text(), "Hello wo");
+    type_keys(&mut buffer, &[Key::Backspace, Key::Left, Key::Left, Key::Delete]);
+    assert_eq!(buffer.text(), "Hellow");
+    assert_eq!(buffer.caret, 5);
+    type_keys(&mut buffer, &[Key::Home, Key::Delete]);
+    assert_eq!(buffer.text(), "ellow");
+    type_keys(&mut buffer, &[Key::End, Key::Right, Key::Backspace]);
+    assert_eq!(buffer.text(), "ello");
+}
+
+#[test]
+fn test_editing_keeps_text_nodes_apart() {
+    // i.e. `<div contenteditable>ab<b>cd</b></div>`.
+    let mut buffer = EditBuffer::new(vec!["ab".to_owned(), "cd".to_owned()], 2);
+    // A caret between two texts inserts into the first one.
+    assert_eq!(buffer.apply(&EditCommand::Insert("x".to_owned())), Some(0));
+    assert_eq!(buffer.texts, vec!["abx".to_owned(), "cd".to_owned()]);
+    assert_eq!(buffer.apply(&EditCommand::Delete(Direction::Forward)), Some(1));
+    assert_eq!(buffer.texts, vec!["abx".to_owned(), "d".to_owned()]);
+    assert_eq!(buffer.apply(&EditCommand::Move(Direction::Backward)), None);
+    assert_eq!(buffer.caret, 2);
+}
+
+#[test]
+fn test_editing_empty_and_multibyte_text() {
+    let mut empty = EditBuffer::new(vec![], 0);
+    assert_eq!(empty.apply(&EditCommand::Delete(Direction::Backward)), None);
+    assert_eq!(empty.apply(&EditCommand::Insert("a".to_owned())), Some(0));
+    assert_eq!(empty.texts, vec!["a".to_owned()]);
+
+    let mut buffer = EditBuffer::new(vec!["é!".to_owned()], 1);
+    assert_eq!(buffer.caret, 0);
+    buffer.apply(&EditCommand::Move(Direction::Forward));
+    assert_eq!(buffer.caret, 2);
+    buffer.apply(&EditCommand::Delete(Direction::Backward));
+    assert_eq!(buffer.text(), "!");
+}
+
+#[test]
+fn test_selection_offsets_in_host_text() {
+    // i.e. `<div contenteditable>ab<b>cd</b>é</div>`.
+    let texts = vec!["ab".to_owned(), "cd".to_owned(), "é".to_owned()];
+    assert_eq!(host_offset(&texts, 0, 1), 1);
+    assert_eq!(host_offset(&texts, 1, 0), 2);
+    assert_eq!(host_offset(&texts, 1, 2), 4);
+    // Offsets past the end of their text are clamped to it, and into the character they land in.
+    assert_eq!(host_offset(&texts, 1, 40), 4);
+    assert_eq!(host_offset(&texts, 2, 1), 4);
+    assert_eq!(host_offset(&texts, 2, 9), 6);
+}
+
+#[test]
+fn test_shift_arrows_extend_the_selection() {
+    assert_eq!(EditCommand::from_key(Key::Left, SHIFT), Some(EditCommand::Select(Direction::Backward)));
+    assert_eq!(EditCommand::from_key(Key::End, SHIFT), Some(EditCommand::SelectToEnd));
+
+    // i.e. `<div contenteditable>ab<b>cd</b></div>`, with the caret after the `d`.
+    let mut buffer = EditBuffer::new(vec!["ab".to_owned(), "cd".to_owned()], 4);
+    for _ in 0..3 {
+        buffer.apply(&EditCommand::from_key(Key::Left, SHIFT).unwrap());
+    }
+    assert_eq!((buffer.anchor, buffer.caret), (4, 1));
+    assert_eq!(text_position(&buffer.texts, buffer.anchor), Some((1, 2)));
+    assert_eq!(text_position(&buffer.texts, buffer.caret), Some((0, 1)));
+
+    buffer.apply(&EditCommand::SelectToStart);
+    assert_eq!((buffer.anchor, buffer.caret), (4, 0));
+    // Moving without shift collapses the selection.
+    buffer.apply(&EditCommand::Move(Direction::Forward));
+    assert_eq!((buffer.anchor, buffer.caret), (1, 1));
+
+    assert_eq!(text_position(&buffer.texts, 2), Some((0, 2)));
+    assert_eq!(text_position(&buffer.texts, 5), None);
+    assert_eq!(text_position(&[], 0), None);
+}
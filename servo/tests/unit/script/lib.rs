@@ -2,14 +2,31 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+extern crate canvas_traits;
+extern crate euclid;
+extern crate ipc_channel;
 extern crate msg;
+extern crate net_traits;
+extern crate profile_traits;
 extern crate script;
+extern crate script_traits;
+extern crate string_cache;
+extern crate style_traits;
+extern crate url;
 extern crate util;
 
 #[cfg(all(test, target_pointer_width = "64"))] mod size_of;
+#[cfg(test)] mod editing;
+#[cfg(test)] mod script_thread;
 #[cfg(test)] mod textinput;
+#[cfg(test)] mod vdom;
 #[cfg(test)] mod dom {
     mod bindings;
     mod blob;
+    mod dragevent;
+    mod element;
+    mod htmlareaelement;
+    mod node;
+    mod nodelist;
     mod xmlhttprequest;
 }
@@ -0,0 +1,85 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use script::dom::bindings::inheritance::Castable;
+use script::dom::create::create_element_simple;
+use script::dom::document::Document;
+use script::dom::element::ElementCreator;
+use script::dom::node::Node;
+use script::script_thread::{SizeCache, is_fragment_navigation};
+use script::vdom::events::EventSet;
+use std::cell::Cell;
+use string_cache::Atom;
+use url::Url;
+use vdom::harness::Client;
+
+fn tree_size(document: &Document) -> usize {
+    document.upcast::<Node>().traverse_preorder().fold(0, |total, node| total + node.heap_size())
+}
+
+#[test]
+fn test_dom_tree_reports_are_per_page() {
+    let client = Client::new();
+    let frame = client.add_page("http://b.test/");
+    let div = create_element_simple(10, Atom::from("div"), &frame, ElementCreator::ParserCreated);
+    frame.upcast::<Node>().AppendChild(div.upcast()).unwrap();
+
+    // Each page only counts the nodes of its own document.
+    let reports = client.dom_tree_reports();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].path, vec!["url(about:blank)".to_owned(), "dom-tree".to_owned()]);
+    assert_eq!(reports[0].size, tree_size(&client.document()));
+    assert_eq!(reports[1].path, vec!["url(http://b.test/)".to_owned(), "dom-tree".to_owned()]);
+    assert_eq!(reports[1].size, tree_size(&frame));
+}
+
+#[test]
+fn test_size_cache_reused_until_mutation() {
+    let cache = SizeCache::new();
+    let walks = Cell::new(0);
+    let walk = |size| {
+        walks.set(walks.get() + 1);
+        size
+    };
+
+    assert_eq!(cache.get_or_compute(3, || walk(40)), 40);
+    assert_eq!(cache.get_or_compute(3, || walk(99)), 40);
+    assert_eq!(walks.get(), 1);
+
+    assert_eq!(cache.get_or_compute(4, || walk(56)), 56);
+    assert_eq!(walks.get(), 2);
+}
+
+#[test]
+fn test_dom_tree_size_follows_mutations() {
+    let client = Client::new();
+    let document = client.document();
+    let before = document.dom_tree_size();
+    assert_eq!(before, tree_size(&document));
+    assert_eq!(document.dom_tree_size(), before);
+
+    let div = client.create(2, 10, "div", EventSet::empty());
+    let grown = document.dom_tree_size();
+    assert_eq!(grown, before + div.heap_size());
+    assert_eq!(client.dom_tree_reports()[0].size, grown);
+
+    client.node(2).RemoveChild(&div).unwrap();
+    assert_eq!(document.dom_tree_size(), before);
+    assert_eq!(client.dom_tree_reports()[0].size, before);
+}
+
+#[test]
+fn test_fragment_navigation() {
+    let current = Url::parse("http://example.com/page?q=1#intro").unwrap();
+    let section = Url::parse("http://example.com/page?q=1#section").unwrap();
+    let missing = Url::parse("http://example.com/page?q=1#missing").unwrap();
+    assert!(is_fragment_navigation(&current, &section));
+    assert!(is_fragment_navigation(&current, &missing));
+    assert!(is_fragment_navigation(&current, &current));
+
+    assert!(!is_fragment_navigation(&current, &Url::parse("http://example.com/page?q=1").unwrap()));
+    assert!(!is_fragment_navigation(&current, &Url::parse("http://example.com/page?q=2#section").unwrap()));
+    assert!(!is_fragment_navigation(&current, &Url::parse("http://example.com/other#section").unwrap()));
+    assert!(!is_fragment_navigation(&current, &Url::parse("https://example.com/page?q=1#section").unwrap()));
+}
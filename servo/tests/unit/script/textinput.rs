@@ -13,7 +13,8 @@ use msg::constellation_msg::CONTROL;
 use msg::constellation_msg::SUPER;
 use msg::constellation_msg::{Key, KeyModifiers};
 use script::clipboard_provider::DummyClipboardContext;
-use script::textinput::{TextInput, TextPoint, Selection, Lines, Direction};
+use script::textinput::{TextInput, TextPoint, Selection, Lines, Direction, clipboard_event_type};
+use script::textinput::{normalize_newlines, strip_newlines};
 use util::str::DOMString;
 
 fn text_input(lines: Lines, s: &str) -> TextInput<DummyClipboardContext> {
@@ -317,6 +318,58 @@ fn test_clipboard_paste() {
     assert_eq!(textinput.get_content(), "abcdefg");
 }
 
+#[test]
+fn test_clipboard_cut() {
+    #[cfg(target_os = "macos")]
+    const MODIFIERS: KeyModifiers = SUPER;
+    #[cfg(not(target_os = "macos"))]
+    const MODIFIERS: KeyModifiers = CONTROL;
+
+    let mut textinput = text_input(Lines::Single, "abcdef");
+    textinput.adjust_horizontal(2, Selection::NotSelected);
+    textinput.adjust_horizontal(2, Selection::Selected);
+    textinput.handle_keydown_aux(Key::X, MODIFIERS);
+    assert_eq!(textinput.get_content(), "abef");
+
+    textinput.handle_keydown_aux(Key::V, MODIFIERS);
+    assert_eq!(textinput.get_content(), "abcdef");
+}
+
+#[test]
+fn test_clipboard_copy_then_paste() {
+    #[cfg(target_os = "macos")]
+    const MODIFIERS: KeyModifiers = SUPER;
+    #[cfg(not(target_os = "macos"))]
+    const MODIFIERS: KeyModifiers = CONTROL;
+
+    let mut textinput = TextInput::new(Lines::Single,
+                                       DOMString::from("abc"),
+                                       DummyClipboardContext::new("old"),
+                                       None);
+    textinput.adjust_horizontal(1, Selection::NotSelected);
+    textinput.adjust_horizontal(2, Selection::Selected);
+    textinput.copy_selection();
+    assert_eq!(textinput.get_content(), "abc");
+
+    textinput.clear_selection();
+    textinput.handle_keydown_aux(Key::V, MODIFIERS);
+    assert_eq!(textinput.get_content(), "abcbc");
+}
+
+#[test]
+fn test_clipboard_event_type() {
+    #[cfg(target_os = "macos")]
+    const MODIFIERS: KeyModifiers = SUPER;
+    #[cfg(not(target_os = "macos"))]
+    const MODIFIERS: KeyModifiers = CONTROL;
+
+    assert_eq!(clipboard_event_type(Key::C, MODIFIERS), Some("copy"));
+    assert_eq!(clipboard_event_type(Key::X, MODIFIERS), Some("cut"));
+    assert_eq!(clipboard_event_type(Key::V, MODIFIERS), Some("paste"));
+    assert_eq!(clipboard_event_type(Key::V, KeyModifiers::empty()), None);
+    assert_eq!(clipboard_event_type(Key::A, MODIFIERS), None);
+}
+
 #[test]
 fn test_textinput_cursor_position_correct_after_clearing_selection() {
     let mut textinput = text_input(Lines::Single, "abcdef");
@@ -370,3 +423,75 @@ fn test_textinput_cursor_position_correct_after_clearing_selection() {
     assert_eq!(textinput.edit_point.index, 0);
     assert_eq!(textinput.edit_point.line, 0);
 }
+
+#[test]
+fn test_textinput_composition_session() {
+    let mut textinput = text_input(Lines::Single, "ab");
+    textinput.edit_point = TextPoint { line: 0, index: 1 };
+
+    textinput.start_composition();
+    assert!(textinput.is_composing());
+    textinput.update_composition("n");
+    assert_eq!(textinput.get_content(), "anb");
+    textinput.update_composition("ni");
+    assert_eq!(textinput.get_content(), "anib");
+    assert!(textinput.end_composition("你"));
+    assert!(!textinput.is_composing());
+    assert_eq!(textinput.get_content(), "a你b");
+    assert_eq!(textinput.edit_point.index, 4);
+}
+
+#[test]
+fn test_textinput_composition_cancel() {
+    let mut textinput = text_input(Lines::Single, "ab");
+    textinput.edit_point = TextPoint { line: 0, index: 1 };
+
+    textinput.start_composition();
+    textinput.update_composition("xy");
+    assert_eq!(textinput.get_content(), "axyb");
+    assert!(!textinput.cancel_composition());
+    assert!(!textinput.is_composing());
+    assert_eq!(textinput.get_content(), "ab");
+    assert_eq!(textinput.edit_point.index, 1);
+}
+
+#[test]
+fn test_textinput_composition_replaces_selection() {
+    let mut textinput = text_input(Lines::Single, "hello");
+    textinput.select_all();
+
+    textinput.start_composition();
+    textinput.update_composition("k");
+    assert!(textinput.end_composition("k"));
+    assert_eq!(textinput.get_content(), "k");
+}
+
+#[test]
+fn test_textinput_normalizes_line_endings() {
+    let mut textinput = text_input(Lines::Multiple, "a\r\nb\rc\nd");
+    assert_eq!(textinput.get_content(), "a\nb\nc\nd");
+
+    textinput.set_content(DOMString::from("\r\r\n\n"));
+    assert_eq!(textinput.get_content(), "\n\n\n");
+
+    textinput.set_content(DOMString::from("ab"));
+    textinput.edit_point = TextPoint { line: 0, index: 1 };
+    textinput.insert_string("x\r\ny");
+    assert_eq!(textinput.get_content(), "ax\nyb");
+}
+
+#[test]
+fn test_single_line_textinput_strips_line_endings() {
+    let mut textinput = text_input(Lines::Single, "a\r\nb\rc\nd");
+    assert_eq!(textinput.get_content(), "abcd");
+
+    textinput.edit_point = TextPoint { line: 0, index: 4 };
+    textinput.insert_string("\r\ne");
+    assert_eq!(textinput.get_content(), "abcde");
+}
+
+#[test]
+fn test_normalize_newlines() {
+    assert_eq!(normalize_newlines("a\r\n\r\nb\r"), "a\n\nb\n");
+    assert_eq!(strip_newlines("a\r\n\r\nb\r"), "ab");
+}
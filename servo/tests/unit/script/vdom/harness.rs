@@ -0,0 +1,512 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A vdom client running on the test thread, with stand-ins for its layout thread, compositor
+//! and server. `Client::new` loads the default content; tests then apply operations with
+//! `Client::apply`, inject input with `Client::click` and `Client::send_event`, and assert on
+//! the messages the server received with `Client::drain`. See `test_client_forwards_click`.
+//!
+//! The node payloads of patches are encoded by the client library, so tests build nodes with
+//! `Client::create`, which inserts them as an `Append` patch would.
+
+use euclid::point::Point2D;
+use euclid::rect::Rect;
+use euclid::scale_factor::ScaleFactor;
+use euclid::size::Size2D;
+use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
+use ipc_channel::router::ROUTER;
+use msg::constellation_msg::{ConstellationChan, Failure, PipelineId, PipelineIndex, PipelineNamespaceId};
+use msg::constellation_msg::WindowSizeData;
+use net_traits::image_cache_thread::ImageCacheThread;
+use profile_traits::mem::Report;
+use profile_traits::{mem, time};
+use script::document_loader::DocumentLoader;
+use script::dom::bindings::js::{JS, LayoutJS, Root};
+use script::dom::create::create_element_simple;
+use script::dom::bindings::inheritance::Castable;
+use script::dom::document::{Document, DocumentSource, FocusType, IsHTMLDocument, LayoutDocumentHelpers};
+use script::dom::element::{Element, ElementCreator};
+use script::dom::node::{HAS_CHANGED, HAS_DIRTY_DESCENDANTS, IS_DIRTY, LayoutNodeHelpers, Node};
+use script::dom::text::Text;
+use script::dom::window::ReflowReason;
+use script::layout_interface::{BoxModelResponse, ContentBoxResponse, ContentBoxesResponse, HitTestResponse};
+use script::layout_interface::{LayoutChan, LayoutRPC, MarginStyleResponse, Msg, NodeGeometryResponse};
+use script::layout_interface::{OffsetParentResponse, ReflowQueryType, ResolvedStyleResponse, ScriptReflow};
+use script::page::{Frame, IterablePage, Page};
+use script::script_thread::{ScriptChan, ScriptPort, ScriptThread, SendableMainThreadScriptChan};
+use script::vdom::events::EventSet;
+use script::vdom::msg::{Reply, ServerMsg};
+use script::vdom::ops::{PatchOp, PatchWriter, apply_ops};
+use script::vdom::undo::UndoLog;
+use script::vdom::verdict::write_verdict;
+use script_traits::{CompositorEvent, InitialScriptState, MouseButton, MouseEventType};
+use script_traits::{ScriptToCompositorMsg, TimerEvent, TimerEventRequest, UntrustedNodeAddress};
+use std::any::Any;
+use std::io::{self, Cursor};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use string_cache::Atom;
+use url::Url;
+use util::str::DOMString;
+use util::thread_state;
+
+/// The id of the reply that marks the end of the messages sent before a drain.
+const DRAIN_MARKER: u32 = u32::max_value();
+
+/// A reflow the layout thread was asked for.
+pub struct LayoutReflow {
+    /// Whether the reflow was for display rather than to answer a query.
+    pub for_display: bool,
+    /// The ids of the nodes that were dirty, in tree order.
+    pub dirty: Vec<u64>,
+    /// Why script asked for the reflow.
+    pub reason: ReflowReason,
+}
+
+/// A script thread with its default content, whose other threads are faked.
+pub struct Client {
+    script_thread: Box<ScriptThread>,
+    pipeline: PipelineId,
+    server: Receiver<ServerMsg>,
+    compositor: Receiver<ScriptToCompositorMsg>,
+    reflows: Receiver<LayoutReflow>,
+    fonts: Receiver<IpcSender<bool>>,
+    scheduler: IpcReceiver<TimerEventRequest>,
+    hit_target: Arc<Mutex<Option<usize>>>,
+    prevent: Arc<AtomicBool>,
+    initial: Vec<ServerMsg>,
+    /// The ports of the threads nothing stands in for, which must stay open.
+    _ports: Vec<Box<Any>>,
+}
+
+impl Client {
+    /// Loads the default content on the current thread, which must not have been a script
+    /// thread before, so each test can have only one client.
+    pub fn new() -> Client {
+        let mut ports: Vec<Box<Any>> = vec![];
+        let id = PipelineId::fake_root_pipeline_id();
+
+        let (compositor, compositor_port) = ipc::channel().unwrap();
+        let (control_chan, control_port) = ipc::channel().unwrap();
+        let (constellation_port, constellation_chan) = ConstellationChan::new();
+        let (layout_to_constellation_port, layout_to_constellation_chan) = ConstellationChan::new();
+        let (scheduler_chan, scheduler_port) = ipc::channel().unwrap();
+        let (resource_thread, resource_port) = ipc::channel().unwrap();
+        let (storage_thread, storage_port) = ipc::channel().unwrap();
+        let (image_cache_chan, image_cache_port) = ipc::channel().unwrap();
+        let (time_profiler_chan, time_profiler_port) = ipc::channel().unwrap();
+        let (mem_profiler_chan, mem_profiler_port) = ipc::channel().unwrap();
+        let (shutdown_chan, shutdown_port) = ipc::channel().unwrap();
+        ports.push(Box::new(constellation_port));
+        ports.push(Box::new(layout_to_constellation_port));
+        ports.push(Box::new(resource_port));
+        ports.push(Box::new(storage_port));
+        ports.push(Box::new(image_cache_port));
+        ports.push(Box::new(time_profiler_port));
+        ports.push(Box::new(mem_profiler_port));
+        ports.push(Box::new(shutdown_port));
+
+        let state = InitialScriptState {
+            id: id,
+            parent_info: None,
+            compositor: compositor,
+            control_chan: control_chan,
+            control_port: control_port,
+            constellation_chan: constellation_chan,
+            layout_to_constellation_chan: layout_to_constellation_chan,
+            scheduler_chan: scheduler_chan,
+            failure_info: Failure {
+                pipeline_id: id,
+                parent_info: None,
+            },
+            resource_thread: resource_thread,
+            storage_thread: storage_thread,
+            image_cache_thread: ImageCacheThread::new(image_cache_chan),
+            time_profiler_chan: time::ProfilerChan(time_profiler_chan),
+            mem_profiler_chan: mem::ProfilerChan(mem_profiler_chan),
+            devtools_chan: None,
+            window_size: Some(WindowSizeData {
+                initial_viewport: Size2D::typed(1024_f32, 768_f32),
+                visible_viewport: Size2D::typed(1024_f32, 768_f32),
+                device_pixel_ratio: ScaleFactor::new(1.0),
+            }),
+            pipeline_namespace_id: PipelineNamespaceId(0),
+            content_process_shutdown_chan: shutdown_chan,
+            vdom_mount: None,
+        };
+
+        let hit_target = Arc::new(Mutex::new(None));
+        let (reflow_chan, reflows) = channel();
+        let (font_chan, fonts) = channel();
+        let (layout_port, layout_chan) = LayoutChan::new();
+        spawn_layout(layout_port, hit_target.clone(), reflow_chan, font_chan);
+
+        let prevent = Arc::new(AtomicBool::new(false));
+        let (client_chan, client_port) = ipc::channel().unwrap();
+        let (server_chan, server_port) = ipc::channel().unwrap();
+        let (msg_chan, server) = channel();
+        spawn_server(server_port, client_chan, prevent.clone(), msg_chan);
+
+        let script_thread = ScriptThread::new_headless(state, layout_chan, client_port, server_chan,
+                                                       Url::parse("about:blank").unwrap());
+        let mut client = Client {
+            script_thread: script_thread,
+            pipeline: id,
+            server: server,
+            compositor: ROUTER.route_ipc_receiver_to_new_mpsc_receiver(compositor_port),
+            reflows: reflows,
+            fonts: fonts,
+            scheduler: scheduler_port,
+            hit_target: hit_target,
+            prevent: prevent,
+            initial: vec![],
+            _ports: ports,
+        };
+        client.initial = client.drain();
+        client.reflows();
+        client
+    }
+
+    pub fn document(&self) -> Root<Document> {
+        self.script_thread.root_page().document()
+    }
+
+    /// The node with an id in the document. Panics if there is none.
+    pub fn node(&self, id: u64) -> Root<Node> {
+        self.document().get_node_by_id(id).expect("no node with this id in the document")
+    }
+
+    /// Whether a node with an id is in the document.
+    pub fn has_node(&self, id: u64) -> bool {
+        self.document().get_node_by_id(id).is_some()
+    }
+
+    /// Appends a new element to a node, subscribed to events, as an `Append` patch would.
+    pub fn create(&self, parent: u64, id: u64, name: &str, events: EventSet) -> Root<Node> {
+        let element = create_element_simple(id, Atom::from(name), &self.document(), ElementCreator::ParserCreated);
+        let node = Root::upcast::<Node>(element);
+        node.subscribe(events);
+        self.node(parent).AppendChild(&node).unwrap();
+        node
+    }
+
+    /// Appends a new text node to a node, as an `Append` patch would.
+    pub fn create_text(&self, parent: u64, id: u64, text: &str) -> Root<Node> {
+        let text = Text::new(id, DOMString::from(text), &self.document());
+        let node = Root::upcast::<Node>(text);
+        self.node(parent).AppendChild(&node).unwrap();
+        node
+    }
+
+    /// Applies operations as the server sends them, then handles the end of the message like
+    /// the script thread does.
+    pub fn apply(&self, ops: &[PatchOp]) {
+        let message = patch_ops(ops);
+        self.apply_with(|doc, log| apply_ops(&mut Cursor::new(message), doc, log));
+    }
+
+    /// Applies operations in a `Transaction` op without patches, as the server sends it, then
+    /// handles the end of the message like the script thread does.
+    pub fn apply_transaction(&self, ops: &[PatchOp]) {
+        let message = patch_ops(ops);
+        self.apply_with(|doc, log| {
+            log.transaction(|transaction| apply_ops(&mut Cursor::new(message), doc, transaction))
+        });
+    }
+
+    /// Applies a message whose body `apply` performs, then handles the end of the message like
+    /// the script thread does. This stands in for the ops with node payloads.
+    pub fn apply_with<F>(&self, apply: F) where F: FnOnce(&Document, &mut UndoLog) -> io::Result<()> {
+        self.script_thread.apply_vdom_message(apply);
+        self.script_thread.finish_batch();
+    }
+
+    /// Handles a message as the script thread receives it from the server, header included.
+    pub fn receive(&self, msg: Vec<u8>) {
+        self.script_thread.handle_msg_from_vdom(msg);
+        self.script_thread.finish_batch();
+    }
+
+    /// Handles an event from the compositor in a batch of its own, with the tasks it queued.
+    pub fn send_event(&self, event: CompositorEvent) {
+        self.script_thread.handle_event(self.pipeline, event);
+        self.script_thread.handle_queued_tasks();
+        self.script_thread.finish_batch();
+    }
+
+    /// Fires the timers the script thread asked the scheduler for since the last call in the
+    /// order they were asked for, each once its duration elapsed, in a batch of their own.
+    pub fn fire_timers(&self) {
+        while let Ok(TimerEventRequest(_, source, id, duration)) = self.scheduler.try_recv() {
+            thread::sleep(Duration::from_millis(duration.get()));
+            self.script_thread.handle_timer_event(TimerEvent(source, id));
+        }
+        self.script_thread.finish_batch();
+    }
+
+    /// Finishes the next font preload layout was asked for, waiting for it if there is none yet,
+    /// as the font cache does once the font loaded or failed to. Layout then has the script
+    /// thread reflow for the new font, in a batch of its own.
+    pub fn load_font(&self, loaded: bool) {
+        self.fonts.recv().unwrap().send(loaded).unwrap();
+        // The script thread is told about the load in a task the router queues.
+        self.script_thread.handle_next_task();
+        self.script_thread.handle_web_font_loaded(self.pipeline);
+        self.script_thread.finish_batch();
+    }
+
+    /// Handles the tasks queued so far, i.e. by the ops of a patch, in a batch of their own.
+    pub fn run_queued_tasks(&self) {
+        self.script_thread.handle_queued_tasks();
+        self.script_thread.finish_batch();
+    }
+
+    /// Makes hit tests find a node, or nothing.
+    pub fn hit(&self, id: Option<u64>) {
+        let address = id.map(|id| self.node(id).to_trusted_node_address().0 as usize);
+        *self.hit_target.lock().unwrap() = address;
+    }
+
+    /// Clicks a node with the left button, as the compositor reports a click.
+    pub fn click(&self, id: u64) {
+        self.hit(Some(id));
+        let point = Point2D::new(10., 10.);
+        for event_type in vec![MouseEventType::MouseDown, MouseEventType::MouseUp, MouseEventType::Click] {
+            self.send_event(CompositorEvent::MouseButtonEvent(event_type, MouseButton::Left, point));
+        }
+    }
+
+    /// Focuses an element, as clicking it would.
+    pub fn focus(&self, id: u64) {
+        let doc = self.document();
+        doc.begin_focus_transaction();
+        doc.request_focus(self.node(id).downcast::<Element>().unwrap());
+        doc.commit_focus_transaction(FocusType::Element);
+        self.script_thread.finish_batch();
+    }
+
+    /// Makes the server prevent or allow the blocking events it receives from now on.
+    pub fn prevent_blocking(&self, prevent: bool) {
+        self.prevent.store(prevent, Ordering::SeqCst);
+    }
+
+    /// The messages the server received when the client started.
+    pub fn initial_msgs(&self) -> &[ServerMsg] {
+        &self.initial
+    }
+
+    /// Returns the messages the server received since the last drain, once every message sent
+    /// so far arrived.
+    pub fn drain(&self) -> Vec<ServerMsg> {
+        self.document().window().vdom_chan().send(ServerMsg::Reply(DRAIN_MARKER, Reply::State(None)));
+        let mut msgs = vec![];
+        loop {
+            match self.server.recv().unwrap() {
+                ServerMsg::Reply(DRAIN_MARKER, _) => return msgs,
+                msg => msgs.push(msg),
+            }
+        }
+    }
+
+    /// Returns the messages the compositor received since the last call.
+    pub fn compositor_msgs(&self) -> Vec<ScriptToCompositorMsg> {
+        let mut msgs = vec![];
+        while let Ok(msg) = self.compositor.try_recv() {
+            msgs.push(msg);
+        }
+        msgs
+    }
+
+    /// Returns the reflows layout was asked for since the last call.
+    pub fn reflows(&self) -> Vec<LayoutReflow> {
+        let mut reflows = vec![];
+        while let Ok(reflow) = self.reflows.try_recv() {
+            reflows.push(reflow);
+        }
+        reflows
+    }
+
+    /// Adds an empty page under the root page, as if a frame of the document had loaded `url`.
+    /// It shares the window of the root page, so only its document is its own.
+    pub fn add_page(&self, url: &str) -> Root<Document> {
+        let root_page = self.script_thread.root_page();
+        let root = root_page.document();
+        let document = Document::new(root.window(),
+                                     None,
+                                     Some(Url::parse(url).unwrap()),
+                                     IsHTMLDocument::HTMLDocument,
+                                     None,
+                                     None,
+                                     DocumentSource::NotFromParser,
+                                     DocumentLoader::new(&root.loader()));
+        let page = Page::new(PipelineId {
+            namespace_id: PipelineNamespaceId(0),
+            index: PipelineIndex(root_page.iter().count() as u32),
+        });
+        page.set_frame(Some(Frame {
+            document: JS::from_rooted(&document),
+            window: JS::from_ref(root.window()),
+        }));
+        root_page.children.borrow_mut().push(Rc::new(page));
+        document
+    }
+
+    /// The `dom-tree` memory reports the script thread sends the memory profiler.
+    pub fn dom_tree_reports(&self) -> Vec<Report> {
+        self.script_thread.dom_tree_reports()
+    }
+}
+
+/// Answers the layout queries of the script thread with a hit test target and empty results.
+struct FakeLayoutRPC {
+    hit_target: Arc<Mutex<Option<usize>>>,
+}
+
+impl LayoutRPC for FakeLayoutRPC {
+    fn content_box(&self) -> ContentBoxResponse {
+        ContentBoxResponse(Rect::zero())
+    }
+
+    fn content_boxes(&self) -> ContentBoxesResponse {
+        ContentBoxesResponse(vec![])
+    }
+
+    fn node_geometry(&self) -> NodeGeometryResponse {
+        NodeGeometryResponse {
+            client_rect: Rect::zero(),
+        }
+    }
+
+    fn node_scroll_area(&self) -> NodeGeometryResponse {
+        NodeGeometryResponse {
+            client_rect: Rect::zero(),
+        }
+    }
+
+    fn hit_test(&self) -> HitTestResponse {
+        HitTestResponse {
+            node_address: self.hit_target.lock().unwrap().map(|address| UntrustedNodeAddress(address as *const _)),
+        }
+    }
+
+    fn resolved_style(&self) -> ResolvedStyleResponse {
+        ResolvedStyleResponse(None)
+    }
+
+    fn offset_parent(&self) -> OffsetParentResponse {
+        OffsetParentResponse::empty()
+    }
+
+    fn margin_style(&self) -> MarginStyleResponse {
+        MarginStyleResponse::empty()
+    }
+
+    fn box_model(&self) -> BoxModelResponse {
+        BoxModelResponse(None)
+    }
+}
+
+/// Runs a layout thread that lays nothing out, but cleans the document like a reflow does and
+/// records the nodes that were dirty. Fonts to preload are handed to the test instead of a font
+/// cache.
+fn spawn_layout(port: Receiver<Msg>, hit_target: Arc<Mutex<Option<usize>>>, reflows: Sender<LayoutReflow>,
+                fonts: Sender<IpcSender<bool>>) {
+    thread::spawn(move || {
+        thread_state::initialize(thread_state::LAYOUT);
+        while let Ok(msg) = port.recv() {
+            match msg {
+                Msg::GetRPC(chan) => {
+                    let rpc = FakeLayoutRPC {
+                        hit_target: hit_target.clone(),
+                    };
+                    chan.send(Box::new(rpc) as Box<LayoutRPC + Send>).unwrap();
+                },
+                Msg::Reflow(reflow) => {
+                    let _ = reflows.send(clean_document(&reflow));
+                },
+                Msg::GetWebFontLoadState(chan) => chan.send(false).unwrap(),
+                Msg::PreloadFont(_, _, result) => {
+                    let _ = fonts.send(result);
+                },
+                Msg::PrepareToExit(chan) => chan.send(()).unwrap(),
+                Msg::ExitNow => break,
+                _ => (),
+            }
+        }
+    });
+}
+
+#[allow(unsafe_code)]
+fn clean_document(reflow: &ScriptReflow) -> LayoutReflow {
+    let mut dirty = vec![];
+    unsafe {
+        let document = LayoutJS::from_trusted_node_address(reflow.document);
+        clean_subtree(document, &mut dirty);
+        document.downcast::<Document>().unwrap().drain_modified_elements();
+    }
+    LayoutReflow {
+        for_display: reflow.query_type == ReflowQueryType::NoQuery,
+        dirty: dirty,
+        reason: reflow.reason,
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe fn clean_subtree(node: LayoutJS<Node>, dirty: &mut Vec<u64>) {
+    if node.get_flag(IS_DIRTY) {
+        dirty.push((*node.unsafe_get()).get_id());
+    }
+    node.set_flag(IS_DIRTY, false);
+    node.set_flag(HAS_CHANGED, false);
+    node.set_flag(HAS_DIRTY_DESCENDANTS, false);
+    let mut child = node.first_child_ref();
+    while let Some(node) = child {
+        clean_subtree(node, dirty);
+        child = node.next_sibling_ref();
+    }
+}
+
+/// Runs a server that passes the messages of the client on, and answers its blocking events
+/// with the verdict `prevent` holds.
+fn spawn_server(port: IpcReceiver<Vec<u8>>,
+                client: IpcSender<Vec<u8>>,
+                prevent: Arc<AtomicBool>,
+                msgs: Sender<ServerMsg>) {
+    thread::spawn(move || {
+        while let Ok(bytes) = port.recv() {
+            let msg = decode(bytes);
+            if let ServerMsg::BlockingEvent(id, _) = msg {
+                let mut verdict = vec![];
+                write_verdict(&mut verdict, id, prevent.load(Ordering::SeqCst)).unwrap();
+                client.send(verdict).unwrap();
+            }
+            if msgs.send(msg).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn decode(msg: Vec<u8>) -> ServerMsg {
+    ServerMsg::read_from(&mut Cursor::new(msg)).unwrap()
+}
+
+/// Builds the operations section of a message from the server.
+pub fn patch_ops(ops: &[PatchOp]) -> Vec<u8> {
+    let mut writer = PatchWriter::new();
+    for op in ops {
+        writer.push(op);
+    }
+    writer.into_bytes()
+}
+
+/// A pair a dispatch waiting for a verdict receives it on, like `Window::new_script_pair`.
+pub fn script_pair() -> (Box<ScriptChan + Send>, Box<ScriptPort + Send>) {
+    let (chan, port) = channel();
+    (Box::new(SendableMainThreadScriptChan(chan)), Box::new(port))
+}
@@ -0,0 +1,2955 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use canvas_traits::{Canvas2dMsg, CanvasCommonMsg, CanvasMsg};
+use euclid::point::Point2D;
+use euclid::scale_factor::ScaleFactor;
+use euclid::size::Size2D;
+use ipc_channel::ipc;
+use msg::constellation_msg::WindowSizeData;
+use script::dom::bindings::error::Error as DomError;
+use script::dom::bindings::inheritance::Castable;
+use script::dom::characterdata::CharacterData;
+use script::dom::comment::Comment;
+use script::dom::create::create_element_simple;
+use script::dom::document::{InputModality, parse_design_mode};
+use script::dom::documentfragment::DocumentFragment;
+use script::dom::dragevent::DragData;
+use script::dom::element::{Element, ElementCreator};
+use script::dom::event::{Event, EventBubbles, EventCancelable};
+use script::dom::htmlcanvaselement::resize_msg;
+use script::dom::htmlinputelement::HTMLInputElement;
+use script::dom::node::Node;
+use script::dom::popstateevent::PopStateEvent;
+use script::dom::screen::screen_size;
+use script::dom::text::Text;
+use script::dom::window::ReflowReason;
+use script::vdom::atoms::{AtomCache, MAX_CACHED_ATOMS};
+use script::vdom::deferred::DeferredPatches;
+use script::vdom::echoes::{EchoSuppression, is_echo};
+use script::vdom::error::PatchError;
+use script::vdom::events::{self, Delivery, EventDelivery, EventSet};
+use script::vdom::format::{merge_runs, text_ranges, valid_range};
+use script::vdom::history::{HistoryEntry, SessionHistory};
+use script::vdom::hover::HoverChanges;
+use script::vdom::live::{self, MutationVersions, selector_dependencies};
+use script::vdom::media::{self, MediaWatch};
+use script::vdom::mousemoves::MouseMoves;
+use script::vdom::moves::Arrangement;
+use script::vdom::msg::{BoxEdges, BoxGeometry, BoxModel, BoxRect, CanvasPixels, EventDetail, EventMsg, Reply};
+use script::vdom::msg::{ClientCapabilities, Connection, ScreenMetrics, ServerMsg, TextRun};
+use script::vdom::ops::{ChildSpec, PatchOp, attribute_changes, blit_msg, coalesce_appends, insertion_index};
+use script::vdom::ops::{PATCH_PROTOCOL_VERSION, create_children, insert_child_at, parse_content_type};
+use script::vdom::ops::{replace_children, send_blit, subscription_list, supported_ops, upgrade_text_to_element};
+use script::vdom::outgoing::{DEFAULT_QUEUE_BOUND, DropPolicy, OutgoingQueue, is_droppable};
+use script::vdom::pool::{ElementPool, MAX_POOLED_PER_NAME};
+use script::vdom::registry::{self, MIN_COMPACTED_CAPACITY};
+use script::vdom::resize::ResizeWatch;
+use script::vdom::selection::SelectionReports;
+use script::vdom::stylesheets::cascade_order;
+use script::vdom::ticks::TickThrottle;
+use script::vdom::tooltips::{TOOLTIP_DWELL_MS, TooltipDwell};
+use script::vdom::tombstones::{LookupError, TOMBSTONE_LIFETIME_NS, Tombstones};
+use script::vdom::tree::{MAX_NODE_DEPTH, NodeToken, build_tree};
+use script::vdom::undo::{MAX_CHECKPOINTS, UndoLog};
+use script::vdom::verdict::{PendingVerdicts, Verdict, write_verdict};
+use script::vdom::visibility::{VisibilityWatch, visible_ratio};
+use script::vdom::check_mutation;
+use script::vdom::wire::{MAX_MESSAGE_SIZE, ReadWire, WriteWire};
+use script_traits::{CompositionEventType, CompositorEvent, EventResult, MouseButton, MouseEventType};
+use script_traits::ScriptToCompositorMsg;
+use style_traits::color_scheme::ColorScheme;
+use self::harness::{Client, patch_ops, script_pair};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::thread;
+use string_cache::Atom;
+use url::Url;
+use util::str::DOMString;
+
+pub mod harness;
+
+#[test]
+fn test_event_set_from_name() {
+    assert_eq!(EventSet::from_name("wheel"), Some(events::WHEEL));
+    assert_eq!(EventSet::from_name("click"), Some(events::CLICK));
+    assert_eq!(EventSet::from_name("not-an-event"), None);
+}
+
+#[test]
+fn test_event_set_from_list() {
+    let set = EventSet::from_list("click, input,,not-an-event ");
+    assert_eq!(set, events::CLICK | events::INPUT);
+    assert!(!set.contains(events::KEYDOWN));
+    assert_eq!(EventSet::from_list(""), EventSet::empty());
+}
+
+#[test]
+fn test_wheel_event_message() {
+    let msg = ServerMsg::Event(EventMsg {
+        listener: 2,
+        target: 7,
+        kind: "wheel".to_owned(),
+        detail: EventDetail::Wheel {
+            client_x: 10,
+            client_y: -4,
+            delta_x: 0.0,
+            delta_y: 53.5,
+            delta_z: 0.0,
+            delta_mode: 0,
+        },
+    });
+
+    let mut reader = Cursor::new(msg.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(1));
+    assert_eq!(reader.get_u64().unwrap(), 2);
+    assert_eq!(reader.get_u64().unwrap(), 7);
+    assert_eq!(reader.get_string().unwrap(), "wheel");
+    assert_eq!(reader.get_u8().unwrap(), 3);
+    assert_eq!(reader.get_i32().unwrap(), 10);
+    assert_eq!(reader.get_i32().unwrap(), -4);
+    assert_eq!(reader.get_f64().unwrap(), 0.0);
+    assert_eq!(reader.get_f64().unwrap(), 53.5);
+    assert_eq!(reader.get_f64().unwrap(), 0.0);
+    assert_eq!(reader.get_u32().unwrap(), 0);
+    assert_eq!(reader.get_tag().unwrap(), None);
+}
+
+#[test]
+fn test_composition_event_message() {
+    let msg = ServerMsg::Event(EventMsg {
+        listener: 4,
+        target: 4,
+        kind: "compositionend".to_owned(),
+        detail: EventDetail::Composition { data: "你".to_owned() },
+    });
+
+    let mut reader = Cursor::new(msg.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(1));
+    assert_eq!(reader.get_u64().unwrap(), 4);
+    assert_eq!(reader.get_u64().unwrap(), 4);
+    assert_eq!(reader.get_string().unwrap(), "compositionend");
+    assert_eq!(reader.get_u8().unwrap(), 4);
+    assert_eq!(reader.get_string().unwrap(), "你");
+    assert_eq!(reader.get_tag().unwrap(), None);
+}
+
+#[test]
+fn test_drag_event_message() {
+    let msg = ServerMsg::Event(EventMsg {
+        listener: 9,
+        target: 12,
+        kind: "drop".to_owned(),
+        detail: EventDetail::Drag {
+            client_x: 5,
+            client_y: 6,
+            items: vec![("text/plain".to_owned(), "row 3".to_owned()),
+                        ("text/uri-list".to_owned(), "http://example.com/".to_owned())],
+        },
+    });
+
+    let mut reader = Cursor::new(msg.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(1));
+    assert_eq!(reader.get_u64().unwrap(), 9);
+    assert_eq!(reader.get_u64().unwrap(), 12);
+    assert_eq!(reader.get_string().unwrap(), "drop");
+    assert_eq!(reader.get_u8().unwrap(), 5);
+    assert_eq!(reader.get_i32().unwrap(), 5);
+    assert_eq!(reader.get_i32().unwrap(), 6);
+    assert_eq!(reader.get_u32().unwrap(), 2);
+    assert_eq!(reader.get_string().unwrap(), "text/plain");
+    assert_eq!(reader.get_string().unwrap(), "row 3");
+    assert_eq!(reader.get_string().unwrap(), "text/uri-list");
+    assert_eq!(reader.get_string().unwrap(), "http://example.com/");
+    assert_eq!(reader.get_tag().unwrap(), None);
+}
+
+#[test]
+fn test_clipboard_event_message() {
+    let msg = ServerMsg::Event(EventMsg {
+        listener: 3,
+        target: 8,
+        kind: "copy".to_owned(),
+        detail: EventDetail::Clipboard { data: "selected".to_owned() },
+    });
+
+    let mut reader = Cursor::new(msg.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(1));
+    assert_eq!(reader.get_u64().unwrap(), 3);
+    assert_eq!(reader.get_u64().unwrap(), 8);
+    assert_eq!(reader.get_string().unwrap(), "copy");
+    assert_eq!(reader.get_u8().unwrap(), 6);
+    assert_eq!(reader.get_string().unwrap(), "selected");
+    assert_eq!(reader.get_tag().unwrap(), None);
+}
+
+#[test]
+fn test_focus_event_message() {
+    let msg = ServerMsg::Event(EventMsg {
+        listener: 4,
+        target: 4,
+        kind: "focus".to_owned(),
+        detail: EventDetail::Focus { visible: true },
+    });
+
+    let mut reader = Cursor::new(msg.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(1));
+    assert_eq!(reader.get_u64().unwrap(), 4);
+    assert_eq!(reader.get_u64().unwrap(), 4);
+    assert_eq!(reader.get_string().unwrap(), "focus");
+    assert_eq!(reader.get_u8().unwrap(), 7);
+    assert_eq!(reader.get_bool().unwrap(), true);
+    assert_eq!(reader.get_tag().unwrap(), None);
+}
+
+#[test]
+fn test_focus_visible_follows_input_modality() {
+    assert!(InputModality::Keyboard.focus_visible());
+    assert!(!InputModality::Pointer.focus_visible());
+}
+
+#[test]
+fn test_set_clipboard_op_round_trip() {
+    let mut buf = vec![];
+    PatchOp::SetClipboard("from server".to_owned()).write_to(&mut buf).unwrap();
+
+    let mut reader = Cursor::new(buf);
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(),
+               Some(PatchOp::SetClipboard("from server".to_owned())));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_unknown_op_is_an_error() {
+    let mut reader = Cursor::new(vec![0xff]);
+    assert!(PatchOp::read_from(&mut reader).is_err());
+}
+
+#[test]
+fn test_blocking_event_message() {
+    let msg = ServerMsg::BlockingEvent(5, EventMsg {
+        listener: 1,
+        target: 2,
+        kind: "submit".to_owned(),
+        detail: EventDetail::None,
+    });
+
+    let mut reader = Cursor::new(msg.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(2));
+    assert_eq!(reader.get_u32().unwrap(), 5);
+    assert_eq!(reader.get_u64().unwrap(), 1);
+    assert_eq!(reader.get_u64().unwrap(), 2);
+    assert_eq!(reader.get_string().unwrap(), "submit");
+    assert_eq!(reader.get_u8().unwrap(), 0);
+    assert_eq!(reader.get_tag().unwrap(), None);
+}
+
+#[test]
+fn test_server_vetoes_blocking_event() {
+    let verdicts = PendingVerdicts::new();
+    let (chan, port) = script_pair();
+    let id = verdicts.register(chan.clone());
+
+    let mut verdict = vec![];
+    write_verdict(&mut verdict, id, true).unwrap();
+    let router = verdicts.clone();
+    thread::spawn(move || assert!(router.route(verdict).is_none()));
+
+    assert_eq!(verdicts.wait(id, chan, port), Verdict::Prevent);
+}
+
+#[test]
+fn test_blocking_event_times_out() {
+    let verdicts = PendingVerdicts::new();
+    let (chan, port) = script_pair();
+    let id = verdicts.register(chan.clone());
+
+    assert_eq!(verdicts.wait(id, chan, port), Verdict::TimedOut);
+
+    // A verdict arriving after the timeout is dropped.
+    let mut verdict = vec![];
+    write_verdict(&mut verdict, id, true).unwrap();
+    assert!(verdicts.route(verdict).is_none());
+}
+
+#[test]
+fn test_route_passes_other_messages_through() {
+    let verdicts = PendingVerdicts::new();
+    assert_eq!(verdicts.route(vec![0, 1, 2]), Some(vec![0, 1, 2]));
+}
+
+#[test]
+fn test_set_vetoable_op_round_trip() {
+    let mut buf = vec![];
+    PatchOp::SetVetoable(3, events::SUBMIT | events::CLICK).write_to(&mut buf).unwrap();
+
+    let mut reader = Cursor::new(buf);
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(),
+               Some(PatchOp::SetVetoable(3, events::SUBMIT | events::CLICK)));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_client_forwards_click() {
+    let client = Client::new();
+    assert_eq!(client.initial_msgs(), &[ServerMsg::ClientCapabilities(ClientCapabilities::new())][..]);
+
+    // A node the server created is clicked, and the click is forwarded to it.
+    client.create(2, 10, "div", events::CLICK);
+    client.click(10);
+    assert_eq!(client.drain(), vec![ServerMsg::Event(EventMsg {
+        listener: 10,
+        target: 10,
+        kind: "click".to_owned(),
+        detail: EventDetail::Mouse { client_x: 10, client_y: 10, button: 0 },
+    })]);
+
+    // Once the server marks its clicks vetoable, the next one waits for the server's verdict.
+    client.apply(&[PatchOp::SetVetoable(10, events::CLICK)]);
+    client.prevent_blocking(true);
+    client.click(10);
+    let msgs = client.drain();
+    assert_eq!(msgs.len(), 1);
+    match msgs[0] {
+        ServerMsg::BlockingEvent(_, ref event) => {
+            assert_eq!((event.listener, event.target, &*event.kind), (10, 10, "click"));
+        },
+        ref msg => panic!("unexpected message {:?}", msg),
+    }
+}
+
+fn composition_msg(kind: &str, data: &str) -> ServerMsg {
+    ServerMsg::Event(EventMsg {
+        listener: 10,
+        target: 10,
+        kind: kind.to_owned(),
+        detail: EventDetail::Composition { data: data.to_owned() },
+    })
+}
+
+#[test]
+fn test_composition_session_edits_focused_input() {
+    let client = Client::new();
+    let events = events::COMPOSITIONSTART | events::COMPOSITIONUPDATE | events::COMPOSITIONEND | events::INPUT;
+    let node = client.create(2, 10, "input", events);
+    let input = node.downcast::<HTMLInputElement>().unwrap();
+    client.focus(10);
+    client.drain();
+
+    // A committed composition replaces its text with the final one, and is an input.
+    client.send_event(CompositorEvent::CompositionEvent(CompositionEventType::Start, String::new()));
+    client.send_event(CompositorEvent::CompositionEvent(CompositionEventType::Update, "ni".to_owned()));
+    assert_eq!(input.Value(), "ni");
+    client.send_event(CompositorEvent::CompositionEvent(CompositionEventType::End, "你".to_owned()));
+    assert_eq!(input.Value(), "你");
+    assert_eq!(client.drain(), vec![
+        composition_msg("compositionstart", ""),
+        composition_msg("compositionupdate", "ni"),
+        composition_msg("compositionend", "你"),
+        ServerMsg::Event(EventMsg {
+            listener: 10,
+            target: 10,
+            kind: "input".to_owned(),
+            detail: EventDetail::None,
+        }),
+    ]);
+
+    // A canceled one ends with no data and leaves the value as it was, so it's no input.
+    client.send_event(CompositorEvent::CompositionEvent(CompositionEventType::Start, String::new()));
+    client.send_event(CompositorEvent::CompositionEvent(CompositionEventType::Update, "hao".to_owned()));
+    assert_eq!(input.Value(), "你hao");
+    client.send_event(CompositorEvent::CompositionEvent(CompositionEventType::Cancel, String::new()));
+    assert_eq!(input.Value(), "你");
+    assert_eq!(client.drain(), vec![
+        composition_msg("compositionstart", ""),
+        composition_msg("compositionupdate", "hao"),
+        composition_msg("compositionend", ""),
+    ]);
+}
+
+/// Scrolls the wheel over a node, and returns whether script allowed the default scroll.
+fn wheel_scroll_allowed(client: &Client, id: u64) -> bool {
+    client.hit(Some(id));
+    client.send_event(CompositorEvent::WheelEvent(Point2D::new(0., 40.), Point2D::new(10., 10.)));
+    let pipeline = client.document().window().pipeline();
+    let results = client.compositor_msgs().into_iter().filter_map(|msg| match msg {
+        ScriptToCompositorMsg::WheelEventProcessed(id, result) => {
+            assert!(id == pipeline);
+            Some(result)
+        },
+        _ => None,
+    }).collect::<Vec<_>>();
+    assert_eq!(results.len(), 1);
+    match results[0] {
+        EventResult::DefaultAllowed => true,
+        EventResult::DefaultPrevented => false,
+    }
+}
+
+#[test]
+fn test_prevented_wheel_event_suppresses_scroll() {
+    let client = Client::new();
+    client.create(2, 10, "div", events::WHEEL);
+    assert!(wheel_scroll_allowed(&client, 10));
+
+    // The server prevents the default of the wheel events it vetoes, so they don't scroll.
+    client.apply(&[PatchOp::SetVetoable(10, events::WHEEL)]);
+    client.prevent_blocking(true);
+    assert!(!wheel_scroll_allowed(&client, 10));
+    client.prevent_blocking(false);
+    assert!(wheel_scroll_allowed(&client, 10));
+
+    // Passive listeners can't prevent scrolling.
+    client.prevent_blocking(true);
+    client.apply(&[PatchOp::SetPassive(10, events::WHEEL)]);
+    assert!(wheel_scroll_allowed(&client, 10));
+}
+
+/// The events the server received, whether it was asked for a verdict on them or not.
+fn forwarded_events(msgs: Vec<ServerMsg>) -> Vec<EventMsg> {
+    msgs.into_iter().filter_map(|msg| match msg {
+        ServerMsg::Event(event) | ServerMsg::BlockingEvent(_, event) => Some(event),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn test_drag_and_drop_sequence() {
+    let client = Client::new();
+    let source = client.create(2, 10, "div", events::DRAGSTART | events::DRAGEND);
+    client.create(2, 11, "div", events::DRAGOVER | events::DROP);
+    source.downcast::<Element>().unwrap().set_string_attribute(&Atom::from("draggable"), DOMString::from("true"));
+    // The server accepts drops on the target by preventing its dragover events.
+    client.apply(&[
+        PatchOp::SetDragData(10, "text/plain".to_owned(), "payload".to_owned()),
+        PatchOp::SetVetoable(11, events::DRAGOVER),
+    ]);
+    client.prevent_blocking(true);
+
+    // Moving the mouse less than the threshold after pressing it isn't a drag.
+    client.hit(Some(10));
+    client.send_event(CompositorEvent::MouseButtonEvent(MouseEventType::MouseDown, MouseButton::Left,
+                                                        Point2D::new(10., 10.)));
+    client.send_event(CompositorEvent::MouseMoveEvent(Some(Point2D::new(12., 11.))));
+    assert_eq!(forwarded_events(client.drain()), vec![]);
+
+    client.hit(Some(11));
+    client.send_event(CompositorEvent::MouseMoveEvent(Some(Point2D::new(40., 10.))));
+    client.send_event(CompositorEvent::MouseButtonEvent(MouseEventType::MouseUp, MouseButton::Left,
+                                                        Point2D::new(40., 10.)));
+    client.send_event(CompositorEvent::MouseButtonEvent(MouseEventType::Click, MouseButton::Left,
+                                                        Point2D::new(40., 10.)));
+    let items = vec![("text/plain".to_owned(), "payload".to_owned())];
+    let drag_event = |target: u64, kind: &str, x: i32| EventMsg {
+        listener: target,
+        target: target,
+        kind: kind.to_owned(),
+        detail: EventDetail::Drag { client_x: x, client_y: 10, items: items.clone() },
+    };
+    assert_eq!(forwarded_events(client.drain()), vec![
+        drag_event(10, "dragstart", 40),
+        drag_event(11, "dragover", 40),
+        drag_event(11, "drop", 40),
+        drag_event(10, "dragend", 40),
+    ]);
+}
+
+#[test]
+fn test_server_msg_round_trip() {
+    let msg = ServerMsg::Event(EventMsg {
+        listener: 1,
+        target: 1,
+        kind: "drop".to_owned(),
+        detail: EventDetail::Drag { client_x: 3, client_y: 4, items: vec![("text/plain".to_owned(), "x".to_owned())] },
+    });
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_state_ops_round_trip() {
+    let ops = vec![PatchOp::SetState("cart".to_owned(), Some("3 items".to_owned())),
+                   PatchOp::SetState("cart".to_owned(), None),
+                   PatchOp::GetState("cart".to_owned(), 17)];
+
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_state_reply_message() {
+    let found = ServerMsg::Reply(17, Reply::State(Some("3 items".to_owned())));
+    let mut reader = Cursor::new(found.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(3));
+    assert_eq!(reader.get_u32().unwrap(), 17);
+    assert_eq!(reader.get_u8().unwrap(), 0);
+    assert_eq!(reader.get_opt_string().unwrap(), Some("3 items".to_owned()));
+    assert_eq!(reader.get_tag().unwrap(), None);
+
+    let missing = ServerMsg::Reply(18, Reply::State(None));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(missing.to_bytes())).unwrap(), missing);
+}
+
+#[test]
+fn test_dataset_ops_round_trip() {
+    let ops = vec![PatchOp::SetDataset(4, "userId".to_owned(), "42".to_owned()),
+                   PatchOp::GetDataset(4, "userId".to_owned(), 5)];
+
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let reply = ServerMsg::Reply(5, Reply::Dataset(Some("42".to_owned())));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(reply.to_bytes())).unwrap(), reply);
+}
+
+#[test]
+fn test_attribute_names_query_round_trip() {
+    let op = PatchOp::GetAttributeNames(4, 6);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::GetAttributeNames(4, 6)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let reply = ServerMsg::Reply(6, Reply::AttributeNames(vec!["id".to_owned(),
+                                                               "xlink:href".to_owned(),
+                                                               "class".to_owned()]));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(reply.to_bytes())).unwrap(), reply);
+    let empty = ServerMsg::Reply(7, Reply::AttributeNames(vec![]));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(empty.to_bytes())).unwrap(), empty);
+}
+
+#[test]
+fn test_clear_children_op_round_trip() {
+    let bytes = patch_ops(&[PatchOp::ClearChildren(12)]);
+    assert_eq!(bytes, vec![8, 12, 0, 0, 0, 0, 0, 0, 0]);
+
+    let mut reader = Cursor::new(bytes);
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(PatchOp::ClearChildren(12)));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_clear_children_removes_and_unregisters_the_subtree() {
+    let client = Client::new();
+    let list = client.create(2, 10, "ul", EventSet::empty());
+    client.create(10, 11, "li", EventSet::empty());
+    client.create_text(11, 12, "item");
+    client.create(10, 13, "li", EventSet::empty());
+
+    client.apply(&[PatchOp::ClearChildren(10)]);
+    assert!(client.drain().is_empty());
+    assert!(list.GetFirstChild().is_none());
+    assert!(client.has_node(10));
+    for id in 11..14 {
+        assert!(!client.has_node(id));
+    }
+}
+
+#[test]
+fn test_replace_children_op_round_trip() {
+    let ops = vec![PatchOp::ReplaceChildren(3, vec![1, 2, 3, 4]),
+                   PatchOp::ReplaceChildren(3, vec![]),
+                   PatchOp::ClearChildren(3)];
+
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_replace_children_swaps_in_the_fragment() {
+    let client = Client::new();
+    let doc = client.document();
+    let list = client.create(2, 10, "ul", EventSet::empty());
+    client.create(10, 11, "li", EventSet::empty());
+    client.create_text(11, 12, "old");
+
+    // The op's payload is a fragment, built here as its reader would.
+    let fragment = DocumentFragment::new(0, &doc);
+    for id in 13..15 {
+        let item = create_element_simple(id, Atom::from("li"), &doc, ElementCreator::ParserCreated);
+        fragment.upcast::<Node>().AppendChild(item.upcast()).unwrap();
+    }
+    client.apply_with(|doc, log| replace_children(doc, &list, &fragment, log));
+    assert!(client.drain().is_empty());
+
+    let children = list.children().map(|child| child.get_id()).collect::<Vec<_>>();
+    assert_eq!(children, vec![13, 14]);
+    assert!(client.node(13) == list.GetFirstChild().unwrap());
+    assert!(!client.has_node(11) && !client.has_node(12));
+    assert!(fragment.upcast::<Node>().GetFirstChild().is_none());
+}
+
+#[test]
+fn test_truncated_replace_children_is_an_error() {
+    let mut bytes = patch_ops(&[PatchOp::ReplaceChildren(3, vec![1, 2, 3, 4])]);
+    bytes.pop();
+    assert!(PatchOp::read_from(&mut Cursor::new(bytes)).is_err());
+}
+
+#[test]
+fn test_transaction_op_round_trip() {
+    let nested = patch_ops(&[PatchOp::ClearChildren(2), PatchOp::SetState("k".to_owned(), None)]);
+    let op = PatchOp::Transaction(nested.clone());
+
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::Transaction(nested)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_failed_transaction_rolls_back_earlier_ops() {
+    let client = Client::new();
+    let div = client.create(2, 10, "div", events::CLICK);
+    let span = client.create(10, 11, "span", EventSet::empty());
+    client.create_text(10, 12, "text");
+    client.apply(&[PatchOp::DiffAttributes(10, vec![("class".to_owned(), "before".to_owned()),
+                                                    ("title".to_owned(), "kept".to_owned())], vec![])]);
+
+    // The third op targets a missing node, so the first two are undone.
+    client.apply_transaction(&[
+        PatchOp::ClearChildren(10),
+        PatchOp::DiffAttributes(10, vec![("class".to_owned(), "after".to_owned()),
+                                         ("data-vdom-on".to_owned(), "input".to_owned())],
+                                vec!["title".to_owned()]),
+        PatchOp::AppendText(99, "text".to_owned()),
+    ]);
+    let msgs = client.drain();
+    assert_eq!(msgs.len(), 1);
+    match msgs[0] {
+        ServerMsg::PatchFailed(PatchError::UnknownId, _) => (),
+        ref msg => panic!("unexpected message {:?}", msg),
+    }
+
+    // The removed children are back in order, and their ids resolve to them again.
+    let children = div.children().map(|child| child.get_id()).collect::<Vec<_>>();
+    assert_eq!(children, vec![11, 12]);
+    assert!(client.node(11) == span);
+    assert!(client.has_node(12));
+
+    // So are the attributes and subscriptions of the element.
+    let element = div.downcast::<Element>().unwrap();
+    assert_eq!(element.get_string_attribute(&Atom::from("class")), "before");
+    assert_eq!(element.get_string_attribute(&Atom::from("title")), "kept");
+    assert!(!element.has_attribute(&Atom::from("data-vdom-on")));
+    assert_eq!(div.subscriptions(), events::CLICK);
+}
+
+#[test]
+fn test_checkpoint_ops_round_trip() {
+    let ops = vec![PatchOp::Checkpoint("before".to_owned()), PatchOp::Revert("before".to_owned())];
+
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_revert_forgets_later_checkpoints() {
+    let mut history = UndoLog::history();
+    history.checkpoint("a".to_owned()).unwrap();
+    history.checkpoint("b".to_owned()).unwrap();
+    history.checkpoint("c".to_owned()).unwrap();
+
+    history.revert("b").unwrap();
+    assert_eq!(history.checkpoints(), vec!["a", "b"]);
+    assert!(history.revert("c").is_err());
+    history.revert("b").unwrap();
+    assert_eq!(history.checkpoints(), vec!["a", "b"]);
+}
+
+#[test]
+fn test_checkpoints_are_bounded() {
+    let mut history = UndoLog::history();
+    for i in 0..MAX_CHECKPOINTS + 2 {
+        history.checkpoint(i.to_string()).unwrap();
+    }
+    assert_eq!(history.checkpoints().len(), MAX_CHECKPOINTS);
+    assert!(history.revert("0").is_err());
+    assert!(history.revert("1").is_err());
+    history.revert("2").unwrap();
+
+    history.checkpoint("2".to_owned()).unwrap();
+    assert_eq!(history.checkpoints(), vec!["2"]);
+}
+
+#[test]
+fn test_checkpoints_need_a_history() {
+    assert!(UndoLog::new().checkpoint("a".to_owned()).is_err());
+    assert!(UndoLog::ignored().checkpoint("a".to_owned()).is_err());
+    assert!(UndoLog::history().revert("a").is_err());
+}
+
+#[test]
+fn test_media_query_evaluate() {
+    assert!(media::evaluate("(min-width: 600px)", &media::device(800.0, 600.0)));
+    assert!(!media::evaluate("(min-width: 600px)", &media::device(400.0, 600.0)));
+    assert!(media::evaluate("", &media::device(400.0, 600.0)));
+    assert!(!media::evaluate("print", &media::device(800.0, 600.0)));
+}
+
+#[test]
+fn test_media_watch_reports_flips() {
+    let mut watch = MediaWatch::new();
+    assert!(!watch.watch("(min-width: 600px)".to_owned(), &media::device(400.0, 300.0)));
+    assert!(watch.watch("(max-width: 1000px)".to_owned(), &media::device(400.0, 300.0)));
+
+    assert_eq!(watch.reevaluate(&media::device(500.0, 300.0)), vec![]);
+    assert_eq!(watch.reevaluate(&media::device(800.0, 300.0)), vec![("(min-width: 600px)".to_owned(), true)]);
+    assert_eq!(watch.reevaluate(&media::device(1200.0, 300.0)), vec![("(max-width: 1000px)".to_owned(), false)]);
+    assert_eq!(watch.reevaluate(&media::device(400.0, 300.0)), vec![("(min-width: 600px)".to_owned(), false),
+                                                                  ("(max-width: 1000px)".to_owned(), true)]);
+}
+
+#[test]
+fn test_media_messages_round_trip() {
+    let ops = vec![PatchOp::MatchMedia("(min-width: 600px)".to_owned(), 8), PatchOp::GetDevicePixelRatio(9)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for msg in vec![ServerMsg::Reply(8, Reply::MediaMatch(true)),
+                    ServerMsg::Reply(9, Reply::DevicePixelRatio(2.0)),
+                    ServerMsg::MediaQueryChanged("(min-width: 600px)".to_owned(), false)] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_prefers_color_scheme_watch() {
+    let mut watch = MediaWatch::new();
+    let light = media::device(800.0, 600.0);
+    let dark = media::device(800.0, 600.0).with_color_scheme(ColorScheme::Dark);
+
+    assert!(!watch.watch("(prefers-color-scheme: dark)".to_owned(), &light));
+    assert_eq!(watch.reevaluate(&dark), vec![("(prefers-color-scheme: dark)".to_owned(), true)]);
+    assert_eq!(watch.reevaluate(&dark), vec![]);
+}
+
+#[test]
+fn test_color_scheme_messages_round_trip() {
+    let ops = vec![PatchOp::SetColorScheme(Some(ColorScheme::Dark)),
+                   PatchOp::SetColorScheme(Some(ColorScheme::Light)),
+                   PatchOp::SetColorScheme(None)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::ColorSchemeChanged(ColorScheme::Dark);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    assert!(ServerMsg::read_from(&mut Cursor::new(vec![5, 0])).is_err());
+}
+
+#[test]
+fn test_overflowing_geometry() {
+    // A 100x40 container whose content is 300px wide.
+    let overflowing = BoxGeometry::new(300, 40, 100, 40);
+    assert!(overflowing.scroll_width > overflowing.client_width);
+    assert_eq!(overflowing.scroll_height, overflowing.client_height);
+    assert!(overflowing.overflows());
+
+    // Content smaller than the container still scrolls over the whole client box.
+    let fitting = BoxGeometry::new(60, 20, 100, 40);
+    assert_eq!((fitting.scroll_width, fitting.scroll_height), (100, 40));
+    assert!(!fitting.overflows());
+}
+
+#[test]
+fn test_geometry_messages_round_trip() {
+    let op = PatchOp::GetGeometry(12, 10);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::GetGeometry(12, 10)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for msg in vec![ServerMsg::Reply(10, Reply::Geometry(Some(BoxGeometry::new(300, 40, 100, 40)))),
+                    ServerMsg::Reply(11, Reply::Geometry(None))] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_patch_reflows_report_their_reason() {
+    let client = Client::new();
+    client.create(2, 10, "div", EventSet::empty());
+    client.reflows();
+
+    client.apply(&[PatchOp::DiffAttributes(10, vec![("style".to_owned(), "color: red".to_owned())], vec![])]);
+    let reflows = client.reflows();
+    assert!(!reflows.is_empty());
+    assert!(reflows.iter().all(|reflow| reflow.reason == ReflowReason::VdomPatch));
+    assert!(reflows.iter().any(|reflow| reflow.for_display && reflow.dirty.contains(&10)));
+}
+
+#[test]
+fn test_tombstones_tell_removed_ids_apart() {
+    let mut tombstones = Tombstones::new();
+    tombstones.bury(3, 0);
+    assert_eq!(tombstones.lookup_error(3, 1), LookupError::NotFound);
+
+    tombstones.set_enabled(true);
+    tombstones.bury(3, 0);
+    assert_eq!(tombstones.lookup_error(3, 1), LookupError::Removed);
+    assert_eq!(tombstones.lookup_error(4, 1), LookupError::NotFound);
+    assert_eq!(tombstones.lookup_error(3, TOMBSTONE_LIFETIME_NS), LookupError::NotFound);
+
+    tombstones.forget(3);
+    assert_eq!(tombstones.lookup_error(3, 1), LookupError::NotFound);
+
+    // Pruning forgets the expired tombstones only.
+    tombstones.bury(6, 0);
+    tombstones.bury(7, TOMBSTONE_LIFETIME_NS);
+    tombstones.prune(TOMBSTONE_LIFETIME_NS + 1);
+    assert_eq!(tombstones.lookup_error(6, 0), LookupError::NotFound);
+    assert_eq!(tombstones.lookup_error(7, TOMBSTONE_LIFETIME_NS + 1), LookupError::Removed);
+
+    tombstones.bury(5, 0);
+    tombstones.set_enabled(false);
+    assert_eq!(tombstones.lookup_error(5, 1), LookupError::NotFound);
+}
+
+#[test]
+fn test_set_tombstones_op_round_trip() {
+    let ops = vec![PatchOp::SetTombstones(true), PatchOp::SetTombstones(false)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_content_edited_message() {
+    let msg = ServerMsg::ContentEdited(21, "Hello wo".to_owned());
+    let mut reader = Cursor::new(msg.to_bytes());
+    assert_eq!(reader.get_tag().unwrap(), Some(6));
+    assert_eq!(reader.get_u64().unwrap(), 21);
+    assert_eq!(reader.get_string().unwrap(), "Hello wo");
+    assert_eq!(reader.get_tag().unwrap(), None);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_append_text_op_round_trip() {
+    let op = PatchOp::AppendText(30, "line 1\n".to_owned());
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::AppendText(30, "line 1\n".to_owned())]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_consecutive_appends_are_coalesced() {
+    let mut ops = vec![];
+    for line in 0..3 {
+        ops.push(PatchOp::AppendText(30, format!("line {}\n", line)));
+    }
+    ops.push(PatchOp::AppendText(31, "other".to_owned()));
+    ops.push(PatchOp::ClearChildren(32));
+    ops.push(PatchOp::AppendText(30, "line 3\n".to_owned()));
+
+    assert_eq!(coalesce_appends(ops), vec![
+        PatchOp::AppendText(30, "line 0\nline 1\nline 2\n".to_owned()),
+        PatchOp::AppendText(31, "other".to_owned()),
+        PatchOp::ClearChildren(32),
+        PatchOp::AppendText(30, "line 3\n".to_owned()),
+    ]);
+}
+
+#[test]
+fn test_document_lang_and_dir_ops_round_trip() {
+    let ops = vec![PatchOp::SetDocumentLang("fr-CA".to_owned()),
+                   PatchOp::SetDocumentDir("rtl".to_owned()),
+                   PatchOp::SetDocumentLang(String::new())];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_repeated_attribute_names_hit_the_atom_cache() {
+    let mut cache = AtomCache::new();
+    let names = ["data-row", "data-column", "data-selected"];
+    for _ in 0..1000 {
+        for name in &names {
+            assert_eq!(&*cache.intern(name), *name);
+        }
+    }
+    assert_eq!(cache.misses(), names.len());
+    assert_eq!(cache.hits(), 1000 * names.len() - names.len());
+    assert_eq!(cache.len(), names.len());
+}
+
+#[test]
+fn test_atom_cache_evicts_the_least_recently_used_name() {
+    let mut cache = AtomCache::new();
+    for index in 0..MAX_CACHED_ATOMS {
+        cache.intern(&format!("data-{}", index));
+    }
+    // Using the first name makes the second one the least recently used.
+    cache.intern("data-0");
+    cache.intern("data-new");
+    assert_eq!(cache.len(), MAX_CACHED_ATOMS);
+    assert_eq!(cache.misses(), MAX_CACHED_ATOMS + 1);
+
+    cache.intern("data-0");
+    assert_eq!(cache.misses(), MAX_CACHED_ATOMS + 1);
+    cache.intern("data-1");
+    assert_eq!(cache.misses(), MAX_CACHED_ATOMS + 2);
+}
+
+#[test]
+fn test_swap_nodes_op_round_trip() {
+    let ops = vec![PatchOp::SwapNodes(40, 41), PatchOp::SwapNodes(41, 7)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+/// The ids of the children of a node, in order.
+fn child_ids(node: &Node) -> Vec<u64> {
+    node.children().map(|child| child.get_id()).collect()
+}
+
+#[test]
+fn test_swap_nodes_exchanges_positions_in_the_document() {
+    let client = Client::new();
+    let first = client.create(2, 10, "ul", EventSet::empty());
+    let second = client.create(2, 20, "ul", EventSet::empty());
+    for id in 11..14 {
+        client.create(10, id, "li", EventSet::empty());
+    }
+    client.create(20, 21, "li", EventSet::empty());
+
+    // Adjacent siblings, then nodes under different parents.
+    client.apply(&[PatchOp::SwapNodes(11, 12), PatchOp::SwapNodes(13, 21)]);
+    assert!(client.drain().is_empty());
+    assert_eq!(child_ids(&first), vec![12, 11, 21]);
+    assert_eq!(child_ids(&second), vec![13]);
+    assert!(client.node(13).GetParentNode().unwrap() == second);
+
+    // A node can't trade places with its ancestor.
+    assert_eq!(failure_code(&client, &[PatchOp::SwapNodes(10, 12)]), Some(2));
+    assert_eq!(child_ids(&first), vec![12, 11, 21]);
+}
+
+#[test]
+fn test_id_enumeration_messages_round_trip() {
+    let op = PatchOp::EnumerateIds(50, 12);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::EnumerateIds(50, 12)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    // A subtree whose second and last text nodes were created by the client, in pre-order.
+    for msg in vec![ServerMsg::Reply(12, Reply::Ids(vec![50, 51, 0, 52, 53, 0])),
+                    ServerMsg::Reply(13, Reply::Ids(vec![]))] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_active_element_messages_round_trip() {
+    let op = PatchOp::GetActiveElement(14);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::GetActiveElement(14)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for msg in vec![ServerMsg::Reply(14, Reply::ActiveElement(60)),
+                    ServerMsg::Reply(15, Reply::ActiveElement(0)),
+                    ServerMsg::ActiveElementChanged(61)] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_focusout_can_be_subscribed_to() {
+    assert_eq!(EventSet::from_name("focusout"), Some(events::FOCUSOUT));
+    assert_eq!(EventSet::from_list("blur, focusout"), events::BLUR | events::FOCUSOUT);
+}
+
+#[test]
+fn test_box_model_nests_the_boxes() {
+    // A 200x100 border box with a 2px border, 10px/20px padding and an 8px top margin.
+    let box_model = BoxModel::new(BoxRect::new(50, 40, 200, 100),
+                                  BoxEdges::new(2, 2, 2, 2),
+                                  BoxEdges::new(10, 20, 10, 20),
+                                  BoxEdges::new(8, 0, 0, 0));
+    assert_eq!(box_model.border, BoxRect::new(50, 40, 200, 100));
+    assert_eq!(box_model.padding, BoxRect::new(52, 42, 196, 96));
+    assert_eq!(box_model.content, BoxRect::new(72, 52, 156, 76));
+    assert_eq!(box_model.margin, BoxRect::new(50, 32, 200, 108));
+}
+
+#[test]
+fn test_box_model_content_box_is_never_negative() {
+    let box_model = BoxModel::new(BoxRect::new(0, 0, 10, 10),
+                                  BoxEdges::new(4, 4, 4, 4),
+                                  BoxEdges::new(4, 4, 4, 4),
+                                  BoxEdges::new(0, 0, 0, 0));
+    assert_eq!(box_model.content, BoxRect::new(8, 8, 0, 0));
+}
+
+#[test]
+fn test_box_model_messages_round_trip() {
+    let op = PatchOp::QueryBoxModel(70, 16);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::QueryBoxModel(70, 16)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let box_model = BoxModel::new(BoxRect::new(0, 0, 100, 50), BoxEdges::new(1, 1, 1, 1),
+                                  BoxEdges::new(4, 4, 4, 4), BoxEdges::new(8, 8, 8, 8));
+    // An element with `display: none` has no boxes.
+    for msg in vec![ServerMsg::Reply(16, Reply::BoxModel(Some(box_model))),
+                    ServerMsg::Reply(17, Reply::BoxModel(None))] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_force_reflow_messages_round_trip() {
+    let op = PatchOp::ForceReflow(18);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::ForceReflow(18)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::Reply(18, Reply::ReflowTiming(1250000, 42));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_forced_reflow_reports_its_timing_and_dirty_nodes() {
+    let client = Client::new();
+    client.create(2, 10, "div", EventSet::empty());
+    client.create(10, 11, "span", EventSet::empty());
+    client.create_text(11, 12, "text");
+    client.apply(&[]);
+    client.reflows();
+
+    // The subtree the patch invalidates is dirty when the reflow is forced.
+    client.apply(&[PatchOp::InvalidateLayout(10), PatchOp::ForceReflow(18)]);
+    let msgs = client.drain();
+    assert_eq!(msgs.len(), 1);
+    match msgs[0] {
+        ServerMsg::Reply(18, Reply::ReflowTiming(elapsed_ns, dirty)) => {
+            assert!(elapsed_ns > 0);
+            assert_eq!(dirty, 3);
+        },
+        ref msg => panic!("unexpected message {:?}", msg),
+    }
+    let forced = client.reflows().into_iter().find(|reflow| reflow.for_display).unwrap();
+    assert_eq!(forced.dirty, vec![10, 11, 12]);
+}
+
+#[test]
+fn test_set_frozen_op_round_trip() {
+    let ops = vec![PatchOp::SetFrozen(80, true), PatchOp::SetFrozen(80, false)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_element_pool_reuses_shells_of_a_churning_list() {
+    let mut pool = ElementPool::new();
+    pool.set_enabled(true);
+    let mut next_shell = 0;
+    let mut allocated = 0;
+    let mut visible = vec![];
+    // Scrolling a virtualized list: each step removes the first row and creates a new one.
+    for _ in 0..100 {
+        if visible.len() == 20 {
+            let row = visible.remove(0);
+            assert!(pool.put("li", row));
+        }
+        let row = match pool.take(&"li") {
+            Some(row) => row,
+            None => {
+                allocated += 1;
+                next_shell += 1;
+                next_shell
+            }
+        };
+        assert!(!visible.contains(&row));
+        visible.push(row);
+    }
+    assert_eq!(allocated, 21);
+    assert_eq!(pool.reused(), 79);
+    assert_eq!(pool.len(), 0);
+    assert_eq!(pool.take(&"div"), None);
+}
+
+#[test]
+fn test_element_pool_is_bounded_and_opt_in() {
+    let mut pool = ElementPool::new();
+    assert!(!pool.put("div", 0));
+    pool.set_enabled(true);
+    for shell in 0..MAX_POOLED_PER_NAME {
+        assert!(pool.put("div", shell));
+    }
+    assert!(!pool.put("div", MAX_POOLED_PER_NAME));
+    assert!(pool.put("span", 0));
+    assert_eq!(pool.len(), MAX_POOLED_PER_NAME + 1);
+
+    pool.set_enabled(false);
+    assert_eq!(pool.len(), 0);
+    assert_eq!(pool.take(&"span"), None);
+}
+
+#[test]
+fn test_set_element_pool_op_round_trip() {
+    let op = PatchOp::SetElementPool(true);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::SetElementPool(true)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_move_node_op_round_trip() {
+    let ops = vec![PatchOp::MoveNode(90, 91, Some(92)), PatchOp::MoveNode(90, 93, None)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_move_node_keeps_focus_within_the_moved_subtree() {
+    let client = Client::new();
+    let from = client.create(2, 10, "div", EventSet::empty());
+    let to = client.create(2, 20, "div", EventSet::empty());
+    client.create(20, 21, "span", EventSet::empty());
+    client.create(10, 11, "form", EventSet::empty());
+    client.create(11, 12, "input", events::FOCUS | events::BLUR);
+    client.focus(12);
+    client.drain();
+
+    // The input is neither blurred nor focused again by the move.
+    client.apply(&[PatchOp::MoveNode(11, 20, Some(21))]);
+    assert!(client.drain().is_empty());
+    assert_eq!(child_ids(&from), Vec::<u64>::new());
+    assert_eq!(child_ids(&to), vec![11, 21]);
+    assert_eq!(client.document().active_element_id(), 12);
+    assert!(client.has_node(11) && client.has_node(12));
+}
+
+#[test]
+fn test_set_html_id_op_round_trip() {
+    let ops = vec![PatchOp::SetHtmlId(100, Some("app".to_owned())),
+                   PatchOp::SetHtmlId(100, Some("main".to_owned())),
+                   PatchOp::SetHtmlId(100, None)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+/// The vdom id of the element the document finds under an HTML id, or 0 if there is none.
+fn element_with_html_id(client: &Client, html_id: &str) -> u64 {
+    client.document().GetElementById(DOMString::from(html_id)).map_or(0, |element| {
+        element.upcast::<Node>().get_id()
+    })
+}
+
+#[test]
+fn test_set_html_id_indexes_the_element_by_its_id() {
+    let client = Client::new();
+    client.create(2, 10, "div", EventSet::empty());
+    client.create(2, 11, "div", EventSet::empty());
+
+    client.apply(&[PatchOp::SetHtmlId(11, Some("app".to_owned()))]);
+    assert!(client.drain().is_empty());
+    assert_eq!(element_with_html_id(&client, "app"), 11);
+
+    // The first element in tree order wins, until its id changes again.
+    client.apply(&[PatchOp::SetHtmlId(10, Some("app".to_owned()))]);
+    assert_eq!(element_with_html_id(&client, "app"), 10);
+    client.apply(&[PatchOp::SetHtmlId(10, Some("main".to_owned()))]);
+    assert_eq!(element_with_html_id(&client, "app"), 11);
+    assert_eq!(element_with_html_id(&client, "main"), 10);
+
+    client.apply(&[PatchOp::SetHtmlId(10, None)]);
+    assert_eq!(element_with_html_id(&client, "main"), 0);
+    assert!(!client.node(10).downcast::<Element>().unwrap().has_attribute(&Atom::from("id")));
+
+    // Only elements have an HTML id.
+    assert_eq!(failure_code(&client, &[PatchOp::SetHtmlId(3, Some("text".to_owned()))]), Some(3));
+}
+
+#[test]
+fn test_canvas_blit_op_round_trip() {
+    let op = PatchOp::CanvasBlit(110, 2, 1, vec![255, 0, 0, 255, 0, 0, 255, 128]);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::CanvasBlit(110, 2, 1, vec![255, 0, 0, 255, 0, 0, 255, 128])]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_canvas_blit_reaches_renderer() {
+    let (renderer, receiver) = ipc::channel().unwrap();
+    renderer.send(blit_msg(2, 3, vec![7; 2 * 3 * 4]).unwrap()).unwrap();
+    match receiver.recv().unwrap() {
+        CanvasMsg::Canvas2d(Canvas2dMsg::PutImageData(rgba, offset, size, dirty_rect)) => {
+            assert_eq!(rgba.len(), 24);
+            assert_eq!((offset.x, offset.y), (0.0, 0.0));
+            assert_eq!((size.width, size.height), (2.0, 3.0));
+            assert_eq!((dirty_rect.size.width, dirty_rect.size.height), (2.0, 3.0));
+        },
+        _ => panic!("expected the image data of the blit")
+    }
+}
+
+#[test]
+fn test_canvas_blit_fails_once_renderer_is_gone() {
+    let (renderer, receiver) = ipc::channel().unwrap();
+    send_blit(&renderer, blit_msg(1, 1, vec![0; 4]).unwrap()).unwrap();
+    drop(receiver);
+    let err = send_blit(&renderer, blit_msg(1, 1, vec![0; 4]).unwrap()).unwrap_err();
+    assert_eq!(PatchError::of(&err), PatchError::UnsupportedOp);
+}
+
+#[test]
+fn test_canvas_blit_checks_length() {
+    assert!(blit_msg(2, 2, vec![0; 15]).is_err());
+    assert!(blit_msg(2, 2, vec![0; 17]).is_err());
+    assert!(blit_msg(0, 0, vec![]).is_ok());
+    assert!(blit_msg(u32::max_value(), u32::max_value(), vec![]).is_err());
+}
+
+#[test]
+fn test_canvas_resize_recreates_bitmap() {
+    let (renderer, receiver) = ipc::channel().unwrap();
+    renderer.send(resize_msg(640, 480)).unwrap();
+    match receiver.recv().unwrap() {
+        CanvasMsg::Common(CanvasCommonMsg::Recreate(size)) => assert_eq!((size.width, size.height), (640, 480)),
+        _ => panic!("expected the canvas to be recreated")
+    }
+}
+
+#[test]
+fn test_canvas_readback_op_round_trip() {
+    let op = PatchOp::CanvasReadback(110, BoxRect::new(-5, 10, 20, 30), 12);
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::CanvasReadback(110, BoxRect::new(-5, 10, 20, 30), 12)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_canvas_readback_replies_with_pixels_of_a_canvas_only() {
+    let client = Client::new();
+    client.create(2, 10, "canvas", EventSet::empty());
+
+    // Nothing was drawn, so the pixels are transparent.
+    client.apply(&[PatchOp::CanvasReadback(10, BoxRect::new(-1, 0, 2, 1), 12)]);
+    let pixels = CanvasPixels {
+        rect: BoxRect::new(0, 0, 1, 1),
+        data: vec![0; 4],
+    };
+    assert_eq!(client.drain(), vec![ServerMsg::Reply(12, Reply::CanvasPixels(Some(pixels)))]);
+
+    // A rectangle outside of the bitmap has no pixels.
+    client.apply(&[PatchOp::CanvasReadback(10, BoxRect::new(400, 0, 2, 1), 13)]);
+    assert_eq!(client.drain(), vec![ServerMsg::Reply(13, Reply::CanvasPixels(None))]);
+
+    // Reading back from another node is a failure, which isn't replied to.
+    assert_eq!(failure_code(&client, &[PatchOp::CanvasReadback(2, BoxRect::new(0, 0, 1, 1), 14)]), Some(3));
+}
+
+#[test]
+fn test_canvas_pixels_reply_round_trip() {
+    let pixels = CanvasPixels {
+        rect: BoxRect::new(1, 2, 1, 1),
+        data: vec![10, 20, 30, 255],
+    };
+    for reply in vec![Reply::CanvasPixels(Some(pixels)), Reply::CanvasPixels(None)] {
+        let msg = ServerMsg::Reply(13, reply);
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_readback_rect_is_clamped_to_canvas() {
+    let canvas = BoxRect::new(0, 0, 300, 150);
+    assert_eq!(BoxRect::new(10, 20, 30, 40).intersection(&canvas), BoxRect::new(10, 20, 30, 40));
+    assert_eq!(BoxRect::new(-10, -20, 30, 40).intersection(&canvas), BoxRect::new(0, 0, 20, 20));
+    assert_eq!(BoxRect::new(290, 140, 30, 40).intersection(&canvas), BoxRect::new(290, 140, 10, 10));
+    assert_eq!(BoxRect::new(400, 10, 30, 40).intersection(&canvas), BoxRect::new(300, 10, 0, 40));
+    assert_eq!(BoxRect::new(i32::max_value(), 0, i32::max_value(), 10).intersection(&canvas),
+               BoxRect::new(300, 0, 0, 10));
+}
+
+#[test]
+fn test_screen_size_matches_window_size() {
+    let window_size = WindowSizeData {
+        initial_viewport: Size2D::typed(1024_f32, 768_f32),
+        visible_viewport: Size2D::typed(1024_f32, 767.6_f32),
+        device_pixel_ratio: ScaleFactor::new(2.0),
+    };
+    let size = screen_size(Some(window_size));
+    assert_eq!((size.width, size.height), (1024, 768));
+    let size = screen_size(None);
+    assert_eq!((size.width, size.height), (0, 0));
+}
+
+#[test]
+fn test_screen_op_and_reply_round_trip() {
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::GetScreen(14)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(PatchOp::GetScreen(14)));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::Reply(14, Reply::Screen(ScreenMetrics {
+        width: 1024,
+        height: 768,
+        avail_width: 1024,
+        avail_height: 768,
+        color_depth: 24,
+        pixel_depth: 24,
+    }));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_window_name_ops_round_trip() {
+    let ops = vec![PatchOp::SetWindowName("preview".to_owned()), PatchOp::GetWindowName(15)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for name in vec!["preview", ""] {
+        let msg = ServerMsg::Reply(15, Reply::WindowName(name.to_owned()));
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_window_name_outlasts_the_patch_that_set_it() {
+    let client = Client::new();
+    client.apply(&[PatchOp::GetWindowName(15)]);
+    assert_eq!(client.drain(), vec![ServerMsg::Reply(15, Reply::WindowName(String::new()))]);
+
+    client.apply(&[PatchOp::SetWindowName("preview".to_owned())]);
+    assert!(client.drain().is_empty());
+    client.apply(&[PatchOp::GetWindowName(16)]);
+    assert_eq!(client.drain(), vec![ServerMsg::Reply(16, Reply::WindowName("preview".to_owned()))]);
+}
+
+fn entry(url: &str, state: Option<&str>) -> HistoryEntry {
+    HistoryEntry {
+        url: url.to_owned(),
+        state: state.map(|state| state.to_owned()),
+    }
+}
+
+#[test]
+fn test_session_history_push_and_back() {
+    let mut history = SessionHistory::new("http://example.com/".to_owned());
+    history.push(entry("http://example.com/a", Some("1")));
+    history.push(entry("http://example.com/b", Some("2")));
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.current().url, "http://example.com/b");
+
+    assert_eq!(history.back(), Some(&entry("http://example.com/a", Some("1"))));
+    assert_eq!(history.back(), Some(&entry("http://example.com/", None)));
+    assert_eq!(history.back(), None);
+    assert_eq!(history.current().url, "http://example.com/");
+}
+
+#[test]
+fn test_session_history_push_forgets_forward_entries() {
+    let mut history = SessionHistory::new("http://example.com/".to_owned());
+    history.push(entry("http://example.com/a", None));
+    history.push(entry("http://example.com/b", None));
+    history.back();
+    history.push(entry("http://example.com/c", None));
+    assert_eq!(history.len(), 3);
+    history.replace(entry("http://example.com/d", Some("replaced")));
+    assert_eq!(history.current(), &entry("http://example.com/d", Some("replaced")));
+    assert_eq!(history.back().map(|entry| &*entry.url), Some("http://example.com/a"));
+}
+
+#[test]
+fn test_client_pops_pushed_states_in_order() {
+    let client = Client::new();
+    let doc = client.document();
+    doc.set_url(Url::parse("http://example.com/").unwrap());
+    client.apply(&[PatchOp::ReplaceState("/".to_owned(), None),
+                   PatchOp::PushState("/a".to_owned(), Some("1".to_owned())),
+                   PatchOp::PushState("/b".to_owned(), Some("2".to_owned()))]);
+    assert_eq!(doc.url().serialize(), "http://example.com/b");
+    assert!(client.drain().is_empty());
+
+    // Each entry is restored in a history traversal task, which tells the server its state.
+    client.apply(&[PatchOp::PopState]);
+    client.run_queued_tasks();
+    assert_eq!(doc.url().serialize(), "http://example.com/a");
+    assert_eq!(client.drain(), vec![ServerMsg::PopState("http://example.com/a".to_owned(), Some("1".to_owned()))]);
+
+    client.apply(&[PatchOp::PopState]);
+    client.run_queued_tasks();
+    assert_eq!(doc.url().serialize(), "http://example.com/");
+    assert_eq!(client.drain(), vec![ServerMsg::PopState("http://example.com/".to_owned(), None)]);
+
+    // There is nothing before the first entry.
+    client.apply(&[PatchOp::PopState]);
+    client.run_queued_tasks();
+    assert!(client.drain().is_empty());
+}
+
+#[test]
+fn test_pop_state_event_carries_the_entry_state() {
+    let event = PopStateEvent::new(DOMString::from("popstate"), EventBubbles::DoesNotBubble,
+                                   EventCancelable::NotCancelable, Some("{\"page\":1}".to_owned()));
+    assert_eq!(event.upcast::<Event>().type_(), Atom::from("popstate"));
+    assert!(!event.upcast::<Event>().bubbles());
+    assert_eq!(*event.state(), Some("{\"page\":1}".to_owned()));
+}
+
+#[test]
+fn test_history_ops_and_pop_state_round_trip() {
+    let ops = vec![PatchOp::PushState("/a".to_owned(), Some("{\"page\":1}".to_owned())),
+                   PatchOp::ReplaceState("/b".to_owned(), None),
+                   PatchOp::PopState];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for msg in vec![ServerMsg::PopState("http://example.com/a".to_owned(), Some("1".to_owned())),
+                    ServerMsg::PopState("http://example.com/".to_owned(), None)] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_hash_changed_round_trip() {
+    let msg = ServerMsg::HashChanged("http://example.com/#intro".to_owned(), "http://example.com/#section".to_owned());
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_client_reports_preloaded_fonts_and_reflows_for_them() {
+    let client = Client::new();
+    client.apply(&[PatchOp::PreloadFont("Inter".to_owned(), "http://example.com/fonts/inter.woff".to_owned())]);
+    client.reflows();
+    client.load_font(true);
+    assert_eq!(client.drain(),
+               vec![ServerMsg::FontLoaded("Inter".to_owned(), "http://example.com/fonts/inter.woff".to_owned())]);
+    assert!(client.reflows().iter().any(|reflow| reflow.reason == ReflowReason::WebFontLoaded));
+
+    // A font that fails to load is reported too, and layout still reflows.
+    client.apply(&[PatchOp::PreloadFont("Inter".to_owned(), "http://example.com/fonts/missing.woff".to_owned())]);
+    client.reflows();
+    client.load_font(false);
+    assert_eq!(client.drain(),
+               vec![ServerMsg::FontError("Inter".to_owned(), "http://example.com/fonts/missing.woff".to_owned())]);
+    assert!(client.reflows().iter().any(|reflow| reflow.reason == ReflowReason::WebFontLoaded));
+}
+
+#[test]
+fn test_preload_font_round_trip() {
+    let op = PatchOp::PreloadFont("Inter".to_owned(), "/fonts/inter.woff".to_owned());
+    let mut reader = Cursor::new(patch_ops(&[op]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(),
+               Some(PatchOp::PreloadFont("Inter".to_owned(), "/fonts/inter.woff".to_owned())));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for msg in vec![ServerMsg::FontLoaded("Inter".to_owned(), "http://example.com/fonts/inter.woff".to_owned()),
+                    ServerMsg::FontError("Inter".to_owned(), "http://example.com/fonts/missing.woff".to_owned())] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_hover_changes() {
+    assert_eq!(EventSet::from_list("click, hover"), events::CLICK | events::HOVER);
+
+    // Moving onto a subscribed element, then off it.
+    let mut changes = HoverChanges::new();
+    changes.record(4, true);
+    assert_eq!(changes.take(), vec![(4, true)]);
+    changes.record(4, false);
+    assert_eq!(changes.take(), vec![(4, false)]);
+    assert_eq!(changes.take(), vec![]);
+
+    // Moving between two children of a subscribed element leaves the parent hovered.
+    changes.record(5, false);
+    changes.record(4, false);
+    changes.record(6, true);
+    changes.record(4, true);
+    assert_eq!(changes.take(), vec![(5, false), (6, true)]);
+
+    for msg in vec![ServerMsg::HoverChanged(4, true), ServerMsg::HoverChanged(4, false)] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_visible_ratio() {
+    let viewport = BoxRect::new(0, 0, 800, 600);
+    assert_eq!(visible_ratio(&BoxRect::new(10, 10, 100, 100), &viewport), 1.0);
+    assert_eq!(visible_ratio(&BoxRect::new(10, 550, 100, 100), &viewport), 0.5);
+    assert_eq!(visible_ratio(&BoxRect::new(10, 700, 100, 100), &viewport), 0.0);
+    assert_eq!(visible_ratio(&BoxRect::new(0, 0, 0, 0), &viewport), 0.0);
+}
+
+#[test]
+fn test_visibility_thresholds() {
+    let mut watch = VisibilityWatch::new();
+    watch.observe(4, vec![0.0, 0.5, 1.0]);
+    assert_eq!(watch.ids(), vec![4]);
+
+    // Scroll a 100px tall element up into a 600px tall viewport, starting below it.
+    let mut crossed = vec![];
+    for &top in &[700, 620, 580, 550, 530, 500, 450] {
+        let ratio = visible_ratio(&BoxRect::new(0, top, 100, 100), &BoxRect::new(0, 0, 800, 600));
+        if watch.update(4, ratio) {
+            crossed.push(ratio);
+        }
+    }
+    assert_eq!(crossed, vec![0.0, 0.2, 0.5, 1.0]);
+    assert!(!watch.update(5, 1.0));
+
+    let op = PatchOp::ObserveVisibility(4, vec![0.0, 0.5, 1.0]);
+    let mut reader = Cursor::new(patch_ops(&[op]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(),
+               Some(PatchOp::ObserveVisibility(4, vec![0.0, 0.5, 1.0])));
+    let msg = ServerMsg::VisibilityChanged(4, 0.5);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+
+    watch.observe(4, vec![]);
+    assert_eq!(watch.ids(), vec![]);
+}
+
+#[test]
+fn test_resize_watch() {
+    let mut watch = ResizeWatch::new();
+    watch.observe(4, true);
+    watch.observe(4, true);
+    assert_eq!(watch.ids(), vec![4]);
+    assert!(watch.update(4, 100, 20));
+
+    // A style patch widens the element; the reflows after it settle on the new size.
+    let reported = [(200, 20), (200, 20), (200, 20)].iter()
+                                                    .filter(|&&(width, height)| watch.update(4, width, height))
+                                                    .count();
+    assert_eq!(reported, 1);
+    assert!(!watch.update(5, 200, 20));
+
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::ObserveResize(4, false)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(PatchOp::ObserveResize(4, false)));
+    let msg = ServerMsg::ResizeObserved(4, 200, 20);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+
+    watch.observe(4, false);
+    assert_eq!(watch.ids(), vec![]);
+}
+
+#[test]
+fn test_design_mode() {
+    assert_eq!(parse_design_mode("on"), Some(true));
+    assert_eq!(parse_design_mode("OFF"), Some(false));
+    assert_eq!(parse_design_mode("yes"), None);
+
+    let ops = vec![PatchOp::SetDesignMode(true), PatchOp::SetDesignMode(false)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+fn run(text: &str, bold: bool) -> TextRun {
+    TextRun {
+        text: text.to_owned(),
+        bold: bold,
+    }
+}
+
+#[test]
+fn test_format_ranges() {
+    // "Hello " + "brave " + "world", with "lo brave wo" selected.
+    let lens = [6, 6, 5];
+    assert_eq!(text_ranges(&lens, 3, 14), vec![(0, 3, 6), (1, 0, 6), (2, 0, 2)]);
+    assert_eq!(text_ranges(&lens, 6, 12), vec![(1, 0, 6)]);
+    assert_eq!(text_ranges(&lens, 6, 6), vec![]);
+
+    assert!(valid_range("héllo", 0, 3));
+    assert!(!valid_range("héllo", 0, 2));
+    assert!(!valid_range("héllo", 4, 3));
+    assert!(!valid_range("héllo", 0, 7));
+}
+
+#[test]
+fn test_bold_runs_and_messages() {
+    // Bolding "lo brave wo" splits the first and last texts around it.
+    let runs = merge_runs(vec![run("Hel", false), run("lo ", true), run("brave ", true), run("wo", true),
+                               run("rld", false), run("", true)]);
+    assert_eq!(runs, vec![run("Hel", false), run("lo brave wo", true), run("rld", false)]);
+
+    let op = PatchOp::FormatBold(4, 3, 14, true);
+    let mut reader = Cursor::new(patch_ops(&[op]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(PatchOp::FormatBold(4, 3, 14, true)));
+    let msg = ServerMsg::ContentFormatted(4, runs);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_tick_throttle() {
+    // A second of frames at 240Hz, throttled to one tick every 100ms.
+    const FRAME_NS: u64 = 1000 * 1000 * 1000 / 240;
+    let mut throttle = TickThrottle::new(100);
+    let sent = (0..240).map(|frame| frame * FRAME_NS).filter(|&now| throttle.tick(now)).collect::<Vec<_>>();
+    assert_eq!(sent.len(), 10);
+    assert_eq!(sent[0], 0);
+    assert!(sent.windows(2).all(|pair| pair[1] - pair[0] >= 100 * 1000 * 1000));
+
+    let mut unthrottled = TickThrottle::new(0);
+    assert!((0..10).all(|frame| unthrottled.tick(frame * FRAME_NS)));
+
+    let ops = vec![PatchOp::SubscribeTicks(Some(100)), PatchOp::SubscribeTicks(None)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    let msg = ServerMsg::Tick(1500.25);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_inline_longhands_op_and_reply_round_trip() {
+    let ops = vec![PatchOp::GetInlineLonghands(4, "margin".to_owned(), 16)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let longhands = vec![("margin-top", "1px"), ("margin-right", "2px"), ("margin-bottom", "1px"),
+                         ("margin-left", "2px")];
+    let msg = ServerMsg::Reply(16, Reply::Longhands(longhands.into_iter()
+                                                             .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                                                             .collect()));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+/// The color the last of the given sheets gives links, as the last sheet wins a tie between
+/// equally specific rules.
+fn winning_color(sheets: &[&str]) -> String {
+    sheets.last().unwrap().trim_left_matches("a { color: ").trim_right_matches(" }").to_owned()
+}
+
+#[test]
+fn test_reorder_stylesheets() {
+    let sheets = vec![(1, "a { color: red }"), (2, "a { color: blue }")];
+    assert_eq!(winning_color(&cascade_order(sheets.clone(), &[])), "blue");
+    assert_eq!(winning_color(&cascade_order(sheets.clone(), &[2, 1])), "red");
+    assert_eq!(winning_color(&cascade_order(sheets.clone(), &[1, 2])), "blue");
+
+    // Sheets the order doesn't list keep their place, and missing ids are ignored.
+    let sheets = vec![(1, "a"), (0, "b"), (2, "c"), (3, "d")];
+    assert_eq!(cascade_order(sheets.clone(), &[3, 9, 1]), vec!["d", "b", "c", "a"]);
+    assert_eq!(cascade_order(sheets, &[2]), vec!["a", "b", "c", "d"]);
+
+    let ops = vec![PatchOp::ReorderStylesheets(vec![2, 1]), PatchOp::ReorderStylesheets(vec![])];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_custom_property_ops_and_reply_round_trip() {
+    let ops = vec![PatchOp::SetCustomProperty(1, "--accent".to_owned(), Some("red".to_owned())),
+                   PatchOp::SetCustomProperty(1, "--accent".to_owned(), None),
+                   PatchOp::GetResolvedStyle(2, "color".to_owned(), 17)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for value in vec![Some("rgb(255, 0, 0)".to_owned()), None] {
+        let msg = ServerMsg::Reply(17, Reply::ResolvedStyle(value));
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_create_children_op_and_reply_round_trip() {
+    // The payload is opaque to the op; a list of 200 items replies with their 200 ids in order.
+    let payload = (0..200).map(|item| item as u8).collect::<Vec<_>>();
+    let ops = vec![PatchOp::CreateChildren(5, payload, 18), PatchOp::CreateChildren(5, vec![], 19)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::Reply(18, Reply::Ids((100..300).collect()));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_truncated_create_children_is_an_error() {
+    let mut bytes = patch_ops(&[PatchOp::CreateChildren(5, vec![1, 2, 3, 4], 18)]);
+    bytes.pop();
+    assert!(PatchOp::read_from(&mut Cursor::new(bytes)).is_err());
+}
+
+#[test]
+fn test_create_children_appends_and_replies_with_the_ids() {
+    let client = Client::new();
+    let doc = client.document();
+    let list = client.create(2, 10, "ul", EventSet::empty());
+    client.create(10, 11, "li", EventSet::empty());
+
+    // The op's payload is a fragment, built here as its reader would.
+    let fragment = DocumentFragment::new(0, &doc);
+    for id in vec![12, 14] {
+        let item = create_element_simple(id, Atom::from("li"), &doc, ElementCreator::ParserCreated);
+        fragment.upcast::<Node>().AppendChild(item.upcast()).unwrap();
+    }
+    let text = Text::new(13, DOMString::from("item"), &doc);
+    fragment.upcast::<Node>().GetFirstChild().unwrap().AppendChild(text.upcast()).unwrap();
+
+    client.apply_with(|doc, log| create_children(doc, &list, &fragment, 7, log));
+    assert_eq!(client.drain(), vec![ServerMsg::Reply(7, Reply::Ids(vec![12, 14]))]);
+    assert_eq!(child_ids(&list), vec![11, 12, 14]);
+    assert!(client.has_node(13));
+    assert_eq!(child_ids(&client.node(12)), vec![13]);
+}
+
+#[test]
+fn test_patch_children_op_round_trip() {
+    // Keeps the first child as it is, updates the text of the second and replaces the third.
+    let specs = vec![ChildSpec::Keep(None, None),
+                     ChildSpec::Keep(Some(vec![1, 2]), Some("second".to_owned())),
+                     ChildSpec::Create(vec![3, 4, 5])];
+    let ops = vec![PatchOp::PatchChildren(6, specs), PatchOp::PatchChildren(6, vec![])];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let mut bytes = patch_ops(&[PatchOp::PatchChildren(6, vec![ChildSpec::Keep(None, None)])]);
+    let spec_tag = bytes.len() - 3;
+    bytes[spec_tag] = 9;
+    assert!(PatchOp::read_from(&mut Cursor::new(bytes)).is_err());
+}
+
+#[test]
+fn test_patch_children_keeps_state_and_drops_the_rest() {
+    let client = Client::new();
+    let form = client.create(2, 10, "form", EventSet::empty());
+    let label = client.create_text(10, 11, "before");
+    client.create(10, 12, "input", events::BLUR);
+    client.create(10, 13, "input", EventSet::empty());
+    client.focus(12);
+    client.drain();
+
+    client.apply(&[PatchOp::PatchChildren(10, vec![ChildSpec::Keep(None, Some("after".to_owned())),
+                                                   ChildSpec::Keep(None, None)])]);
+    assert!(client.drain().is_empty());
+    assert_eq!(child_ids(&form), vec![11, 12]);
+    assert_eq!(label.downcast::<Text>().unwrap().upcast::<CharacterData>().Data(), "after");
+    assert_eq!(client.document().active_element_id(), 12);
+    assert!(!client.has_node(13));
+
+    // Keeping a child past the end, or setting the text of an element, fails the patch.
+    let past_end = vec![ChildSpec::Keep(None, None), ChildSpec::Keep(None, None), ChildSpec::Keep(None, None)];
+    assert_eq!(failure_code(&client, &[PatchOp::PatchChildren(10, past_end)]), Some(2));
+    let text = vec![ChildSpec::Keep(None, None), ChildSpec::Keep(None, Some("text".to_owned()))];
+    assert_eq!(failure_code(&client, &[PatchOp::PatchChildren(10, text)]), Some(3));
+    assert_eq!(child_ids(&form), vec![11, 12]);
+}
+
+/// Builds a payload of elements nested `depth` deep around a leaf, returning the root and the
+/// (parent, child) pairs appended, or the error.
+fn build_nested(depth: usize) -> ::std::io::Result<(Option<usize>, Vec<(usize, usize)>)> {
+    let mut tokens = (0..depth).map(NodeToken::Open)
+                               .chain(Some(NodeToken::Leaf(depth)))
+                               .chain((0..depth).map(|_| NodeToken::Close));
+    let mut appended = vec![];
+    let root = try!(build_tree(|| Ok(tokens.next().unwrap_or(NodeToken::Close)),
+                               |&parent, &child| appended.push((parent, child))));
+    Ok((root, appended))
+}
+
+#[test]
+fn test_build_tree_with_bounded_depth() {
+    let (root, appended) = build_nested(3).unwrap();
+    assert_eq!(root, Some(0));
+    assert_eq!(appended, vec![(2, 3), (1, 2), (0, 1)]);
+
+    let (root, appended) = build_nested(MAX_NODE_DEPTH).unwrap();
+    assert_eq!(root, Some(0));
+    assert_eq!(appended.len(), MAX_NODE_DEPTH);
+
+    let err = build_nested(MAX_NODE_DEPTH + 1).unwrap_err();
+    assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidData);
+
+    let (root, appended) = build_nested(0).unwrap();
+    assert_eq!(root, Some(0));
+    assert!(appended.is_empty());
+    assert_eq!(build_tree::<usize, _, _>(|| Ok(NodeToken::Close), |_, _| ()).unwrap(), None);
+}
+
+#[test]
+fn test_set_texts_op_and_message_round_trip() {
+    let texts = (0..100).map(|cell| (100 + cell, format!("cell {}", cell))).collect::<Vec<_>>();
+    let ops = vec![PatchOp::SetTexts(texts.clone()), PatchOp::SetTexts(vec![])];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    match PatchOp::read_from(&mut reader).unwrap() {
+        Some(PatchOp::SetTexts(read)) => {
+            assert_eq!(read.len(), 100);
+            assert_eq!(read[42], (142, "cell 42".to_owned()));
+            assert_eq!(read, texts);
+        },
+        op => panic!("expected SetTexts, got {:?}", op),
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(PatchOp::SetTexts(vec![])));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::TextsNotSet(vec![7, 9]);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_subscription_list() {
+    let subscribed = EventSet::from_list("keydown,click,submit,hover");
+    let vetoable = events::CLICK | events::SUBMIT | events::PASTE;
+    assert_eq!(subscribed.names(), vec!["click", "keydown", "submit", "hover"]);
+    assert_eq!(subscription_list(subscribed, vetoable),
+               vec![("click".to_owned(), true), ("keydown".to_owned(), false), ("submit".to_owned(), true),
+                    ("hover".to_owned(), false)]);
+    assert!(subscription_list(EventSet::empty(), vetoable).is_empty());
+    assert_eq!(EventSet::all().names().len(), 31);
+
+    let mut reader = Cursor::new(patch_ops(&[PatchOp::GetSubscriptions(8, 20)]));
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(PatchOp::GetSubscriptions(8, 20)));
+    let msg = ServerMsg::Reply(20, Reply::Subscriptions(subscription_list(subscribed, vetoable)));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+/// Applies append patches, as (parent, child) pairs, to a tree of ids rooted at 1, returning
+/// the children of each node.
+fn append_all(appends: &[(u64, u64)]) -> ::std::io::Result<HashMap<u64, Vec<u64>>> {
+    let tree = RefCell::new(HashMap::new());
+    tree.borrow_mut().insert(1, vec![]);
+    let mut deferred = DeferredPatches::new();
+    for &(parent, child) in appends {
+        try!(deferred.apply(parent, child, |id| tree.borrow().contains_key(&id), |parent, child| {
+            let mut tree = tree.borrow_mut();
+            tree.get_mut(&parent).unwrap().push(child);
+            tree.insert(child, vec![]);
+            Ok(())
+        }));
+    }
+    try!(deferred.finish());
+    Ok(tree.into_inner())
+}
+
+#[test]
+fn test_deferred_patches_wait_for_their_target() {
+    // The grandchild and the child are appended before their parents are.
+    let tree = append_all(&[(6, 7), (5, 6), (5, 8), (1, 5), (1, 9)]).unwrap();
+    assert_eq!(tree[&1], vec![5, 9]);
+    assert_eq!(tree[&5], vec![6, 8]);
+    assert_eq!(tree[&6], vec![7]);
+
+    let tree = append_all(&[(1, 5), (5, 6)]).unwrap();
+    assert_eq!(tree[&5], vec![6]);
+
+    assert!(append_all(&[(4, 7), (1, 5)]).is_err());
+
+    let mut deferred = DeferredPatches::new();
+    deferred.apply(4, (), |_| false, |_, _| Ok(())).unwrap();
+    deferred.apply(3, (), |_| false, |_, _| Ok(())).unwrap();
+    assert_eq!(deferred.unresolved(), vec![4, 3]);
+}
+
+#[test]
+fn test_move_batch_rotates_nodes_among_parents() {
+    // Rotates 1, 2 and 3 between parents holding [1, 2] and [3].
+    let mut arrangement = Arrangement::new(vec![vec![1, 2], vec![3]]);
+    arrangement.move_node(1, 1, None).unwrap();
+    arrangement.move_node(3, 0, Some(2)).unwrap();
+    arrangement.move_node(2, 1, Some(1)).unwrap();
+    assert_eq!(arrangement.children(0), &[3]);
+    assert_eq!(arrangement.children(1), &[2, 1]);
+
+    // Once 2 moved away, moving a node before it in its old parent is invalid, and changes
+    // nothing.
+    let before = Arrangement::new(vec![vec![3], vec![2, 1]]);
+    assert!(arrangement.move_node(1, 0, Some(2)).is_err());
+    assert_eq!(arrangement, before);
+
+    // A node moved before itself stays before its next sibling.
+    arrangement.move_node(2, 1, Some(2)).unwrap();
+    assert_eq!(arrangement.children(1), &[2, 1]);
+}
+
+#[test]
+fn test_replace_document_element_op_round_trip() {
+    let ops = vec![PatchOp::ReplaceDocumentElement(vec![1, 2, 3, 4]),
+                   PatchOp::ReplaceDocumentElement(vec![])];
+
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_replace_document_element_unregisters_only_the_old_tree() {
+    let client = Client::new();
+    let doc = client.document();
+    let comment = Comment::new(30, DOMString::from("kept"), &doc);
+    doc.upcast::<Node>().AppendChild(comment.upcast()).unwrap();
+    client.apply(&[PatchOp::SetTombstones(true)]);
+
+    // The op's payload is a new tree, built here as its reader would.
+    let html = create_element_simple(20, Atom::from("html"), &doc, ElementCreator::ParserCreated);
+    let body = create_element_simple(21, Atom::from("body"), &doc, ElementCreator::ParserCreated);
+    html.upcast::<Node>().AppendChild(body.upcast()).unwrap();
+    client.apply_with(|doc, log| check_mutation(doc.replace_vdom_document_element(&html, log)));
+    assert!(client.drain().is_empty());
+
+    assert_eq!(doc.GetDocumentElement().unwrap().upcast::<Node>().get_id(), 20);
+    assert!(client.has_node(20) && client.has_node(21));
+    for id in 1..4 {
+        assert_eq!(doc.lookup_node_by_id(id).err(), Some(LookupError::Removed));
+    }
+    // Nodes of the document outside the document element keep their ids.
+    assert!(client.has_node(30));
+}
+
+#[test]
+fn test_parse_content_type() {
+    assert_eq!(parse_content_type("text/html").unwrap(), ("text/html".to_owned(), None));
+    assert_eq!(parse_content_type("Text/HTML; charset=Shift_JIS").unwrap(),
+               ("text/html".to_owned(), Some("shift_jis".to_owned())));
+    assert_eq!(parse_content_type("application/xhtml+xml; charset=latin1").unwrap(),
+               ("application/xhtml+xml".to_owned(), Some("windows-1252".to_owned())));
+    assert!(parse_content_type("html").is_err());
+    assert!(parse_content_type("").is_err());
+    assert!(parse_content_type("text/html; charset=klingon").is_err());
+}
+
+#[test]
+fn test_content_type_ops_round_trip() {
+    let ops = vec![PatchOp::SetContentType("text/html; charset=utf-8".to_owned()), PatchOp::GetContentType(16)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::Reply(16, Reply::ContentType("text/html".to_owned(), "UTF-8".to_owned()));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_upgrade_text_to_element_op_round_trip() {
+    let ops = vec![PatchOp::UpgradeTextToElement(12, vec![1, 2, 3, 4]),
+                   PatchOp::UpgradeTextToElement(13, vec![])];
+
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let mut bytes = patch_ops(&[PatchOp::UpgradeTextToElement(12, vec![1, 2, 3, 4])]);
+    bytes.pop();
+    assert!(PatchOp::read_from(&mut Cursor::new(bytes)).is_err());
+}
+
+#[test]
+fn test_upgrade_text_to_element_takes_over_the_id() {
+    let client = Client::new();
+    let doc = client.document();
+    let div = client.create(2, 10, "div", EventSet::empty());
+    let text = client.create_text(10, 11, "item");
+    client.create_text(10, 12, "next");
+
+    // The op's payload is an element, built here as its reader would.
+    let em = create_element_simple(0, Atom::from("em"), &doc, ElementCreator::ParserCreated);
+    client.apply_with(|doc, log| upgrade_text_to_element(doc, &text, em.upcast(), log));
+    assert!(client.drain().is_empty());
+    assert_eq!(child_ids(&div), vec![11, 12]);
+    assert!(&*client.node(11) == em.upcast::<Node>());
+    assert!(text.GetParentNode().is_none());
+
+    // An element can't be upgraded again.
+    let strong = create_element_simple(0, Atom::from("strong"), &doc, ElementCreator::ParserCreated);
+    client.apply_with(|doc, log| upgrade_text_to_element(doc, em.upcast(), strong.upcast(), log));
+    let msgs = client.drain();
+    assert_eq!(msgs.len(), 1);
+    match msgs[0] {
+        ServerMsg::PatchFailed(PatchError::TypeMismatch, _) => (),
+        ref msg => panic!("unexpected message {:?}", msg),
+    }
+    assert!(&*client.node(11) == em.upcast::<Node>());
+}
+
+/// The code of the error `failure` reports to the server, after checking the message round trips.
+fn reported_code<T: ::std::fmt::Debug>(failure: ::std::io::Result<T>) -> u8 {
+    let err = failure.unwrap_err();
+    let msg = ServerMsg::PatchFailed(PatchError::of(&err), err.to_string());
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    msg.to_bytes()[1]
+}
+
+#[test]
+fn test_patch_errors_are_reported_with_stable_codes() {
+    let mut deferred = DeferredPatches::new();
+    deferred.apply(4, (), |_| false, |_, _| Ok(())).unwrap();
+    assert_eq!(reported_code(deferred.finish()), 1);
+    assert_eq!(reported_code(check_mutation::<()>(Err(DomError::NotFound))), 1);
+
+    let mut arrangement = Arrangement::new(vec![vec![1], vec![2]]);
+    assert_eq!(reported_code(arrangement.move_node(1, 0, Some(2))), 2);
+    assert_eq!(reported_code(check_mutation::<()>(Err(DomError::HierarchyRequest))), 2);
+
+    assert_eq!(reported_code::<()>(Err(PatchError::TypeMismatch.error("upgrade targets an element"))), 3);
+
+    let mut truncated = patch_ops(&[PatchOp::ReplaceChildren(3, vec![1, 2, 3, 4])]);
+    truncated.pop();
+    assert_eq!(reported_code(PatchOp::read_from(&mut Cursor::new(truncated))), 4);
+    assert_eq!(reported_code(parse_content_type("html")), 4);
+    assert_eq!(reported_code(check_mutation::<()>(Err(DomError::InvalidCharacter))), 4);
+
+    assert_eq!(reported_code(build_nested(MAX_NODE_DEPTH + 1)), 5);
+
+    assert_eq!(reported_code(PatchOp::read_from(&mut Cursor::new(vec![255]))), 6);
+    assert_eq!(reported_code(UndoLog::new().checkpoint("saved".to_owned())), 6);
+
+    assert_eq!(reported_code::<()>(Err(PatchError::RemovedId.error("patch targets a removed node"))), 7);
+
+    for code in 1..8 {
+        assert_eq!(PatchError::from_wire(code).unwrap().to_wire(), code);
+    }
+    assert!(PatchError::from_wire(0).is_err());
+    assert!(PatchError::from_wire(8).is_err());
+    assert_eq!(PatchError::of(&PatchError::LimitExceeded.error("too deep")), PatchError::LimitExceeded);
+}
+
+/// The code of the failure the server is sent for a message of operations, if it fails.
+fn failure_code(client: &Client, ops: &[PatchOp]) -> Option<u8> {
+    client.apply(ops);
+    client.drain().into_iter().find(|msg| match *msg {
+        ServerMsg::PatchFailed(..) => true,
+        _ => false,
+    }).map(|msg| msg.to_bytes()[1])
+}
+
+#[test]
+fn test_message_without_header_is_reported_as_malformed() {
+    let client = Client::new();
+    client.receive(vec![]);
+    let failures: Vec<_> = client.drain().into_iter().filter_map(|msg| match msg {
+        ServerMsg::PatchFailed(kind, _) => Some(kind),
+        _ => None,
+    }).collect();
+    assert_eq!(failures, vec![PatchError::Malformed]);
+
+    // The client goes on applying the messages after it.
+    assert_eq!(failure_code(&client, &[PatchOp::SetTitle(2, None)]), None);
+}
+
+#[test]
+fn test_ops_targeting_missing_nodes_fail_with_unknown_id() {
+    let client = Client::new();
+    let ops = vec![
+        PatchOp::SetVetoable(99, events::CLICK),
+        PatchOp::SetDataset(99, "key".to_owned(), "value".to_owned()),
+        PatchOp::GetDataset(99, "key".to_owned(), 1),
+        PatchOp::GetAttributeNames(99, 1),
+        PatchOp::ClearChildren(99),
+        PatchOp::ReplaceChildren(99, vec![]),
+        PatchOp::GetGeometry(99, 1),
+        PatchOp::AppendText(99, "text".to_owned()),
+        PatchOp::SwapNodes(99, 3),
+        PatchOp::SwapNodes(3, 99),
+        PatchOp::EnumerateIds(99, 1),
+        PatchOp::QueryBoxModel(99, 1),
+        PatchOp::SetFrozen(99, true),
+        PatchOp::MoveNode(99, 2, None),
+        PatchOp::MoveNode(3, 99, None),
+        PatchOp::MoveNode(3, 2, Some(99)),
+        PatchOp::SetHtmlId(99, Some("id".to_owned())),
+        PatchOp::CanvasBlit(99, 0, 0, vec![]),
+        PatchOp::CanvasReadback(99, BoxRect::new(0, 0, 1, 1), 1),
+        PatchOp::FormatBold(99, 0, 1, true),
+        PatchOp::GetInlineLonghands(99, "margin".to_owned(), 1),
+        PatchOp::SetCustomProperty(99, "--accent".to_owned(), None),
+        PatchOp::GetResolvedStyle(99, "color".to_owned(), 1),
+        PatchOp::CreateChildren(99, vec![], 1),
+        PatchOp::PatchChildren(99, vec![]),
+        PatchOp::GetSubscriptions(99, 1),
+        PatchOp::UpgradeTextToElement(99, vec![]),
+        PatchOp::SetSelection(99, 0, 3, 0),
+        PatchOp::SetSelection(3, 0, 99, 0),
+        PatchOp::InsertAt(99, 0, vec![]),
+        PatchOp::SetTitle(99, None),
+        PatchOp::SetPassive(99, events::WHEEL),
+        PatchOp::SetDragData(99, "text/plain".to_owned(), "data".to_owned()),
+        PatchOp::QueryPropagationPath(99, 1),
+        PatchOp::SetElementScroll(99, 0.0, 0.0),
+        PatchOp::DiffAttributes(99, vec![], vec![]),
+        PatchOp::InvalidateLayout(99),
+    ];
+    for op in ops {
+        let description = format!("{:?}", op);
+        assert_eq!(failure_code(&client, &[op]), Some(1), "{}", description);
+    }
+
+    // Targeting a node of the wrong type is another failure.
+    assert_eq!(failure_code(&client, &[PatchOp::SetTitle(3, None)]), Some(3));
+
+    // A removed node is told apart from an unknown one while it is remembered.
+    client.apply(&[PatchOp::SetTombstones(true), PatchOp::ClearChildren(2)]);
+    assert_eq!(failure_code(&client, &[PatchOp::AppendText(3, "text".to_owned())]), Some(7));
+    client.apply(&[PatchOp::SetTombstones(false)]);
+    assert_eq!(failure_code(&client, &[PatchOp::AppendText(3, "text".to_owned())]), Some(1));
+
+    // So is the document element, for the ops setting its attributes.
+    let html = client.node(1);
+    html.GetParentNode().unwrap().RemoveChild(&html).unwrap();
+    assert_eq!(failure_code(&client, &[PatchOp::SetDocumentLang("fr".to_owned())]), Some(1));
+    assert_eq!(failure_code(&client, &[PatchOp::SetDocumentDir("rtl".to_owned())]), Some(1));
+}
+
+#[test]
+fn test_frozen_subtrees_reject_mutations_until_unfrozen() {
+    let client = Client::new();
+    client.create(2, 10, "div", EventSet::empty());
+    client.create(10, 11, "span", EventSet::empty());
+    let text = client.create_text(11, 12, "frozen");
+    client.apply(&[PatchOp::SetFrozen(10, true)]);
+
+    // Mutating the frozen node or anything under it fails, and leaves the node untouched.
+    let drag_data = PatchOp::SetDragData(11, "text/plain".to_owned(), "data".to_owned());
+    assert_eq!(failure_code(&client, &[PatchOp::AppendText(12, "!".to_owned())]), Some(2));
+    assert_eq!(failure_code(&client, &[drag_data]), Some(2));
+    assert_eq!(failure_code(&client, &[PatchOp::ClearChildren(10)]), Some(2));
+    assert_eq!(text.GetTextContent().unwrap(), "frozen");
+    assert!(client.has_node(11));
+
+    // Only the node the subtree was frozen at unfreezes it.
+    assert_eq!(failure_code(&client, &[PatchOp::SetFrozen(11, false),
+                                       PatchOp::AppendText(12, "!".to_owned())]), Some(2));
+    assert_eq!(failure_code(&client, &[PatchOp::SetFrozen(10, false),
+                                       PatchOp::AppendText(12, "!".to_owned())]), None);
+    assert_eq!(text.GetTextContent().unwrap(), "frozen!");
+    assert_eq!(failure_code(&client, &[PatchOp::ClearChildren(10)]), None);
+    assert!(!client.has_node(11));
+}
+
+#[test]
+fn test_frozen_nodes_are_counted_until_they_leave_the_document() {
+    let client = Client::new();
+    let doc = client.document();
+    client.create(2, 10, "div", EventSet::empty());
+    client.create(10, 11, "span", EventSet::empty());
+    client.create(2, 20, "div", EventSet::empty());
+    client.apply(&[PatchOp::SetFrozen(11, true)]);
+    assert!(doc.has_vdom_frozen_nodes());
+
+    // A frozen node moved along with its ancestor stays frozen.
+    client.apply(&[PatchOp::MoveNode(10, 20, None)]);
+    assert!(doc.has_vdom_frozen_nodes());
+    assert_eq!(failure_code(&client, &[PatchOp::AppendText(11, "!".to_owned())]), Some(2));
+
+    // Removing it with its ancestor unfreezes it, unless a transaction fails and is rolled back.
+    let failing = patch_ops(&[PatchOp::ClearChildren(20), PatchOp::AppendText(99, "!".to_owned())]);
+    assert!(failure_code(&client, &[PatchOp::Transaction(failing)]).is_some());
+    assert!(client.has_node(11));
+    assert_eq!(failure_code(&client, &[PatchOp::AppendText(11, "!".to_owned())]), Some(2));
+    client.apply(&[PatchOp::ClearChildren(20)]);
+    assert!(!doc.has_vdom_frozen_nodes());
+
+    // So does resetting a kept node, in or out of the document.
+    let text = Text::new(30, DOMString::from("kept"), &doc);
+    text.upcast::<Node>().set_frozen(true);
+    assert!(doc.has_vdom_frozen_nodes());
+    text.upcast::<Node>().reset_shell(31);
+    assert!(!doc.has_vdom_frozen_nodes());
+    assert!(!text.upcast::<Node>().is_frozen());
+}
+
+#[test]
+fn test_validate_patches_op_and_reply_round_trip() {
+    let patches = patch_ops(&[PatchOp::SetTexts(vec![(4, "four".to_owned())])]);
+    let ops = vec![PatchOp::ValidatePatches(patches, 21), PatchOp::ValidatePatches(vec![], 22)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let errors = vec![PatchError::UnknownId, PatchError::HierarchyViolation, PatchError::Malformed,
+                      PatchError::RemovedId];
+    for errors in vec![errors, vec![]] {
+        let msg = ServerMsg::Reply(21, Reply::PatchErrors(errors));
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_validate_patches_checks_the_targets_of_trailing_ops() {
+    let client = Client::new();
+    client.create(2, 10, "div", EventSet::empty());
+    client.apply(&[PatchOp::SetFrozen(10, true)]);
+    client.drain();
+
+    let patches = patch_ops(&[
+        PatchOp::SetTitle(2, Some("valid".to_owned())),
+        PatchOp::SetTitle(3, None),
+        PatchOp::AppendText(2, "text".to_owned()),
+        PatchOp::GetSubscriptions(99, 1),
+        PatchOp::DiffAttributes(10, vec![], vec![]),
+        PatchOp::GetAttributeNames(10, 2),
+    ]);
+    client.apply(&[PatchOp::ValidatePatches(patches, 21)]);
+    let errors = vec![PatchError::TypeMismatch, PatchError::TypeMismatch, PatchError::UnknownId,
+                      PatchError::HierarchyViolation];
+    assert_eq!(client.drain(), vec![ServerMsg::Reply(21, Reply::PatchErrors(errors))]);
+    // Validating applies nothing.
+    assert!(!client.node(2).downcast::<Element>().unwrap().has_attribute(&Atom::from("title")));
+}
+
+#[test]
+fn test_selection_ops_and_messages_round_trip() {
+    let ops = vec![PatchOp::SetSelection(4, 0, 6, 12), PatchOp::GetSelection(23)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    for msg in vec![ServerMsg::SelectionChanged(3, 2, 5, 7),
+                    ServerMsg::Reply(23, Reply::Selection(Some((3, 7, 3, 7)))),
+                    ServerMsg::Reply(23, Reply::Selection(None))] {
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_selection_changes_are_reported_once_per_batch() {
+    let mut reports = SelectionReports::new();
+    assert_eq!(reports.flush(), None);
+
+    // A held shift+arrow extends the selection a character per key press.
+    reports.changed((4, 5), (4, 4));
+    reports.changed((4, 5), (4, 3));
+    reports.changed((4, 5), (4, 2));
+    assert_eq!(reports.flush(), Some(((4, 5), (4, 2))));
+    assert_eq!(reports.flush(), None);
+
+    // Changes that end up where the selection was last reported aren't.
+    reports.changed((4, 5), (4, 3));
+    reports.changed((4, 5), (4, 2));
+    assert_eq!(reports.flush(), None);
+    reports.changed((4, 2), (4, 2));
+    assert_eq!(reports.flush(), Some(((4, 2), (4, 2))));
+}
+
+#[test]
+fn test_selection_of_a_removed_editing_host_is_forgotten() {
+    let mut reports = SelectionReports::new();
+    reports.changed((4, 0), (4, 3));
+    assert_eq!(reports.flush(), Some(((4, 0), (4, 3))));
+
+    // The host is removed with a change still pending, then one with the same ends focused.
+    reports.changed((4, 0), (4, 2));
+    reports.forget();
+    assert_eq!(reports.flush(), None);
+    reports.changed((4, 0), (4, 3));
+    assert_eq!(reports.flush(), Some(((4, 0), (4, 3))));
+}
+
+/// Inserts `new` into `children` at `index` the way `InsertAt` does.
+fn insert_at(children: &mut Vec<u64>, index: u32, new: u64) {
+    match insertion_index(index, children.len() as u32) {
+        Some(index) => children.insert(index, new),
+        None => children.push(new),
+    }
+}
+
+#[test]
+fn test_insert_at_clamps_the_index() {
+    let mut children = vec![1, 2, 3];
+    insert_at(&mut children, 0, 4);
+    assert_eq!(children, vec![4, 1, 2, 3]);
+    insert_at(&mut children, 2, 5);
+    assert_eq!(children, vec![4, 1, 5, 2, 3]);
+    insert_at(&mut children, 5, 6);
+    assert_eq!(children, vec![4, 1, 5, 2, 3, 6]);
+    insert_at(&mut children, 1000, 7);
+    assert_eq!(children, vec![4, 1, 5, 2, 3, 6, 7]);
+
+    let mut empty = vec![];
+    insert_at(&mut empty, 3, 8);
+    assert_eq!(empty, vec![8]);
+
+    let ops = vec![PatchOp::InsertAt(4, 2, vec![1, 2, 3]), PatchOp::InsertAt(4, u32::max_value(), vec![])];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_insert_at_places_the_node_among_the_children() {
+    let client = Client::new();
+    let doc = client.document();
+    let list = client.create(2, 10, "ul", EventSet::empty());
+    for id in 11..13 {
+        client.create(10, id, "li", EventSet::empty());
+    }
+
+    // The op's payloads are nodes, built here as its reader would.
+    let insert = |id, index| {
+        let item = create_element_simple(id, Atom::from("li"), &doc, ElementCreator::ParserCreated);
+        client.apply_with(|_, log| insert_child_at(&list, index, item.upcast(), log));
+    };
+    insert(13, 1);
+    insert(14, 0);
+    insert(15, u32::max_value());
+    assert!(client.drain().is_empty());
+    assert_eq!(child_ids(&list), vec![14, 11, 13, 12, 15]);
+    assert!(client.has_node(13) && client.has_node(14) && client.has_node(15));
+}
+
+#[test]
+fn test_registry_compacts_after_removing_a_large_subtree() {
+    let mut registry = HashMap::new();
+    for id in 1..10001u64 {
+        registry.insert(id, id * 2);
+    }
+    let mut removed = 0;
+    for id in 1001..10001u64 {
+        registry.remove(&id);
+        removed += 1;
+    }
+    let capacity = registry.capacity();
+    assert!(registry::compact(&mut registry, &mut removed));
+    assert!(registry.capacity() < capacity);
+    assert_eq!(removed, 0);
+    assert_eq!(registry.len(), 1000);
+    for id in 1..1001u64 {
+        assert_eq!(registry.get(&id), Some(&(id * 2)));
+    }
+    assert!(!registry::compact(&mut registry, &mut removed));
+}
+
+#[test]
+fn test_registry_is_only_compacted_when_mostly_removed() {
+    assert!(!registry::should_compact(10, MIN_COMPACTED_CAPACITY, 1000));
+    assert!(!registry::should_compact(600, 1024, 400));
+    assert!(registry::should_compact(400, 1024, 600));
+
+    let mut registry = HashMap::new();
+    registry.insert(1u64, ());
+    let mut removed = 50;
+    assert!(!registry::compact(&mut registry, &mut removed));
+    assert_eq!(removed, 50);
+}
+
+#[test]
+fn test_event_delivery_per_event_type() {
+    let click = events::CLICK;
+    let mousemove = events::MOUSEMOVE;
+    let mut delivery = EventDelivery::new();
+    assert!(delivery.blocks(click, true, click | mousemove));
+    assert!(!delivery.blocks(click, false, click));
+    assert!(!delivery.blocks(click, true, EventSet::empty()));
+
+    delivery.set(click, Delivery::Synchronous);
+    delivery.set(mousemove, Delivery::Asynchronous);
+    assert_eq!(delivery.of(click), Delivery::Synchronous);
+    assert_eq!(delivery.of(mousemove), Delivery::Asynchronous);
+    assert!(delivery.blocks(click, true, EventSet::empty()));
+    assert!(delivery.blocks(click, false, EventSet::empty()));
+    assert!(!delivery.blocks(mousemove, true, click | mousemove));
+    assert!(!delivery.blocks(mousemove, false, mousemove));
+
+    delivery.set(click | mousemove, Delivery::Vetoable);
+    assert_eq!(delivery, EventDelivery::new());
+
+    let ops = vec![PatchOp::SetEventDelivery(click, Delivery::Synchronous),
+                   PatchOp::SetEventDelivery(mousemove, Delivery::Asynchronous)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+    assert!(Delivery::from_wire(3).is_err());
+}
+
+#[test]
+fn test_tooltip_is_reported_after_the_dwell_time() {
+    let dwell_ns = TOOLTIP_DWELL_MS * 1000 * 1000;
+    let mut tooltip = TooltipDwell::new();
+    let generation = tooltip.hover(Some(4), 1000).unwrap();
+    assert_eq!(tooltip.hover(Some(4), 2000), None);
+    assert_eq!(tooltip.dwelt(generation, 1000 + dwell_ns - 1), None);
+    assert_eq!(tooltip.dwelt(generation, 1000 + dwell_ns), Some(4));
+    assert_eq!(tooltip.dwelt(generation, 1000 + 2 * dwell_ns), None);
+
+    // The mouse left before the dwell time passed.
+    let generation = tooltip.hover(Some(5), 0).unwrap();
+    assert_eq!(tooltip.hover(None, 10), None);
+    assert_eq!(tooltip.dwelt(generation, dwell_ns), None);
+
+    // The mouse moved on to another titled element.
+    let first = tooltip.hover(Some(5), 0).unwrap();
+    let second = tooltip.hover(Some(6), 10).unwrap();
+    assert_eq!(tooltip.dwelt(first, dwell_ns + 10), None);
+    assert_eq!(tooltip.dwelt(second, dwell_ns + 10), Some(6));
+
+    let ops = vec![PatchOp::SetTitle(4, Some("Delete the row".to_owned())), PatchOp::SetTitle(4, None)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::Tooltip(4, "Delete the row".to_owned());
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_client_reports_tooltip_when_dwell_timer_fires() {
+    let client = Client::new();
+    client.create(2, 10, "button", EventSet::empty());
+    client.create(2, 11, "button", EventSet::empty());
+    client.apply(&[PatchOp::SetTitle(10, Some("Delete the row".to_owned())),
+                   PatchOp::SetTitle(11, Some("Edit the row".to_owned()))]);
+    client.drain();
+
+    // The dwell on the first button is replaced by the one on the second before it fires.
+    client.hit(Some(10));
+    client.send_event(CompositorEvent::MouseMoveEvent(Some(Point2D::new(10., 10.))));
+    client.hit(Some(11));
+    client.send_event(CompositorEvent::MouseMoveEvent(Some(Point2D::new(30., 10.))));
+    client.fire_timers();
+    let tooltips: Vec<_> = client.drain().into_iter().filter(|msg| match *msg {
+        ServerMsg::Tooltip(..) => true,
+        _ => false,
+    }).collect();
+    assert_eq!(tooltips, vec![ServerMsg::Tooltip(11, "Edit the row".to_owned())]);
+}
+
+fn mousemove(client_x: i32) -> ServerMsg {
+    ServerMsg::Event(EventMsg {
+        listener: 4,
+        target: 4,
+        kind: "mousemove".to_owned(),
+        detail: EventDetail::Mouse { client_x: client_x, client_y: 0, button: 0 },
+    })
+}
+
+fn drain_queue(queue: &mut OutgoingQueue) -> Vec<ServerMsg> {
+    let mut msgs = vec![];
+    while let Some(msg) = queue.pop() {
+        msgs.push(ServerMsg::read_from(&mut Cursor::new(msg)).unwrap());
+    }
+    msgs
+}
+
+#[test]
+fn test_outgoing_queue_drops_the_oldest_droppable_messages() {
+    assert!(is_droppable(&mousemove(0)));
+    assert!(is_droppable(&ServerMsg::Tick(16.0)));
+    assert!(!is_droppable(&ServerMsg::Reply(1, Reply::State(None))));
+    assert!(!is_droppable(&ServerMsg::PatchFailed(PatchError::Malformed, "bad".to_owned())));
+
+    let mut queue = OutgoingQueue::new();
+    queue.push(&ServerMsg::Reply(1, Reply::State(None)));
+    for client_x in 0..DEFAULT_QUEUE_BOUND as i32 + 10 {
+        queue.push(&mousemove(client_x));
+    }
+    queue.push(&ServerMsg::PatchFailed(PatchError::Malformed, "bad".to_owned()));
+    assert_eq!(queue.len(), DEFAULT_QUEUE_BOUND);
+
+    let msgs = drain_queue(&mut queue);
+    assert_eq!(msgs[0], ServerMsg::DroppedMessages(12));
+    assert_eq!(msgs[1], ServerMsg::Reply(1, Reply::State(None)));
+    assert_eq!(msgs[2], mousemove(12));
+    assert_eq!(msgs[msgs.len() - 2], mousemove(DEFAULT_QUEUE_BOUND as i32 + 9));
+    assert_eq!(msgs[msgs.len() - 1], ServerMsg::PatchFailed(PatchError::Malformed, "bad".to_owned()));
+    assert!(queue.pop().is_none());
+}
+
+#[test]
+fn test_outgoing_queue_never_drops_critical_messages() {
+    let mut queue = OutgoingQueue::new();
+    queue.configure(2, DropPolicy::DropNewest);
+    queue.push(&mousemove(0));
+    queue.push(&mousemove(1));
+    queue.push(&mousemove(2));
+    for reply in 0..3 {
+        queue.push(&ServerMsg::Reply(reply, Reply::State(None)));
+    }
+    queue.push(&mousemove(3));
+    assert_eq!(drain_queue(&mut queue), vec![ServerMsg::DroppedMessages(4),
+                                             ServerMsg::Reply(0, Reply::State(None)),
+                                             ServerMsg::Reply(1, Reply::State(None)),
+                                             ServerMsg::Reply(2, Reply::State(None))]);
+
+    let ops = vec![PatchOp::SetOutgoingQueue(64, DropPolicy::DropNewest),
+                   PatchOp::SetOutgoingQueue(1024, DropPolicy::DropOldest)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let msg = ServerMsg::DroppedMessages(7);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_passive_listeners_never_block_or_prevent_scrolling() {
+    let passive = events::passive_events();
+    assert_eq!(passive, events::TOUCHSTART | events::TOUCHMOVE | events::WHEEL);
+
+    let mut delivery = EventDelivery::new();
+    assert!(events::waits_for_verdict(&delivery, events::TOUCHMOVE, true, events::TOUCHMOVE, EventSet::empty()));
+    assert!(!events::waits_for_verdict(&delivery, events::TOUCHMOVE, true, events::TOUCHMOVE, passive));
+    delivery.set(events::WHEEL, Delivery::Synchronous);
+    assert!(events::waits_for_verdict(&delivery, events::WHEEL, true, EventSet::empty(), EventSet::empty()));
+    assert!(!events::waits_for_verdict(&delivery, events::WHEEL, true, EventSet::empty(), events::WHEEL));
+
+    assert!(events::default_allowed(events::WHEEL, false, EventSet::empty()));
+    assert!(!events::default_allowed(events::WHEEL, true, EventSet::empty()));
+    assert!(events::default_allowed(events::WHEEL, true, events::WHEEL));
+    assert!(!events::default_allowed(events::TOUCHSTART, true, events::WHEEL));
+
+    let ops = vec![PatchOp::SetPassive(4, events::TOUCHMOVE | events::WHEEL),
+                   PatchOp::SetPassive(4, EventSet::empty())];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_selector_collections_depend_only_on_what_their_selectors_observe() {
+    let active = selector_dependencies(".active").unwrap();
+    assert_eq!(active, live::STRUCTURE | live::CLASSES);
+    assert_eq!(selector_dependencies("ul > li:not(.done)").unwrap(), live::STRUCTURE | live::CLASSES);
+    assert_eq!(selector_dependencies("#main, a[href]").unwrap(), live::STRUCTURE | live::IDS | live::ATTRIBUTES);
+    assert_eq!(selector_dependencies("p:empty").unwrap(), live::STRUCTURE | live::TEXT);
+    assert_eq!(selector_dependencies("li:hover").unwrap(), live::STRUCTURE | live::STATE);
+    assert_eq!(selector_dependencies("li:first-child").unwrap(), live::STRUCTURE);
+    assert_eq!(selector_dependencies("li["), None);
+
+    // A `.active` collection recomputes after a class changes, but not after text edits.
+    let mut versions = MutationVersions::new();
+    let cached = versions.version(active);
+    versions.note(live::TEXT);
+    versions.note(live::STATE);
+    versions.note(live::ATTRIBUTES);
+    assert_eq!(versions.version(active), cached);
+    versions.note(live::CLASSES | live::ATTRIBUTES);
+    let cached = versions.version(active);
+    versions.note(live::TEXT);
+    assert_eq!(versions.version(active), cached);
+    versions.note(live::STRUCTURE);
+    assert!(versions.version(active) != cached);
+}
+
+#[test]
+fn test_primed_drag_data_is_carried_by_dragstart() {
+    let ops = vec![PatchOp::SetDragData(4, "text/plain".to_owned(), "row 3".to_owned()),
+                   PatchOp::SetDragData(4, "application/json".to_owned(), "{\"row\":3}".to_owned())];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    let mut primed = DragData::new();
+    for expected in ops {
+        let op = PatchOp::read_from(&mut reader).unwrap().unwrap();
+        assert_eq!(op, expected);
+        if let PatchOp::SetDragData(4, ref mime, ref data) = op {
+            primed.set_data(mime, data);
+        }
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    let dragstart = ServerMsg::Event(EventMsg {
+        listener: 4,
+        target: 4,
+        kind: "dragstart".to_owned(),
+        detail: EventDetail::Drag { client_x: 1, client_y: 2, items: primed.items().to_vec() },
+    });
+    match ServerMsg::read_from(&mut Cursor::new(dragstart.to_bytes())).unwrap() {
+        ServerMsg::Event(EventMsg { detail: EventDetail::Drag { items, .. }, .. }) => {
+            assert_eq!(items, vec![("text/plain".to_owned(), "row 3".to_owned()),
+                                   ("application/json".to_owned(), "{\"row\":3}".to_owned())]);
+        },
+        msg => panic!("unexpected message {:?}", msg),
+    }
+}
+
+#[test]
+fn test_suppressed_regions_hold_back_echoes() {
+    let ops = vec![PatchOp::BeginSuppress, PatchOp::SetSelection(5, 0, 5, 3), PatchOp::EndSuppress];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    // Selecting in an editing host focuses it, which like a value set echoes back as these.
+    let focus = ServerMsg::Event(EventMsg {
+        listener: 4,
+        target: 4,
+        kind: "focus".to_owned(),
+        detail: EventDetail::Focus { visible: false },
+    });
+    let echoes = vec![focus, ServerMsg::ActiveElementChanged(4), ServerMsg::ContentEdited(4, "set".to_owned())];
+    assert!(echoes.iter().all(is_echo));
+    assert!(!is_echo(&ServerMsg::Reply(23, Reply::ActiveElement(4))));
+    assert!(!is_echo(&ServerMsg::PatchFailed(PatchError::UnknownId, "patch targets a missing node".to_owned())));
+
+    let mut suppression = EchoSuppression::new();
+    assert!(!suppression.suppressing());
+    suppression.begin();
+    suppression.begin();
+    suppression.end().unwrap();
+    assert!(suppression.suppressing());
+    suppression.end().unwrap();
+    assert!(!suppression.suppressing());
+    assert_eq!(reported_code(suppression.end()), reported_code::<()>(Err(PatchError::Malformed.error("x"))));
+
+    // Outside of a suppressed region, the changes echo.
+    let client = Client::new();
+    client.document().window().vdom_chan().send(ServerMsg::ActiveElementChanged(4));
+    assert_eq!(client.drain(), vec![ServerMsg::ActiveElementChanged(4)]);
+}
+
+#[test]
+fn test_propagation_path_follows_the_ancestry_of_a_nested_node() {
+    let ops = vec![PatchOp::QueryPropagationPath(64, 23), PatchOp::QueryPropagationPath(0, 24)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    // The path of the leaf of a deeply nested payload goes through every element around it.
+    let (root, appended) = build_nested(64).unwrap();
+    let parents = appended.iter().map(|&(parent, child)| (child as u64, parent as u64)).collect::<HashMap<_, _>>();
+    let mut path = vec![64];
+    while let Some(&parent) = parents.get(path.last().unwrap()) {
+        path.push(parent);
+    }
+    assert_eq!(path.len(), 65);
+    assert_eq!(path.last().cloned(), root.map(|root| root as u64));
+    assert!(path.windows(2).all(|pair| pair[0] == pair[1] + 1));
+
+    let msg = ServerMsg::Reply(23, Reply::Ids(path));
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+/// A `mousemove` event forwarded to `listener` at the given position.
+fn move_event(listener: u64, client_x: i32, client_y: i32) -> EventMsg {
+    EventMsg {
+        listener: listener,
+        target: listener,
+        kind: "mousemove".to_owned(),
+        detail: EventDetail::Mouse { client_x: client_x, client_y: client_y, button: 0 },
+    }
+}
+
+#[test]
+fn test_mouse_moves_are_coalesced_per_frame() {
+    let ops = vec![PatchOp::SetMoveCoalescing(Some(0)), PatchOp::SetMoveCoalescing(Some(16)),
+                   PatchOp::SetMoveCoalescing(None)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    // Until the server asks for coalescing, every move is forwarded.
+    let mut moves = MouseMoves::new();
+    assert_eq!(moves.coalesce(move_event(4, 1, 1)), Some(move_event(4, 1, 1)));
+
+    // Many moves in a frame are forwarded as one, at the final position.
+    moves.set_interval(Some(0));
+    for x in 0..100 {
+        assert_eq!(moves.coalesce(move_event(4, x, 2 * x)), None);
+    }
+    assert_eq!(moves.flush(0, false), vec![move_event(4, 99, 198)]);
+    assert!(moves.flush(1, false).is_empty());
+    assert_eq!(moves.schedule(1), None);
+
+    // Each listener gets its latest move.
+    moves.coalesce(move_event(4, 1, 0));
+    moves.coalesce(move_event(7, 2, 0));
+    moves.coalesce(move_event(4, 3, 0));
+    assert_eq!(moves.flush(2, false), vec![move_event(4, 3, 0), move_event(7, 2, 0)]);
+
+    // With an interval, moves that aren't due wait for a scheduled flush, unless another event
+    // forces them out.
+    let ms = 1000 * 1000;
+    moves.set_interval(Some(16));
+    moves.coalesce(move_event(4, 5, 0));
+    assert!(moves.flush(2 + 10 * ms, false).is_empty());
+    assert_eq!(moves.schedule(2 + 10 * ms), Some(6 * ms));
+    assert_eq!(moves.schedule(2 + 11 * ms), None);
+    moves.scheduled_flush();
+    assert_eq!(moves.flush(2 + 16 * ms, false), vec![move_event(4, 5, 0)]);
+    moves.coalesce(move_event(4, 6, 0));
+    assert_eq!(moves.flush(2 + 17 * ms, true), vec![move_event(4, 6, 0)]);
+}
+
+#[test]
+fn test_client_flushes_held_back_mouse_moves_when_timer_fires() {
+    let client = Client::new();
+    client.create(2, 10, "div", events::MOUSEMOVE | events::CLICK);
+    client.apply(&[PatchOp::SetMoveCoalescing(Some(50))]);
+    client.hit(Some(10));
+    let kinds_and_x = |msgs: Vec<ServerMsg>| -> Vec<(String, i32)> {
+        forwarded_events(msgs).into_iter().map(|event| match event.detail {
+            EventDetail::Mouse { client_x, .. } => (event.kind, client_x),
+            _ => (event.kind, -1),
+        }).collect()
+    };
+
+    // The first move is due; the second is held back until the timer flushes it.
+    client.send_event(CompositorEvent::MouseMoveEvent(Some(Point2D::new(1., 1.))));
+    client.send_event(CompositorEvent::MouseMoveEvent(Some(Point2D::new(2., 1.))));
+    assert_eq!(kinds_and_x(client.drain()), vec![("mousemove".to_owned(), 1)]);
+    client.fire_timers();
+    assert_eq!(kinds_and_x(client.drain()), vec![("mousemove".to_owned(), 2)]);
+
+    // A click forces the held back move out first, and the timer armed for it is cancelled.
+    client.send_event(CompositorEvent::MouseMoveEvent(Some(Point2D::new(3., 1.))));
+    client.click(10);
+    client.fire_timers();
+    let kinds: Vec<_> = kinds_and_x(client.drain()).into_iter().map(|(kind, _)| kind).collect();
+    assert_eq!(kinds, vec!["mousemove", "click"]);
+}
+
+#[test]
+fn test_element_scroll_is_clamped_to_the_scroll_range() {
+    let ops = vec![PatchOp::SetElementScroll(4, 120.5, 0.0), PatchOp::SetElementScroll(4, -3.0, 1e9)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    // An overflow:auto container 200 by 100 pixels around content 500 by 400 pixels.
+    let container = BoxGeometry::new(500, 400, 200, 100);
+    assert!(container.overflows());
+    assert_eq!(container.clamp_scroll(120.5, 0.0), (120.5, 0.0));
+    assert_eq!(container.clamp_scroll(-3.0, 1e9), (0.0, 300.0));
+    assert_eq!(container.clamp_scroll(1e9, ::std::f64::NAN), (300.0, 0.0));
+
+    let fitting = BoxGeometry::new(100, 50, 200, 100);
+    assert!(!fitting.overflows());
+    assert_eq!(fitting.clamp_scroll(10.0, 10.0), (0.0, 0.0));
+}
+
+#[test]
+fn test_connection_tells_detached_nodes_from_unknown_ids() {
+    let ops = vec![PatchOp::IsConnected(4, 23), PatchOp::IsConnected(9, 24)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    // Node 4 is in the document, node 5 was detached, and 9 was never registered.
+    let mut tombstones = Tombstones::new();
+    tombstones.set_enabled(true);
+    tombstones.bury(5, 0);
+    assert_eq!(Connection::of(Ok(4)), Connection::Connected);
+    assert_eq!(Connection::of::<u64>(Err(tombstones.lookup_error(5, 1))), Connection::Disconnected);
+    assert_eq!(Connection::of::<u64>(Err(tombstones.lookup_error(9, 1))), Connection::Unknown);
+
+    for connection in vec![Connection::Connected, Connection::Disconnected, Connection::Unknown] {
+        let msg = ServerMsg::Reply(23, Reply::Connection(connection));
+        assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+    }
+}
+
+#[test]
+fn test_is_connected_replies_for_the_document() {
+    let client = Client::new();
+    for id in 10..13 {
+        client.create(2, id, "div", EventSet::empty());
+    }
+    let body = client.node(2);
+    body.RemoveChild(&client.node(11)).unwrap();
+    client.apply(&[PatchOp::SetTombstones(true)]);
+    body.RemoveChild(&client.node(12)).unwrap();
+
+    // 11 was removed before tombstones were kept, so it's as unknown as an id never used.
+    client.apply(&[PatchOp::IsConnected(10, 1), PatchOp::IsConnected(11, 2), PatchOp::IsConnected(12, 3),
+                   PatchOp::IsConnected(99, 4)]);
+    assert_eq!(client.drain(), vec![
+        ServerMsg::Reply(1, Reply::Connection(Connection::Connected)),
+        ServerMsg::Reply(2, Reply::Connection(Connection::Unknown)),
+        ServerMsg::Reply(3, Reply::Connection(Connection::Disconnected)),
+        ServerMsg::Reply(4, Reply::Connection(Connection::Unknown)),
+    ]);
+}
+
+#[test]
+fn test_attribute_diffs_only_change_what_differs() {
+    let set = vec![("href".to_owned(), "/next".to_owned()),
+                   ("title".to_owned(), "Next".to_owned()),
+                   ("rel".to_owned(), "next".to_owned())];
+    let remove = vec!["target".to_owned(), "download".to_owned()];
+    let ops = vec![PatchOp::DiffAttributes(4, set.clone(), remove.clone()), PatchOp::DiffAttributes(4, vec![], vec![])];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+
+    // The link already has its title, and has no `download` attribute to remove.
+    let mut attrs = vec![("href".to_owned(), "/prev".to_owned()),
+                         ("title".to_owned(), "Next".to_owned()),
+                         ("target".to_owned(), "_blank".to_owned())];
+    let changes = attribute_changes(|name| {
+        attrs.iter().find(|&&(ref attr, _)| attr == name).map(|&(_, ref value)| value.clone())
+    }, set, remove);
+    assert_eq!(changes, vec![("href".to_owned(), Some("/next".to_owned())),
+                             ("rel".to_owned(), Some("next".to_owned())),
+                             ("target".to_owned(), None)]);
+
+    for (name, value) in changes {
+        attrs.retain(|&(ref attr, _)| *attr != name);
+        if let Some(value) = value {
+            attrs.push((name, value));
+        }
+    }
+    attrs.sort();
+    assert_eq!(attrs, vec![("href".to_owned(), "/next".to_owned()),
+                           ("rel".to_owned(), "next".to_owned()),
+                           ("title".to_owned(), "Next".to_owned())]);
+
+    // An attribute the diff sets and removes ends up removed.
+    let changes = attribute_changes(|_| None, vec![("rel".to_owned(), "next".to_owned())], vec!["rel".to_owned()]);
+    assert_eq!(changes, vec![("rel".to_owned(), Some("next".to_owned())), ("rel".to_owned(), None)]);
+}
+
+#[test]
+fn test_attribute_diffs_apply_to_the_element() {
+    let client = Client::new();
+    let node = client.create(2, 10, "a", EventSet::empty());
+    client.apply(&[PatchOp::DiffAttributes(10, vec![("href".to_owned(), "/prev".to_owned()),
+                                                    ("title".to_owned(), "Next".to_owned()),
+                                                    ("target".to_owned(), "_blank".to_owned())], vec![])]);
+
+    client.apply(&[PatchOp::DiffAttributes(10, vec![("href".to_owned(), "/next".to_owned()),
+                                                    ("title".to_owned(), "Next".to_owned())],
+                                           vec!["target".to_owned(), "download".to_owned()])]);
+    assert!(client.drain().is_empty());
+    let link = node.downcast::<Element>().unwrap();
+    assert_eq!(link.get_string_attribute(&Atom::from("href")), "/next");
+    assert_eq!(link.get_string_attribute(&Atom::from("title")), "Next");
+    assert!(!link.has_attribute(&Atom::from("target")));
+    assert!(!link.has_attribute(&Atom::from("download")));
+
+    // Only elements have attributes.
+    assert_eq!(failure_code(&client, &[PatchOp::DiffAttributes(3, vec![], vec![])]), Some(3));
+}
+
+#[test]
+fn test_invalidate_layout_op_round_trips() {
+    let ops = vec![PatchOp::InvalidateLayout(4), PatchOp::ForceReflow(23), PatchOp::InvalidateLayout(9)];
+    let mut reader = Cursor::new(patch_ops(&ops));
+    for op in ops {
+        assert_eq!(PatchOp::read_from(&mut reader).unwrap(), Some(op));
+    }
+    assert_eq!(PatchOp::read_from(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn test_invalidate_layout_dirties_only_the_subtree() {
+    let client = Client::new();
+    client.create(2, 10, "div", EventSet::empty());
+    client.create(10, 11, "span", EventSet::empty());
+    client.create_text(11, 12, "text");
+    client.create(2, 20, "div", EventSet::empty());
+    // The reflow after an empty message lays out the new nodes.
+    client.apply(&[]);
+    client.reflows();
+
+    client.apply(&[PatchOp::InvalidateLayout(10)]);
+    assert!(client.drain().is_empty());
+    let dirty = client.reflows().into_iter().filter(|reflow| reflow.for_display)
+                      .flat_map(|reflow| reflow.dirty).collect::<Vec<_>>();
+    assert_eq!(dirty, vec![10, 11, 12]);
+}
+
+#[test]
+fn test_client_capabilities_report_the_compiled_in_limits() {
+    let capabilities = ClientCapabilities::new();
+    assert_eq!(capabilities.protocol_version, PATCH_PROTOCOL_VERSION);
+    assert_eq!(capabilities.max_node_depth as usize, MAX_NODE_DEPTH);
+    assert_eq!(capabilities.max_checkpoints as usize, MAX_CHECKPOINTS);
+    assert_eq!(capabilities.queue_bound as usize, DEFAULT_QUEUE_BOUND);
+    assert_eq!(capabilities.max_message_size as usize, MAX_MESSAGE_SIZE);
+
+    // Every supported op reads back, and the first tag past them doesn't.
+    let ops = supported_ops();
+    assert_eq!(capabilities.ops, ops);
+    assert_eq!(ops[0], 1);
+    assert!(ops.windows(2).all(|pair| pair[1] == pair[0] + 1));
+    let unsupported = ops[ops.len() - 1] + 1;
+    let err = PatchOp::read_from(&mut Cursor::new(vec![unsupported])).unwrap_err();
+    assert_eq!(PatchError::of(&err), PatchError::UnsupportedOp);
+    let last = patch_ops(&[PatchOp::InvalidateLayout(4)]);
+    assert_eq!(last[0], ops[ops.len() - 1]);
+
+    let msg = ServerMsg::ClientCapabilities(capabilities);
+    assert_eq!(ServerMsg::read_from(&mut Cursor::new(msg.to_bytes())).unwrap(), msg);
+}
+
+#[test]
+fn test_byte_strings_longer_than_a_message_are_refused() {
+    let mut buf = vec![];
+    buf.put_u32(MAX_MESSAGE_SIZE as u32 + 1).unwrap();
+    let err = Cursor::new(buf).get_bytes().unwrap_err();
+    assert_eq!(PatchError::of(&err), PatchError::LimitExceeded);
+
+    let mut buf = vec![];
+    buf.put_bytes(b"fits").unwrap();
+    assert_eq!(Cursor::new(buf).get_bytes().unwrap(), b"fits");
+}
@@ -0,0 +1,44 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use app_units::Au;
+use euclid::size::Size2D;
+use media_queries::CSSErrorReporterTest;
+use std::sync::Arc;
+use style::properties::{ComputedValues, cascade, parse_one_declaration};
+use style::selector_matching::DeclarationBlock;
+
+fn computed(declarations: &[(&str, &str)], parent: Option<&ComputedValues>) -> ComputedValues {
+    let url = url!("about::test");
+    let declarations = declarations.iter().flat_map(|&(name, value)| {
+        parse_one_declaration(name, value, &url, Box::new(CSSErrorReporterTest)).unwrap()
+    }).collect();
+    let blocks = [DeclarationBlock::from_declarations(Arc::new(declarations))];
+    cascade(Size2D::new(Au::from_px(800), Au::from_px(600)), &blocks, false, parent, None,
+            Box::new(CSSErrorReporterTest)).0
+}
+
+#[test]
+fn test_custom_property_is_inherited_and_substituted() {
+    let root = computed(&[("--accent", "red")], None);
+    assert_eq!(root.computed_value_to_string("--accent"), Ok("red".to_owned()));
+
+    let red = computed(&[("color", "red")], None).computed_value_to_string("color");
+    let child = computed(&[], Some(&root));
+    assert_eq!(child.computed_value_to_string("--accent"), root.computed_value_to_string("--accent"));
+    let grandchild = computed(&[("color", "var(--accent)")], Some(&child));
+    assert_eq!(grandchild.computed_value_to_string("color"), red);
+}
+
+#[test]
+fn test_custom_property_fallback() {
+    let blue = computed(&[("color", "blue")], None).computed_value_to_string("color");
+    let style = computed(&[("color", "var(--missing, blue)")], None);
+    assert_eq!(style.computed_value_to_string("color"), blue);
+    assert!(style.computed_value_to_string("--missing").is_err());
+
+    let root = computed(&[("--accent", "red")], None);
+    let style = computed(&[("color", "var(--accent, blue)")], Some(&root));
+    assert!(style.computed_value_to_string("color") != blue);
+}
@@ -18,6 +18,7 @@ extern crate url;
 extern crate util;
 
 #[cfg(test)] mod attr;
+#[cfg(test)] mod custom_properties;
 #[cfg(test)] mod logical_geometry;
 #[cfg(test)] mod media_queries;
 #[cfg(test)] mod stylesheets;
@@ -11,6 +11,7 @@ use style::media_queries::*;
 use style::servo::Stylesheet;
 use style::stylesheets::{Origin, CSSRuleIteratorExt};
 use style::values::specified;
+use style_traits::color_scheme::ColorScheme;
 
 pub struct CSSErrorReporterTest;
 
@@ -360,6 +361,7 @@ fn test_matching_simple() {
     let device = Device {
         media_type: MediaType::Screen,
         viewport_size: Size2D::typed(200.0, 100.0),
+        color_scheme: ColorScheme::Light,
     };
 
     media_query_test(&device, "@media not all { a { color: red; } }", 0);
@@ -379,6 +381,7 @@ fn test_matching_width() {
     let device = Device {
         media_type: MediaType::Screen,
         viewport_size: Size2D::typed(200.0, 100.0),
+        color_scheme: ColorScheme::Light,
     };
 
     media_query_test(&device, "@media { a { color: red; } }", 1);
@@ -423,9 +426,22 @@ fn test_matching_invalid() {
     let device = Device {
         media_type: MediaType::Screen,
         viewport_size: Size2D::typed(200.0, 100.0),
+        color_scheme: ColorScheme::Light,
     };
 
     media_query_test(&device, "@media fridge { a { color: red; } }", 0);
     media_query_test(&device, "@media screen and (height: 100px) { a { color: red; } }", 0);
     media_query_test(&device, "@media not print and (width: 100) { a { color: red; } }", 0);
 }
+
+#[test]
+fn test_matching_prefers_color_scheme() {
+    let light = Device::new(MediaType::Screen, Size2D::typed(200.0, 100.0));
+    let dark = Device::new(MediaType::Screen, Size2D::typed(200.0, 100.0)).with_color_scheme(ColorScheme::Dark);
+
+    media_query_test(&light, "@media (prefers-color-scheme: dark) { a { color: red; } }", 0);
+    media_query_test(&dark, "@media (prefers-color-scheme: dark) { a { color: red; } }", 1);
+    media_query_test(&light, "@media (prefers-color-scheme: light) { a { color: red; } }", 1);
+    media_query_test(&dark, "@media not screen and (prefers-color-scheme: dark) { a { color: red; } }", 0);
+    media_query_test(&dark, "@media (prefers-color-scheme: dim) { a { color: red; } }", 0);
+}
@@ -9,9 +9,12 @@ use std::borrow::ToOwned;
 use std::sync::Arc;
 use std::sync::Mutex;
 use string_cache::Atom;
+use style::element_state::IN_FOCUS_VISIBLE_STATE;
 use style::properties::{PropertyDeclaration, PropertyDeclarationBlock, DeclaredValue, longhands};
+use style::properties::{Shorthand, parse_one_declaration, parse_one_declaration_with_priority};
 use style::stylesheets::{CSSRule, StyleRule, Origin};
 use style::error_reporting::ParseErrorReporter;
+use style::selector_impl::NonTSPseudoClass;
 use style::servo::Stylesheet;
 
 #[test]
@@ -188,6 +191,53 @@ impl ParseErrorReporter for CSSInvalidErrorReporterTest {
 }
 
 
+#[test]
+fn test_parse_focus_visible() {
+    let url = url!("about::test");
+    let stylesheet = Stylesheet::from_str(":focus-visible { }", url, Origin::Author,
+                                          Box::new(CSSErrorReporterTest));
+    let rule = match stylesheet.rules[0] {
+        CSSRule::Style(ref rule) => rule,
+        _ => panic!("expected a style rule"),
+    };
+    assert_eq!(rule.selectors[0].compound_selectors.simple_selectors,
+               vec![SimpleSelector::NonTSPseudoClass(NonTSPseudoClass::FocusVisible)]);
+    assert_eq!(NonTSPseudoClass::FocusVisible.state_flag(), IN_FOCUS_VISIBLE_STATE);
+}
+
+#[test]
+fn test_parse_one_declaration_with_priority() {
+    let url = url!("about::test");
+    let red = cssparser::RGBA { red: 1., green: 0., blue: 0., alpha: 1. };
+    for &(value, expected_important) in &[("red !important", true), ("red", false)] {
+        let (declarations, important) =
+            parse_one_declaration_with_priority("color", value, &url, Box::new(CSSErrorReporterTest)).unwrap();
+        assert_eq!(important, expected_important);
+        assert_eq!(declarations.len(), 1);
+        match declarations[0] {
+            PropertyDeclaration::Color(DeclaredValue::Value(ref color)) => assert_eq!(color.parsed, red),
+            _ => panic!("expected a color declaration"),
+        }
+    }
+    assert!(parse_one_declaration_with_priority("color", "red !important blue", &url,
+                                                Box::new(CSSErrorReporterTest)).is_err());
+    assert!(parse_one_declaration_with_priority("color", "red !", &url, Box::new(CSSErrorReporterTest)).is_err());
+}
+
+#[test]
+fn test_expand_margin_shorthand() {
+    let url = url!("about::test");
+    let declarations = parse_one_declaration("margin", "1px 2px", &url, Box::new(CSSErrorReporterTest)).unwrap();
+    let longhands = Shorthand::from_name("margin").unwrap().longhands();
+    assert_eq!(longhands.to_vec(), vec!["margin-top", "margin-right", "margin-bottom", "margin-left"]);
+    assert_eq!(declarations.len(), longhands.len());
+    for (declaration, &(longhand, value)) in declarations.iter().zip(&[("margin-top", "1px"), ("margin-right", "2px"),
+                                                                      ("margin-bottom", "1px"), ("margin-left", "2px")]) {
+        assert!(declaration.matches(longhand));
+        assert_eq!(declaration.value(), value);
+    }
+}
+
 #[test]
 fn test_report_error_stylesheet() {
     let css = r"
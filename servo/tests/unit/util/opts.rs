@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::path::Path;
-use util::opts::parse_url_or_filename;
+use util::opts::{parse_url_or_filename, parse_vdom_mount};
 
 #[test]
 fn test_argument_parsing() {
@@ -37,3 +37,12 @@ fn test_argument_parsing() {
     assert!(url.query.is_none());
     assert!(url.fragment.is_none());
 }
+
+#[test]
+fn test_vdom_mount_parsing() {
+    assert_eq!(parse_vdom_mount("app"), Some("app".to_owned()));
+    assert_eq!(parse_vdom_mount("#app"), Some("app".to_owned()));
+    assert_eq!(parse_vdom_mount(""), None);
+    assert_eq!(parse_vdom_mount("#"), None);
+    assert_eq!(parse_vdom_mount("my app"), None);
+}